@@ -0,0 +1,180 @@
+//! Checkpoints of a fuzzing campaign, so that a campaign that crashed or was
+//! killed resumes instead of starting over.
+//!
+//! A checkpoint is the whole fuzzer state: the input and infant state
+//! corpora, the metadata of the schedulers, feedbacks (including the coverage
+//! history map) and oracles (including the token contexts), and the known
+//! bugs. The parts of the fuzzer kept outside of its state, i.e., the corpus
+//! minimizer, the covered branches and the maps of the infant feedbacks, are
+//! saved with it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Name of the checkpoint file in the work dir
+pub const CAMPAIGN_FILE: &str = "campaign.cbor";
+
+/// A feedback (or other part of the fuzzer) keeping campaign data outside of
+/// the fuzzer state, saved in the checkpoints as a snapshot
+pub trait CampaignPart {
+    type Snapshot: Serialize + DeserializeOwned;
+
+    fn snapshot(&self) -> Self::Snapshot;
+
+    fn restore(&mut self, snapshot: Self::Snapshot);
+}
+
+/// A checkpoint of a campaign, `S` is the fuzzer state and `F` / `R` the
+/// snapshots of the infant feedbacks
+#[derive(Serialize, Deserialize)]
+pub struct Campaign<S, F, R> {
+    pub state: S,
+    /// Coverage hash to (testcase idx, fav factor) of the corpus minimizer
+    pub minimizer_map: HashMap<u64, (usize, f64)>,
+    /// Branches covered by the input corpus
+    pub covered_branches: HashSet<usize>,
+    pub infant_feedback: F,
+    pub infant_result_feedback: R,
+}
+
+/// Save the campaign to the work dir. The previous checkpoint is only
+/// replaced once the new one is completely written.
+pub fn save_campaign<C>(work_dir: &str, campaign: &C) -> Result<(), Box<dyn Error>>
+where
+    C: Serialize,
+{
+    fs::create_dir_all(work_dir)?;
+    let path = Path::new(work_dir).join(CAMPAIGN_FILE);
+    let tmp_path = path.with_extension("cbor.tmp");
+    serde_cbor::to_writer(BufWriter::new(File::create(&tmp_path)?), campaign)?;
+    fs::rename(tmp_path, &path)?;
+    info!("Saved campaign checkpoint to {}", path.display());
+    Ok(())
+}
+
+/// Load the campaign from the work dir, if a checkpoint exists
+pub fn load_campaign<C>(work_dir: &str) -> Option<C>
+where
+    C: DeserializeOwned,
+{
+    let path = Path::new(work_dir).join(CAMPAIGN_FILE);
+    if !path.exists() {
+        return None;
+    }
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("Failed to open campaign checkpoint {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    match serde_cbor::from_reader(BufReader::new(file)) {
+        Ok(campaign) => {
+            info!("Resuming campaign from {}", path.display());
+            Some(campaign)
+        }
+        Err(e) => {
+            warn!("Failed to load campaign checkpoint {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+    use super::*;
+    use crate::{
+        evm::{
+            input::ConciseEVMInput,
+            onchain::endpoints::Chain,
+            tokens::{
+                get_uniswap_info,
+                v2_transformer::UniswapPairContext,
+                PairContextTy,
+                PathContext,
+                TokenContext,
+                UniswapProvider,
+            },
+            types::{EVMAddress, EVMU256},
+            vm::EVMState,
+        },
+        feedback::DataflowFeedback,
+        generic_vm::vm_executor::MAP_SIZE,
+    };
+
+    #[test]
+    #[cfg(feature = "dataflow")]
+    fn test_campaign_round_trip() {
+        let work_dir = std::env::temp_dir().join(format!("ityfuzz_checkpoint_{}", std::process::id()));
+        let work_dir = work_dir.to_str().unwrap();
+
+        let pair = EVMAddress::from_slice(&[1; 20]);
+        let token_ctx = TokenContext {
+            swaps: vec![PathContext {
+                route: vec![PairContextTy::Uniswap(Rc::new(RefCell::new(UniswapPairContext {
+                    pair_address: pair,
+                    side: 1,
+                    uniswap_info: Arc::new(get_uniswap_info(&UniswapProvider::UniswapV2, &Chain::ETH)),
+                    initial_reserves: (EVMU256::from(1000), EVMU256::from(2000)),
+                    ..Default::default()
+                })))],
+            }],
+            decimals: Some(9),
+            sell_tax_bps: Some(500),
+            ..Default::default()
+        };
+
+        let (mut read_map, mut write_map) = (vec![true; MAP_SIZE], vec![0u8; MAP_SIZE]);
+        let mut dataflow = DataflowFeedback::<EVMState, EVMAddress, EVMAddress, Vec<u8>, ConciseEVMInput>::new(
+            &mut read_map,
+            &mut write_map,
+        );
+        let mut write_snapshot = dataflow.snapshot();
+        write_snapshot[7][2] = true;
+        dataflow.restore(write_snapshot);
+
+        let campaign = Campaign {
+            state: &token_ctx,
+            minimizer_map: HashMap::from([(42, (3, 0.5))]),
+            covered_branches: HashSet::from([1, 2, 3]),
+            infant_feedback: (),
+            infant_result_feedback: dataflow.snapshot(),
+        };
+        save_campaign(work_dir, &campaign).unwrap();
+        let loaded: Campaign<TokenContext, (), Vec<[bool; 4]>> = load_campaign(work_dir).unwrap();
+        fs::remove_dir_all(work_dir).unwrap();
+
+        assert_eq!(loaded.minimizer_map, campaign.minimizer_map);
+        assert_eq!(loaded.covered_branches, campaign.covered_branches);
+        assert!(loaded.infant_result_feedback[7][2]);
+        assert_eq!(
+            loaded
+                .infant_result_feedback
+                .iter()
+                .filter(|b| b.contains(&true))
+                .count(),
+            1
+        );
+        assert_eq!(loaded.state.decimals, Some(9));
+        assert_eq!(loaded.state.sell_tax_bps, Some(500));
+        assert_eq!(loaded.state.swaps[0].pair_addresses(), vec![pair]);
+        let PairContextTy::Uniswap(loaded_pair) = &loaded.state.swaps[0].route[0] else {
+            panic!("not a uniswap hop");
+        };
+        assert_eq!(
+            loaded_pair.borrow().initial_reserves,
+            (EVMU256::from(1000), EVMU256::from(2000))
+        );
+        assert_eq!(loaded_pair.borrow().uniswap_info.pool_fee, 30);
+    }
+}
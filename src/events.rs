@@ -0,0 +1,111 @@
+//! Events of a fuzzing campaign, streamed to subscribers of
+//! [`crate::fuzzer::ItyFuzzer::subscribe`], e.g., to show live progress on a
+//! dashboard without parsing the logs.
+
+use std::time::Duration;
+
+use libafl_bolts::bolts_prelude::SerdeAnyMap;
+
+use crate::{fuzzer::OracleFinding, memory::MemoryUsage};
+
+/// Stats a VM keeps in the metadata of the fuzz state (e.g., the swap routes
+/// discovered for the tokens), surfaced in the campaign stats and the final
+/// summary, see [`crate::fuzzer::ItyFuzzer::register_stats_reporter`]
+pub trait StatsReporter {
+    /// Name shown in the stats
+    fn name(&self) -> &'static str;
+
+    /// The stats kept in `metadata`, one line per entry
+    fn report(&self, metadata: &SerdeAnyMap) -> Vec<String>;
+}
+
+/// Stats of a [`StatsReporter`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReportedStats {
+    pub name: &'static str,
+    pub lines: Vec<String>,
+}
+
+/// Progress of the campaign, emitted periodically
+#[derive(Clone, Debug, Default)]
+pub struct CampaignStats {
+    pub elapsed: Duration,
+    pub executions: u64,
+    pub execs_per_sec: f64,
+    /// Number of testcases in the input corpus
+    pub corpus_size: usize,
+    /// Number of branches covered by the input corpus
+    pub coverage: usize,
+    /// Number of distinct bugs found so far
+    pub findings: usize,
+    /// Stats of the registered [`StatsReporter`]s, e.g., the swap routes and
+    /// pairs discovered per token
+    pub reported: Vec<ReportedStats>,
+    /// Number of infant states kept for scheduling
+    pub infant_states: usize,
+    /// Number of infant states evicted to keep the infant state corpus
+    /// bounded
+    pub evicted_infant_states: usize,
+    /// Estimated memory held by the caches and the infant state corpus
+    pub memory: MemoryUsage,
+}
+
+#[derive(Clone, Debug)]
+pub enum CampaignEvent {
+    Stats(CampaignStats),
+    /// Findings of an execution and the (minimized) trace reaching them
+    Finding {
+        findings: Vec<OracleFinding>,
+        trace: String,
+    },
+}
+
+/// Why a campaign ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The time or execution budget is used up
+    BudgetUsedUp,
+    /// A bug is found and the campaign is not set to run forever
+    Found,
+    /// As many distinct bugs as to stop after are found
+    FindingLimit,
+    /// There is nothing to fuzz, e.g., the target has no function to call
+    NothingToFuzz,
+    /// The transactions to replay are executed
+    Replayed,
+}
+
+impl StopReason {
+    /// Exit code of the CLI when the campaign ends for this reason
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StopReason::FindingLimit => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Findings of an execution and the (minimized) trace reaching them
+#[derive(Clone, Debug)]
+pub struct CampaignFinding {
+    pub findings: Vec<OracleFinding>,
+    pub trace: String,
+    /// Transactions of the trace, one JSON object per line, which can be
+    /// replayed
+    pub replayable: String,
+    /// Whether replaying `replayable` reproduces the findings
+    pub verified: bool,
+    /// Seed of the random number generator of the campaign
+    pub rng_seed: Option<u64>,
+}
+
+/// Results of a campaign run to completion
+#[derive(Clone, Debug)]
+pub struct CampaignResult<I> {
+    pub stop_reason: StopReason,
+    /// Progress of the campaign when it ended
+    pub stats: CampaignStats,
+    pub findings: Vec<CampaignFinding>,
+    /// Inputs of the corpus
+    pub corpus: Vec<I>,
+}
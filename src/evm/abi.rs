@@ -3,6 +3,7 @@ use std::{
     collections::HashMap,
     fmt::{Debug, Display, Formatter},
     ops::{Deref, DerefMut},
+    str::FromStr,
 };
 
 use ethers::types::I256;
@@ -24,7 +25,7 @@ use super::{
     utils::{colored_address, prettify_value},
 };
 /// Definition of ABI types and their encoding, decoding, mutating methods
-use crate::evm::abi::ABILossyType::{TArray, TDynamic, TEmpty, TUnknown, T256};
+use crate::evm::abi::ABILossyType::{TArray, TDynamic, TEmpty, TRaw, TUnknown, T256};
 use crate::{
     evm::{
         concolic::expr::Expr,
@@ -106,6 +107,80 @@ impl ABIAddressToInstanceMap {
     }
 }
 
+/// Percentage of address mutations picking an interesting address by default
+pub const DEFAULT_INTERESTING_ADDRESS_PERCENT: u64 = 30;
+
+/// Addresses that the address mutator samples from with a higher probability,
+/// e.g., well-known constants and discovered tokens, pairs and routers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterestingAddressMetadata {
+    pub addresses: Vec<EVMAddress>,
+    /// Percentage of address mutations picking one of `addresses`, the others
+    /// pick from the address pool (callers and contracts), or the zero address
+    /// 10% of the time
+    #[serde(default = "default_interesting_address_percent")]
+    pub percent: u64,
+}
+
+fn default_interesting_address_percent() -> u64 {
+    DEFAULT_INTERESTING_ADDRESS_PERCENT
+}
+
+impl_serdeany!(InterestingAddressMetadata);
+
+impl Default for InterestingAddressMetadata {
+    fn default() -> Self {
+        let mut addresses = vec![
+            EVMAddress::zero(),
+            EVMAddress::from_str("0x000000000000000000000000000000000000dead").unwrap(),
+        ];
+        // precompiles
+        addresses.extend((1..=9).map(EVMAddress::from_low_u64_be));
+        Self {
+            addresses,
+            percent: DEFAULT_INTERESTING_ADDRESS_PERCENT,
+        }
+    }
+}
+
+impl InterestingAddressMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an interesting address
+    pub fn add(&mut self, address: EVMAddress) {
+        if !self.addresses.contains(&address) {
+            self.addresses.push(address);
+        }
+    }
+}
+
+/// Add an address the address mutator should sample more often
+pub fn register_interesting_address<S: HasMetadata>(address: EVMAddress, state: &mut S) {
+    if !state.has_metadata::<InterestingAddressMetadata>() {
+        state.add_metadata(InterestingAddressMetadata::new());
+    }
+    state
+        .metadata_map_mut()
+        .get_mut::<InterestingAddressMetadata>()
+        .unwrap()
+        .add(address);
+}
+
+/// Set how often (in percent) the address mutator picks an interesting address,
+/// higher values exploit the known contracts, lower values explore the others
+pub fn set_interesting_address_percent<S: HasMetadata>(percent: u64, state: &mut S) {
+    if !state.has_metadata::<InterestingAddressMetadata>() {
+        state.add_metadata(InterestingAddressMetadata::new());
+    }
+    state
+        .metadata_map_mut()
+        .get_mut::<InterestingAddressMetadata>()
+        .unwrap()
+        .percent = percent.min(100);
+}
+
 pub fn register_abi_instance<S: HasMetadata>(address: EVMAddress, abi: BoxedABI, state: &mut S) {
     let abi_map = state
         .metadata_map_mut()
@@ -128,6 +203,8 @@ pub enum ABILossyType {
     TEmpty,
     /// Unknown type (e.g., those we don't know ABI, it can be any type)
     TUnknown,
+    /// Raw calldata that is not ABI encoded (including the function hash)
+    TRaw,
 }
 
 /// Traits of ABI types (encoding, decoding, etc.)
@@ -196,7 +273,9 @@ pub struct BoxedABI {
 
 impl Display for BoxedABI {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.function == [0; 4] {
+        if self.is_raw() {
+            write!(f, "raw({})", self.b.to_string())
+        } else if self.function == [0; 4] {
             write!(f, "Stepping with return: {}", hex::encode(self.b.to_string()))
         } else {
             write!(f, "{}{}", self.get_func_name(), self.b.to_string())
@@ -222,6 +301,9 @@ impl BoxedABI {
 
     /// Get the function hash + encoded args (transaction data)
     pub fn get_bytes(&self) -> Vec<u8> {
+        if self.is_raw() {
+            return self.b.get_bytes();
+        }
         [Vec::from(self.function), self.b.get_bytes()].concat()
     }
 
@@ -235,6 +317,11 @@ impl BoxedABI {
         self.b.is_static()
     }
 
+    /// Determine if this is raw calldata, which does not have a function hash
+    pub fn is_raw(&self) -> bool {
+        matches!(self.b.get_type(), TRaw)
+    }
+
     /// Get the ABI type of args.
     /// If the function has more than one args, it will return Array type (tuple
     /// of args)
@@ -250,6 +337,7 @@ impl BoxedABI {
             TDynamic => "ADynamic".to_string(),
             TEmpty => "AEmpty".to_string(),
             TUnknown => "AUnknown".to_string(),
+            TRaw => "ARaw".to_string(),
         }
     }
 
@@ -293,6 +381,9 @@ impl BoxedABI {
 
     /// Set the bytes to args, used for decoding
     pub fn set_bytes(&mut self, bytes: Vec<u8>) -> bool {
+        if self.is_raw() {
+            return self.b.set_bytes(bytes);
+        }
         self.b.set_bytes(bytes[4..].to_vec())
     }
 
@@ -351,6 +442,7 @@ where
             1 => BoxedABI::new(Box::new(AArray {
                 data: vec![sample_abi(state, 32); vec_size],
                 dynamic_size: false,
+                element: None,
             })),
             // array[]
             2 => {
@@ -358,14 +450,16 @@ where
                 BoxedABI::new(Box::new(AArray {
                     data: vec![abi; vec_size],
                     dynamic_size: false,
+                    element: None,
                 }))
             }
             // array[...]
             3 => {
                 let abi = sample_abi(state, 32);
                 BoxedABI::new(Box::new(AArray {
-                    data: vec![abi; vec_size],
+                    data: vec![abi.clone(); vec_size],
                     dynamic_size: true,
+                    element: Some(abi),
                 }))
             }
             _ => unreachable!(),
@@ -412,7 +506,21 @@ impl BoxedABI {
                     return MutationResult::Skipped;
                 }
                 if a256.is_address {
-                    if state.rand_mut().below(100) < 90 {
+                    let (interesting_len, interesting_percent) = state
+                        .metadata_map()
+                        .get::<InterestingAddressMetadata>()
+                        .map_or((0, 0), |meta| (meta.addresses.len(), meta.percent));
+                    let roll = state.rand_mut().below(100);
+                    if interesting_len > 0 && roll < interesting_percent {
+                        let idx = state.rand_mut().below(interesting_len as u64) as usize;
+                        a256.data = state
+                            .metadata_map()
+                            .get::<InterestingAddressMetadata>()
+                            .unwrap()
+                            .addresses[idx]
+                            .0
+                            .to_vec();
+                    } else if roll < 90 {
                         a256.data = state.get_rand_address().0.to_vec();
                     } else {
                         a256.data = [0; 20].to_vec();
@@ -435,7 +543,11 @@ impl BoxedABI {
 
                 let data_len = aarray.data.len();
                 if data_len == 0 {
-                    return MutationResult::Skipped;
+                    if aarray.element.is_none() {
+                        return MutationResult::Skipped;
+                    }
+                    aarray.data.push(aarray.new_element());
+                    return MutationResult::Mutated;
                 }
                 if aarray.dynamic_size {
                     match state.rand_mut().below(100) {
@@ -485,6 +597,13 @@ impl BoxedABI {
                     MutationResult::Mutated
                 }
             }
+            // mutate the whole calldata as bytes, within the length limit
+            TRaw => {
+                let araw = self.b.deref_mut().as_any().downcast_mut::<ARaw>().unwrap();
+                let res = byte_mutator_with_expansion(state, araw, vm_slots);
+                araw.data.truncate(araw.max_len);
+                res
+            }
         }
     }
 }
@@ -753,6 +872,9 @@ impl ABI for ADynamic {
             self.data = Vec::new();
             return true;
         }
+        if bytes.len() < 32 || bytes.len() < 32 + get_size(&bytes) {
+            return false;
+        }
         self.data = bytes[32..32 + get_size(&bytes)].to_vec();
         true
     }
@@ -792,6 +914,20 @@ pub struct AArray {
     /// whether the size of the array is dynamic (i.e., is it dynamic size
     /// array)
     pub(crate) dynamic_size: bool,
+    /// Default element of a dynamic size array, so that an array emptied by
+    /// the mutator or the decoder can grow again with elements of its type
+    #[serde(default)]
+    pub(crate) element: Option<BoxedABI>,
+}
+
+impl AArray {
+    /// A new element, of the type of the elements of a dynamic size array
+    fn new_element(&self) -> BoxedABI {
+        match self.data.first() {
+            Some(first) => first.clone(),
+            None => self.element.clone().unwrap_or_default(),
+        }
+    }
 }
 
 impl Input for AArray {
@@ -901,13 +1037,16 @@ impl ABI for AArray {
     // Set the bytes in self.data accordingly
     fn set_bytes(&mut self, bytes: Vec<u8>) -> bool {
         let base_offset = if self.dynamic_size {
+            if bytes.len() < 32 {
+                return false;
+            }
             let array_size = get_size(&bytes);
+            // each element takes at least a word
+            if array_size > bytes.len() / 32 {
+                return false;
+            }
             while self.data.len() < array_size {
-                if self.data.is_empty() {
-                    self.data.push(BoxedABI::default());
-                } else {
-                    self.data.push(self.data[0].clone());
-                }
+                self.data.push(self.new_element());
             }
             self.data.truncate(array_size);
             32
@@ -921,9 +1060,14 @@ impl ABI for AArray {
             let (item_offset, size) = match item.get_type() {
                 T256 => (offset, 32),
                 TArray if item.is_static() => (offset, item.b.get_size()),
-                TArray | TDynamic => (get_size(&bytes[offset + base_offset..]), 32),
+                TArray | TDynamic => {
+                    if offset + base_offset + 32 > bytes.len() {
+                        return false;
+                    }
+                    (get_size(&bytes[offset + base_offset..]), 32)
+                }
                 TEmpty => (0, 0),
-                TUnknown => {
+                TUnknown | TRaw => {
                     unreachable!()
                 }
             };
@@ -932,7 +1076,9 @@ impl ABI for AArray {
             if start + size > bytes.len() {
                 return false;
             }
-            item.b.set_bytes(bytes[start..].to_vec());
+            if !item.b.set_bytes(bytes[start..].to_vec()) {
+                return false;
+            }
             offset += size;
         }
         true
@@ -1085,6 +1231,80 @@ impl ABI for AUnknown {
     }
 }
 
+/// [`ARaw`] is used to represent the whole calldata as opaque bytes, for
+/// contracts that do not dispatch with ABI-encoded calldata (e.g., fallback
+/// routers written in assembly)
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ARaw {
+    /// calldata, including the function hash (if any)
+    pub data: Vec<u8>,
+    /// maximum length of the calldata
+    pub max_len: usize,
+}
+
+impl ARaw {
+    pub fn new(max_len: usize) -> Self {
+        Self { data: vec![], max_len }
+    }
+}
+
+impl Input for ARaw {
+    fn generate_name(&self, idx: usize) -> String {
+        format!("ARaw_{}", idx)
+    }
+}
+
+impl HasBytesVec for ARaw {
+    fn bytes(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        self.data.as_mut()
+    }
+}
+
+#[typetag::serde]
+impl ABI for ARaw {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn get_bytes(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    fn get_type(&self) -> ABILossyType {
+        TRaw
+    }
+
+    fn set_bytes(&mut self, bytes: Vec<u8>) -> bool {
+        self.data = bytes;
+        self.data.truncate(self.max_len);
+        true
+    }
+
+    fn to_string(&self) -> String {
+        vec_to_hex(&self.data)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_concolic(&self) -> Vec<Box<Expr>> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, _)| Expr::sym_byte(format!("ARaw_{}", i)))
+            .collect()
+    }
+
+    fn get_size(&self) -> usize {
+        self.data.len()
+    }
+}
+
 /// Create a [`BoxedABI`] with default arg given the ABI type in string
 pub fn get_abi_type_boxed(abi_name: &str) -> BoxedABI {
     BoxedABI {
@@ -1156,18 +1376,18 @@ pub fn get_abi_type(abi_name: &str, with_address: &Option<Vec<u8>>) -> Box<dyn A
                 })
                 .collect(),
             dynamic_size: false,
+            element: None,
         });
     }
     if abi_name_str.ends_with("[]") {
+        let element = BoxedABI {
+            b: get_abi_type(&abi_name[..abi_name_str.len() - 2], with_address),
+            function: [0; 4],
+        };
         return Box::new(AArray {
-            data: vec![
-                BoxedABI {
-                    b: get_abi_type(&abi_name[..abi_name_str.len() - 2], with_address),
-                    function: [0; 4]
-                };
-                1
-            ],
+            data: vec![element.clone()],
             dynamic_size: true,
+            element: Some(element),
         });
     } else if abi_name_str.ends_with(']') && abi_name_str.contains('[') {
         let split = abi_name_str.rsplit_once('[').unwrap();
@@ -1188,6 +1408,7 @@ pub fn get_abi_type(abi_name: &str, with_address: &Option<Vec<u8>>) -> Box<dyn A
                 len
             ],
             dynamic_size: false,
+            element: None,
         });
     }
     get_abi_type_basic(abi_name, 32, with_address)
@@ -1265,6 +1486,8 @@ fn get_abi_type_basic(abi_name: &str, abi_bs: usize, with_address: &Option<Vec<u
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use hex;
 
     use super::*;
@@ -1300,6 +1523,18 @@ mod tests {
         debug!("result: {:?} abi: {:?}", mutation_result, hex::encode(abi.get_bytes()));
     }
 
+    #[test]
+    fn test_raw() {
+        let mut abi = BoxedABI::new(Box::new(ARaw::new(8)));
+        let mut test_state = FuzzState::new(0);
+        for _ in 0..100 {
+            abi.mutate::<EVMAddress, EVMAddress, EVMState, EVMFuzzState, ConciseEVMInput>(&mut test_state);
+            assert!(abi.get_bytes().len() <= 8);
+        }
+        assert!(abi.set_bytes(vec![0xaa; 10]));
+        assert_eq!(abi.get_bytes(), vec![0xaa; 8]);
+    }
+
     #[test]
     fn test_tuple_static() {
         let mut abi = get_abi_type_boxed(&String::from("(uint256,uint256)"));
@@ -1411,10 +1646,87 @@ mod tests {
         debug!("result: {:?} abi: {:?}", mutation_result, hex::encode(abibytes));
     }
 
+    /// Calldata of `f((uint256,address)[])` with the given elements
+    fn encode_tuple_array(function: [u8; 4], elements: &[(u64, u8)]) -> Vec<u8> {
+        let word = |v: u64| EVMU256::from(v).to_be_bytes::<32>().to_vec();
+        let mut bytes = [function.to_vec(), word(0x20), word(elements.len() as u64)].concat();
+        for (amount, address) in elements {
+            bytes.extend(word(*amount));
+            bytes.extend([vec![0; 12], vec![*address; 20]].concat());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_tuple_array() {
+        let function = [0x12, 0x34, 0x56, 0x78];
+        let mut abi = get_abi_type_boxed("((uint256,address)[])");
+        abi.set_func(function);
+        let calldata = encode_tuple_array(function, &[(1, 0xaa), (2, 0xbb)]);
+        assert!(abi.set_bytes(calldata.clone()));
+        assert_eq!(abi.get_bytes(), calldata);
+
+        // the fields are mutated one at a time
+        let mut test_state: EVMFuzzState = FuzzState::new(0);
+        test_state.addresses_pool.push(EVMAddress::zero());
+        for _ in 0..100 {
+            let before = abi.get_bytes();
+            abi.mutate::<EVMAddress, EVMAddress, EVMState, EVMFuzzState, ConciseEVMInput>(&mut test_state);
+            let after = abi.get_bytes();
+            if before.len() == after.len() {
+                let changed = before[4..]
+                    .chunks(32)
+                    .zip(after[4..].chunks(32))
+                    .filter(|(a, b)| a != b)
+                    .count();
+                assert!(changed <= 1);
+            }
+            assert!(abi.set_bytes(after.clone()));
+            assert_eq!(abi.get_bytes(), after);
+        }
+
+        // emptied arrays still decode elements of their type
+        let empty = encode_tuple_array(function, &[]);
+        assert!(abi.set_bytes(empty.clone()));
+        assert_eq!(abi.get_bytes(), empty);
+        let calldata = encode_tuple_array(function, &[(3, 0xcc)]);
+        assert!(abi.set_bytes(calldata.clone()));
+        assert_eq!(abi.get_bytes(), calldata);
+
+        // truncated calldata
+        assert!(!abi.set_bytes(calldata[..calldata.len() - 32].to_vec()));
+    }
+
     #[test]
     fn test_100_times() {
         for _ in 0..100 {
             test_complex();
         }
     }
+
+    #[test]
+    fn test_interesting_address() {
+        let mut test_state: EVMFuzzState = FuzzState::new(0);
+        let token = EVMAddress::from_str("0x0e09fabb73bd3ade0a17ecc321fd13a19e81ce82").unwrap();
+        register_interesting_address(token, &mut test_state);
+        register_interesting_address(token, &mut test_state);
+        let meta = test_state.metadata_map().get::<InterestingAddressMetadata>().unwrap();
+        assert_eq!(meta.addresses.iter().filter(|a| **a == token).count(), 1);
+        assert!(meta.addresses.contains(&EVMAddress::zero()));
+
+        let interesting = meta.addresses.clone();
+
+        // always pick an interesting address
+        set_interesting_address_percent(100, &mut test_state);
+        test_state.add_address(&token);
+        let mut abi = get_abi_type_boxed(&String::from("address"));
+        let mut picked = HashSet::new();
+        for _ in 0..100 {
+            abi.mutate::<EVMAddress, EVMAddress, EVMState, EVMFuzzState, ConciseEVMInput>(&mut test_state);
+            let address = EVMAddress::from_slice(&abi.get_bytes()[12..]);
+            assert!(interesting.contains(&address));
+            picked.insert(address);
+        }
+        assert!(picked.contains(&token));
+    }
 }
@@ -0,0 +1,153 @@
+//! Arguments of functions pinned to a value while the other arguments are
+//! fuzzed, e.g., to always pass a specific token address.
+//!
+//! A pinned argument is set on the ABI instance of the function when it is
+//! added to the corpus and marked as not mutated, so that it is kept by the
+//! mutator. The pins are applied again over the concolic solutions, which
+//! set the whole calldata. The pinned values are part of the calldata written
+//! to the traces.
+
+use std::str::FromStr;
+
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::evm::{
+    abi::{AArray, BoxedABI, A256},
+    function_filter::{split_list, FunctionPattern},
+    types::{EVMAddress, EVMU256},
+};
+
+/// Argument `arg_index` (from 0) of `function` pinned to `value`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArgPin {
+    pub function: FunctionPattern,
+    pub arg_index: usize,
+    pub value: EVMU256,
+}
+
+impl FromStr for ArgPin {
+    type Err = String;
+
+    /// Format: `function:arg_index:value`, where function is a selector or a
+    /// signature, optionally prefixed by the contract address (see
+    /// [`FunctionPattern`]) and value is in hex (0x...) or decimal
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().rsplitn(3, ':');
+        let (Some(value), Some(arg_index), Some(function)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("arg pin {} should be function:arg_index:value", s));
+        };
+        Ok(Self {
+            function: FunctionPattern::from_str(function)?,
+            arg_index: arg_index
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid argument index {}", arg_index))?,
+            value: EVMU256::from_str(value.trim()).map_err(|_| format!("invalid argument value {}", value))?,
+        })
+    }
+}
+
+/// The pinned arguments, consulted whenever an ABI instance of a function is
+/// added to the corpus
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ArgPinMetadata {
+    pub pins: Vec<ArgPin>,
+}
+
+impl_serdeany!(ArgPinMetadata);
+
+impl ArgPinMetadata {
+    /// Parse a comma separated list of pins
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let pins = split_list(s)
+            .into_iter()
+            .filter(|pin| !pin.trim().is_empty())
+            .map(ArgPin::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { pins })
+    }
+
+    /// Set the arguments of `abi`, called on `address`, that are pinned and
+    /// mark them as not mutated. Only arguments encoded in a word (integers,
+    /// addresses, booleans and fixed-size bytes) can be pinned, the others are
+    /// skipped with a warning. Returns the number of arguments pinned.
+    pub fn apply(&self, address: &EVMAddress, abi: &mut BoxedABI) -> usize {
+        let pins = self
+            .pins
+            .iter()
+            .filter(|pin| pin.function.matches(address, &abi.function))
+            .collect::<Vec<_>>();
+        if pins.is_empty() {
+            return 0;
+        }
+        let function = abi.get_func_name();
+        let Some(args) = abi.b.as_any().downcast_mut::<AArray>() else {
+            warn!("{} has no arguments to pin", function);
+            return 0;
+        };
+        let mut pinned = 0;
+        for pin in pins {
+            let Some(arg) = args
+                .data
+                .get_mut(pin.arg_index)
+                .and_then(|arg| arg.b.as_any().downcast_mut::<A256>())
+            else {
+                warn!("argument {} of {} can not be pinned", pin.arg_index, function);
+                continue;
+            };
+            let len = arg.data.len();
+            arg.data = pin.value.to_be_bytes::<32>()[32 - len..].to_vec();
+            arg.dont_mutate = true;
+            pinned += 1;
+        }
+        pinned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evm::{abi::get_abi_type_boxed, input::ConciseEVMInput, types::EVMFuzzState, vm::EVMState},
+        state::FuzzState,
+    };
+
+    #[test]
+    fn test_arg_pins() {
+        let target = EVMAddress::from_low_u64_be(1);
+        let token = "0x00000000000000000000000000000000000000aa";
+        let pins = ArgPinMetadata::parse(&format!(
+            "transfer(address,uint256):0:{},{:?}:0xa9059cbb:1:100",
+            token, target
+        ))
+        .unwrap();
+        assert_eq!(pins.pins.len(), 2);
+        assert_eq!(
+            pins.pins[1].function,
+            FunctionPattern::Function(target, [0xa9, 0x05, 0x9c, 0xbb])
+        );
+        assert!(ArgPinMetadata::parse("0xa9059cbb:100").is_err());
+
+        let mut abi = get_abi_type_boxed("(address,uint256)");
+        abi.set_func([0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(pins.apply(&EVMAddress::from_low_u64_be(2), &mut abi.clone()), 1);
+        assert_eq!(pins.apply(&target, &mut abi), 2);
+
+        let word = |v: u64| EVMU256::from(v).to_be_bytes::<32>().to_vec();
+        let expected = abi.get_bytes();
+        assert_eq!(expected, [abi.function.to_vec(), word(0xaa), word(100)].concat());
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        state.addresses_pool.push(EVMAddress::zero());
+        for _ in 0..100 {
+            abi.mutate::<EVMAddress, EVMAddress, EVMState, EVMFuzzState, ConciseEVMInput>(&mut state);
+        }
+        assert_eq!(abi.get_bytes(), expected);
+        // decoded bytes are always set, the pins are applied again
+        assert!(abi.set_bytes([abi.function.to_vec(), vec![0xff; 64]].concat()));
+        assert_ne!(abi.get_bytes(), expected);
+        assert_eq!(pins.apply(&target, &mut abi), 2);
+        assert_eq!(abi.get_bytes(), expected);
+    }
+}
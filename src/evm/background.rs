@@ -0,0 +1,78 @@
+//! Real transactions of the block after the fork block, interleaved with the
+//! fuzzed transactions to find ordering dependent bugs
+
+use std::fmt::{self, Display, Formatter};
+
+use bytes::Bytes;
+use crypto::{digest::Digest, sha3::Sha3};
+use serde::{Deserialize, Serialize};
+
+use crate::evm::types::{checksum, EVMAddress, EVMU256};
+
+/// A transaction fetched from the chain
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackgroundTxn {
+    pub from: EVMAddress,
+    pub to: EVMAddress,
+    pub value: EVMU256,
+    pub data: Bytes,
+}
+
+impl Display for BackgroundTxn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {}.call{{value: {}}}(0x{})",
+            checksum(&self.from),
+            checksum(&self.to),
+            self.value,
+            hex::encode(&self.data)
+        )
+    }
+}
+
+/// Where a background transaction runs relative to the fuzzed transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackgroundPosition {
+    Before,
+    After,
+}
+
+/// Picks the background transaction (if any) to run with a fuzzed
+/// transaction. The pick only depends on the fuzzed transaction, across runs
+/// too, so that replaying a finding interleaves the same background
+/// transactions.
+#[derive(Clone, Debug, Default)]
+pub struct BackgroundTxnProvider {
+    pub txns: Vec<BackgroundTxn>,
+}
+
+impl BackgroundTxnProvider {
+    pub fn new(txns: Vec<BackgroundTxn>) -> Self {
+        Self { txns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txns.is_empty()
+    }
+
+    /// One third of the fuzzed transactions run alone, the rest run after or
+    /// before one of the background transactions
+    pub fn pick(&self, caller: &EVMAddress, data: &[u8]) -> Option<(BackgroundPosition, &BackgroundTxn)> {
+        if self.txns.is_empty() {
+            return None;
+        }
+        let mut hasher = Sha3::keccak256();
+        hasher.input(caller.as_bytes());
+        hasher.input(data);
+        let mut digest = [0u8; 32];
+        hasher.result(&mut digest);
+        let hash = u64::from_be_bytes(digest[..8].try_into().unwrap()) as usize;
+        let position = match hash % 3 {
+            0 => return None,
+            1 => BackgroundPosition::Before,
+            _ => BackgroundPosition::After,
+        };
+        Some((position, &self.txns[(hash / 3) % self.txns.len()]))
+    }
+}
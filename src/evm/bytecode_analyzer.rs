@@ -1,12 +1,15 @@
 use std::collections::HashSet;
 
 use libafl::state::{HasMetadata, State};
-use revm_interpreter::opcode::JUMPI;
+use revm_interpreter::opcode::{CALL, CALLCODE, DELEGATECALL, JUMPI, MUL, PUSH1, PUSH29, PUSH4, SHL, STATICCALL};
 use revm_primitives::Bytecode;
 
 use crate::evm::bytecode_iterator::all_bytecode;
 /// Analysis passes for EVM bytecode
-use crate::mutation_utils::ConstantPoolMetadata;
+use crate::mutation_utils::{ConstantPoolMetadata, SelectorDictionaryMetadata};
+
+/// Number of instructions searched for a call after a selector is pushed
+const SELECTOR_CALL_WINDOW: usize = 64;
 
 /// Find all constants in the bytecode by observing PUSH instructions.
 ///
@@ -40,12 +43,56 @@ pub fn find_constants(bytecode: &Bytecode) -> HashSet<Vec<u8>> {
     constants
 }
 
+/// Find the selectors of the calls made by the contract, i.e., PUSH4
+/// immediates shifted to the head of a word (`PUSH1 0xe0 SHL`, or `PUSH29
+/// 0x01.. MUL` by older compilers) shortly before a CALL.
+///
+/// The selectors the dispatcher of the contract compares with are not
+/// shifted, so they are not included.
+pub fn find_call_selectors(bytecode: &Bytecode) -> HashSet<[u8; 4]> {
+    let bytes = bytecode.bytes().to_vec();
+    let ops = all_bytecode(&bytes);
+    let mut selectors = HashSet::new();
+    for (i, (pc, op)) in ops.iter().enumerate() {
+        if *op != PUSH4 || pc + 5 > bytes.len() {
+            continue;
+        }
+        let shifted = match ops.get(i + 1..i + 3) {
+            Some([(push_pc, PUSH1), (_, SHL)]) => bytes.get(push_pc + 1) == Some(&0xe0),
+            Some([(push_pc, PUSH29), (_, MUL)]) => bytes.get(push_pc + 1) == Some(&0x01),
+            _ => false,
+        };
+        let calls = || {
+            ops.iter()
+                .skip(i + 3)
+                .take(SELECTOR_CALL_WINDOW)
+                .any(|(_, op)| matches!(*op, CALL | CALLCODE | DELEGATECALL | STATICCALL))
+        };
+        if shifted && calls() {
+            selectors.insert(bytes[pc + 1..pc + 5].try_into().unwrap());
+        }
+    }
+    selectors
+}
+
 /// Add constants in smart contract to the global state's
-/// [`ConstantPoolMetadata`] this can be costly, ensure sampling to be cheap
+/// [`ConstantPoolMetadata`], and the selectors it calls to the
+/// [`SelectorDictionaryMetadata`]. This can be costly, ensure sampling to be
+/// cheap
 pub fn add_analysis_result_to_state<S>(bytecode: &Bytecode, state: &mut S)
 where
     S: HasMetadata + State,
 {
+    let selectors = find_call_selectors(bytecode);
+    if !selectors.is_empty() {
+        let meta = state
+            .metadata_map_mut()
+            .get_or_insert_with(SelectorDictionaryMetadata::new);
+        for selector in selectors {
+            meta.add(selector);
+        }
+    }
+
     let constants = find_constants(bytecode);
     match state.metadata_map_mut().get_mut::<ConstantPoolMetadata>() {
         Some(meta) => {
@@ -79,4 +126,15 @@ mod tests {
         let constants = find_constants(&bytecode);
         debug!("{:?}", constants.iter().map(hex::encode).collect::<Vec<String>>());
     }
+
+    #[test]
+    fn test_find_call_selectors() {
+        // PUSH4 0xa9059cbb PUSH1 0xe0 SHL PUSH1 0x00 MSTORE ... CALL
+        let call = "63a9059cbb60e01b60005260006000604460006000735aaeb6053f3e94c9b9a09f33669435e7ef1beaed5af1";
+        // PUSH4 0x23b872dd EQ PUSH2 0x0010 JUMPI, as in the dispatcher
+        let dispatch = "6323b872dd1461001057";
+        let bytecode = Bytecode::new_raw(Bytes::from(hex::decode(format!("{}{}fd", dispatch, call)).unwrap()));
+        let selectors = find_call_selectors(&bytecode);
+        assert_eq!(selectors, HashSet::from([[0xa9, 0x05, 0x9c, 0xbb]]));
+    }
 }
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, fmt::Debug, ops::Deref, rc::Rc};
+use std::{cell::RefCell, fmt::Debug, ops::Deref, rc::Rc, time::Duration};
 
 use libafl::{
     corpus::{Corpus, Testcase},
@@ -11,21 +11,28 @@ use libafl::{
     Error,
     Evaluator,
 };
-use libafl_bolts::{impl_serdeany, Named};
+use libafl_bolts::{current_time, impl_serdeany, Named};
 use revm_primitives::HashSet;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info};
 
 use crate::{
     evm::{
+        arg_pins::ArgPinMetadata,
         concolic::concolic_host::{ConcolicHost, Field, Solution, ALL_SOLUTIONS, ALL_WORKER_THREADS},
         input::{EVMInput, EVMInputT},
         middlewares::middleware::MiddlewareType,
+        scheduler::UncoveredBranchesMetadata,
         types::{EVMFuzzExecutor, EVMFuzzState, EVMQueueExecutor},
     },
     generic_vm::{vm_executor::GenericVM, vm_state::VMStateT},
     input::VMInputT,
 };
+/// Testcases solved for on each plateau at most, the ones reaching the most
+/// uncovered branches first. The others are left to the next plateaus, so
+/// that the fuzzer gets back to mutating in between.
+pub const MAX_PLATEAU_TESTCASES: usize = 8;
+
 pub struct ConcolicStage<OT> {
     pub enabled: bool,
     pub allow_symbolic_addresses: bool,
@@ -33,6 +40,13 @@ pub struct ConcolicStage<OT> {
     pub vm_executor: Rc<RefCell<EVMQueueExecutor>>,
     pub phantom: std::marker::PhantomData<OT>,
     pub num_threads: usize,
+    /// Only solve once the corpus stopped growing for this long, for the
+    /// branches the fuzzer is stuck at, instead of for every new testcase
+    pub plateau: Option<Duration>,
+    last_corpus_size: usize,
+    last_progress: Duration,
+    /// Testcases already solved for on a plateau
+    solved_on_plateau: HashSet<usize>,
 }
 
 impl<OT> UsesState for ConcolicStage<OT> {
@@ -53,8 +67,57 @@ impl<OT> ConcolicStage<OT> {
             vm_executor,
             phantom: std::marker::PhantomData,
             num_threads,
+            plateau: None,
+            last_corpus_size: 0,
+            last_progress: current_time(),
+            solved_on_plateau: HashSet::new(),
         }
     }
+
+    /// Only run concolic execution once the corpus stopped growing for
+    /// `plateau`, on the testcases reaching branches with an uncovered side
+    pub fn set_plateau(&mut self, plateau: Duration) {
+        self.plateau = Some(plateau);
+    }
+
+    /// Testcases reaching uncovered branches not solved for yet, if the
+    /// coverage plateaued
+    fn plateau_testcases(&mut self, state: &EVMFuzzState, plateau: Duration) -> Option<Vec<usize>> {
+        let corpus_size = state.corpus().count();
+        if corpus_size != self.last_corpus_size {
+            self.last_corpus_size = corpus_size;
+            self.last_progress = current_time();
+        }
+        if current_time().saturating_sub(self.last_progress) < plateau {
+            return None;
+        }
+        self.last_progress = current_time();
+        let stuck = state
+            .metadata_map()
+            .get::<UncoveredBranchesMetadata>()
+            .map_or(vec![], |meta| meta.stuck_testcases());
+        let (idxs, unsolved) = plateau_batch(stuck, &mut self.solved_on_plateau);
+        info!(
+            "No new testcase for {}s, running concolic execution on {} of {} testcases reaching uncovered branches",
+            plateau.as_secs(),
+            idxs.len(),
+            unsolved
+        );
+        Some(idxs)
+    }
+}
+
+/// The first [`MAX_PLATEAU_TESTCASES`] testcases of `stuck` not `solved` yet,
+/// marked as solved, and how many were not solved yet
+fn plateau_batch(stuck: Vec<CorpusId>, solved: &mut HashSet<usize>) -> (Vec<usize>, usize) {
+    let unsolved = stuck
+        .into_iter()
+        .map(usize::from)
+        .filter(|idx| !solved.contains(idx))
+        .collect::<Vec<_>>();
+    let batch = unsolved.iter().take(MAX_PLATEAU_TESTCASES).copied().collect::<Vec<_>>();
+    solved.extend(batch.iter().copied());
+    (batch, unsolved.len())
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -92,13 +155,29 @@ where
             });
         }
 
-        let meta = state
-            .metadata_map()
-            .get::<ConcolicPrioritizationMetadata>()
-            .unwrap()
-            .clone();
+        let idxs = match self.plateau {
+            Some(plateau) => match self.plateau_testcases(state, plateau) {
+                Some(idxs) => idxs,
+                None => {
+                    // testcases are picked from the uncovered branches instead
+                    state
+                        .metadata_map_mut()
+                        .get_mut::<ConcolicPrioritizationMetadata>()
+                        .unwrap()
+                        .interesting_idx
+                        .clear();
+                    return Ok(());
+                }
+            },
+            None => state
+                .metadata_map()
+                .get::<ConcolicPrioritizationMetadata>()
+                .unwrap()
+                .interesting_idx
+                .clone(),
+        };
 
-        for idx in &meta.interesting_idx {
+        for idx in &idxs {
             info!("Running concolic execution on testcase #{}", idx);
 
             let testcase = state
@@ -150,6 +229,8 @@ where
         }
 
         {
+            // the solutions do not know about the pinned args
+            let pins = state.metadata_map().get::<ArgPinMetadata>().cloned();
             let metadata = state
                 .metadata_map_mut()
                 .get_mut::<ConcolicPrioritizationMetadata>()
@@ -166,6 +247,9 @@ where
                 // if cannot set_bytes AND no fields to modify, skip
                 if data_abi.set_bytes(solution.input) {
                     // This can fail if e.g. solving for an array
+                    if let Some(pins) = &pins {
+                        pins.apply(&new_testcase.get_contract(), &mut data_abi);
+                    }
                     new_testcase.data = Some(data_abi);
                 } else if solution.fields.is_empty() {
                     continue;
@@ -272,3 +356,27 @@ where
         self.inner.append_metadata(state, observers, testcase)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plateau_testcases_capped() {
+        let stuck = (0..20usize).map(CorpusId::from).collect::<Vec<_>>();
+        let mut solved = HashSet::new();
+        let mut plateaus = vec![];
+        loop {
+            let (idxs, unsolved) = plateau_batch(stuck.clone(), &mut solved);
+            assert_eq!(unsolved, 20usize.saturating_sub(plateaus.len() * MAX_PLATEAU_TESTCASES));
+            if idxs.is_empty() {
+                break;
+            }
+            plateaus.push(idxs);
+        }
+        // every stuck testcase is solved for once, over several plateaus
+        assert_eq!(plateaus.len(), 3);
+        assert_eq!(plateaus[0], (0..8).collect::<Vec<_>>());
+        assert_eq!(plateaus[2], (16..20).collect::<Vec<_>>());
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     cell::RefCell,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fmt::{self, Debug},
     rc::Rc,
     str::FromStr,
@@ -10,10 +10,16 @@ use std::{
 use crate::evm::contract_utils::ContractLoader;
 use crate::{
     evm::{
+        arg_pins::ArgPinMetadata,
         blaz::builder::BuildJob,
-        onchain::endpoints::OnChainConfig,
-        oracles::erc20::IERC20OracleFlashloan,
-        types::EVMAddress,
+        function_filter::FunctionFilterMetadata,
+        input::CallValueMetadata,
+        onchain::{endpoints::OnChainConfig, fee::TxFeeModel},
+        oracles::{erc20::IERC20OracleFlashloan, nft::NftCollection},
+        predeploys::Predeploy,
+        state_override::StateOverride,
+        tokens::balance_slot::BalanceSlot,
+        types::{EVMAddress, EVMU256},
     },
     oracle::{Oracle, Producer},
 };
@@ -60,28 +66,59 @@ pub struct Config<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E> {
     pub onchain: Option<OnChainConfig>,
     pub onchain_storage_fetching: Option<StorageFetchingMode>,
     pub flashloan: bool,
+    pub max_borrowed_assets: usize,
+    /// Decimals of WETH, i.e., the asset profits are measured in
+    pub quote_decimals: u8,
+    pub infant_state_limit: usize,
+    pub multi_block: bool,
     pub concolic: bool,
     pub concolic_caller: bool,
     pub concolic_timeout: u32,
     pub concolic_num_threads: usize,
+    /// Seconds without new testcases before concolic execution solves the
+    /// branches the fuzzer is stuck at, `None` to solve for every testcase
+    pub concolic_plateau: Option<u64>,
     pub contract_loader: ContractLoader,
+    pub raw_calldata_targets: HashSet<EVMAddress>,
+    pub raw_calldata_max_len: usize,
+    /// Also send plain ETH and raw calldata to the targets, to fuzz their
+    /// `receive()` / `fallback()`
+    pub fuzz_fallback: bool,
+    /// Probe view functions with fuzzed arguments for anomalous values
+    pub probe_views: bool,
+    /// Attacker contract deployed with the targets, also a caller
+    pub attacker_contract: Option<EVMAddress>,
     pub oracle: Vec<Rc<RefCell<dyn Oracle<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E>>>>,
     pub producers: Vec<Rc<RefCell<dyn Producer<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E>>>>,
     pub replay_file: Option<String>,
     pub flashloan_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
     pub selfdestruct_oracle: bool,
     pub reentrancy_oracle: bool,
+    pub storage_collision_oracle: bool,
+    pub access_control_oracle: bool,
+    pub funds_locked_oracle: bool,
     // pub state_comp_oracle: Option<String>,
     // pub state_comp_matching: Option<String>,
     pub work_dir: String,
     pub write_relationship: bool,
+    pub dump_recovered_abi: bool,
     pub run_forever: bool,
+    pub stop_after_findings: Option<usize>,
     pub sha3_bypass: bool,
+    pub gas_feedback: bool,
     pub base_path: String,
     pub echidna_oracle: bool,
     pub invariant_oracle: bool,
+    pub nft_theft_oracle: bool,
+    pub nft_collections: Vec<NftCollection>,
+    /// Contracts monitored by the NFT theft oracle, empty means the fuzzed
+    /// contracts
+    pub nft_holders: Vec<EVMAddress>,
+    pub allowance_drain_oracle: bool,
+    pub allowance_victims: Vec<EVMAddress>,
     pub panic_on_bug: bool,
     pub spec_id: String,
+    pub max_call_depth: u64,
     pub only_fuzz: HashSet<EVMAddress>,
     pub typed_bug: bool,
     pub arbitrary_external_call: bool,
@@ -89,6 +126,43 @@ pub struct Config<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E> {
     pub builder: Option<BuildJob>,
     pub local_files_basedir_pattern: Option<String>,
     pub load_corpus: String,
+    /// Seconds between checkpoints of the campaign
+    pub checkpoint_interval: Option<u64>,
+    pub max_time: Option<u64>,
+    pub max_execs: Option<usize>,
+    /// Soft cap on the estimated memory of the caches, in MiB
+    pub max_memory: Option<u64>,
+    pub assert_invariants: bool,
+    pub resume: bool,
+    pub call_value: CallValueMetadata,
+    pub function_filter: FunctionFilterMetadata,
+    /// Arguments of functions kept at a value while the others are mutated
+    pub arg_pins: ArgPinMetadata,
+    pub interesting_addresses: Vec<EVMAddress>,
+    /// Percentage of address mutations picking an interesting address
+    pub interesting_address_percent: u64,
+    /// Values added to the constant pool of the mutator, e.g., token amounts,
+    /// role hashes and storage keys
+    pub dictionary_values: Vec<EVMU256>,
+    /// When set, flashloan is disabled and callers start with this balance
+    pub caller_balance: Option<EVMU256>,
+    /// Largest holders of the tokens given by the user, also callers
+    pub top_holders: Vec<EVMAddress>,
+    /// Predeploys and precompiles of the chain installed as stubs
+    pub predeploys: Vec<Predeploy>,
+    /// Interleave the transactions of the block after the fork block with the
+    /// fuzzed ones
+    pub background_txns: bool,
+    /// Transaction fees subtracted from the net profit of findings
+    pub tx_fee_model: TxFeeModel,
+    /// Balances mapping slots of tokens, given by the user
+    pub balance_slots: HashMap<EVMAddress, BalanceSlot>,
+    /// Probe the balances mapping slots of the other tokens
+    pub probe_balance_slots: bool,
+    /// Write the private keys of the callers to the work dir
+    pub dump_caller_keys: bool,
+    /// Changes to the state applied before the corpus is initialized
+    pub state_overrides: Vec<StateOverride>,
     #[cfg(feature = "use_presets")]
     pub preset_file_path: String,
 }
@@ -101,9 +175,19 @@ impl<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E> Debug
             .field("onchain", &self.onchain)
             // .field("onchain_storage_fetching", &self.onchain_storage_fetching)
             .field("flashloan", &self.flashloan)
+            .field("max_borrowed_assets", &self.max_borrowed_assets)
+            .field("quote_decimals", &self.quote_decimals)
+            .field("infant_state_limit", &self.infant_state_limit)
+            .field("multi_block", &self.multi_block)
             .field("concolic", &self.concolic)
             .field("concolic_caller", &self.concolic_caller)
+            .field("concolic_plateau", &self.concolic_plateau)
             .field("contract_loader", &self.contract_loader)
+            .field("raw_calldata_targets", &self.raw_calldata_targets)
+            .field("raw_calldata_max_len", &self.raw_calldata_max_len)
+            .field("fuzz_fallback", &self.fuzz_fallback)
+            .field("probe_views", &self.probe_views)
+            .field("attacker_contract", &self.attacker_contract)
             // .field("oracle", &self.oracle)
             // .field("producers", &self.producers)
             .field("replay_file", &self.replay_file)
@@ -113,14 +197,44 @@ impl<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E> Debug
             // .field("state_comp_matching", &self.state_comp_matching)
             .field("work_dir", &self.work_dir)
             .field("write_relationship", &self.write_relationship)
+            .field("dump_recovered_abi", &self.dump_recovered_abi)
             .field("run_forever", &self.run_forever)
+            .field("stop_after_findings", &self.stop_after_findings)
             .field("sha3_bypass", &self.sha3_bypass)
+            .field("gas_feedback", &self.gas_feedback)
             .field("base_path", &self.base_path)
             .field("echidna_oracle", &self.echidna_oracle)
             .field("panic_on_bug", &self.panic_on_bug)
             .field("spec_id", &self.spec_id)
+            .field("max_call_depth", &self.max_call_depth)
             .field("only_fuzz", &self.only_fuzz)
             .field("typed_bug", &self.typed_bug)
+            .field("nft_theft_oracle", &self.nft_theft_oracle)
+            .field("nft_collections", &self.nft_collections)
+            .field("nft_holders", &self.nft_holders)
+            .field("allowance_drain_oracle", &self.allowance_drain_oracle)
+            .field("allowance_victims", &self.allowance_victims)
+            .field("call_value", &self.call_value)
+            .field("function_filter", &self.function_filter)
+            .field("arg_pins", &self.arg_pins)
+            .field("interesting_addresses", &self.interesting_addresses)
+            .field("interesting_address_percent", &self.interesting_address_percent)
+            .field("dictionary_values", &self.dictionary_values)
+            .field("caller_balance", &self.caller_balance)
+            .field("top_holders", &self.top_holders)
+            .field("predeploys", &self.predeploys)
+            .field("background_txns", &self.background_txns)
+            .field("tx_fee_model", &self.tx_fee_model)
+            .field("balance_slots", &self.balance_slots)
+            .field("probe_balance_slots", &self.probe_balance_slots)
+            .field("dump_caller_keys", &self.dump_caller_keys)
+            .field("state_overrides", &self.state_overrides)
+            .field("checkpoint_interval", &self.checkpoint_interval)
+            .field("max_time", &self.max_time)
+            .field("max_execs", &self.max_execs)
+            .field("max_memory", &self.max_memory)
+            .field("assert_invariants", &self.assert_invariants)
+            .field("resume", &self.resume)
             // .field("builder", &self.builder)
             .finish()
     }
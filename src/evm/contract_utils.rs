@@ -50,7 +50,7 @@ use crate::evm::{
         offchain_config::OffchainConfig,
     },
     bytecode_iterator::all_bytecode,
-    onchain::endpoints::OnChainConfig,
+    onchain::{abi_decompiler::fetch_abi_heimdall, endpoints::OnChainConfig},
 };
 
 // to use this address, call rand_utils::fixed_address(FIX_DEPLOYER)
@@ -58,6 +58,16 @@ pub static FIX_DEPLOYER: &str = "8b21e662154b4bbc1ec0754d0238875fe3d22fa6";
 pub static FOUNDRY_DEPLOYER: &str = "1804c8AB1F12E6bbf3894d4083f33e07309d1f38";
 pub static FOUNDRY_SETUP_ADDR: &str = "7FA9385bE102ac3EAc297483Dd6233D62b3e1496";
 
+/// Admin functions of upgradeable proxies (name, args, payable), handled by
+/// the proxy itself rather than its implementation. Some proxies do not
+/// declare them in their ABI (e.g., the transparent proxies of OpenZeppelin 5
+/// dispatch them in the fallback).
+const PROXY_ADMIN_FUNCTIONS: [(&str, &str, bool); 3] = [
+    ("upgradeTo", "address", false),
+    ("upgradeToAndCall", "address,bytes", true),
+    ("changeAdmin", "address", false),
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ABIConfig {
     pub abi: String,
@@ -95,6 +105,9 @@ pub struct ContractLoader {
     pub contracts: Vec<ContractInfo>,
     pub abis: Vec<ABIInfo>,
     pub setup_data: Option<SetupData>,
+    /// ABIs forced on contracts without ABI, merged with the ABIs recovered
+    /// from their bytecode
+    pub forced_abis: HashMap<EVMAddress, Vec<ABIConfig>>,
 }
 
 impl ContractLoader {
@@ -110,7 +123,12 @@ impl ContractLoader {
                     (addr.is_some() && contract.deployed_address == addr.unwrap())
                 {
                     debug!("Forcing ABI for {}", contract.name);
-                    contract.abi = ContractLoader::parse_abi_str(&abi);
+                    let abi = ContractLoader::parse_abi_str(&abi);
+                    if contract.abi.is_empty() {
+                        self.forced_abis.insert(contract.deployed_address, abi);
+                    } else {
+                        contract.abi = abi;
+                    }
                 }
             }
         }
@@ -392,6 +410,30 @@ impl ContractLoader {
             },
             abis: vec![abi_result],
             setup_data: None,
+            forced_abis: HashMap::new(),
+        }
+    }
+
+    /// Load the single contract whose bytecode (.bin) matches the path prefix
+    /// `prefix`, e.g., ./build/Attacker*, errors out if the bytecode of no
+    /// contract or of multiple contracts matches it
+    pub fn from_unique_prefix(prefix: &str, state: &mut EVMFuzzState) -> Result<ContractInfo, String> {
+        let bins = glob(prefix)
+            .map_err(|e| format!("invalid path prefix {}: {}", prefix, e))?
+            .filter_map(|path| path.ok())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "bin"))
+            .collect::<Vec<_>>();
+        match bins.len() {
+            0 => Err(format!("no bytecode (.bin) matches {}", prefix)),
+            1 => Self::from_prefix(prefix, state, &vec![], &[], vec![], None, HashMap::new())
+                .contracts
+                .pop()
+                .ok_or_else(|| format!("the bytecode of {} is empty", bins[0].display())),
+            _ => Err(format!(
+                "{} is ambiguous, it matches the bytecode of {}",
+                prefix,
+                bins.iter().map(|path| path.display().to_string()).join(", ")
+            )),
         }
     }
 
@@ -485,6 +527,7 @@ impl ContractLoader {
             contracts,
             abis,
             setup_data: None,
+            forced_abis: HashMap::new(),
         }
     }
 
@@ -524,13 +567,18 @@ impl ContractLoader {
 
             let contract_code = bytecode.expect("Failed to get bytecode");
 
-            let abi_parsed = if let Some(abi) = abi {
+            let mut abi_parsed = if let Some(abi) = abi {
                 Self::parse_abi_str(&abi)
             } else {
                 debug!("ABI not found for {}, we'll decompile", addr);
                 vec![]
             };
 
+            // the proxy is still deployed at and called through `addr`
+            if let Some(implementation) = onchain.fetch_proxy_implementation(addr) {
+                abi_parsed = Self::proxy_abi(onchain, &contract_code, abi_parsed, implementation);
+            }
+
             let (files, source_map_replacements, raw_source_map) = if let Some(job_result) = build_artifact.clone() {
                 (
                     job_result.sources.clone(),
@@ -563,7 +611,59 @@ impl ContractLoader {
             contracts,
             abis,
             setup_data: Some(setup_data),
+            forced_abis: HashMap::new(),
+        }
+    }
+
+    /// Admin functions of proxies missing from `abi`
+    fn missing_proxy_admin_functions(abi: &[ABIConfig]) -> Vec<ABIConfig> {
+        PROXY_ADMIN_FUNCTIONS
+            .iter()
+            .map(|(name, args, is_payable)| {
+                let mut function = ABIConfig {
+                    abi: format!("({})", args),
+                    function: [0; 4],
+                    function_name: name.to_string(),
+                    is_static: false,
+                    is_payable: *is_payable,
+                    is_constructor: false,
+                    should_add_corpus: true,
+                };
+                set_hash(&format!("{}({})", name, args), &mut function.function);
+                function
+            })
+            .filter(|function| !abi.iter().any(|f| f.function == function.function))
+            .collect()
+    }
+
+    /// Functions of a proxy: its own (e.g., admin functions of transparent
+    /// proxies, fuzzed even if its ABI does not declare them) and those of
+    /// its implementation
+    fn proxy_abi(
+        onchain: &mut OnChainConfig,
+        proxy_code: &str,
+        proxy_abi: Vec<ABIConfig>,
+        implementation: EVMAddress,
+    ) -> Vec<ABIConfig> {
+        let mut abi = if proxy_abi.is_empty() {
+            fetch_abi_heimdall(proxy_code.to_string())
+        } else {
+            proxy_abi
+        };
+        abi.extend(Self::missing_proxy_admin_functions(&abi));
+        let implementation_abi = match onchain.fetch_abi(implementation) {
+            Some(implementation_abi) => Self::parse_abi_str(&implementation_abi),
+            None => {
+                debug!("ABI not found for implementation {:?}, decompiling", implementation);
+                fetch_abi_heimdall(onchain.get_contract_code(implementation, false))
+            }
+        };
+        for function in implementation_abi.into_iter().filter(|f| !f.is_constructor) {
+            if !abi.iter().any(|f| f.function == function.function) {
+                abi.push(function);
+            }
         }
+        abi
     }
 
     pub fn from_config(offchain_artifacts: &Vec<OffChainArtifact>, offchain_config: &OffchainConfig) -> Self {
@@ -617,6 +717,7 @@ impl ContractLoader {
             contracts,
             abis,
             setup_data: None,
+            forced_abis: HashMap::new(),
         }
     }
 
@@ -718,6 +819,7 @@ impl ContractLoader {
             contracts,
             abis,
             setup_data: None,
+            forced_abis: HashMap::new(),
         }
     }
 
@@ -790,6 +892,7 @@ impl ContractLoader {
             contracts,
             abis,
             setup_data,
+            forced_abis: HashMap::new(),
         }
     }
 
@@ -1126,6 +1229,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_unique_prefix() {
+        let dir = std::env::temp_dir().join(format!("ityfuzz_prefix_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["Attacker", "AttackerHelper"] {
+            std::fs::write(dir.join(format!("{}.bin", name)), "6080").unwrap();
+            std::fs::write(dir.join(format!("{}.abi", name)), "[]").unwrap();
+        }
+        let prefix = |name: &str| format!("{}/{}*", dir.display(), name);
+        let mut state = FuzzState::new(0);
+
+        let err = ContractLoader::from_unique_prefix(&prefix("Attacker"), &mut state).unwrap_err();
+        assert!(err.contains("Attacker.bin") && err.contains("AttackerHelper.bin"));
+        let helper = ContractLoader::from_unique_prefix(&prefix("AttackerH"), &mut state).unwrap();
+        assert_eq!(helper.code, vec![0x60, 0x80]);
+        assert!(ContractLoader::from_unique_prefix(&prefix("Victim"), &mut state).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_extract_function_hash() {
         // uniswap v2 router
@@ -1175,4 +1297,22 @@ mod tests {
         assert_eq!(abi_cfg.function_name, "constructor");
         assert_eq!(abi_cfg.abi, "(address)");
     }
+
+    #[test]
+    fn test_missing_proxy_admin_functions() {
+        let functions = ContractLoader::missing_proxy_admin_functions(&[]);
+        assert_eq!(functions.len(), 3);
+        // upgradeTo(address)
+        assert_eq!(functions[0].function, [0x36, 0x59, 0xcf, 0xe6]);
+        // upgradeToAndCall(address,bytes)
+        assert_eq!(functions[1].function, [0x4f, 0x1e, 0xf2, 0x86]);
+        assert!(functions[1].is_payable);
+        // changeAdmin(address)
+        assert_eq!(functions[2].function, [0x8f, 0x28, 0x39, 0x70]);
+
+        let abi = ContractLoader::parse_abi_str(
+            r#"[{"inputs":[{"name":"newImplementation","type":"address"}],"name":"upgradeTo","outputs":[],"stateMutability":"nonpayable","type":"function"}]"#,
+        );
+        assert_eq!(ContractLoader::missing_proxy_admin_functions(&abi).len(), 2);
+    }
 }
@@ -0,0 +1,84 @@
+//! Export of the input corpus as standalone files, to share reproducers or
+//! inspect the corpus with other tools.
+//!
+//! Unlike a checkpoint (see [`crate::checkpoint`]), which is only meant to
+//! resume the campaign, each exported file is the whole transaction sequence
+//! of a testcase, from the initial state, in the replay format: one JSON
+//! [`ConciseEVMInput`] (caller, contract, calldata, value, ...) per line. The
+//! files can be given to `--replay-file` or `--load-corpus`.
+
+use std::{fs, io, path::Path};
+
+use tracing::info;
+
+use crate::{
+    evm::{
+        input::ConciseEVMInput,
+        types::{EVMExecutionResult, EVMFuzzState},
+    },
+    fuzzer::corpus_inputs,
+    input::ConciseSerde,
+};
+
+/// Write each testcase of the corpus of `state` to `<dir>/<id>_replayable`,
+/// returns the number of files written
+pub fn export_corpus(dir: &str, state: &mut EVMFuzzState) -> io::Result<usize> {
+    fs::create_dir_all(dir)?;
+    let inputs = corpus_inputs(state);
+
+    for (idx, input) in &inputs {
+        // transactions leading to the state the input is executed in
+        let mut content = input.sstate.trace.to_file_str(state);
+        let concise_input = ConciseEVMInput::from_input(input, &EVMExecutionResult::empty_result());
+        content.push_str(&String::from_utf8(concise_input.serialize_concise()).unwrap());
+        content.push('\n');
+        fs::write(
+            Path::new(dir).join(format!("{}_replayable", usize::from(*idx))),
+            content,
+        )?;
+    }
+    info!("Exported {} corpus testcases to {}", inputs.len(), dir);
+    Ok(inputs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{
+        corpus::{Corpus, Testcase},
+        state::HasCorpus,
+    };
+
+    use super::*;
+    use crate::evm::types::{EVMAddress, EVMU256};
+
+    #[test]
+    fn test_export_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ityfuzz_corpus_export_{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let mut state = EVMFuzzState::new(0);
+        let txns = (1..=2u8)
+            .map(|i| ConciseEVMInput {
+                caller: EVMAddress::from_slice(&[i; 20]),
+                contract: EVMAddress::from_slice(&[i + 10; 20]),
+                txn_value: Some(EVMU256::from(i)),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        for txn in &txns {
+            let (input, _) = txn.to_input(Default::default());
+            state.corpus_mut().add(Testcase::new(input)).unwrap();
+        }
+
+        assert_eq!(export_corpus(dir, &mut state).unwrap(), 2);
+        for (idx, txn) in txns.iter().enumerate() {
+            let content = fs::read_to_string(Path::new(dir).join(format!("{}_replayable", idx))).unwrap();
+            let lines = content.lines().collect::<Vec<_>>();
+            assert_eq!(lines.len(), 1);
+            let read = ConciseEVMInput::deserialize_concise(lines[0].as_bytes());
+            assert_eq!(read.caller, txn.caller);
+            assert_eq!(read.contract, txn.contract);
+            assert_eq!(read.txn_value, txn.txn_value);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,6 +6,7 @@ use std::{
     ops::Deref,
     path::Path,
     rc::Rc,
+    str::FromStr,
     time::Duration,
 };
 
@@ -21,26 +22,41 @@ use libafl::{
 use libafl_bolts::impl_serdeany;
 use revm_primitives::{Bytecode, Env};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use super::{scheduler::ABIScheduler, srcmap::SOURCE_MAP_PROVIDER};
 /// Utilities to initialize the corpus
 /// Add all potential calls with default args to the corpus
-use crate::evm::abi::{get_abi_type_boxed, BoxedABI};
+use crate::evm::abi::{get_abi_type_boxed, ARaw, BoxedABI};
 #[cfg(feature = "print_txn_corpus")]
 use crate::fuzzer::DUMP_FILE_COUNT;
 use crate::{
     dump_txn,
     evm::{
+        arg_pins::ArgPinMetadata,
         blaz::builder::BuildJobResult,
         bytecode_analyzer,
         contract_utils::{extract_sig_from_contract, ABIConfig, ContractLoader},
-        input::{ConciseEVMInput, EVMInput, EVMInputTy},
+        function_filter::FunctionFilterMetadata,
+        input::{CallValueMetadata, ConciseEVMInput, EVMInput, EVMInputTy},
         middlewares::cheatcode::CHEATCODE_ADDRESS,
         mutator::AccessPattern,
-        onchain::{abi_decompiler::fetch_abi_heimdall, flashloan::register_borrow_txn, BLACKLIST_ADDR},
+        onchain::{
+            abi_decompiler::fetch_abi_heimdall,
+            flashloan::{register_borrow_txn, TokenContextMetadata},
+            BLACKLIST_ADDR,
+        },
+        oracles::view_probe::register_view_function,
+        permit::{caller_key, CallerKeyMetadata},
+        predeploys::Predeploy,
         presets::Preset,
+        selector_stats::SelectorExecutionMetadata,
+        tokens::{
+            balance_slot::{probe_balance_slot, BalanceSlotMetadata, BALANCE_OF},
+            DEFAULT_DECIMALS,
+        },
         types::{
+            checksum,
             fixed_address,
             EVMAddress,
             EVMExecutionResult,
@@ -60,6 +76,12 @@ use crate::{
 
 pub const INITIAL_BALANCE: u128 = 100_000_000_000_000_000_000; // 100 ether
 
+/// owner()
+const OWNER: [u8; 4] = [0x8d, 0xa5, 0xcb, 0x5b];
+/// Slot of the owner of OpenZeppelin's `OwnableUpgradeable`, i.e., the ERC-7201
+/// namespace `openzeppelin.storage.Ownable`
+const OWNABLE_STORAGE_SLOT: &str = "0x9016d09d72d40fdae2fd8ceac6b6234c7706214fd39c1cd1e609a0528c199300";
+
 pub struct EVMCorpusInitializer<'a, SC, ISC>
 where
     SC: ABIScheduler<State = EVMFuzzState> + Clone,
@@ -72,6 +94,51 @@ where
     #[cfg(feature = "use_presets")]
     presets: Vec<&'a dyn Preset<EVMInput, EVMState, SC>>,
     work_dir: String,
+    caller_balance: EVMU256,
+    /// ABIs recovered from the bytecode of contracts without ABI
+    recovered_abis: HashMap<EVMAddress, Vec<ABIConfig>>,
+    /// Contracts also fuzzed with raw calldata, and the maximum calldata length
+    raw_calldata_targets: HashSet<EVMAddress>,
+    raw_calldata_max_len: usize,
+    /// Value of the plain ETH transfers and raw calldata sent to each target
+    /// to reach `receive()` / `fallback()`, `None` to not send them
+    fuzz_fallback: Option<EVMU256>,
+    /// Probe the balances mapping slot of tokens, see
+    /// [`crate::evm::tokens::balance_slot`]
+    probe_balance_slots: bool,
+    /// Write the private keys of the callers to the work dir
+    dump_caller_keys: bool,
+    /// Contract orchestrating exploits, also a caller
+    attacker_contract: Option<EVMAddress>,
+    /// Largest holders of tokens, also callers
+    top_holders: Vec<EVMAddress>,
+    /// Predeploys and precompiles of the chain, installed as stubs
+    predeploys: Vec<Predeploy>,
+}
+
+/// A function of a recovered ABI, as dumped by
+/// [`EVMCorpusInitializer::dump_recovered_abi`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecoveredFunction {
+    pub selector: String,
+    /// Name of the function, if the selector is known
+    pub name: String,
+    /// Inferred argument types, e.g., `(uint256,address)`
+    pub args: String,
+    pub is_static: bool,
+    pub is_payable: bool,
+}
+
+impl From<&ABIConfig> for RecoveredFunction {
+    fn from(abi: &ABIConfig) -> Self {
+        Self {
+            selector: format!("0x{}", hex::encode(abi.function)),
+            name: abi.function_name.clone(),
+            args: abi.abi.clone(),
+            is_static: abi.is_static,
+            is_payable: abi.is_payable,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -176,9 +243,189 @@ where
             #[cfg(feature = "use_presets")]
             presets: vec![],
             work_dir,
+            caller_balance: EVMU256::from(INITIAL_BALANCE),
+            recovered_abis: HashMap::new(),
+            raw_calldata_targets: HashSet::new(),
+            raw_calldata_max_len: 0,
+            fuzz_fallback: None,
+            probe_balance_slots: false,
+            dump_caller_keys: false,
+            attacker_contract: None,
+            top_holders: vec![],
+            predeploys: vec![],
         }
     }
 
+    /// Also fuzz these contracts with raw calldata of at most `max_len` bytes,
+    /// which is mutated as opaque bytes instead of ABI-encoded args
+    pub fn set_raw_calldata_targets(&mut self, targets: HashSet<EVMAddress>, max_len: usize) {
+        self.raw_calldata_targets = targets;
+        self.raw_calldata_max_len = max_len;
+    }
+
+    /// Also send plain ETH transfers (empty calldata) and raw calldata to each
+    /// target whose ABI does not declare `receive()` / `fallback()`, so that
+    /// their logic is fuzzed. The value starts at the first nonzero
+    /// interesting value of `call_value` and is mutated like the others.
+    pub fn set_fuzz_fallback(&mut self, call_value: &CallValueMetadata) {
+        let value = call_value
+            .interesting_values
+            .iter()
+            .find(|value| **value > EVMU256::ZERO)
+            .cloned()
+            .unwrap_or(EVMU256::ZERO);
+        self.fuzz_fallback = Some(value);
+    }
+
+    /// Deploy `attacker` with the targets and send transactions from it too,
+    /// so that the profits it keeps are accounted
+    pub fn set_attacker_contract(&mut self, attacker: EVMAddress) {
+        self.attacker_contract = Some(attacker);
+    }
+
+    /// Also send transactions from these holders of tokens, whose balances
+    /// are read from the chain
+    pub fn set_top_holders(&mut self, holders: Vec<EVMAddress>) {
+        self.top_holders = holders;
+    }
+
+    /// Install the predeploys and precompiles of the chain, see
+    /// [`crate::evm::predeploys`]
+    pub fn set_predeploys(&mut self, predeploys: Vec<Predeploy>) {
+        self.predeploys = predeploys;
+    }
+
+    /// Addresses of the contracts whose ABI is recovered from the bytecode
+    pub fn recovered_abi_addresses(&self) -> Vec<EVMAddress> {
+        self.recovered_abis.keys().cloned().collect()
+    }
+
+    /// Dump the ABI recovered from the bytecode of `addr` (selectors and
+    /// inferred argument types) to a JSON file at `path`
+    pub fn dump_recovered_abi(&self, addr: &EVMAddress, path: &str) -> std::io::Result<()> {
+        let abis = self.recovered_abis.get(addr).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no ABI is recovered for {:?}", addr),
+            )
+        })?;
+        let functions = abis.iter().map(RecoveredFunction::from).collect_vec();
+        let mut file = File::create(path)?;
+        file.write_all(serde_json::to_string_pretty(&functions)?.as_bytes())
+    }
+
+    /// Find the balances mapping slot of the tokens whose slot is not given
+    pub fn set_probe_balance_slots(&mut self, probe: bool) {
+        self.probe_balance_slots = probe;
+    }
+
+    /// Write the private keys of the callers to `<work_dir>/caller_keys.json`,
+    /// e.g., to sign permits when replaying
+    pub fn set_dump_caller_keys(&mut self, dump: bool) {
+        self.dump_caller_keys = dump;
+    }
+
+    /// Record the balances mapping slot of `token`, if `balanceOf` reads it
+    fn probe_balance_slot(&mut self, token: EVMAddress) {
+        let meta = self
+            .state
+            .metadata_map_mut()
+            .get_or_insert_with(BalanceSlotMetadata::default);
+        // probed now rather than with the first execution
+        meta.pending.retain(|pending| *pending != token);
+        if meta.slots.contains_key(&token) {
+            return;
+        }
+        let vm_state = self.executor.host.evmstate.clone();
+        let found = probe_balance_slot(&vm_state, token, |calls, probed| {
+            self.executor.fast_static_call(calls, probed, self.state)
+        });
+        self.executor.host.evmstate = vm_state;
+        match found {
+            Some(slot) => {
+                info!("Found the balances slot of {:?}: {:?}", token, slot);
+                self.state
+                    .metadata_map_mut()
+                    .get_mut::<BalanceSlotMetadata>()
+                    .unwrap()
+                    .slots
+                    .insert(token, slot);
+            }
+            None => debug!("No balances slot found for {:?}, using balanceOf", token),
+        }
+    }
+
+    /// Measure the buy and sell taxes of `token`, if it is a token with a swap
+    /// route, and warn if it is a honeypot
+    fn measure_token_taxes(&mut self, token: EVMAddress) {
+        let Some(flashloan_mid) = self.executor.host.flashloan_middleware.clone() else {
+            return;
+        };
+        let flashloan_oracle = flashloan_mid.deref().borrow().flashloan_oracle.clone();
+        let Some(mut token_ctx) = flashloan_oracle.deref().borrow().known_tokens.get(&token).cloned() else {
+            return;
+        };
+        if token_ctx.is_weth || token_ctx.swaps.is_empty() {
+            return;
+        }
+        // 0.1 WETH
+        let decimals = token_ctx.weth_decimals.unwrap_or(DEFAULT_DECIMALS).saturating_sub(1);
+        let amount = EVMU256::from(10).pow(EVMU256::from(decimals));
+        token_ctx.measure_taxes(amount, self.state, self.executor);
+
+        match token_ctx.honeypot_reason() {
+            Some(reason) => warn!("{} is a honeypot: {}", checksum(&token), reason),
+            None => debug!(
+                "taxes of {}: buy {:?} bps, sell {:?} bps",
+                checksum(&token),
+                token_ctx.buy_tax_bps,
+                token_ctx.sell_tax_bps
+            ),
+        }
+        if let Some(ctx) = flashloan_oracle.deref().borrow_mut().known_tokens.get_mut(&token) {
+            ctx.buy_tax_bps = token_ctx.buy_tax_bps;
+            ctx.sell_tax_bps = token_ctx.sell_tax_bps;
+        }
+        if let Some(ctx) = self
+            .state
+            .metadata_map_mut()
+            .get_mut::<TokenContextMetadata>()
+            .and_then(|meta| meta.contexts.get_mut(&token))
+        {
+            ctx.buy_tax_bps = token_ctx.buy_tax_bps;
+            ctx.sell_tax_bps = token_ctx.sell_tax_bps;
+        }
+    }
+
+    /// Owner of `contract`, returned by `owner()` or kept in the storage of
+    /// `OwnableUpgradeable`
+    fn detect_owner(&mut self, contract: EVMAddress) -> Option<EVMAddress> {
+        let vm_state = self.executor.host.evmstate.clone();
+        let res = self
+            .executor
+            .fast_static_call(&[(contract, Bytes::from(OWNER.to_vec()))], &vm_state, self.state);
+        self.executor.host.evmstate = vm_state;
+
+        // an address, other than zero or the contract itself
+        let as_owner = |word: EVMU256| {
+            let bytes = word.to_be_bytes::<32>();
+            let owner = EVMAddress::from_slice(&bytes[12..]);
+            (bytes[..12].iter().all(|b| *b == 0) && !owner.is_zero() && owner != contract).then_some(owner)
+        };
+        res.first()
+            .filter(|ret| ret.len() == 32)
+            .and_then(|ret| as_owner(EVMU256::from_be_slice(ret)))
+            .or_else(|| {
+                let slot = EVMU256::from_str(OWNABLE_STORAGE_SLOT).unwrap();
+                self.executor.host.evmstate.sload(contract, slot).and_then(as_owner)
+            })
+    }
+
+    /// Set the initial ETH balance of callers (Default: [`INITIAL_BALANCE`])
+    pub fn set_caller_balance(&mut self, balance: EVMU256) {
+        self.caller_balance = balance;
+    }
+
     #[cfg(feature = "use_presets")]
     pub fn register_preset(&mut self, preset: &'a dyn Preset<EVMInput, EVMState, SC>) {
         self.presets.push(preset);
@@ -189,6 +436,7 @@ where
         self.setup_default_callers(loader);
         self.setup_contract_callers(loader);
         self.init_cheatcode_contract();
+        self.init_predeploys();
         self.initialize_contract(loader);
         self.initialize_source_map(loader);
         self.initialize_corpus(loader)
@@ -231,6 +479,11 @@ where
             if deployed_address != CHEATCODE_ADDRESS {
                 self.state.add_address(&deployed_address);
             }
+            if self.attacker_contract == Some(deployed_address) {
+                info!("Using {} as the attacker contract", contract.name);
+                self.state.add_caller(&deployed_address);
+                self.fund_caller(deployed_address);
+            }
         }
         info!("Deployed all contracts\n");
     }
@@ -291,6 +544,14 @@ where
             env: artifacts.initial_env.clone(),
         });
 
+        // target senders given by Foundry replace the callers, owners included
+        let detect_owners = loader
+            .setup_data
+            .as_ref()
+            .map_or(true, |setup_data| setup_data.target_senders.is_empty());
+        let mut owners_detected = 0;
+        let mut owners_probed = 0;
+
         for contract in &mut loader.contracts {
             if contract.abi.is_empty() {
                 // this contract's abi is not available, we will use 3 layers to handle this
@@ -327,6 +588,22 @@ where
                         .collect_vec();
                     contract.abi = abis;
                 }
+                if let Some(forced_abi) = loader.forced_abis.get(&contract.deployed_address) {
+                    info!("Using the forced ABI of {}", contract.name);
+                    let forced_sigs: HashSet<[u8; 4]> = forced_abi.iter().map(|abi| abi.function).collect();
+                    contract.abi = forced_abi
+                        .iter()
+                        .cloned()
+                        .chain(
+                            contract
+                                .abi
+                                .drain(..)
+                                .filter(|abi| !forced_sigs.contains(&abi.function)),
+                        )
+                        .collect_vec();
+                }
+                self.recovered_abis
+                    .insert(contract.deployed_address, contract.abi.clone());
             }
 
             artifacts
@@ -342,7 +619,9 @@ where
 
             let mut name = contract.name.clone().trim_end_matches('*').to_string();
             if name != format!("{:?}", contract.deployed_address) {
-                name = format!("{}({:?})", name, contract.deployed_address.clone());
+                name = format!("{}({})", name, checksum(&contract.deployed_address));
+            } else {
+                name = checksum(&contract.deployed_address);
             }
             artifacts.address_to_name.insert(contract.deployed_address, name);
 
@@ -360,6 +639,10 @@ where
                     contract.abi.clone()
                 );
             }
+            if self.probe_balance_slots && contract.abi.iter().any(|abi| abi.function == BALANCE_OF) {
+                self.probe_balance_slot(contract.deployed_address);
+            }
+            self.measure_token_taxes(contract.deployed_address);
 
             if unsafe {
                 BLACKLIST_ADDR.is_some() && BLACKLIST_ADDR.as_ref().unwrap().contains(&contract.deployed_address)
@@ -367,6 +650,22 @@ where
                 continue;
             }
 
+            if detect_owners {
+                owners_probed += 1;
+                match self.detect_owner(contract.deployed_address) {
+                    Some(owner) => {
+                        info!(
+                            "Detected owner {} of {}, calling it more often",
+                            checksum(&owner),
+                            checksum(&contract.deployed_address)
+                        );
+                        self.state.add_privileged_caller(&owner);
+                        owners_detected += 1;
+                    }
+                    None => debug!("No owner detected for {}", checksum(&contract.deployed_address)),
+                }
+            }
+
             let mut target_sig = None; // none means all selectors are targeted
 
             if let Some(setup_data) = &loader.setup_data {
@@ -413,8 +712,33 @@ where
                     }
                 }
 
+                if let Some(filter) = self.state.metadata_map().get::<FunctionFilterMetadata>() &&
+                    !filter.is_allowed(&contract.deployed_address, &abi.function)
+                {
+                    debug!("Skipping function because of function filter: {}", name);
+                    continue;
+                }
+
                 self.add_abi(&abi, contract.deployed_address, &mut artifacts);
             }
+
+            if self.raw_calldata_targets.contains(&contract.deployed_address) {
+                self.add_raw_calldata(contract.deployed_address, EVMU256::ZERO, &mut artifacts);
+            }
+
+            if let Some(value) = self.fuzz_fallback {
+                let declares = |name: &str| contract.abi.iter().any(|abi| abi.function_name == name);
+                if !declares("!receive!") {
+                    self.add_plain_transfer(contract.deployed_address, value, &mut artifacts);
+                }
+                if !declares("!fallback!") && !self.raw_calldata_targets.contains(&contract.deployed_address) {
+                    self.add_raw_calldata(contract.deployed_address, value, &mut artifacts);
+                }
+            }
+        }
+
+        if detect_owners {
+            info!("Detected the owner of {}/{} contracts", owners_detected, owners_probed);
         }
 
         let mut tc = Testcase::new(artifacts.initial_state.clone());
@@ -432,15 +756,18 @@ where
     }
 
     pub fn setup_default_callers(&mut self, loader: &mut ContractLoader) {
+        // holders act with their real balances, which are not profits
+        for holder in self.top_holders.clone() {
+            self.state.add_bystander_caller(&holder);
+            self.fund_caller(holder);
+        }
+
         // We override default callers when target senders are specified
         if let Some(setup_data) = &loader.setup_data {
             if !setup_data.target_senders.is_empty() {
                 for caller in setup_data.target_senders.iter() {
                     self.state.add_caller(caller);
-                    self.executor
-                        .host
-                        .evmstate
-                        .set_balance(*caller, EVMU256::from(INITIAL_BALANCE));
+                    self.fund_caller(*caller);
                 }
                 return;
             }
@@ -454,11 +781,48 @@ where
 
         for caller in default_callers {
             self.state.add_caller(&caller);
-            self.executor
-                .host
-                .evmstate
-                .set_balance(caller, EVMU256::from(INITIAL_BALANCE));
+            self.fund_caller(caller);
         }
+
+        // a caller with a known private key, whose permits are signed, see
+        // [`crate::evm::permit`]
+        let mut keys = CallerKeyMetadata::new();
+        let caller = keys.add(caller_key(0));
+        self.state.add_caller(&caller);
+        self.fund_caller(caller);
+        if self.dump_caller_keys {
+            match self.write_caller_keys(&keys) {
+                Ok(path) => info!("Wrote the private keys of the callers to {}", path),
+                Err(e) => error!("Failed to dump the private keys of callers: {}", e),
+            }
+        }
+        self.state.metadata_map_mut().insert(keys);
+    }
+
+    /// Give `caller` the initial balance of callers, unless its balance is
+    /// already set (e.g., by a state override)
+    fn fund_caller(&mut self, caller: EVMAddress) {
+        self.executor
+            .host
+            .evmstate
+            .balance
+            .entry(caller)
+            .or_insert(self.caller_balance);
+    }
+
+    /// Dump the private keys of the callers to `<work_dir>/caller_keys.json`,
+    /// returns the path written
+    fn write_caller_keys(&self, keys: &CallerKeyMetadata) -> std::io::Result<String> {
+        let keys = keys
+            .keys
+            .iter()
+            .map(|(caller, key)| (checksum(caller), format!("0x{}", hex::encode(key))))
+            .collect::<HashMap<_, _>>();
+        std::fs::create_dir_all(&self.work_dir)?;
+        let path = format!("{}/caller_keys.json", self.work_dir);
+        let mut file = File::create(&path)?;
+        file.write_all(serde_json::to_string_pretty(&keys)?.as_bytes())?;
+        Ok(path)
     }
 
     pub fn setup_contract_callers(&mut self, loader: &mut ContractLoader) {
@@ -481,10 +845,7 @@ where
             self.executor
                 .host
                 .set_code(caller, Bytecode::new_raw(Bytes::from(vec![0xfd, 0x00])), self.state);
-            self.executor
-                .host
-                .evmstate
-                .set_balance(caller, EVMU256::from(INITIAL_BALANCE));
+            self.executor.host.evmstate.set_balance(caller, self.caller_balance);
         }
     }
 
@@ -496,6 +857,17 @@ where
         );
     }
 
+    fn init_predeploys(&mut self) {
+        for predeploy in &self.predeploys {
+            info!("Installing {} at {:?}", predeploy.name, predeploy.address);
+            self.executor.host.set_code(
+                predeploy.address,
+                Bytecode::new_raw(Bytes::from(predeploy.bytecode())),
+                self.state,
+            );
+        }
+    }
+
     fn add_abi(&mut self, abi: &ABIConfig, deployed_address: EVMAddress, artifacts: &mut EVMInitializationArtifacts) {
         if abi.is_constructor {
             return;
@@ -511,12 +883,20 @@ where
                     .insert(abi.function, HashSet::from([deployed_address]));
             }
         }
+        register_view_function(deployed_address, abi, self.state);
         #[cfg(not(feature = "fuzz_static"))]
         if abi.is_static {
             return;
         }
+        self.state
+            .metadata_map_mut()
+            .get_or_insert_with(SelectorExecutionMetadata::default)
+            .register(deployed_address, abi);
         let mut abi_instance = get_abi_type_boxed(&abi.abi);
         abi_instance.set_func_with_signature(abi.function, &abi.function_name, &abi.abi);
+        if let Some(pins) = self.state.metadata_map().get::<ArgPinMetadata>() {
+            pins.apply(&deployed_address, &mut abi_instance);
+        }
 
         artifacts
             .address_to_abi_object
@@ -543,6 +923,9 @@ where
             randomness: vec![0],
             repeat: 1,
             swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
         };
         add_input_to_corpus!(self.state, &mut self.scheduler, input.clone(), artifacts);
         #[cfg(feature = "print_txn_corpus")]
@@ -561,4 +944,72 @@ where
             }
         }
     }
+
+    /// Add a transaction sending `value` with empty calldata, to reach
+    /// `receive()` (or `fallback()` without `receive()`)
+    fn add_plain_transfer(
+        &mut self,
+        deployed_address: EVMAddress,
+        value: EVMU256,
+        artifacts: &mut EVMInitializationArtifacts,
+    ) {
+        let input = EVMInput {
+            caller: self.state.get_rand_caller(),
+            contract: deployed_address,
+            data: None,
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: Some(value),
+            step: false,
+            env: artifacts.initial_env.clone(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            input_type: EVMInputTy::ABI,
+            direct_data: Default::default(),
+            randomness: vec![0],
+            repeat: 1,
+            swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
+        };
+        add_input_to_corpus!(self.state, &mut self.scheduler, input, artifacts);
+    }
+
+    /// Add a transaction sending `value` whose whole calldata is mutated as
+    /// raw bytes, to reach fallback logic and non-ABI dispatchers
+    fn add_raw_calldata(
+        &mut self,
+        deployed_address: EVMAddress,
+        value: EVMU256,
+        artifacts: &mut EVMInitializationArtifacts,
+    ) {
+        let abi_instance = BoxedABI::new(Box::new(ARaw::new(self.raw_calldata_max_len)));
+        artifacts
+            .address_to_abi_object
+            .entry(deployed_address)
+            .or_default()
+            .push(abi_instance.clone());
+        let input = EVMInput {
+            caller: self.state.get_rand_caller(),
+            contract: deployed_address,
+            data: Some(abi_instance),
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: Some(value),
+            step: false,
+            env: artifacts.initial_env.clone(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            input_type: EVMInputTy::ABI,
+            direct_data: Default::default(),
+            randomness: vec![0],
+            repeat: 1,
+            swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
+        };
+        add_input_to_corpus!(self.state, &mut self.scheduler, input, artifacts);
+    }
 }
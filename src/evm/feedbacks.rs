@@ -1,5 +1,6 @@
 use std::{
     cell::RefCell,
+    collections::HashMap,
     fmt::{Debug, Formatter},
     ops::Deref,
     rc::Rc,
@@ -18,7 +19,7 @@ use libafl_bolts::Named;
 
 use super::{input::EVMInput, types::EVMFuzzState};
 use crate::{
-    evm::{input::ConciseEVMInput, middlewares::sha3_bypass::Sha3TaintAnalysis, vm::EVMExecutor},
+    evm::{input::ConciseEVMInput, middlewares::sha3_bypass::Sha3TaintAnalysis, types::EVMAddress, vm::EVMExecutor},
     generic_vm::vm_state::VMStateT,
     input::VMInputT,
 };
@@ -142,3 +143,90 @@ where
         todo!()
     }
 }
+
+/// Marks inputs as interesting when they use more gas than any previous input
+/// calling the same function, steering the fuzzer towards unbounded loops and
+/// storage blowups (i.e., gas griefing).
+#[allow(clippy::type_complexity)]
+pub struct MaxGasFeedback<VS, SC>
+where
+    VS: VMStateT,
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    /// Max gas used by each function, keyed by (contract, selector)
+    pub max_gas: HashMap<(EVMAddress, [u8; 4]), u64>,
+    pub evm_executor: Rc<RefCell<EVMExecutor<VS, ConciseEVMInput, SC>>>,
+    pub enabled: bool,
+}
+
+impl<VS, SC> MaxGasFeedback<VS, SC>
+where
+    VS: VMStateT,
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    #[allow(clippy::type_complexity)]
+    pub fn new(evm_executor: Rc<RefCell<EVMExecutor<VS, ConciseEVMInput, SC>>>, enabled: bool) -> Self {
+        Self {
+            max_gas: HashMap::new(),
+            evm_executor,
+            enabled,
+        }
+    }
+}
+
+impl<VS, SC> Feedback<EVMFuzzState> for MaxGasFeedback<VS, SC>
+where
+    VS: VMStateT + 'static,
+    SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut EVMFuzzState,
+        _manager: &mut EM,
+        input: &EVMInput,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = EVMFuzzState>,
+        OT: ObserversTuple<EVMFuzzState>,
+    {
+        if !self.enabled || input.is_step() {
+            return Ok(false);
+        }
+        let selector = match input.data {
+            Some(ref abi) => abi.function,
+            None => [0; 4],
+        };
+        let gas = self.evm_executor.deref().borrow().last_gas_used;
+        let max_gas = self.max_gas.entry((input.contract, selector)).or_insert(0);
+        if gas > *max_gas {
+            *max_gas = gas;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+impl<VS, SC> Named for MaxGasFeedback<VS, SC>
+where
+    VS: VMStateT,
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    fn name(&self) -> &str {
+        "MaxGasFeedback"
+    }
+}
+
+impl<VS, SC> Debug for MaxGasFeedback<VS, SC>
+where
+    VS: VMStateT,
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("MaxGasFeedback")
+            .field("max_gas", &self.max_gas)
+            .field("enabled", &self.enabled)
+            .finish()
+    }
+}
@@ -0,0 +1,143 @@
+//! Campaigns against several fork blocks, to find bugs that only exist before
+//! or after an on-chain upgrade.
+//!
+//! Each block is fuzzed by a sub-campaign in a child process, with the same
+//! arguments except for the fork block and the work dir
+//! (`<work_dir>/block_<number>`), so that the state is fetched at that block
+//! and its findings are tagged with it.
+
+use std::{
+    env,
+    fs::File,
+    io::Write,
+    path::Path,
+    process::{Child, Command},
+};
+
+use serde::Serialize;
+use tracing::{error, info};
+
+/// Arguments overridden for each sub-campaign, as (long name, short name)
+const OVERRIDDEN_ARGS: [(&str, Option<&str>); 3] = [
+    ("--fork-blocks", None),
+    ("--onchain-block-number", Some("-b")),
+    ("--work-dir", Some("-w")),
+];
+
+/// Result of the sub-campaign of a fork block
+#[derive(Debug, Serialize)]
+struct BlockSummary {
+    block: u64,
+    work_dir: String,
+    exit_code: Option<i32>,
+    findings: usize,
+}
+
+/// Drop the overridden arguments (and their values) from `args`
+fn strip_overridden_args(args: &[String]) -> Vec<String> {
+    let mut res = vec![];
+    let mut skip_value = false;
+    for arg in args {
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        let overridden = OVERRIDDEN_ARGS.iter().find(|(long, short)| {
+            arg == long || Some(arg.as_str()) == *short || arg.starts_with(&format!("{}=", long))
+        });
+        match overridden {
+            Some((long, _)) if !arg.starts_with(&format!("{}=", long)) => skip_value = true,
+            Some(_) => {}
+            None => res.push(arg.clone()),
+        }
+    }
+    res
+}
+
+fn count_findings(work_dir: &str) -> usize {
+    std::fs::read_to_string(Path::new(work_dir).join("vuln_info.jsonl"))
+        .map(|content| content.lines().filter(|line| !line.trim().is_empty()).count())
+        .unwrap_or(0)
+}
+
+/// Run a sub-campaign for each block concurrently and wait for all of them.
+/// The findings of each block are summarized in
+/// `<work_dir>/fork_blocks.json`. Returns the exit code, which is nonzero if
+/// any sub-campaign failed.
+pub fn run_fork_block_campaigns(blocks: &[u64], work_dir: &str) -> i32 {
+    let args = strip_overridden_args(&env::args().skip(1).collect::<Vec<_>>());
+    let exe = env::current_exe().expect("failed to locate the ityfuzz executable");
+
+    let children: Vec<(u64, String, std::io::Result<Child>)> = blocks
+        .iter()
+        .map(|block| {
+            let block_dir = format!("{}/block_{}", work_dir, block);
+            info!("Fuzzing at fork block {} in {}", block, block_dir);
+            let child = Command::new(&exe)
+                .args(&args)
+                .arg("--onchain-block-number")
+                .arg(block.to_string())
+                .arg("--work-dir")
+                .arg(&block_dir)
+                .spawn();
+            (*block, block_dir, child)
+        })
+        .collect();
+
+    let mut exit_code = 0;
+    let mut summaries = vec![];
+    for (block, block_dir, child) in children {
+        let status = match child.and_then(|mut child| child.wait()) {
+            Ok(status) => status.code(),
+            Err(e) => {
+                error!("Sub-campaign of fork block {} failed: {}", block, e);
+                None
+            }
+        };
+        let findings = count_findings(&block_dir);
+        info!("Fork block {}: {} finding(s)", block, findings);
+        if status != Some(0) {
+            exit_code = status.unwrap_or(1);
+        }
+        summaries.push(BlockSummary {
+            block,
+            work_dir: block_dir,
+            exit_code: status,
+            findings,
+        });
+    }
+
+    let summary_path = format!("{}/fork_blocks.json", work_dir);
+    let written = std::fs::create_dir_all(work_dir)
+        .and_then(|_| File::create(&summary_path))
+        .and_then(|mut file| file.write_all(serde_json::to_string_pretty(&summaries)?.as_bytes()));
+    if let Err(e) = written {
+        error!("Failed to write {}: {}", summary_path, e);
+    }
+    exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_overridden_args() {
+        let args = [
+            "evm",
+            "-t",
+            "0x1",
+            "--fork-blocks",
+            "1,2",
+            "-b",
+            "3",
+            "--work-dir=dir",
+            "-c",
+            "eth",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        assert_eq!(strip_overridden_args(&args), vec!["evm", "-t", "0x1", "-c", "eth"]);
+    }
+}
@@ -0,0 +1,150 @@
+//! Allowlist / denylist of target functions to focus fuzzing on
+
+use std::str::FromStr;
+
+use crypto::{digest::Digest, sha3::Sha3};
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+
+use crate::evm::types::EVMAddress;
+
+/// A function of the targets, either a selector of any contract or a
+/// selector of a specific contract
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FunctionPattern {
+    Selector([u8; 4]),
+    Function(EVMAddress, [u8; 4]),
+}
+
+impl FunctionPattern {
+    pub fn matches(&self, address: &EVMAddress, selector: &[u8; 4]) -> bool {
+        match self {
+            FunctionPattern::Selector(s) => s == selector,
+            FunctionPattern::Function(addr, s) => addr == address && s == selector,
+        }
+    }
+}
+
+/// Parse a selector given either in hex (`0x715018a6`) or as a function
+/// signature (`renounceOwnership()`)
+fn parse_selector(s: &str) -> Result<[u8; 4], String> {
+    if s.contains('(') {
+        let mut hasher = Sha3::keccak256();
+        hasher.input_str(s);
+        let mut hash = [0u8; 32];
+        hasher.result(&mut hash);
+        return Ok(hash[..4].try_into().unwrap());
+    }
+    let bytes = hex::decode(s.trim_start_matches("0x")).map_err(|e| format!("invalid selector {}: {}", s, e))?;
+    bytes.try_into().map_err(|_| format!("selector {} is not 4 bytes", s))
+}
+
+impl FromStr for FunctionPattern {
+    type Err = String;
+
+    /// Format: `selector` or `address:selector`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.split_once(':') {
+            Some((address, selector)) => Ok(FunctionPattern::Function(
+                EVMAddress::from_str(address.trim()).map_err(|_| format!("invalid address {}", address))?,
+                parse_selector(selector.trim())?,
+            )),
+            None => Ok(FunctionPattern::Selector(parse_selector(s)?)),
+        }
+    }
+}
+
+/// Split a comma separated list whose items may contain function
+/// signatures, the commas inside their parentheses do not separate items
+pub fn split_list(s: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (idx, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&s[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&s[start..]);
+    items
+}
+
+/// Decides which functions of the targets are fuzzed. Denied functions are
+/// never fuzzed, and when the allowlist is not empty, only the allowed
+/// functions are fuzzed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FunctionFilterMetadata {
+    pub allow: Vec<FunctionPattern>,
+    pub deny: Vec<FunctionPattern>,
+}
+
+impl_serdeany!(FunctionFilterMetadata);
+
+impl FunctionFilterMetadata {
+    pub fn new(allow: Vec<FunctionPattern>, deny: Vec<FunctionPattern>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Parse a comma separated list of patterns. Commas inside the
+    /// parentheses of a function signature do not separate patterns.
+    pub fn parse_patterns(s: &str) -> Result<Vec<FunctionPattern>, String> {
+        split_list(s)
+            .into_iter()
+            .filter(|p| !p.trim().is_empty())
+            .map(FunctionPattern::from_str)
+            .collect()
+    }
+
+    pub fn is_allowed(&self, address: &EVMAddress, selector: &[u8; 4]) -> bool {
+        if self.deny.iter().any(|p| p.matches(address, selector)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|p| p.matches(address, selector))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_patterns() {
+        let patterns = FunctionFilterMetadata::parse_patterns(
+            "0x715018a6,transfer(address,uint256), 0x0000000000000000000000000000000000000001:0xa9059cbb",
+        )
+        .unwrap();
+        assert_eq!(patterns.len(), 3);
+        assert_eq!(patterns[0], FunctionPattern::Selector([0x71, 0x50, 0x18, 0xa6]));
+        assert_eq!(patterns[1], FunctionPattern::Selector([0xa9, 0x05, 0x9c, 0xbb]));
+        assert_eq!(
+            patterns[2],
+            FunctionPattern::Function(
+                EVMAddress::from_str("0x0000000000000000000000000000000000000001").unwrap(),
+                [0xa9, 0x05, 0x9c, 0xbb]
+            )
+        );
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let target = EVMAddress::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        let other = EVMAddress::from_str("0x0000000000000000000000000000000000000002").unwrap();
+        let transfer = [0xa9, 0x05, 0x9c, 0xbb];
+        let renounce = [0x71, 0x50, 0x18, 0xa6];
+
+        let filter = FunctionFilterMetadata::new(vec![], vec![FunctionPattern::Selector(renounce)]);
+        assert!(filter.is_allowed(&target, &transfer));
+        assert!(!filter.is_allowed(&target, &renounce));
+
+        let filter = FunctionFilterMetadata::new(vec![FunctionPattern::Function(target, transfer)], vec![]);
+        assert!(filter.is_allowed(&target, &transfer));
+        assert!(!filter.is_allowed(&other, &transfer));
+        assert!(!filter.is_allowed(&target, &renounce));
+    }
+}
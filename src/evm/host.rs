@@ -74,12 +74,14 @@ use crate::{
         contract_utils::extract_sig_from_contract,
         corpus_initializer::ABIMap,
         input::{EVMInput, EVMInputTy},
+        logs::EVMLog,
         middlewares::middleware::{add_corpus, CallMiddlewareReturn, Middleware, MiddlewareType},
         mutator::AccessPattern,
         onchain::{
             abi_decompiler::fetch_abi_heimdall,
             flashloan::{register_borrow_txn, Flashloan},
         },
+        oracles::view_probe::register_view_function,
         types::{as_u64, generate_random_address, is_zero, EVMAddress, EVMU256},
         vm::{is_reverted_or_control_leak, EVMState, SinglePostExecution, IN_DEPLOY, IS_FAST_CALL_STATIC},
     },
@@ -111,6 +113,11 @@ pub static mut RET_SIZE: usize = 0;
 pub static mut RET_OFFSET: usize = 0;
 
 pub static mut PANIC_ON_BUG: bool = false;
+
+/// Track and enforce ETH balances of accounts instead of giving every account
+/// unlimited ETH. Enabled by the `real_balance` feature or by fuzzing with a
+/// fixed caller balance.
+pub static mut REAL_BALANCE: bool = cfg!(feature = "real_balance");
 // for debugging purpose, return ControlLeak when the calls amount exceeds this
 // value
 pub static mut CALL_UNTIL: u32 = u32::MAX;
@@ -158,6 +165,56 @@ pub fn is_precompile(address: EVMAddress, num_of_precompiles: usize) -> bool {
     num.wrapping_sub(1) < num_of_precompiles as u16
 }
 
+/// A successful `transferFrom(from, to, amount)` call observed during
+/// execution
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TransferFromCall {
+    pub token: EVMAddress,
+    /// `msg.sender` of the call, i.e., who spends the allowance
+    pub spender: EVMAddress,
+    pub from: EVMAddress,
+    pub to: EVMAddress,
+    pub amount: EVMU256,
+}
+
+/// Return data of a successful call observed during execution
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallReturn {
+    pub address: EVMAddress,
+    pub selector: [u8; 4],
+    pub data: Bytes,
+}
+
+/// A call frame entered during execution: whose storage it uses and whose
+/// code it runs
+#[derive(Clone, Debug, Default)]
+pub struct CallFrame {
+    pub address: EVMAddress,
+    pub code_address: EVMAddress,
+    /// Slots read by the frame
+    pub reads: Vec<EVMU256>,
+}
+
+impl CallFrame {
+    /// Whether the frame runs another account's code, i.e., DELEGATECALL
+    /// or CALLCODE
+    pub fn is_delegated(&self) -> bool {
+        self.address != self.code_address
+    }
+}
+
+/// An SSTORE observed during execution and the frame performing it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageWrite {
+    pub address: EVMAddress,
+    /// Code running when the slot is written, which differs from `address`
+    /// in delegated frames
+    pub code_address: EVMAddress,
+    pub slot: EVMU256,
+    pub prev: EVMU256,
+    pub value: EVMU256,
+}
+
 #[allow(clippy::type_complexity)]
 pub struct FuzzHost<SC>
 where
@@ -201,6 +258,23 @@ where
     pub current_self_destructs: Vec<(EVMAddress, usize)>,
     // arbitrary calls
     pub current_arbitrary_calls: Vec<(EVMAddress, EVMAddress, usize)>,
+    // transferFrom calls
+    pub current_transfer_froms: Vec<TransferFromCall>,
+    // logs emitted during the transaction
+    pub current_logs: Vec<EVMLog>,
+    // return data of calls during the transaction
+    pub current_call_returns: Vec<CallReturn>,
+    // call frames of the transaction, the first one is the top-level call
+    pub call_frames: Vec<CallFrame>,
+    // storage writes during the transaction, except the ones of reverted calls
+    pub current_storage_writes: Vec<StorageWrite>,
+    // slots read by accounts' own code before they delegatecall
+    pub current_delegating_reads: HashSet<(EVMAddress, EVMU256)>,
+    /// Record the storage writes, for the detectors inspecting them
+    pub record_storage_writes: bool,
+    /// Record the slots read before delegating, for the storage collision
+    /// detector
+    pub record_delegating_reads: bool,
     // integer_overflow
     pub current_integer_overflow: HashSet<(EVMAddress, usize, &'static str)>,
     // relations file handle
@@ -228,6 +302,9 @@ where
 
     /// Depth of call stack
     pub call_depth: u64,
+    /// Calls nested deeper than this fail instead of being executed, see
+    /// [`FuzzHost::set_max_call_depth`]
+    pub max_call_depth: u64,
     /// Prank information
     pub prank: Option<Prank>,
     /// Expected revert information
@@ -236,6 +313,11 @@ where
     pub expected_emits: VecDeque<ExpectedEmit>,
     /// Expected calls
     pub expected_calls: ExpectedCallTracker,
+    /// Gas spent by all interpreters of the current transaction. Subcalls
+    /// return no gas, so the gas forwarded to them is charged to the caller
+    /// as well and is tracked separately in `gas_forwarded`.
+    pub gas_spent: u64,
+    pub gas_forwarded: u64,
 }
 
 impl<SC> Debug for FuzzHost<SC>
@@ -289,6 +371,14 @@ where
             setcode_data: self.setcode_data.clone(),
             current_self_destructs: self.current_self_destructs.clone(),
             current_arbitrary_calls: self.current_arbitrary_calls.clone(),
+            current_transfer_froms: self.current_transfer_froms.clone(),
+            current_logs: self.current_logs.clone(),
+            current_call_returns: self.current_call_returns.clone(),
+            call_frames: self.call_frames.clone(),
+            current_storage_writes: self.current_storage_writes.clone(),
+            current_delegating_reads: self.current_delegating_reads.clone(),
+            record_storage_writes: self.record_storage_writes,
+            record_delegating_reads: self.record_delegating_reads,
             current_integer_overflow: self.current_integer_overflow.clone(),
             relations_file: self.relations_file.try_clone().unwrap(),
             relations_hash: self.relations_hash.clone(),
@@ -302,10 +392,13 @@ where
             mapping_sstore_pcs_to_slot: self.mapping_sstore_pcs_to_slot.clone(),
             jumpi_trace: self.jumpi_trace,
             call_depth: self.call_depth,
+            max_call_depth: self.max_call_depth,
             prank: self.prank.clone(),
             expected_emits: self.expected_emits.clone(),
             expected_revert: self.expected_revert.clone(),
             expected_calls: self.expected_calls.clone(),
+            gas_spent: 0,
+            gas_forwarded: 0,
         }
     }
 }
@@ -319,6 +412,12 @@ const UNBOUND_CALL_THRESHOLD: usize = 50;
 // unbounded
 const CONTROL_LEAK_THRESHOLD: usize = 50;
 
+// gas given to the callee of a value transfer on top of the forwarded gas
+const CALL_STIPEND: u64 = 2300;
+
+/// Maximum depth of nested calls allowed by the EVM
+pub const EVM_MAX_CALL_DEPTH: u64 = 1024;
+
 impl<SC> FuzzHost<SC>
 where
     SC: Scheduler<State = EVMFuzzState> + Clone,
@@ -349,6 +448,14 @@ where
             setcode_data: HashMap::new(),
             current_self_destructs: Default::default(),
             current_arbitrary_calls: Default::default(),
+            current_transfer_froms: Default::default(),
+            current_logs: Default::default(),
+            current_call_returns: Default::default(),
+            call_frames: Default::default(),
+            current_storage_writes: Default::default(),
+            current_delegating_reads: Default::default(),
+            record_storage_writes: false,
+            record_delegating_reads: false,
             current_integer_overflow: Default::default(),
             relations_file: std::fs::File::create(format!("{}/relations.log", workdir)).unwrap(),
             relations_hash: HashSet::new(),
@@ -362,10 +469,13 @@ where
             mapping_sstore_pcs_to_slot: Default::default(),
             jumpi_trace: 37,
             call_depth: 0,
+            max_call_depth: EVM_MAX_CALL_DEPTH,
             prank: None,
             expected_revert: None,
             expected_emits: VecDeque::new(),
             expected_calls: ExpectedCallTracker::new(),
+            gas_spent: 0,
+            gas_forwarded: 0,
         }
     }
 
@@ -373,6 +483,12 @@ where
         self.spec_id = SpecId::from(spec_id.as_str());
     }
 
+    /// Limit the depth of nested calls (Default: [`EVM_MAX_CALL_DEPTH`]). A
+    /// lower limit bounds the cost of deeply recursing executions.
+    pub fn set_max_call_depth(&mut self, max_call_depth: u64) {
+        self.max_call_depth = max_call_depth.min(EVM_MAX_CALL_DEPTH);
+    }
+
     /// custom spec id run_inspect
     pub fn run_inspect(&mut self, interp: &mut Interpreter, state: &mut EVMFuzzState) -> InstructionResult {
         match self.spec_id {
@@ -398,6 +514,11 @@ where
         }
     }
 
+    /// Gas used by the current transaction, including its subcalls
+    pub fn gas_used(&self) -> u64 {
+        self.gas_spent.saturating_sub(self.gas_forwarded)
+    }
+
     pub fn remove_all_middlewares(&mut self) {
         self.middlewares_enabled = false;
         self.middlewares.deref().borrow_mut().clear();
@@ -711,6 +832,7 @@ where
                     );
 
                     let ret = self.run_inspect(&mut interp, state);
+                    self.gas_spent += interp.gas.spent();
                     return (ret, Gas::new(0), interp.return_value());
                 }
             }
@@ -747,6 +869,7 @@ where
             );
 
             let ret = self.run_inspect(&mut interp, state);
+            self.gas_spent += interp.gas.spent();
             return (ret, Gas::new(0), interp.return_value());
         }
 
@@ -776,6 +899,19 @@ where
         }
     }
 
+    /// The innermost call frame, the top-level call of the transaction (at
+    /// `address`) if no call is entered yet
+    fn current_frame(&mut self, address: EVMAddress) -> &mut CallFrame {
+        if self.call_frames.is_empty() {
+            self.call_frames.push(CallFrame {
+                address,
+                code_address: address,
+                reads: vec![],
+            });
+        }
+        self.call_frames.last_mut().unwrap()
+    }
+
     /// Apply the prank
     pub fn apply_prank(&mut self, contract_caller: &EVMAddress, input: &mut CallInputs) {
         if let Some(prank) = &self.prank {
@@ -999,6 +1135,25 @@ where
                     interp.stack.data()[interp.stack.len() - 1 - $idx]
                 };
             }
+
+            // record operands of comparisons into the dictionary used by the mutator
+            #[cfg(feature = "cmp_dictionary")]
+            macro_rules! record_cmp {
+                ($v1:expr, $v2:expr) => {
+                    if $v1 != $v2 &&
+                        let Some(dict) = state
+                            .metadata_map_mut()
+                            .get_mut::<crate::mutation_utils::CmpDictionaryMetadata>()
+                    {
+                        dict.add($v1);
+                        dict.add($v2);
+                    }
+                };
+            }
+            #[cfg(not(feature = "cmp_dictionary"))]
+            macro_rules! record_cmp {
+                ($v1:expr, $v2:expr) => {};
+            }
             match *interp.instruction_pointer {
                 // 0xfd => {
                 //     debug!("fd {} @ {:?}", interp.program_counter(), interp.contract.address);
@@ -1086,6 +1241,7 @@ where
                     if abs_diff < CMP_MAP[idx] {
                         CMP_MAP[idx] = abs_diff;
                     }
+                    record_cmp!(v1, v2);
                 }
 
                 #[cfg(feature = "cmp")]
@@ -1106,6 +1262,7 @@ where
                     if abs_diff < CMP_MAP[idx] {
                         CMP_MAP[idx] = abs_diff;
                     }
+                    record_cmp!(v1, v2);
                 }
 
                 #[cfg(feature = "cmp")]
@@ -1122,6 +1279,13 @@ where
                     if abs_diff < CMP_MAP[idx] {
                         CMP_MAP[idx] = abs_diff;
                     }
+                    record_cmp!(v1, v2);
+                }
+
+                #[cfg(feature = "cmp_dictionary")]
+                0x03 => {
+                    // SUB
+                    record_cmp!(fast_peek!(0), fast_peek!(1));
                 }
 
                 0xf1 | 0xf2 | 0xf4 | 0xfa => {
@@ -1173,16 +1337,13 @@ where
     }
 
     fn balance(&mut self, address: EVMAddress) -> Option<(EVMU256, bool)> {
-        #[cfg(feature = "real_balance")]
-        {
+        if unsafe { REAL_BALANCE } {
             if let Some(balance) = self.evmstate.get_balance(&address) {
                 return Some((*balance, true));
             }
             self.evmstate.set_balance(address, self.next_slot);
             Some((self.next_slot, true))
-        }
-        #[cfg(not(feature = "real_balance"))]
-        {
+        } else {
             Some((EVMU256::MAX, true))
         }
     }
@@ -1203,6 +1364,12 @@ where
     }
 
     fn sload(&mut self, address: EVMAddress, index: EVMU256) -> Option<(EVMU256, bool)> {
+        if self.record_delegating_reads {
+            let frame = self.current_frame(address);
+            if !frame.is_delegated() {
+                frame.reads.push(index);
+            }
+        }
         if let Some(account) = self.evmstate.get_mut(&address) {
             if let Some(slot) = account.get(&index) {
                 return Some((*slot, true));
@@ -1224,6 +1391,22 @@ where
         index: EVMU256,
         value: EVMU256,
     ) -> Option<(EVMU256, EVMU256, EVMU256, bool)> {
+        if self.record_storage_writes {
+            let prev = self
+                .evmstate
+                .get(&address)
+                .and_then(|account| account.get(&index))
+                .cloned()
+                .unwrap_or_default();
+            let code_address = self.current_frame(address).code_address;
+            self.current_storage_writes.push(StorageWrite {
+                address,
+                code_address,
+                slot: index,
+                prev,
+                value,
+            });
+        }
         match self.evmstate.get_mut(&address) {
             Some(account) => {
                 account.insert(index, value);
@@ -1264,6 +1447,12 @@ where
             }
         }
 
+        self.current_logs.push(EVMLog {
+            address: _address,
+            topics: _topics.iter().map(|topic| EVMU256::from_be_bytes(topic.0)).collect(),
+            data: _data.to_vec(),
+        });
+
         #[cfg(feature = "print_logs")]
         {
             let mut hasher = DefaultHasher::new();
@@ -1291,6 +1480,7 @@ where
         inputs: &mut CreateInputs,
         state: &mut EVMFuzzState,
     ) -> (InstructionResult, Option<EVMAddress>, Gas, Bytes) {
+        self.gas_forwarded += inputs.gas_limit;
         if unsafe { IN_DEPLOY } {
             // todo: use nonce + hash instead
             let r_addr = generate_random_address(state);
@@ -1311,6 +1501,7 @@ where
                 MEM_LIMIT,
             );
             let ret = self.run_inspect(&mut interp, state);
+            self.gas_spent += interp.gas.spent();
             debug!("create: {:?} -> {:?} = {:?}", inputs.caller, r_addr, ret);
             if !is_reverted_or_control_leak(&ret) {
                 let runtime_code: Bytes = interp.return_value();
@@ -1350,6 +1541,7 @@ where
                     handle_contract_insertion!(state, self, r_addr, parsed_abi);
 
                     parsed_abi.iter().filter(|v| !v.is_constructor).for_each(|abi| {
+                        register_view_function(r_addr, abi, state);
                         #[cfg(not(feature = "fuzz_static"))]
                         if abi.is_static {
                             return;
@@ -1375,6 +1567,9 @@ where
                             randomness: vec![0],
                             repeat: 1,
                             swap_data: HashMap::new(),
+                            swap_routes: HashMap::new(),
+                            co_borrows: vec![],
+                            block_advance: None,
                         };
                         add_corpus(self, state, &input);
                     });
@@ -1395,11 +1590,26 @@ where
         output_info: (usize, usize),
         state: &mut EVMFuzzState,
     ) -> (InstructionResult, Gas, Bytes) {
+        // the stipend of value transfers is not charged to the caller
+        let stipend = if input.transfer.value.is_zero() {
+            0
+        } else {
+            CALL_STIPEND
+        };
+        // counted for rejected calls as well, the caller is charged the gas
+        // limit but no frame spends it
+        self.gas_forwarded += input.gas_limit.saturating_sub(stipend);
+        // the call fails without being executed, as the EVM does at depth 1024
+        if self.call_depth >= self.max_call_depth {
+            debug!("call depth limit {} reached, failing the call", self.max_call_depth);
+            return (Revert, Gas::new(0), Bytes::new());
+        }
         self.apply_prank(&interp.contract().caller, input);
-        self.call_depth += 1;
+        // swaps routed through aggregators are only visible in the calldata
+        self.evmstate.swap_data.push_calldata(&input.contract, &input.input);
 
         let value = EVMU256::from(input.transfer.value);
-        if cfg!(feature = "real_balance") && value != EVMU256::ZERO {
+        if unsafe { REAL_BALANCE } && value != EVMU256::ZERO {
             let sender = input.transfer.source;
             debug!("call sender: {:?}", sender);
             let current = if let Some(balance) = self.evmstate.get_balance(&sender) {
@@ -1422,6 +1632,24 @@ where
             };
         }
 
+        self.call_depth += 1;
+        let frame = CallFrame {
+            address: input.context.address,
+            code_address: input.context.code_address,
+            reads: vec![],
+        };
+        if self.record_delegating_reads && frame.is_delegated() {
+            let caller = self.current_frame(interp.contract.address);
+            if !caller.is_delegated() {
+                let reads = caller.reads.iter().map(|slot| (caller.address, *slot)).collect_vec();
+                self.current_delegating_reads.extend(reads);
+            }
+        }
+        self.call_frames.push(frame);
+        let logs_len = self.current_logs.len();
+        let writes_len = self.current_storage_writes.len();
+        let returns_len = self.current_call_returns.len();
+
         let mut res = if is_precompile(input.contract, self.precompiles.len()) {
             self.call_precompile(input, state)
         } else if unsafe { IS_FAST_CALL_STATIC || IS_FAST_CALL } {
@@ -1430,8 +1658,38 @@ where
             self.call_allow_control_leak(input, interp, output_info, state)
         };
 
+        self.call_frames.pop();
         let ret_buffer = res.2.clone();
 
+        // transferFrom(address,address,uint256)
+        if input.input.len() >= 100 &&
+            input.input[..4] == [0x23, 0xb8, 0x72, 0xdd] &&
+            !is_reverted_or_control_leak(&res.0)
+        {
+            self.current_transfer_froms.push(TransferFromCall {
+                token: input.context.address,
+                spender: input.context.caller,
+                from: EVMAddress::from_slice(&input.input[16..36]),
+                to: EVMAddress::from_slice(&input.input[48..68]),
+                amount: EVMU256::from_be_slice(&input.input[68..100]),
+            });
+        }
+
+        // logs, storage writes and return data of reverted calls are discarded
+        if is_reverted_or_control_leak(&res.0) && res.0 != ControlLeak {
+            self.current_logs.truncate(logs_len);
+            self.current_storage_writes.truncate(writes_len);
+            self.current_call_returns.truncate(returns_len);
+        }
+
+        if input.input.len() >= 4 && !is_reverted_or_control_leak(&res.0) {
+            self.current_call_returns.push(CallReturn {
+                address: input.contract,
+                selector: input.input[..4].try_into().unwrap(),
+                data: ret_buffer.clone(),
+            });
+        }
+
         self.call_depth -= 1;
         res = self.check_expected(input, res);
         self.clean_prank();
@@ -1,4 +1,10 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Debug, ops::Deref, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter},
+    ops::Deref,
+    rc::Rc,
+};
 
 use bytes::Bytes;
 use colored::{ColoredString, Colorize};
@@ -7,7 +13,7 @@ use libafl::{
     mutators::MutationResult,
     prelude::{HasBytesVec, HasMaxSize, HasMetadata, HasRand, State},
 };
-use libafl_bolts::{prelude::Rand, HasLen};
+use libafl_bolts::{impl_serdeany, prelude::Rand, HasLen};
 use revm_primitives::Env;
 use serde::{Deserialize, Deserializer, Serialize};
 
@@ -18,8 +24,13 @@ use super::{
 use crate::{
     evm::{
         abi::{AEmpty, AUnknown, BoxedABI},
+        background::{BackgroundPosition, BackgroundTxn},
+        logs::EVMLog,
         mutator::AccessPattern,
-        types::{checksum, EVMAddress, EVMStagedVMState, EVMU256, EVMU512},
+        permit::{permit_owner, CallerKeyMetadata},
+        state_schema::{deserialize_trace_version, TRACE_VERSION},
+        tokens::{SwapImpact, SwapType, WethAction},
+        types::{checksum, serialize_checksum, EVMAddress, EVMStagedVMState, EVMU256, EVMU512},
         vm::EVMState,
     },
     generic_vm::{
@@ -42,7 +53,8 @@ pub enum EVMInputTy {
     Borrow,
     /// An arbitrary external call with same address tx
     ArbitraryCallBoundedAddr,
-    /// [Depreciated] A liquidation transaction
+    /// A sale of all the tokens of the contract held by the caller, i.e., a
+    /// borrow flipped in place
     Liquidate,
 }
 
@@ -76,6 +88,9 @@ pub trait EVMInputT {
     /// Get input type
     fn get_input_type(&self) -> EVMInputTy;
 
+    /// Set input type
+    fn set_input_type(&mut self, ty: EVMInputTy);
+
     /// Get additional random bytes for mutator
     fn get_randomness(&self) -> Vec<u8>;
 
@@ -93,6 +108,80 @@ pub trait EVMInputT {
     fn get_repeat(&self) -> usize;
 
     fn get_swap_data(&self) -> HashMap<String, SwapInfo>;
+
+    /// Get the pair addresses of the swap routes pinned for this input
+    fn get_swap_routes(&self) -> HashMap<String, Vec<EVMAddress>>;
+
+    /// Pin the pair addresses of the swap route of the given type
+    fn set_swap_route(&mut self, ty: SwapType, route: Vec<EVMAddress>);
+
+    /// Get the other assets bought along with the token of a borrow
+    fn get_co_borrows(&self) -> Vec<(EVMAddress, EVMU256)>;
+
+    /// Set the other assets bought along with the token of a borrow
+    fn set_co_borrows(&mut self, co_borrows: Vec<(EVMAddress, EVMU256)>);
+
+    /// Get the block boundary before the transaction, if any
+    fn get_block_advance(&self) -> Option<BlockAdvance>;
+
+    /// Set the block boundary before the transaction
+    fn set_block_advance(&mut self, advance: Option<BlockAdvance>);
+
+    /// Sign the transaction if it is a `permit` of a caller with a known
+    /// private key
+    fn sign_permit(&mut self, keys: &CallerKeyMetadata);
+}
+
+/// Whether the mutator inserts block boundaries between transactions, so that
+/// exploits spanning several blocks (e.g., a TWAP update followed by a trade)
+/// are reachable
+pub static mut MULTI_BLOCK: bool = false;
+
+/// Seconds between two blocks, the timestamp advances along with the number
+pub const SECONDS_PER_BLOCK: u64 = 12;
+
+/// Blocks mined between a transaction and the previous one of the sequence.
+///
+/// The advance is cumulated in the VM state ([`EVMState::block_offset`]), so
+/// the following transactions of the sequence run in the new block too.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockAdvance {
+    pub blocks: u64,
+    pub seconds: u64,
+}
+
+impl BlockAdvance {
+    /// Blocks the mutator advances by: the next block, five minutes, an hour
+    /// and a day
+    const CHOICES: [u64; 5] = [1, 1, 25, 300, 7200];
+
+    pub fn new(blocks: u64) -> Self {
+        Self {
+            blocks,
+            seconds: blocks.saturating_mul(SECONDS_PER_BLOCK),
+        }
+    }
+
+    /// Pick a realistic advance, mostly the next block
+    pub fn random<S: HasRand>(state: &mut S) -> Self {
+        let idx = state.rand_mut().below(Self::CHOICES.len() as u64) as usize;
+        Self::new(Self::CHOICES[idx])
+    }
+
+    pub fn add(&mut self, other: BlockAdvance) {
+        self.blocks = self.blocks.saturating_add(other.blocks);
+        self.seconds = self.seconds.saturating_add(other.seconds);
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.blocks == 0 && self.seconds == 0
+    }
+}
+
+impl Display for BlockAdvance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "+{} block(s), +{}s", self.blocks, self.seconds)
+    }
 }
 
 /// EVM Input
@@ -146,11 +235,30 @@ pub struct EVMInput {
     /// Swap data
     #[serde(skip_deserializing)]
     pub swap_data: HashMap<String, SwapInfo>,
+
+    /// Pair addresses of the swap route taken by each swap type (buy / sell),
+    /// pins the route when replaying
+    #[serde(default)]
+    pub swap_routes: HashMap<String, Vec<EVMAddress>>,
+
+    /// Other assets (and the ETH spent on each) bought atomically along with
+    /// the token of a borrow
+    #[serde(default)]
+    pub co_borrows: Vec<(EVMAddress, EVMU256)>,
+
+    /// Block boundary before the transaction
+    #[serde(default)]
+    pub block_advance: Option<BlockAdvance>,
 }
 
 /// EVM Input Minimum for Deserializing
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ConciseEVMInput {
+    /// Version of the trace format the transaction was written with, see
+    /// [`TRACE_VERSION`]
+    #[serde(default, deserialize_with = "deserialize_trace_version")]
+    pub version: u32,
+
     /// Input type
     pub input_type: EVMInputTy,
 
@@ -196,18 +304,55 @@ pub struct ConciseEVMInput {
     /// Swap data
     #[serde(skip_deserializing)]
     pub swap_data: HashMap<String, SwapInfo>,
+
+    /// Pair addresses of the swap route taken by each swap type (buy / sell)
+    #[serde(default)]
+    pub swap_routes: HashMap<String, Vec<EVMAddress>>,
+
+    /// Other assets bought atomically along with the token of a borrow
+    #[serde(default)]
+    pub co_borrows: Vec<(EVMAddress, EVMU256)>,
+
+    /// Block boundary before the transaction
+    #[serde(default)]
+    pub block_advance: Option<BlockAdvance>,
+
+    /// Price impact of each hop of the swaps
+    #[serde(default)]
+    pub swap_impacts: Vec<SwapImpact>,
+
+    /// ETH wrapped or unwrapped through WETH by the swaps
+    #[serde(default)]
+    pub weth_actions: Vec<WethAction>,
+
+    /// Logs emitted by the transaction
+    #[serde(default)]
+    pub logs: Vec<EVMLog>,
+
+    /// EIP-2930 access list, empty when not recorded
+    #[serde(default)]
+    pub access_list: Vec<(EVMAddress, Vec<EVMU256>)>,
+
+    /// Real transaction run before or after the transaction
+    #[serde(default)]
+    pub background_txn: Option<(BackgroundPosition, BackgroundTxn)>,
 }
 
 /// EVM Input Minimum for Deserializing with human readable ABI
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ConciseEVMInputReadable {
+    /// Version of the trace format, [`TRACE_VERSION`]
+    pub version: u32,
+
     /// Input type
     pub input_type: EVMInputTy,
 
     /// Caller address
+    #[serde(serialize_with = "serialize_checksum")]
     pub caller: EVMAddress,
 
     /// Contract address
+    #[serde(serialize_with = "serialize_checksum")]
     pub contract: EVMAddress,
 
     /// Input data in ABI format
@@ -245,6 +390,38 @@ pub struct ConciseEVMInputReadable {
 
     /// return data
     pub return_data: Option<Vec<u8>>,
+
+    /// Pair addresses of the swap route taken by each swap type (buy / sell)
+    #[serde(default)]
+    pub swap_routes: HashMap<String, Vec<EVMAddress>>,
+
+    /// Other assets bought atomically along with the token of a borrow
+    #[serde(default)]
+    pub co_borrows: Vec<(EVMAddress, EVMU256)>,
+
+    /// Block boundary before the transaction
+    #[serde(default)]
+    pub block_advance: Option<BlockAdvance>,
+
+    /// Price impact of each hop of the swaps
+    #[serde(default)]
+    pub swap_impacts: Vec<SwapImpact>,
+
+    /// ETH wrapped or unwrapped through WETH by the swaps
+    #[serde(default)]
+    pub weth_actions: Vec<WethAction>,
+
+    /// Logs emitted by the transaction
+    #[serde(default)]
+    pub logs: Vec<EVMLog>,
+
+    /// EIP-2930 access list, empty when not recorded
+    #[serde(default)]
+    pub access_list: Vec<(EVMAddress, Vec<EVMU256>)>,
+
+    /// Real transaction run before or after the transaction
+    #[serde(default)]
+    pub background_txn: Option<(BackgroundPosition, BackgroundTxn)>,
 }
 
 impl ConciseEVMInput {
@@ -262,8 +439,14 @@ impl ConciseEVMInput {
         };
 
         let swap_data = execution_result.new_state.state.get_swap_data();
+        let swap_routes = execution_result.new_state.state.swap_routes.clone();
+        let swap_impacts = execution_result.new_state.state.swap_impacts.clone();
+        let weth_actions = execution_result.new_state.state.weth_actions.clone();
+        let logs = execution_result.logs.clone();
+        let background_txn = execution_result.new_state.state.background_txn.clone();
 
         Self {
+            version: TRACE_VERSION,
             input_type: input.get_input_type(),
             caller: input.get_caller(),
             contract: input.get_contract(),
@@ -287,6 +470,14 @@ impl ConciseEVMInput {
             },
             return_data,
             swap_data,
+            swap_routes,
+            co_borrows: input.get_co_borrows(),
+            block_advance: input.get_block_advance(),
+            swap_impacts,
+            weth_actions,
+            logs,
+            access_list: input.get_vm_env().tx.access_list.clone(),
+            background_txn,
         }
     }
 
@@ -295,6 +486,7 @@ impl ConciseEVMInput {
         I: VMInputT<EVMState, EVMAddress, EVMAddress, ConciseEVMInput> + EVMInputT,
     {
         Self {
+            version: TRACE_VERSION,
             input_type: input.get_input_type(),
             caller: input.get_caller(),
             contract: input.get_contract(),
@@ -315,10 +507,20 @@ impl ConciseEVMInput {
             call_leak,
             return_data: None,
             swap_data: input.get_swap_data(),
+            swap_routes: input.get_swap_routes(),
+            co_borrows: input.get_co_borrows(),
+            block_advance: input.get_block_advance(),
+            swap_impacts: vec![],
+            weth_actions: vec![],
+            logs: vec![],
+            access_list: input.get_vm_env().tx.access_list.clone(),
+            background_txn: None,
         }
     }
 
     pub fn to_input(&self, sstate: EVMStagedVMState) -> (EVMInput, u32) {
+        let mut env = self.env.clone();
+        env.tx.access_list = self.access_list.clone();
         (
             EVMInput {
                 input_type: self.input_type.clone(),
@@ -332,7 +534,7 @@ impl ConciseEVMInput {
                 sstate_idx: 0,
                 txn_value: self.txn_value,
                 step: self.step,
-                env: self.env.clone(),
+                env,
                 access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
                 liquidation_percent: self.liquidation_percent,
                 #[cfg(not(feature = "debug"))]
@@ -342,6 +544,9 @@ impl ConciseEVMInput {
                 randomness: self.randomness.clone(),
                 repeat: self.repeat,
                 swap_data: self.swap_data.clone(),
+                swap_routes: self.swap_routes.clone(),
+                co_borrows: self.co_borrows.clone(),
+                block_advance: self.block_advance,
             },
             self.call_leak,
         )
@@ -349,6 +554,7 @@ impl ConciseEVMInput {
 
     pub fn to_readable(&self) -> ConciseEVMInputReadable {
         ConciseEVMInputReadable {
+            version: TRACE_VERSION,
             input_type: self.input_type.clone(),
             caller: self.caller,
             contract: self.contract,
@@ -367,6 +573,14 @@ impl ConciseEVMInput {
             layer: self.layer,
             call_leak: self.call_leak,
             return_data: self.return_data.clone(),
+            swap_routes: self.swap_routes.clone(),
+            co_borrows: self.co_borrows.clone(),
+            block_advance: self.block_advance,
+            swap_impacts: self.swap_impacts.clone(),
+            weth_actions: self.weth_actions.clone(),
+            logs: self.logs.clone(),
+            access_list: self.access_list.clone(),
+            background_txn: self.background_txn.clone(),
         }
     }
 
@@ -385,7 +599,7 @@ impl ConciseEVMInput {
                         self.as_borrow()
                     }
                 }
-                EVMInputTy::Liquidate => None,
+                EVMInputTy::Liquidate => self.as_sell(),
             },
         }
 
@@ -454,12 +668,34 @@ impl ConciseEVMInput {
     #[allow(dead_code)]
     #[inline]
     fn as_borrow(&self) -> Option<String> {
+        let swap = |token: &EVMAddress, value: EVMU256| {
+            format!(
+                "{}.{}{{value: {}}}(0, path:(WETH → {}), address(this), block.timestamp);",
+                colored_address("Router"),
+                self.colored_fn_name("swapExactETHForTokens"),
+                prettify_value(value).truecolor(0x99, 0x00, 0xcc),
+                colored_address(&checksum(token))
+            )
+        };
+        // the other assets of a multi-asset borrow are bought in the same
+        // transaction
+        Some(
+            std::iter::once((self.contract, self.txn_value.unwrap_or_default()))
+                .chain(self.co_borrows.iter().cloned())
+                .map(|(token, value)| swap(&token, value))
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    fn as_sell(&self) -> Option<String> {
         Some(format!(
-            "{}.{}{}(0, path:(WETH → {}), address(this), block.timestamp);",
+            "{}.{}(100% Balance, 0, path:({} → WETH), address(this), block.timestamp);",
             colored_address("Router"),
-            self.colored_fn_name("swapExactETHForTokens"),
-            self.colored_value(),
-            colored_address(&self.contract())
+            self.colored_fn_name("swapExactTokensForETH"),
+            colored_address(&checksum(&self.contract))
         ))
     }
 
@@ -504,6 +740,80 @@ impl ConciseEVMInput {
         [call, liq].join("\n")
     }
 
+    /// Append the price impact of each hop of the swaps in this transaction
+    #[inline]
+    fn append_swap_impacts(&self, indent: &str, call: String) -> String {
+        if self.swap_impacts.is_empty() {
+            return call;
+        }
+        let impacts = self
+            .swap_impacts
+            .iter()
+            .map(|impact| format!("{}│  ├─ swap {}", indent, impact))
+            .collect::<Vec<_>>();
+        [vec![call], impacts].concat().join("\n")
+    }
+
+    /// Append the ETH wrapped or unwrapped through WETH in this transaction
+    #[inline]
+    fn append_weth_actions(&self, indent: &str, call: String) -> String {
+        if self.weth_actions.is_empty() {
+            return call;
+        }
+        let actions = self
+            .weth_actions
+            .iter()
+            .map(|action| format!("{}│  ├─ {}", indent, action))
+            .collect::<Vec<_>>();
+        [vec![call], actions].concat().join("\n")
+    }
+
+    /// Append the logs emitted by this transaction
+    #[inline]
+    fn append_logs(&self, indent: &str, call: String) -> String {
+        if self.logs.is_empty() {
+            return call;
+        }
+        let logs = self
+            .logs
+            .iter()
+            .map(|log| format!("{}│  ├─ emit {}", indent, log))
+            .collect::<Vec<_>>();
+        [vec![call], logs].concat().join("\n")
+    }
+
+    /// Show the background transaction run before or after the call
+    fn add_background_txn(&self, indent: &str, call: String) -> String {
+        match &self.background_txn {
+            Some((position, txn)) => {
+                let txn = format!(
+                    "{}├─ {}",
+                    indent,
+                    format!("[background] {}", txn).truecolor(0x99, 0x99, 0x99)
+                );
+                match position {
+                    BackgroundPosition::Before => format!("{}\n{}", txn, call),
+                    BackgroundPosition::After => format!("{}\n{}", call, txn),
+                }
+            }
+            None => call,
+        }
+    }
+
+    /// Mark the block boundary before the call, e.g., `vm.roll` and `vm.warp`
+    /// in foundry
+    fn prepend_block_advance(&self, indent: &str, call: String) -> String {
+        match self.block_advance {
+            Some(advance) => format!(
+                "{}├─ {}\n{}",
+                indent,
+                format!("[next block: {}]", advance).truecolor(0x99, 0x99, 0x99),
+                call
+            ),
+            None => call,
+        }
+    }
+
     #[inline]
     fn colored_value(&self) -> String {
         let value = self.txn_value.unwrap_or_default();
@@ -585,6 +895,22 @@ impl SolutionTx for ConciseEVMInput {
         self.input_type == EVMInputTy::Borrow
     }
 
+    fn is_sell(&self) -> bool {
+        self.input_type == EVMInputTy::Liquidate
+    }
+
+    fn co_borrows(&self) -> Vec<(String, String)> {
+        self.co_borrows
+            .iter()
+            .map(|(token, amount)| (checksum(token), prettify_value(*amount)))
+            .collect()
+    }
+
+    #[cfg(not(feature = "debug"))]
+    fn is_raw_calldata(&self) -> bool {
+        self.data.as_ref().map_or(false, |d| d.is_raw())
+    }
+
     fn liq_percent(&self) -> u8 {
         self.liquidation_percent
     }
@@ -593,6 +919,45 @@ impl SolutionTx for ConciseEVMInput {
         self.swap_data.clone()
     }
 
+    fn access_list(&self) -> Vec<(String, Vec<String>)> {
+        self.access_list
+            .iter()
+            .map(|(address, slots)| {
+                let slots = slots.iter().map(|slot| format!("0x{:x}", slot)).collect();
+                (checksum(address), slots)
+            })
+            .collect()
+    }
+
+    fn logs(&self) -> Vec<String> {
+        self.logs.iter().map(|log| log.to_string()).collect()
+    }
+
+    fn background_txn(&self) -> Option<(bool, String, String, String, String)> {
+        self.background_txn.as_ref().map(|(position, txn)| {
+            (
+                *position == BackgroundPosition::Before,
+                checksum(&txn.from),
+                checksum(&txn.to),
+                txn.value.to_string(),
+                hex::encode(&txn.data),
+            )
+        })
+    }
+
+    fn block_number(&self) -> Option<u64> {
+        let number = self.env.block.number;
+        if number > EVMU256::from(u64::MAX) {
+            None
+        } else {
+            Some(number.as_limbs()[0])
+        }
+    }
+
+    fn block_advance(&self) -> Option<(u64, u64)> {
+        self.block_advance.map(|advance| (advance.blocks, advance.seconds))
+    }
+
     #[cfg(not(feature = "debug"))]
     fn calldata(&self) -> String {
         match self.data {
@@ -668,6 +1033,10 @@ impl EVMInputT for EVMInput {
         self.input_type.clone()
     }
 
+    fn set_input_type(&mut self, ty: EVMInputTy) {
+        self.input_type = ty;
+    }
+
     fn get_randomness(&self) -> Vec<u8> {
         self.randomness.clone()
     }
@@ -691,6 +1060,44 @@ impl EVMInputT for EVMInput {
     fn get_swap_data(&self) -> HashMap<String, SwapInfo> {
         self.swap_data.clone()
     }
+
+    fn get_swap_routes(&self) -> HashMap<String, Vec<EVMAddress>> {
+        self.swap_routes.clone()
+    }
+
+    fn set_swap_route(&mut self, ty: SwapType, route: Vec<EVMAddress>) {
+        self.swap_routes.insert(String::from(ty), route);
+    }
+
+    fn get_co_borrows(&self) -> Vec<(EVMAddress, EVMU256)> {
+        self.co_borrows.clone()
+    }
+
+    fn set_co_borrows(&mut self, co_borrows: Vec<(EVMAddress, EVMU256)>) {
+        self.co_borrows = co_borrows;
+    }
+
+    fn get_block_advance(&self) -> Option<BlockAdvance> {
+        self.block_advance
+    }
+
+    fn set_block_advance(&mut self, advance: Option<BlockAdvance>) {
+        self.block_advance = advance;
+    }
+
+    fn sign_permit(&mut self, keys: &CallerKeyMetadata) {
+        let Some(abi) = self.data.as_mut() else {
+            return;
+        };
+        let calldata = abi.get_bytes();
+        let Some(owner) = permit_owner(&calldata) else {
+            return;
+        };
+        let nonce = self.sstate.state.permit_nonce(&self.contract, &owner);
+        if let Some(calldata) = keys.sign(&self.contract, &calldata, nonce) {
+            abi.set_bytes(calldata);
+        }
+    }
 }
 
 ///
@@ -792,6 +1199,61 @@ impl<'a> HasBytesVec for MutatorInput<'a> {
     }
 }
 
+/// Controls how the value (msg.value) of a transaction is mutated
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CallValueMetadata {
+    /// Values the mutator picks from half of the time instead of mutating
+    /// bytes randomly
+    pub interesting_values: Vec<EVMU256>,
+    /// Upper bound of the value
+    pub max_value: EVMU256,
+}
+
+impl_serdeany!(CallValueMetadata);
+
+/// Denominators of the fractions of the WETH reserves of the pools added to
+/// the interesting values, e.g., 10 adds 10% of the reserves
+pub const RESERVE_FRACTIONS: [u64; 4] = [1, 10, 100, 1000];
+
+impl Default for CallValueMetadata {
+    fn default() -> Self {
+        let one_ether = EVMU256::from(10).pow(EVMU256::from(18));
+        Self {
+            interesting_values: vec![EVMU256::ZERO, EVMU256::from(1), one_ether],
+            max_value: EVMU256::from(u128::MAX),
+        }
+    }
+}
+
+impl CallValueMetadata {
+    pub fn new(interesting_values: Vec<EVMU256>, max_value: EVMU256) -> Self {
+        Self {
+            interesting_values: interesting_values.into_iter().filter(|v| *v <= max_value).collect(),
+            max_value,
+        }
+    }
+
+    /// Add the fractions of `reserve` in [`RESERVE_FRACTIONS`] to the
+    /// interesting values, the ones above `max_value` are skipped
+    pub fn add_reserve_fractions(&mut self, reserve: EVMU256) {
+        for denominator in RESERVE_FRACTIONS {
+            let value = reserve / EVMU256::from(denominator);
+            if value > EVMU256::ZERO && value <= self.max_value && !self.interesting_values.contains(&value) {
+                self.interesting_values.push(value);
+            }
+        }
+    }
+
+    /// Bound the value to `[0, max_value]`
+    pub fn bound(&self, value: EVMU256) -> EVMU256 {
+        if value <= self.max_value {
+            value
+        } else {
+            value % (self.max_value + EVMU256::from(1))
+        }
+    }
+}
+
 impl EVMInput {
     impl_env_mutator_u256!(basefee, block, false);
     impl_env_mutator_u256!(timestamp, block, true);
@@ -845,6 +1307,23 @@ impl EVMInput {
     where
         S: State + HasCaller<EVMAddress> + HasRand + HasMetadata,
     {
+        let meta = state_
+            .metadata_map()
+            .get::<CallValueMetadata>()
+            .cloned()
+            .unwrap_or_default();
+
+        // bias towards boundary values
+        if !meta.interesting_values.is_empty() && state_.rand_mut().below(2) == 0 {
+            let idx = state_.rand_mut().below(meta.interesting_values.len() as u64) as usize;
+            let value = meta.interesting_values[idx];
+            if input.get_txn_value() == Some(value) {
+                return MutationResult::Skipped;
+            }
+            input.set_txn_value(value);
+            return MutationResult::Mutated;
+        }
+
         let vm_slots = input.get_state().get(&input.get_contract()).cloned();
         let input_by: [u8; 32] = input.get_txn_value().unwrap_or_default().to_be_bytes();
         let mut input_vec = input_by.to_vec();
@@ -857,7 +1336,7 @@ impl EVMInput {
         (0..16).for_each(|i| {
             input_vec[i] = 0;
         });
-        input.set_txn_value(EVMU256::try_from_be_slice(input_vec.as_slice()).unwrap());
+        input.set_txn_value(meta.bound(EVMU256::try_from_be_slice(input_vec.as_slice()).unwrap()));
         res
     }
 
@@ -923,6 +1402,9 @@ impl ConciseSerde for ConciseEVMInput {
         // Stepping with return
         if self.step {
             let res = self.as_stepping_with_return(&indent, tree_level);
+            let res = self.append_logs(&indent, res);
+            let res = self.append_weth_actions(&indent, res);
+            let res = self.append_swap_impacts(&indent, res);
             return self.append_liquidation(indent, res);
         }
 
@@ -945,6 +1427,8 @@ impl ConciseSerde for ConciseEVMInput {
             call.push_str(fallback.as_str());
         }
 
+        let mut call = self.append_logs(&indent, call);
+
         if self.return_data.is_some() {
             let mut ret = indent.clone();
             let v = self.return_data.as_ref().unwrap();
@@ -953,6 +1437,10 @@ impl ConciseSerde for ConciseEVMInput {
             call.push_str(ret.as_str());
         }
 
+        let call = self.append_weth_actions(&indent, call);
+        let call = self.append_swap_impacts(&indent, call);
+        let call = self.add_background_txn(&indent, call);
+        let call = self.prepend_block_advance(&indent, call);
         self.append_liquidation(indent, call)
     }
 
@@ -976,6 +1464,10 @@ impl ConciseSerde for ConciseEVMInput {
     fn is_step(&self) -> bool {
         self.step
     }
+
+    fn randomness(&self) -> Vec<u8> {
+        self.randomness.clone()
+    }
 }
 
 impl VMInputT<EVMState, EVMAddress, EVMAddress, ConciseEVMInput> for EVMInput {
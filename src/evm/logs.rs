@@ -0,0 +1,132 @@
+//! Events emitted during a transaction, decoded against the signatures of
+//! common ERC20 / DEX events so that findings show what happened
+
+use std::fmt::{Display, Formatter};
+
+use crypto::{digest::Digest, sha3::Sha3};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::evm::types::{checksum, EVMAddress, EVMU256};
+
+/// Known events: signature and whether each parameter is indexed
+const KNOWN_EVENTS: [(&str, &[bool]); 7] = [
+    ("Transfer(address,address,uint256)", &[true, true, false]),
+    ("Approval(address,address,uint256)", &[true, true, false]),
+    ("Deposit(address,uint256)", &[true, false]),
+    ("Withdrawal(address,uint256)", &[true, false]),
+    ("Sync(uint112,uint112)", &[false, false]),
+    (
+        "Swap(address,uint256,uint256,uint256,uint256,address)",
+        &[true, false, false, false, false, true],
+    ),
+    ("Mint(address,uint256,uint256)", &[true, false, false]),
+];
+
+/// Topic 0 of the known events
+static KNOWN_TOPICS: Lazy<Vec<(EVMU256, &'static str, &'static [bool])>> = Lazy::new(|| {
+    KNOWN_EVENTS
+        .iter()
+        .map(|(signature, indexed)| {
+            let mut hasher = Sha3::keccak256();
+            hasher.input_str(signature);
+            let mut topic = [0u8; 32];
+            hasher.result(&mut topic);
+            (EVMU256::from_be_bytes(topic), *signature, *indexed)
+        })
+        .collect()
+});
+
+/// A log emitted by LOG0-LOG4. As in the EVM, the logs of reverted calls are
+/// discarded, and a reverted transaction emits no logs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EVMLog {
+    pub address: EVMAddress,
+    pub topics: Vec<EVMU256>,
+    pub data: Vec<u8>,
+}
+
+impl EVMLog {
+    /// Decode the log if it is one of the known events, e.g.,
+    /// `Transfer(0x.., 0x.., 100)`
+    pub fn decode(&self) -> Option<String> {
+        let topic0 = self.topics.first()?;
+        let (_, signature, indexed) = KNOWN_TOPICS.iter().find(|(topic, _, _)| topic == topic0)?;
+        let (name, params) = signature.trim_end_matches(')').split_once('(')?;
+        let types = params.split(',').collect::<Vec<_>>();
+
+        let mut topics = self.topics[1..].iter();
+        let mut words = self.data.chunks(32);
+        let mut args = vec![];
+        for (ty, is_indexed) in types.iter().zip(indexed.iter()) {
+            let word = if *is_indexed {
+                *topics.next()?
+            } else {
+                let word = words.next()?;
+                if word.len() != 32 {
+                    return None;
+                }
+                EVMU256::from_be_slice(word)
+            };
+            args.push(if *ty == "address" {
+                checksum(&EVMAddress::from_slice(&word.to_be_bytes::<32>()[12..]))
+            } else {
+                word.to_string()
+            });
+        }
+        Some(format!("{}({})", name, args.join(", ")))
+    }
+}
+
+impl Display for EVMLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.decode() {
+            Some(decoded) => write!(f, "{}.{}", checksum(&self.address), decoded),
+            None => write!(
+                f,
+                "{}.log(topics: [{}], data: 0x{})",
+                checksum(&self.address),
+                self.topics
+                    .iter()
+                    .map(|topic| format!("{:#066x}", topic))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                hex::encode(&self.data)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_transfer() {
+        let from = EVMAddress::from_str("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        let to = EVMAddress::zero();
+        let address_topic = |addr: &EVMAddress| EVMU256::from_be_slice(&[vec![0; 12], addr.0.to_vec()].concat());
+        let log = EVMLog {
+            address: to,
+            topics: vec![
+                EVMU256::from_str("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef").unwrap(),
+                address_topic(&from),
+                address_topic(&to),
+            ],
+            data: EVMU256::from(100).to_be_bytes::<32>().to_vec(),
+        };
+        assert_eq!(
+            log.decode().unwrap(),
+            format!("Transfer({}, {}, 100)", checksum(&from), checksum(&to))
+        );
+
+        let unknown = EVMLog {
+            address: to,
+            topics: vec![EVMU256::from(1)],
+            data: vec![],
+        };
+        assert!(unknown.decode().is_none());
+    }
+}
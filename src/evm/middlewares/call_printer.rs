@@ -12,7 +12,7 @@ use crate::evm::{
     host::FuzzHost,
     middlewares::middleware::{Middleware, MiddlewareType},
     srcmap::{RawSourceMapInfo, SOURCE_MAP_PROVIDER},
-    types::{as_u64, convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256},
+    types::{as_u64, checksum, convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
@@ -113,7 +113,7 @@ impl CallPrinter {
     }
 
     fn translate_address(&self, a: EVMAddress) -> String {
-        self.address_to_name.get(&a).unwrap_or(&format!("{:?}", a)).to_string()
+        self.address_to_name.get(&a).unwrap_or(&checksum(&a)).to_string()
     }
 }
 
@@ -1262,6 +1262,9 @@ mod tests {
                     randomness: vec![],
                     repeat: 1,
                     swap_data: HashMap::new(),
+                    swap_routes: HashMap::new(),
+                    co_borrows: vec![],
+                    block_advance: None,
                 };
                 let mut state = FuzzState::new(0);
                 // deposit some ETH to the test contract
@@ -28,7 +28,14 @@ impl ReentrancyTracer {
 pub struct ReentrancyData {
     pub reads: HashMap<(EVMAddress, EVMU256), Vec<u32>>,
     pub need_writes: HashMap<(EVMAddress, EVMU256), Vec<u32>>,
-    pub found: HashSet<(EVMAddress, EVMU256)>,
+    /// (contract, slot, depth of the first invocation) => (selector of the
+    /// reentered function, call depth of the reentry), recorded when the
+    /// reentered function reads the slot
+    #[serde(default)]
+    pub reentries: HashMap<(EVMAddress, EVMU256, u32), ([u8; 4], u32)>,
+    /// (contract, slot, selector of the reentered function, call depth of the
+    /// reentry)
+    pub found: HashSet<(EVMAddress, EVMU256, [u8; 4], u32)>,
 }
 
 fn merge_sorted_vec_dedup(dst: &mut Vec<u32>, another_one: &Vec<u32>) {
@@ -78,6 +85,16 @@ fn merge_sorted_vec_dedup(dst: &mut Vec<u32>, another_one: &Vec<u32>) {
     *dst = merged;
 }
 
+/// Selector of the function executed by the current frame
+fn current_selector(interp: &Interpreter) -> [u8; 4] {
+    let mut selector = [0u8; 4];
+    let input = &interp.contract.input;
+    if input.len() >= 4 {
+        selector.copy_from_slice(&input[..4]);
+    }
+    selector
+}
+
 // Reentrancy: Read, Read, Write
 impl<SC> Middleware<SC> for ReentrancyTracer
 where
@@ -121,6 +138,14 @@ where
                 if found_smaller.is_empty() {
                     return;
                 }
+                let selector = current_selector(interp);
+                for smaller in &found_smaller {
+                    host.evmstate
+                        .reentrancy_metadata
+                        .reentries
+                        .entry((interp.contract.address, slot_idx, *smaller))
+                        .or_insert((selector, depth));
+                }
                 let write_entry = host
                     .evmstate
                     .reentrancy_metadata
@@ -142,10 +167,15 @@ where
                 for i in write_entry.iter() {
                     if depth == *i {
                         // panic!("Reentrancy found at depth: {}, slot: {}", depth, slot_idx);
-                        host.evmstate
-                            .reentrancy_metadata
+                        let metadata = &mut host.evmstate.reentrancy_metadata;
+                        let (selector, reentry_depth) = metadata
+                            .reentries
+                            .get(&(interp.contract.address, slot_idx, depth))
+                            .cloned()
+                            .unwrap_or((current_selector(interp), depth));
+                        metadata
                             .found
-                            .insert((interp.contract.address, slot_idx));
+                            .insert((interp.contract.address, slot_idx, selector, reentry_depth));
                         return;
                     }
                 }
@@ -181,6 +211,10 @@ where
             .for_each(|(_, depths)| {
                 depths.retain(|&x| x <= depth);
             });
+        evm_state
+            .reentrancy_metadata
+            .reentries
+            .retain(|(_, _, first), _| *first <= depth);
     }
 }
 
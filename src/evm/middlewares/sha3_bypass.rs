@@ -486,6 +486,9 @@ mod tests {
             randomness: vec![],
             repeat: 1,
             swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
         };
 
         let res = evm_executor.execute(&input, &mut state);
@@ -3,10 +3,12 @@ use std::{cell::RefCell, ops::Deref, rc::Rc};
 use bytes::Bytes;
 use itertools::Itertools;
 use libafl::{
+    corpus::CorpusId,
     prelude::Corpus,
     state::{HasCorpus, HasMetadata},
 };
 use revm_primitives::Bytecode;
+use tracing::warn;
 
 use super::types::EVMStagedVMState;
 use crate::{
@@ -25,6 +27,16 @@ use crate::{
     tracer::TxnTrace,
 };
 
+/// Parse a replay file the same way as `--replay-file`: one serialized input
+/// per line, shorter lines are skipped
+pub fn parse_replay(replay: &str) -> Result<Vec<ConciseEVMInput>, serde_json::Error> {
+    replay
+        .split('\n')
+        .filter(|line| line.len() >= 4)
+        .map(serde_json::from_str::<ConciseEVMInput>)
+        .collect()
+}
+
 pub struct EVMMinimizer {
     evm_executor_ref: Rc<RefCell<EVMQueueExecutor>>,
 }
@@ -172,4 +184,100 @@ impl<E: libafl::executors::HasObservers>
             .map(|(tx, call_leak)| ConciseEVMInput::from_input_with_call_leak(&tx, call_leak))
             .collect_vec()
     }
+
+    fn verify(
+        &mut self,
+        state: &mut EVMFuzzState,
+        _exec: &mut E,
+        replay_file: &str,
+        objective: &mut EVMOracleFeedback<'_>,
+        corpus_id: usize,
+    ) -> bool {
+        let txns = match std::fs::read_to_string(replay_file)
+            .map_err(|e| e.to_string())
+            .and_then(|replay| parse_replay(&replay).map_err(|e| e.to_string()))
+        {
+            Ok(txns) => txns,
+            Err(e) => {
+                warn!("Failed to read the replay file {} of the finding: {}", replay_file, e);
+                return false;
+            }
+        };
+        let Some(bug_idx_needed) = state
+            .metadata_map()
+            .get::<BugMetadata>()
+            .and_then(|bug_meta| bug_meta.corpus_idx_to_bug.get(&corpus_id).cloned())
+        else {
+            return false;
+        };
+        // the replay starts from the initial state, the first infant state
+        let Some(initial_state) = state
+            .infant_states_state
+            .corpus()
+            .get(CorpusId::from(0usize))
+            .ok()
+            .and_then(|testcase| testcase.borrow().input().clone())
+        else {
+            return false;
+        };
+
+        // the same way as `--replay-file`, leaving the result of the execution
+        // of the finding as it is
+        let result = state.get_execution_result().clone();
+        let mut current_state = initial_state;
+        let mut is_solution = false;
+        for txn in txns {
+            let (tx, call_until) = txn.to_input(current_state.clone());
+            unsafe {
+                CALL_UNTIL = call_until;
+            }
+            let res = {
+                let mut executor = self.evm_executor_ref.deref().borrow_mut();
+                executor.execute(&tx, state)
+            };
+            state.set_execution_result(res);
+            is_solution |= objective.reproduces(state, &tx, &bug_idx_needed);
+            current_state = state.get_execution_result().new_state.clone();
+            if state.get_execution_result().reverted {
+                break;
+            }
+        }
+        unsafe {
+            CALL_UNTIL = u32::MAX;
+        }
+        state.set_execution_result(result);
+        is_solution
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::ConciseSerde;
+
+    #[test]
+    fn test_parse_written_replay() {
+        let txns = (1..=3u8)
+            .map(|i| ConciseEVMInput {
+                caller: EVMAddress::from_slice(&[i; 20]),
+                contract: EVMAddress::from_slice(&[i + 10; 20]),
+                repeat: i as usize,
+                ..Default::default()
+            })
+            .collect_vec();
+        let txn_json = txns
+            .iter()
+            .map(|ci| String::from_utf8(ci.serialize_concise()).unwrap())
+            .join("\n");
+        let path = std::env::temp_dir().join("ityfuzz_test_parse_written_replay");
+        std::fs::write(&path, format!("{}\n", txn_json)).unwrap();
+
+        let parsed = parse_replay(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(parsed.len(), txns.len());
+        for (parsed, txn) in parsed.iter().zip(txns.iter()) {
+            assert_eq!(parsed.serialize_concise(), txn.serialize_concise());
+        }
+        assert!(parse_replay("{\"caller\": 1}\n").is_err());
+    }
 }
@@ -1,26 +1,39 @@
 pub mod abi;
+pub mod arg_pins;
+pub mod background;
 pub mod blaz;
 pub mod bytecode_analyzer;
 pub mod bytecode_iterator;
 pub mod concolic;
 pub mod config;
 pub mod contract_utils;
+pub mod corpus_export;
 pub mod corpus_initializer;
 pub mod cov_stage;
 pub mod feedbacks;
+pub mod fork_blocks;
+pub mod function_filter;
 pub mod host;
 pub mod input;
+pub mod logs;
 pub mod middlewares;
 pub mod minimizer;
 pub mod mutator;
 pub mod onchain;
 pub mod oracle;
 pub mod oracles;
+pub mod permit;
+pub mod predeploys;
 pub mod presets;
 pub mod producers;
+pub mod project_config;
 pub mod scheduler;
+pub mod selector_stats;
 pub mod solution;
 pub mod srcmap;
+pub mod state_diff;
+pub mod state_override;
+pub mod state_schema;
 pub mod tokens;
 pub mod types;
 pub mod utils;
@@ -36,6 +49,7 @@ use std::{
     str::FromStr,
 };
 
+use arg_pins::ArgPinMetadata;
 use blaz::{
     builder::{BuildJob, BuildJobResult},
     offchain_artifacts::OffChainArtifact,
@@ -44,20 +58,40 @@ use blaz::{
 use clap::Parser;
 use config::{Config, StorageFetchingMode};
 use contract_utils::ContractLoader;
+use corpus_export::export_corpus;
 use ethers::types::Transaction;
-use input::{ConciseEVMInput, EVMInput};
+use fork_blocks::run_fork_block_campaigns;
+use function_filter::FunctionFilterMetadata;
+use input::{CallValueMetadata, ConciseEVMInput, EVMInput};
 use itertools::Itertools;
+use libafl::Error;
 use num_cpus;
-use onchain::endpoints::{Chain, OnChainConfig};
-use oracles::{erc20::IERC20OracleFlashloan, v2_pair::PairBalanceOracle};
+use onchain::{
+    endpoints::{Chain, OnChainConfig},
+    fee::TxFeeModel,
+    flashloan::MinProfit,
+};
+use oracles::{erc20::IERC20OracleFlashloan, nft::NftCollection, v2_pair::PairBalanceOracle};
+use predeploys::chain_predeploys;
 use producers::erc20::ERC20Producer;
 use serde::Deserialize;
 use serde_json::json;
-use types::{EVMAddress, EVMFuzzState, EVMU256};
+use state_override::StateOverride;
+use tokens::{
+    balance_slot::BalanceSlot,
+    parse_token_amount,
+    uniswap::{describe_uniswap_paths, get_weth},
+    SwapData,
+    SwapType,
+    DEFAULT_DECIMALS,
+};
+use tracing::{error, info, warn};
+use types::{EVMAddress, EVMConfig, EVMFuzzState, EVMU256};
 use vm::EVMState;
 
 use self::types::EVMQueueExecutor;
 use crate::{
+    events::CampaignResult,
     fuzzers::evm_fuzzer::evm_fuzzer,
     oracle::{Oracle, Producer},
     state::FuzzState,
@@ -98,9 +132,15 @@ struct RPCCall {
 #[command(author, version, about, long_about = None)]
 pub struct EvmArgs {
     /// Glob pattern / address to find contracts
-    #[arg(short, long)]
+    #[arg(short, long, default_value = "")]
     target: String,
 
+    /// Project config file (TOML, or JSON) giving the flags below, keyed by
+    /// the flags in snake_case. Flags given on the command line override the
+    /// values of the file.
+    #[arg(long)]
+    project_file: Option<String>,
+
     #[arg(long, default_value = "false")]
     fetch_tx_data: bool,
 
@@ -122,6 +162,20 @@ pub struct EvmArgs {
     #[arg(long, short = 'b')]
     onchain_block_number: Option<u64>,
 
+    /// Onchain - Fuzz at each of these block numbers (separated by comma)
+    /// concurrently, in sub-campaigns with work dirs <work-dir>/block_<number>,
+    /// to find bugs that only exist before / after an upgrade. Overrides
+    /// --onchain-block-number.
+    #[arg(long, default_value = "")]
+    fork_blocks: String,
+
+    /// Onchain - Block number to read the reserves of Uniswap pairs at, which
+    /// are used to value tokens (Default: onchain-block-number). Code and
+    /// storage, including the reserves seen during execution, are always
+    /// fetched at onchain-block-number.
+    #[arg(long)]
+    reserves_block_number: Option<u64>,
+
     /// Onchain Customize - RPC endpoint URL (Default: inferred from
     /// chain-type), Example: https://rpc.ankr.com/eth
     #[arg(long, short = 'u')]
@@ -166,10 +220,40 @@ pub struct EvmArgs {
     #[arg(long, default_value = "0")]
     concolic_num_threads: usize,
 
+    /// With --concolic, only solve once no new testcase was found for this
+    /// many seconds, for the branches with an uncovered side (e.g., equality
+    /// on a hash) the fuzzer is stuck at (Default: solve for every new
+    /// testcase)
+    #[arg(long)]
+    concolic_plateau: Option<u64>,
+
     /// Enable flashloan
     #[arg(short, long, default_value = "false")]
     flashloan: bool,
 
+    /// Maximum number of assets borrowed atomically by a single flashloan,
+    /// e.g., 2 to imbalance a pool with two borrowed tokens (Default: 1)
+    #[arg(long, default_value = "1")]
+    max_borrowed_assets: usize,
+
+    /// Minimum net profit reported by the flashloan oracle, in WETH (in wei,
+    /// or in ether with a decimal point) or as a percentage of the borrowed
+    /// amount, e.g., 0.5% (Default: 0.01 ether, to filter out rounding noise)
+    #[arg(long, default_value = "0.01")]
+    min_profit: String,
+
+    /// Maximum number of infant states (VM states to fuzz from) kept for
+    /// scheduling, the least recently scheduled ones are evicted beyond it
+    /// (Default: 500)
+    #[arg(long, default_value = "500")]
+    infant_state_limit: usize,
+
+    /// Insert block boundaries between transactions, to find exploits
+    /// spanning several blocks, e.g., a TWAP update followed by a trade
+    /// (Default: false)
+    #[arg(long, default_value = "false")]
+    multi_block: bool,
+
     /// Panic when a typed_bug() is called (Default: false)
     #[arg(long, default_value = "false")]
     panic_on_bug: bool,
@@ -195,10 +279,21 @@ pub struct EvmArgs {
     #[arg(long, default_value = "false")]
     write_relationship: bool,
 
+    /// Dump the ABIs recovered from the bytecode of contracts without ABI to
+    /// `<work_dir>/recovered_abi/<address>.json`
+    #[arg(long, default_value = "false")]
+    dump_recovered_abi: bool,
+
     /// Do not quit when a bug is found, continue find new bugs
     #[arg(long, default_value = "false")]
     run_forever: bool,
 
+    /// Quit with a nonzero status once this many distinct bugs are found,
+    /// e.g., to use ItyFuzz as a pass/fail gate in CI. Takes precedence over
+    /// --run-forever
+    #[arg(long)]
+    stop_after_findings: Option<usize>,
+
     /// random seed
     #[arg(long, default_value = "1667840158231589000")]
     seed: u64,
@@ -208,6 +303,11 @@ pub struct EvmArgs {
     #[arg(long, default_value = "false")]
     sha3_bypass: bool,
 
+    /// Also keep inputs that use more gas than before for the same function,
+    /// to find gas griefing / DoS bugs (Experimental)
+    #[arg(long, default_value = "false")]
+    gas_feedback: bool,
+
     /// Only fuzz contracts with the addresses, separated by comma
     #[arg(long, default_value = "")]
     only_fuzz: String,
@@ -222,6 +322,11 @@ pub struct EvmArgs {
     #[arg(long, default_value = "Latest")]
     spec_id: String,
 
+    /// Maximum depth of nested calls, deeper calls fail. Lower it to bound the
+    /// cost of deeply recursing executions.
+    #[arg(long, default_value = "1024")]
+    max_call_depth: u64,
+
     /// Builder URL. If specified, will use this builder to build contracts
     /// instead of using bins and abis.
     #[arg(long, default_value = "")]
@@ -255,6 +360,42 @@ pub struct EvmArgs {
     #[arg(long, default_value = "")]
     load_corpus: String,
 
+    /// Save the campaign (corpora, coverage, known bugs) to the work dir
+    /// every this many seconds (Default: never)
+    #[arg(long)]
+    checkpoint_interval: Option<u64>,
+
+    /// Resume the campaign saved in the work dir by --checkpoint-interval
+    #[arg(long, default_value = "false")]
+    resume: bool,
+
+    /// Export each testcase of the corpus to this directory once fuzzing
+    /// stops, as a standalone file in the replay format (--replay-file)
+    #[arg(long, default_value = "")]
+    export_corpus: String,
+
+    /// Stop fuzzing after this many seconds (Default: no limit)
+    #[arg(long)]
+    max_time: Option<u64>,
+
+    /// Stop fuzzing after this many executions, whatever the elapsed time
+    /// (Default: no limit)
+    #[arg(long)]
+    max_execs: Option<usize>,
+
+    /// Soft cap on the estimated memory of the caches and the infant state
+    /// corpus, in MiB: the caches, then infant states, are evicted when it is
+    /// exceeded (Default: no limit)
+    #[arg(long)]
+    max_memory: Option<u64>,
+
+    /// Assert that the invariants (invariant_* and echidna_* functions) hold:
+    /// only the invariant detectors run, the first violation quits with a
+    /// nonzero status, and using up the budget (--max-time / --max-execs)
+    /// quits with a zero status
+    #[arg(long, default_value = "false")]
+    assert_invariants: bool,
+
     /// Specify the setup file that deploys all the contract. Fuzzer invokes
     /// setUp() to deploy.
     #[arg(long, default_value = "")]
@@ -262,10 +403,191 @@ pub struct EvmArgs {
 
     /// Forcing a contract to use the given abi. This is useful when the
     /// contract is a complex proxy or decompiler has trouble to detect the abi.
+    /// For contracts without ABI, the functions recovered from the bytecode
+    /// that are not in the given abi are still fuzzed.
     /// Format: address:abi_file,...
     #[arg(long, default_value = "")]
     force_abi: String,
 
+    /// Also fuzz these contracts (separated by comma) with raw calldata,
+    /// mutated as opaque bytes instead of ABI-encoded args, to reach fallback
+    /// logic and custom (non 4-byte selector) dispatchers
+    #[arg(long, default_value = "")]
+    raw_calldata_targets: String,
+
+    /// Attacker contract deployed after the targets, given as the path prefix
+    /// of its .bin (creation code) and .abi files, e.g., ./build/Attacker*. Its
+    /// functions are fuzzed like those of the targets and it acts as a caller,
+    /// so that exploits needing atomic multi-step logic (e.g., in a flashloan
+    /// callback) can be found.
+    #[arg(long, default_value = "")]
+    attacker_contract: String,
+
+    /// Maximum length (in bytes) of the raw calldata
+    #[arg(long, default_value = "256")]
+    raw_calldata_max_len: usize,
+
+    /// Also send plain ETH (empty calldata) and raw calldata to the targets
+    /// whose ABI does not declare receive() / fallback(), so that logic in
+    /// them (e.g., compounding on ETH receipt) is fuzzed. The value is picked
+    /// from --call-value-interesting
+    #[arg(long, default_value = "false")]
+    fuzz_fallback: bool,
+
+    /// Probe the view functions of the targets with fuzzed arguments before
+    /// and after each transaction changing their storage, and report those
+    /// whose value changed 10x (e.g., a manipulatable price) as hints in
+    /// <work_dir>/view_hints.jsonl. Hints are not findings, the states
+    /// reaching them and their arguments are explored more
+    #[arg(long, default_value = "false")]
+    probe_views: bool,
+
+    /// Interesting values of msg.value, separated by comma, in wei or in
+    /// ether with a decimal point (e.g., 2.0). The mutator picks from these
+    /// half of the time. (Default: 0,1,1 ether)
+    #[arg(long, default_value = "")]
+    call_value_interesting: String,
+
+    /// Maximum msg.value of fuzzed transactions, which also caps the WETH
+    /// borrowed by flashloan, in wei or in ether with a decimal point
+    /// (Default: 2^128 - 1)
+    #[arg(long, default_value = "")]
+    call_value_max: String,
+
+    /// Fuzz with real capital: each caller starts with this amount of ETH (in
+    /// wei, or in ether with a decimal point), ETH balances are tracked and
+    /// enforced, and flashloan is disabled (overrides --flashloan).
+    /// Findings then reflect what an attacker without flashloan could do.
+    /// (Default: disabled, callers have unlimited ETH)
+    #[arg(long)]
+    caller_balance: Option<String>,
+
+    /// Onchain - Interleave the transactions of the block after the fork block
+    /// with the fuzzed transactions, running one of them before or after most
+    /// fuzzed transactions, to find ordering dependent bugs
+    #[arg(long, default_value = "false")]
+    background_txns: bool,
+
+    /// Only fuzz these functions of the targets, separated by comma. Each is
+    /// a selector (0x715018a6) or a signature (renounceOwnership()),
+    /// optionally prefixed by the contract address (address:selector)
+    #[arg(long, default_value = "")]
+    fuzz_functions: String,
+
+    /// Never fuzz these functions of the targets, same format as
+    /// --fuzz-functions
+    #[arg(long, default_value = "")]
+    skip_functions: String,
+
+    /// Arguments of functions kept at a value while the other arguments are
+    /// fuzzed, separated by comma. Format: function:arg_index:value, where
+    /// function is in the format of --fuzz-functions, arg_index starts at 0
+    /// and value is in hex (0x...) or decimal. Only integer, address, bool
+    /// and fixed-size bytes arguments can be pinned.
+    #[arg(long, default_value = "")]
+    pin_args: String,
+
+    /// Addresses the address mutator samples from more often, separated by
+    /// comma. Well-known addresses (zero, 0xdead, precompiles) and discovered
+    /// tokens / pairs / routers are always included.
+    #[arg(long, default_value = "")]
+    interesting_addresses: String,
+
+    /// Percentage (0-100) of address mutations picking an interesting address
+    /// instead of a caller / contract of the address pool. Lower it to explore
+    /// contracts other than the known ones, raise it to focus on them
+    #[arg(long, default_value = "30")]
+    interesting_address_percent: u64,
+
+    /// NFT collections monitored by the nft_theft detector, separated by
+    /// comma. Format: address:erc721:id;id;... or address:erc1155:id;id;...
+    #[arg(long, default_value = "")]
+    nft_collections: String,
+
+    /// Contracts whose NFTs must not end up owned by callers, separated by
+    /// comma (Default: the fuzzed contracts)
+    #[arg(long, default_value = "")]
+    nft_holders: String,
+
+    /// Decimals of tokens that do not implement decimals(), separated by
+    /// comma. Format: address:decimals
+    #[arg(long, default_value = "")]
+    token_decimals: String,
+
+    /// Only discover the swap routes of these tokens (separated by comma),
+    /// print them and exit, without fuzzing. Requires an onchain config.
+    #[arg(long, default_value = "")]
+    print_paths: String,
+
+    /// Values added to the mutator's dictionary, e.g., role hashes or storage
+    /// keys the exploit needs to write, separated by comma, in hex (0x...) or
+    /// decimal. Constants pushed by the contracts' code are always included.
+    #[arg(long, default_value = "")]
+    dictionary_values: String,
+
+    /// Interesting token amounts added to the mutator's dictionary, separated
+    /// by comma. Format: address:amount;amount;... where an amount with a
+    /// decimal point (e.g., 2.0) is scaled by the token's decimals() and any
+    /// other amount is raw
+    #[arg(long, default_value = "")]
+    token_amounts: String,
+
+    /// Storage slots of the balances mapping of tokens, read and written
+    /// directly instead of calling balanceOf, separated by comma. Format:
+    /// address:slot or address:slot:vyper
+    #[arg(long, default_value = "")]
+    balance_slots: String,
+
+    /// Find the balances mapping slot of tokens without --balance-slots by
+    /// probing their storage
+    #[arg(long, default_value = "false")]
+    probe_balance_slots: bool,
+
+    /// Write the private keys of the callers signing permits to
+    /// <work_dir>/caller_keys.json, also done with --export-corpus
+    #[arg(long, default_value = "false")]
+    dump_caller_keys: bool,
+
+    /// Functions recognized as swaps besides the router functions, e.g., the
+    /// swap entrypoint of a reflection token, separated by comma. Format:
+    /// selector:type:path_index, where type is buy, sell, swap, deposit or
+    /// withdraw and path_index is the index of the token path argument
+    #[arg(long, default_value = "")]
+    swap_selectors: String,
+
+    /// Also send transactions from the largest holders of tokens (that are
+    /// not contracts), so that the fuzzer acts with their real balances,
+    /// separated by comma. Format: address:count. Requires an onchain config.
+    #[arg(long, default_value = "")]
+    top_holders: String,
+
+    /// Install the predeploys and precompiles of this chain (optimism, base,
+    /// arbitrum), e.g., the L1Block of the OP Stack, as stubs returning sane
+    /// defaults, so that calls to them do not revert. Defaults to the chain
+    /// forked from, on which only the precompiles are installed
+    #[arg(long, default_value = "")]
+    predeploys: String,
+
+    /// Changes to the state applied before fuzzing (and in setUp() of the
+    /// generated Foundry tests), separated by comma. Format:
+    /// storage:address:slot:value, balance:address:amount (in wei, or in
+    /// ether with a decimal point), code:address:hex or nonce:address:nonce
+    #[arg(long, default_value = "")]
+    state_overrides: String,
+
+    /// Transaction fees subtracted from the net profit of findings, e.g., the
+    /// L1 data fee on L2s. Format: key=value,... with keys gas_price (wei),
+    /// l1_base_fee (wei), l1_fee_scalar (millionths) and l1_fee_overhead (L1
+    /// gas), overriding the chain's defaults. (Default: free)
+    #[arg(long, default_value = "")]
+    tx_fee_model: String,
+
+    /// Accounts monitored by the allowance_drain detector, i.e., accounts
+    /// that have approved the targets to spend their tokens, separated by
+    /// comma
+    #[arg(long, default_value = "")]
+    allowance_victims: String,
+
     /// Preset file. If specified, will load the preset file and match past
     /// exploit template.
     #[cfg(feature = "use_presets")]
@@ -316,6 +638,11 @@ enum OracleType {
     TypedBug,
     SelfDestruct,
     Invariant,
+    NftTheft,
+    AllowanceDrain,
+    StorageCollision,
+    AccessControl,
+    FundsLocked,
 }
 
 impl OracleType {
@@ -331,6 +658,11 @@ impl OracleType {
             OracleType::TypedBug => "typed_bug",
             OracleType::SelfDestruct => "selfdestruct",
             OracleType::Invariant => "invariant",
+            OracleType::NftTheft => "nft_theft",
+            OracleType::AllowanceDrain => "allowance_drain",
+            OracleType::StorageCollision => "storage_collision",
+            OracleType::AccessControl => "access_control",
+            OracleType::FundsLocked => "funds_locked",
         }
     }
 
@@ -346,6 +678,11 @@ impl OracleType {
             "typed_bug" => OracleType::TypedBug,
             "selfdestruct" => OracleType::SelfDestruct,
             "invariant" => OracleType::Invariant,
+            "nft_theft" => OracleType::NftTheft,
+            "allowance_drain" => OracleType::AllowanceDrain,
+            "storage_collision" => OracleType::StorageCollision,
+            "access_control" => OracleType::AccessControl,
+            "funds_locked" => OracleType::FundsLocked,
             _ => panic!("Invalid detector type: {}", s),
         }
     }
@@ -370,6 +707,9 @@ impl OracleType {
                     OracleType::StateComparison,
                     OracleType::TypedBug,
                     OracleType::SelfDestruct,
+                    OracleType::StorageCollision,
+                    OracleType::AccessControl,
+                    OracleType::FundsLocked,
                 ];
             }
             if detector == "high_confidence" {
@@ -381,6 +721,7 @@ impl OracleType {
                     OracleType::TypedBug,
                     OracleType::SelfDestruct,
                     OracleType::Invariant,
+                    OracleType::AccessControl,
                 ];
             }
 
@@ -390,8 +731,32 @@ impl OracleType {
     }
 }
 
+/// Run a fuzzing campaign until its budget is used up or it stops on findings,
+/// without going through the CLI, e.g., to embed ityfuzz in another tool.
+///
+/// `state` is the one the contracts of `config` are loaded with (see
+/// [`ContractLoader`]), it holds the corpus of the campaign afterwards.
+pub fn run_campaign(config: EVMConfig, state: &mut EVMFuzzState) -> Result<CampaignResult<EVMInput>, Error> {
+    evm_fuzzer(config, state)
+}
+
+/// Run the `evm` subcommand, returns the exit code
 #[allow(clippy::type_complexity)]
-pub fn evm_main(args: EvmArgs) {
+pub fn evm_main(args: EvmArgs) -> i32 {
+    let fork_blocks = args
+        .fork_blocks
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.trim().parse::<u64>().expect("failed to parse fork block"))
+        .collect_vec();
+    if !fork_blocks.is_empty() {
+        return run_fork_block_campaigns(&fork_blocks, &args.work_dir);
+    }
+
+    assert!(
+        !args.target.is_empty(),
+        "a target is required (--target, or target in the project file)"
+    );
     let target = args.target.clone();
     let work_dir = args.work_dir.clone();
     let work_path = Path::new(work_dir.as_str());
@@ -447,9 +812,101 @@ pub fn evm_main(args: EvmArgs) {
     if onchain.is_some() && !etherscan_api_key.is_empty() {
         onchain.as_mut().unwrap().etherscan_api_key = etherscan_api_key.split(',').map(|s| s.to_string()).collect();
     }
+    if let Some(onchain) = onchain.as_mut() {
+        if let Some(block_number) = args.reserves_block_number {
+            onchain.set_reserves_block_number(block_number);
+        }
+        for item in args.token_decimals.split(',').filter(|s| !s.trim().is_empty()) {
+            let (token, decimals) = item.split_once(':').expect("token decimals should be address:decimals");
+            onchain.set_token_decimals(
+                EVMAddress::from_str(token.trim()).expect("failed to parse token address"),
+                decimals.trim().parse().expect("failed to parse token decimals"),
+            );
+        }
+    }
+    for item in args.swap_selectors.split(',').filter(|s| !s.trim().is_empty()) {
+        let [selector, ty, path_index] = item.split(':').collect::<Vec<_>>()[..] else {
+            panic!("swap selector should be selector:type:path_index");
+        };
+        let selector = hex::decode(selector.trim().trim_start_matches("0x")).expect("failed to parse swap selector");
+        SwapData::register_selector(
+            selector.try_into().expect("swap selector should be 4 bytes"),
+            SwapType::from_str(ty.trim()).expect("failed to parse swap type"),
+            path_index.trim().parse().expect("failed to parse swap path index"),
+        );
+    }
+    let mut top_holders = vec![];
+    for item in args.top_holders.split(',').filter(|s| !s.trim().is_empty()) {
+        let (token, count) = item.split_once(':').expect("top holders should be address:count");
+        let token = EVMAddress::from_str(token.trim()).expect("failed to parse token address");
+        let count = count.trim().parse().expect("failed to parse top holders count");
+        let onchain = onchain.as_mut().expect("--top-holders requires an onchain config");
+        let holders = onchain.fetch_top_holders(token, count);
+        info!("top holders of {:?}: {:?}", token, holders);
+        for holder in holders {
+            if !top_holders.contains(&holder) {
+                top_holders.push(holder);
+            }
+        }
+    }
+    let predeploy_chain = if !args.predeploys.is_empty() {
+        Some(Chain::from_str(&args.predeploys).expect("Invalid chain of predeploys"))
+    } else {
+        onchain
+            .as_ref()
+            .and_then(|onchain| Chain::from_str(&onchain.chain_name).ok())
+    };
+    let predeploys = predeploy_chain
+        .map(|chain| chain_predeploys(&chain, onchain.is_some()))
+        .unwrap_or_default();
+    if !args.print_paths.is_empty() {
+        let tokens = args
+            .print_paths
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| EVMAddress::from_str(s.trim()).expect("failed to parse token address"))
+            .collect_vec();
+        let onchain = onchain.as_mut().expect("--print-paths requires an onchain config");
+        println!("{}", describe_uniswap_paths(onchain, &tokens));
+        return 0;
+    }
+    let mut dictionary_values = args
+        .dictionary_values
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| EVMU256::from_str(s.trim()).expect("failed to parse dictionary value"))
+        .collect_vec();
+    for item in args.token_amounts.split(',').filter(|s| !s.trim().is_empty()) {
+        let (token, amounts) = item
+            .split_once(':')
+            .expect("token amounts should be address:amount;amount;...");
+        let token = EVMAddress::from_str(token.trim()).expect("failed to parse token address");
+        let decimals = onchain
+            .as_mut()
+            .and_then(|onchain| onchain.fetch_token_decimals(token))
+            .unwrap_or_else(|| {
+                warn!("decimals of {:?} unknown, assuming {}", token, DEFAULT_DECIMALS);
+                DEFAULT_DECIMALS
+            });
+        for amount in amounts.split(';').filter(|s| !s.trim().is_empty()) {
+            dictionary_values.push(parse_token_amount(amount, decimals).expect("failed to parse token amount"));
+        }
+    }
+    // decimals of WETH, i.e., the asset profits are measured in
+    let quote_decimals = onchain
+        .as_mut()
+        .and_then(|onchain| {
+            let weth = EVMAddress::from_str(&get_weth(&onchain.chain_name)).ok()?;
+            onchain.fetch_token_decimals(weth)
+        })
+        .unwrap_or(DEFAULT_DECIMALS);
     let erc20_producer = Rc::new(RefCell::new(ERC20Producer::new()));
 
     let flashloan_oracle = Rc::new(RefCell::new(IERC20OracleFlashloan::new(erc20_producer.clone())));
+    flashloan_oracle
+        .borrow_mut()
+        .set_min_profit(MinProfit::from_str(&args.min_profit).expect("failed to parse minimum profit"));
+    flashloan_oracle.borrow_mut().set_quote_decimals(quote_decimals);
 
     // let harness_code = "oracle_harness()";
     // let mut harness_hash: [u8; 4] = [0; 4];
@@ -505,7 +962,15 @@ pub fn evm_main(args: EvmArgs) {
         >,
     > = vec![];
 
-    let oracle_types = OracleType::from_strs(args.detectors.as_str());
+    let oracle_types = if args.assert_invariants {
+        assert!(
+            args.max_time.is_some() || args.max_execs.is_some(),
+            "--assert-invariants requires a budget (--max-time or --max-execs)"
+        );
+        vec![OracleType::Echidna, OracleType::Invariant]
+    } else {
+        OracleType::from_strs(args.detectors.as_str())
+    };
 
     if oracle_types.contains(&OracleType::Pair) {
         oracles.push(Rc::new(RefCell::new(PairBalanceOracle::new())));
@@ -672,8 +1137,44 @@ pub fn evm_main(args: EvmArgs) {
 
     contract_loader.force_abi(force_abis);
 
+    let attacker_contract = if !args.attacker_contract.is_empty() {
+        let attacker = ContractLoader::from_unique_prefix(&args.attacker_contract, &mut state)
+            .unwrap_or_else(|e| panic!("failed to load the attacker contract: {}", e));
+        let address = attacker.deployed_address;
+        contract_loader.contracts.push(attacker);
+        Some(address)
+    } else {
+        None
+    };
+
+    let caller_balance = args
+        .caller_balance
+        .as_ref()
+        .map(|balance| parse_token_amount(balance, DEFAULT_DECIMALS).expect("failed to parse caller balance"));
+    if caller_balance.is_some() && args.flashloan {
+        warn!("flashloan is disabled when fuzzing with caller balance");
+    }
+    let mut tx_fee_model = onchain
+        .as_ref()
+        .and_then(|onchain| Chain::from_str(&onchain.chain_name).ok())
+        .map(|chain| TxFeeModel::for_chain(&chain))
+        .unwrap_or_default();
+    tx_fee_model
+        .apply_overrides(&args.tx_fee_model)
+        .expect("failed to parse tx fee model");
+
     let config = Config {
         contract_loader,
+        raw_calldata_targets: args
+            .raw_calldata_targets
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| EVMAddress::from_str(s).expect("failed to parse raw calldata target"))
+            .collect(),
+        raw_calldata_max_len: args.raw_calldata_max_len,
+        fuzz_fallback: args.fuzz_fallback,
+        probe_views: args.probe_views,
+        attacker_contract,
         only_fuzz: if !args.only_fuzz.is_empty() {
             args.only_fuzz
                 .split(',')
@@ -686,6 +1187,7 @@ pub fn evm_main(args: EvmArgs) {
         concolic: args.concolic,
         concolic_caller: args.concolic_caller,
         concolic_timeout: args.concolic_timeout,
+        concolic_plateau: args.concolic_plateau,
         concolic_num_threads: {
             if args.concolic_num_threads == 0 {
                 num_cpus::get()
@@ -695,7 +1197,11 @@ pub fn evm_main(args: EvmArgs) {
         },
         oracle: oracles,
         producers,
-        flashloan: args.flashloan,
+        flashloan: args.flashloan && caller_balance.is_none(),
+        max_borrowed_assets: args.max_borrowed_assets.max(1),
+        quote_decimals,
+        infant_state_limit: args.infant_state_limit,
+        multi_block: args.multi_block,
         onchain_storage_fetching: if is_onchain {
             Some(
                 StorageFetchingMode::from_str(args.onchain_storage_fetching.as_str())
@@ -708,15 +1214,42 @@ pub fn evm_main(args: EvmArgs) {
         flashloan_oracle,
         selfdestruct_oracle: oracle_types.contains(&OracleType::SelfDestruct),
         reentrancy_oracle: oracle_types.contains(&OracleType::Reentrancy),
+        storage_collision_oracle: oracle_types.contains(&OracleType::StorageCollision),
+        access_control_oracle: oracle_types.contains(&OracleType::AccessControl),
+        funds_locked_oracle: oracle_types.contains(&OracleType::FundsLocked),
         work_dir: args.work_dir.clone(),
         write_relationship: args.write_relationship,
+        dump_recovered_abi: args.dump_recovered_abi,
         run_forever: args.run_forever,
+        stop_after_findings: args.stop_after_findings,
         sha3_bypass: args.sha3_bypass,
+        gas_feedback: args.gas_feedback,
         base_path: args.base_path,
         echidna_oracle: oracle_types.contains(&OracleType::Echidna),
         invariant_oracle: oracle_types.contains(&OracleType::Invariant),
+        nft_theft_oracle: oracle_types.contains(&OracleType::NftTheft),
+        nft_collections: args
+            .nft_collections
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| NftCollection::from_str(s).expect("failed to parse nft collection"))
+            .collect(),
+        nft_holders: args
+            .nft_holders
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| EVMAddress::from_str(s.trim()).expect("failed to parse nft holder"))
+            .collect(),
+        allowance_drain_oracle: oracle_types.contains(&OracleType::AllowanceDrain),
+        allowance_victims: args
+            .allowance_victims
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| EVMAddress::from_str(s.trim()).expect("failed to parse allowance victim"))
+            .collect(),
         panic_on_bug: args.panic_on_bug,
         spec_id: args.spec_id,
+        max_call_depth: args.max_call_depth,
         typed_bug: oracle_types.contains(&OracleType::TypedBug),
         arbitrary_external_call: oracle_types.contains(&OracleType::ArbitraryCall),
         math_calculate_oracle: oracle_types.contains(&OracleType::MathCalculate),
@@ -728,6 +1261,76 @@ pub fn evm_main(args: EvmArgs) {
         #[cfg(feature = "use_presets")]
         preset_file_path: args.preset_file_path,
         load_corpus: args.load_corpus,
+        checkpoint_interval: args.checkpoint_interval,
+        max_time: args.max_time,
+        max_execs: args.max_execs,
+        max_memory: args.max_memory,
+        assert_invariants: args.assert_invariants,
+        resume: args.resume,
+        caller_balance,
+        top_holders,
+        predeploys,
+        tx_fee_model,
+        balance_slots: args
+            .balance_slots
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|item| {
+                let (token, slot) = item
+                    .split_once(':')
+                    .expect("balance slot should be address:slot[:vyper]");
+                (
+                    EVMAddress::from_str(token.trim()).expect("failed to parse token address"),
+                    BalanceSlot::from_str(slot).expect("failed to parse balance slot"),
+                )
+            })
+            .collect(),
+        probe_balance_slots: args.probe_balance_slots,
+        // the exported replays are signed with these keys
+        dump_caller_keys: args.dump_caller_keys || !args.export_corpus.is_empty(),
+        state_overrides: args
+            .state_overrides
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| StateOverride::from_str(s).expect("failed to parse state override"))
+            .collect(),
+        background_txns: args.background_txns,
+        call_value: {
+            let default = CallValueMetadata::default();
+            let max_value = if args.call_value_max.is_empty() {
+                default.max_value
+            } else {
+                parse_token_amount(&args.call_value_max, DEFAULT_DECIMALS).expect("failed to parse call value max")
+            };
+            let interesting_values = if args.call_value_interesting.is_empty() {
+                default.interesting_values
+            } else {
+                args.call_value_interesting
+                    .split(',')
+                    .map(|s| parse_token_amount(s, DEFAULT_DECIMALS).expect("failed to parse interesting call value"))
+                    .collect()
+            };
+            CallValueMetadata::new(interesting_values, max_value)
+        },
+        dictionary_values,
+        interesting_addresses: args
+            .interesting_addresses
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| EVMAddress::from_str(s.trim()).expect("failed to parse interesting address"))
+            .collect(),
+        interesting_address_percent: {
+            assert!(
+                args.interesting_address_percent <= 100,
+                "--interesting-address-percent should be between 0 and 100"
+            );
+            args.interesting_address_percent
+        },
+        function_filter: FunctionFilterMetadata::new(
+            FunctionFilterMetadata::parse_patterns(&args.fuzz_functions).expect("failed to parse fuzz functions"),
+            FunctionFilterMetadata::parse_patterns(&args.skip_functions).expect("failed to parse skip functions"),
+        ),
+        arg_pins: ArgPinMetadata::parse(&args.pin_args).expect("failed to parse pinned args"),
     };
 
     let mut abis_map: HashMap<String, Vec<Vec<serde_json::Value>>> = HashMap::new();
@@ -759,5 +1362,18 @@ pub fn evm_main(args: EvmArgs) {
         .expect("Failed to open or create abis.json");
 
     writeln!(file, "{}", json_str).expect("Failed to write abis to abis.json");
-    evm_fuzzer(config, &mut state)
+    match run_campaign(config, &mut state) {
+        Ok(result) => {
+            if !args.export_corpus.is_empty() &&
+                let Err(e) = export_corpus(&args.export_corpus, &mut state)
+            {
+                error!("Failed to export the corpus to {}: {}", args.export_corpus, e);
+            }
+            result.stop_reason.exit_code()
+        }
+        Err(e) => {
+            error!("Fuzzing failed: {}", e);
+            1
+        }
+    }
 }
@@ -12,13 +12,16 @@ use libafl_bolts::{prelude::Rand, Named};
 use revm_interpreter::Interpreter;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use super::onchain::flashloan::CAN_LIQUIDATE;
+use super::onchain::flashloan::{SwapTokenMetadata, CAN_LIQUIDATE, MAX_BORROWED_ASSETS};
 /// Mutator for EVM inputs
-use crate::evm::input::EVMInputT;
+use crate::evm::input::{BlockAdvance, EVMInputT, MULTI_BLOCK};
 use crate::{
     evm::{
         abi::ABIAddressToInstanceMap,
-        input::EVMInputTy::Borrow,
+        function_filter::FunctionFilterMetadata,
+        input::EVMInputTy::{Borrow, Liquidate},
+        permit::CallerKeyMetadata,
+        tokens::SwapType,
         types::{convert_u256_to_h160, EVMAddress, EVMU256},
         vm::{Constraint, EVMStateT},
     },
@@ -117,6 +120,18 @@ where
         }
     }
 
+    /// Sign the input if it is a `permit` of a caller with a known private
+    /// key, see [`crate::evm::permit`]
+    fn sign_permit<I, S>(input: &mut I, state: &S)
+    where
+        I: EVMInputT,
+        S: HasMetadata,
+    {
+        if let Some(keys) = state.metadata_map().get::<CallerKeyMetadata>() {
+            input.sign_permit(keys);
+        }
+    }
+
     fn ensures_constraint<I, S>(input: &mut I, state: &mut S, new_vm_state: &VS, constraints: Vec<Constraint>) -> bool
     where
         I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
@@ -126,12 +141,12 @@ where
         for constraint in &constraints {
             match constraint {
                 Constraint::MustStepNow => {
-                    if input.get_input_type() == Borrow {
+                    if matches!(input.get_input_type(), Borrow | Liquidate) {
                         return false;
                     }
                 }
                 Constraint::Contract(_) => {
-                    if input.get_input_type() == Borrow {
+                    if matches!(input.get_input_type(), Borrow | Liquidate) {
                         return false;
                     }
                 }
@@ -180,6 +195,136 @@ where
         }
         true
     }
+
+    /// Flip the direction of the token swap of the input in place: a buy
+    /// (borrow) of a token becomes a sale of all of the token held, and a sale
+    /// becomes a buy of the token sold. The pinned route and the randomness
+    /// are kept, so that the round trip goes through the same pairs
+    fn flip_swap_direction<I>(input: &mut I) -> MutationResult
+    where
+        I: EVMInputT,
+    {
+        let routes = input.get_swap_routes();
+        match input.get_input_type() {
+            // buy -> sell
+            Borrow => {
+                if unsafe { !CAN_LIQUIDATE } {
+                    return MutationResult::Skipped;
+                }
+                if let Some(route) = routes.get(&String::from(SwapType::Buy)) {
+                    input.set_swap_route(SwapType::Sell, route.clone());
+                }
+                input.set_input_type(Liquidate);
+                input.set_co_borrows(vec![]);
+            }
+            // sell -> buy
+            Liquidate => {
+                if let Some(route) = routes.get(&String::from(SwapType::Sell)) {
+                    input.set_swap_route(SwapType::Buy, route.clone());
+                }
+                input.set_input_type(Borrow);
+                if input.get_txn_value().map_or(true, |v| v == EVMU256::ZERO) {
+                    // 10 ETH, same as the initial borrow inputs
+                    input.set_txn_value(EVMU256::from(10_000_000_000_000_000_000_u128));
+                }
+            }
+            _ => return MutationResult::Skipped,
+        }
+        MutationResult::Mutated
+    }
+
+    /// Add, resize or drop another asset bought along with the token of a
+    /// borrow, so that multiple assets are borrowed atomically, e.g., to
+    /// imbalance a multi-asset pool
+    fn mutate_co_borrows<I, S>(input: &mut I, state: &mut S) -> MutationResult
+    where
+        I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
+        S: State + HasRand + HasMetadata,
+    {
+        let max_assets = unsafe { MAX_BORROWED_ASSETS };
+        if max_assets <= 1 {
+            return MutationResult::Skipped;
+        }
+        // 1, 10, 100 or 1000 ETH
+        let amount = EVMU256::from(10).pow(EVMU256::from(18 + state.rand_mut().below(4)));
+        let mut co_borrows = input.get_co_borrows();
+        if !co_borrows.is_empty() {
+            let idx = state.rand_mut().below(co_borrows.len() as u64) as usize;
+            if co_borrows.len() + 1 >= max_assets || state.rand_mut().below(100) < 50 {
+                if state.rand_mut().below(100) < 50 {
+                    co_borrows.remove(idx);
+                } else {
+                    co_borrows[idx].1 = amount;
+                }
+                input.set_co_borrows(co_borrows);
+                return MutationResult::Mutated;
+            }
+        }
+
+        let tokens = match state.metadata_map().get::<SwapTokenMetadata>() {
+            Some(meta) => meta
+                .tokens
+                .iter()
+                // the token of the input itself may be picked, which only
+                // increases the amount bought
+                .filter(|token| co_borrows.iter().all(|(t, _)| t != *token))
+                .cloned()
+                .collect::<Vec<_>>(),
+            None => return MutationResult::Skipped,
+        };
+        if tokens.is_empty() {
+            return MutationResult::Skipped;
+        }
+        let token = tokens[state.rand_mut().below(tokens.len() as u64) as usize];
+        co_borrows.push((token, amount));
+        input.set_co_borrows(co_borrows);
+        MutationResult::Mutated
+    }
+
+    /// Add or drop a block boundary before the transaction, or change how
+    /// many blocks are mined at it, e.g., to let a TWAP oracle update between
+    /// two transactions
+    fn mutate_block_advance<I, S>(input: &mut I, state: &mut S) -> MutationResult
+    where
+        I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
+        S: State + HasRand,
+    {
+        if input.get_block_advance().is_some() && state.rand_mut().below(100) < 50 {
+            input.set_block_advance(None);
+            return MutationResult::Mutated;
+        }
+        let advance = BlockAdvance::random(state);
+        if input.get_block_advance() == Some(advance) {
+            return MutationResult::Skipped;
+        }
+        input.set_block_advance(Some(advance));
+        MutationResult::Mutated
+    }
+
+    /// Send the transaction from another caller. Each transaction of a
+    /// sequence has its own caller, so this makes multi-actor exploits (e.g.,
+    /// caller A approves and caller B transfers) reachable even when the
+    /// contract never reads `msg.sender` directly.
+    ///
+    /// All callers belong to the attacker: the flashloan oracle pools the
+    /// earnings and debts of every caller, and the victims are the accounts
+    /// outside of the caller set.
+    fn reassign_caller<I, S>(input: &mut I, state: &mut S) -> MutationResult
+    where
+        I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
+        S: State + HasRand + HasCaller<Addr>,
+    {
+        // a step resumes the transaction of the original caller
+        if input.is_step() {
+            return MutationResult::Skipped;
+        }
+        let caller = state.get_rand_caller();
+        if caller == input.get_caller() {
+            return MutationResult::Skipped;
+        }
+        input.set_caller(caller);
+        MutationResult::Mutated
+    }
 }
 
 impl<VS, Loc, Addr, SC, CI> Named for FuzzMutator<VS, Loc, Addr, SC, CI>
@@ -220,12 +365,18 @@ where
             // if flashloan_v2, we don't mutate if it's a borrow
             if input.get_input_type() != Borrow {
                 match state.get_next_call() {
-                    Some((addr, abi)) => {
+                    Some((addr, abi))
+                        if state
+                            .metadata_map()
+                            .get::<FunctionFilterMetadata>()
+                            .map_or(true, |filter| filter.is_allowed(&addr, &abi.function)) =>
+                    {
                         input.set_contract_and_abi(addr, Some(abi));
                         input.mutate(state);
+                        Self::sign_permit(input, state);
                         return Ok(MutationResult::Mutated);
                     }
-                    None => {
+                    _ => {
                         // debug!("cannot find next call");
                     }
                 }
@@ -279,7 +430,7 @@ where
                         mutated = true;
                     };
                 }
-                if input.get_input_type() != Borrow {
+                if !matches!(input.get_input_type(), Borrow | Liquidate) {
                     turn_to_step!();
                 }
 
@@ -317,7 +468,7 @@ where
             // (use to select the paths to buy token), VM state, and bytes
             if input.get_input_type() == Borrow {
                 let rand_u8 = state.rand_mut().below(255) as u8;
-                return match state.rand_mut().below(3) {
+                let mut res = match state.rand_mut().below(3) {
                     0 => {
                         // mutate the randomness
                         input.set_randomness(vec![rand_u8; 1]);
@@ -326,6 +477,34 @@ where
                     // mutate the bytes
                     _ => input.mutate(state),
                 };
+                // the swaps of the borrow are mutated on top of the above, so
+                // that their distribution is unchanged
+                // sell the bought tokens back through the same path
+                if state.rand_mut().below(4) == 0 && Self::flip_swap_direction(input) == MutationResult::Mutated {
+                    return MutationResult::Mutated;
+                }
+                // borrow another asset along with the token
+                if unsafe { MAX_BORROWED_ASSETS > 1 } &&
+                    state.rand_mut().below(4) == 0 &&
+                    Self::mutate_co_borrows(input, state) == MutationResult::Mutated
+                {
+                    res = MutationResult::Mutated;
+                }
+                return res;
+            }
+
+            // a sale has no bytes, only the randomness (used to select the
+            // path to sell the token) and its direction are mutated
+            if input.get_input_type() == Liquidate {
+                return match state.rand_mut().below(4) {
+                    // buy the sold tokens back through the same path
+                    0 => Self::flip_swap_direction(input),
+                    _ => {
+                        let rand_u8 = state.rand_mut().below(255) as u8;
+                        input.set_randomness(vec![rand_u8; 1]);
+                        MutationResult::Mutated
+                    }
+                };
             }
 
             // mutate the bytes or VM state or liquidation percent (percentage of token to
@@ -345,6 +524,8 @@ where
                     input.set_randomness(vec![rand_u8; 1]);
                     MutationResult::Mutated
                 }
+                14..=16 => Self::reassign_caller(input, state),
+                17..=18 if unsafe { MULTI_BLOCK } => Self::mutate_block_advance(input, state),
                 _ => input.mutate(state),
             }
         };
@@ -366,6 +547,50 @@ where
             }
             tries += 1;
         }
+        Self::sign_permit(input, state);
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evm::{
+            input::{ConciseEVMInput, EVMInputTy},
+            types::{EVMFuzzMutator, EVMStagedVMState},
+        },
+        input::VMInputT,
+    };
+
+    #[test]
+    fn test_flip_swap_direction() {
+        unsafe {
+            CAN_LIQUIDATE = true;
+        }
+        let token = EVMAddress::from_slice(&[1; 20]);
+        let route = vec![EVMAddress::from_slice(&[2; 20])];
+        let value = EVMU256::from(1_000_000_000_000_000_000_u128);
+        let (mut input, _) = ConciseEVMInput {
+            input_type: EVMInputTy::Borrow,
+            contract: token,
+            txn_value: Some(value),
+            ..Default::default()
+        }
+        .to_input(EVMStagedVMState::new_uninitialized());
+        input.set_swap_route(SwapType::Buy, route.clone());
+
+        // a buy of the token becomes a sale of the same token
+        assert_eq!(EVMFuzzMutator::flip_swap_direction(&mut input), MutationResult::Mutated);
+        assert_eq!(input.get_input_type(), EVMInputTy::Liquidate);
+        assert_eq!(input.get_contract(), token);
+        assert_eq!(input.get_swap_routes().get("sell"), Some(&route));
+
+        // and back to the buy
+        assert_eq!(EVMFuzzMutator::flip_swap_direction(&mut input), MutationResult::Mutated);
+        assert_eq!(input.get_input_type(), EVMInputTy::Borrow);
+        assert_eq!(input.get_contract(), token);
+        assert_eq!(input.get_txn_value(), Some(value));
+        assert_eq!(input.get_swap_routes().get("buy"), Some(&route));
+    }
+}
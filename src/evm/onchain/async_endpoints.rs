@@ -0,0 +1,248 @@
+//! Non-blocking counterpart of [`OnChainConfig`] for callers running on an
+//! async runtime (e.g., embedding ItyFuzz in a tokio service)
+
+use std::{collections::HashMap, fmt::Debug, str::FromStr, time::Duration};
+
+use bytes::Bytes;
+use revm_interpreter::analysis::to_analysed;
+use revm_primitives::Bytecode;
+use serde_json::{json, Value};
+use tracing::{debug, error, info};
+
+use super::endpoints::{get_header, Chain, OnChainConfig};
+use crate::evm::types::{EVMAddress, EVMU256};
+
+/// Number of attempts for a single RPC request
+const MAX_RETRIES: usize = 3;
+
+#[derive(Clone, Default)]
+pub struct AsyncOnChainConfig {
+    pub endpoint_url: String,
+    pub client: reqwest::Client,
+    pub chain_id: u32,
+    pub block_number: String,
+
+    balance_cache: HashMap<EVMAddress, EVMU256>,
+    slot_cache: HashMap<(EVMAddress, EVMU256), EVMU256>,
+    code_cache: HashMap<EVMAddress, String>,
+    code_cache_analyzed: HashMap<EVMAddress, Bytecode>,
+}
+
+impl Debug for AsyncOnChainConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncOnChainConfig")
+            .field("endpoint_url", &self.endpoint_url)
+            .field("chain_id", &self.chain_id)
+            .field("block_number", &self.block_number)
+            .field("balance_cache", &self.balance_cache)
+            .field("slot_cache", &self.slot_cache)
+            .field("code_cache", &self.code_cache)
+            .finish()
+    }
+}
+
+impl From<&OnChainConfig> for AsyncOnChainConfig {
+    fn from(config: &OnChainConfig) -> Self {
+        Self::new_raw(
+            config.endpoint_url.clone(),
+            config.chain_id,
+            config.block_number.clone(),
+        )
+    }
+}
+
+impl AsyncOnChainConfig {
+    /// Create a config for the chain, `block_number` 0 means the latest block
+    pub async fn new(chain: Chain, block_number: u64) -> Self {
+        let mut s = Self::new_raw(
+            chain.get_chain_rpc(),
+            chain.get_chain_id(),
+            format!("0x{:x}", block_number),
+        );
+        if block_number == 0 {
+            s.set_latest_block_number().await;
+        }
+        s
+    }
+
+    fn new_raw(endpoint_url: String, chain_id: u32, block_number: String) -> Self {
+        Self {
+            endpoint_url,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(20))
+                .build()
+                .expect("build client failed"),
+            chain_id,
+            block_number,
+            ..Default::default()
+        }
+    }
+
+    async fn post(&self, url: String, data: String) -> Option<String> {
+        for _ in 0..MAX_RETRIES {
+            let resp = self
+                .client
+                .post(url.to_string())
+                .headers(get_header())
+                .body(data.to_string())
+                .send()
+                .await;
+            match resp {
+                Ok(resp) => match resp.text().await {
+                    Ok(t) => return Some(t),
+                    Err(e) => error!("{:?}", e),
+                },
+                Err(e) => error!("Error: {}", e),
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        error!("did not succeed within {} tries", MAX_RETRIES);
+        None
+    }
+
+    async fn _request_with_id(&self, method: String, params: String, id: u32) -> Option<Value> {
+        let data = format!(
+            "{{\"jsonrpc\":\"2.0\", \"method\": \"{}\", \"params\": {}, \"id\": {}}}",
+            method, params, id
+        );
+        self.post(self.endpoint_url.clone(), data)
+            .await
+            .and_then(|resp| serde_json::from_str(&resp).ok())
+            .and_then(|json: Value| json.get("result").cloned())
+            .or_else(|| {
+                error!("failed to fetch from {}", self.endpoint_url);
+                None
+            })
+    }
+
+    async fn _request(&self, method: String, params: String) -> Option<Value> {
+        self._request_with_id(method, params, self.chain_id).await
+    }
+
+    pub async fn set_latest_block_number(&mut self) {
+        match self._request("eth_blockNumber".to_string(), "[]".to_string()).await {
+            Some(resp) => {
+                self.block_number = resp.as_str().unwrap().to_string();
+                debug!("latest block number is {}", self.block_number);
+            }
+            None => panic!("fail to get latest block number"),
+        }
+    }
+
+    pub async fn get_balance(&mut self, address: EVMAddress) -> EVMU256 {
+        if let Some(balance) = self.balance_cache.get(&address) {
+            return *balance;
+        }
+
+        let params = format!("[\"0x{:x}\",\"{}\"]", address, self.block_number);
+        let resp_string = match self._request("eth_getBalance".to_string(), params).await {
+            Some(resp) => resp.as_str().unwrap().to_string(),
+            None => "".to_string(),
+        };
+        let balance = EVMU256::from_str(&resp_string).unwrap();
+        info!("balance of {address:?} at {} is {balance}", self.block_number);
+        self.balance_cache.insert(address, balance);
+        balance
+    }
+
+    pub async fn get_contract_code(&mut self, address: EVMAddress, force_cache: bool) -> String {
+        if let Some(code) = self.code_cache.get(&address) {
+            return code.clone();
+        }
+        if force_cache {
+            return "".to_string();
+        }
+
+        info!("fetching code from {}", hex::encode(address));
+
+        let params = format!("[\"0x{:x}\",\"{}\"]", address, self.block_number);
+        let resp_string = match self._request("eth_getCode".to_string(), params).await {
+            Some(resp) => resp.as_str().unwrap().to_string(),
+            None => "".to_string(),
+        }
+        .trim_start_matches("0x")
+        .to_string();
+        self.code_cache.insert(address, resp_string.clone());
+        resp_string
+    }
+
+    pub async fn get_contract_code_analyzed(&mut self, address: EVMAddress, force_cache: bool) -> Bytecode {
+        if let Some(code) = self.code_cache_analyzed.get(&address) {
+            return code.clone();
+        }
+
+        let code = self.get_contract_code(address, force_cache).await;
+        let contract_code = to_analysed(Bytecode::new_raw(Bytes::from(
+            hex::decode(code).expect("fail to decode contract code"),
+        )));
+        self.code_cache_analyzed.insert(address, contract_code.clone());
+        contract_code
+    }
+
+    pub async fn get_contract_slot(&mut self, address: EVMAddress, slot: EVMU256, force_cache: bool) -> EVMU256 {
+        if let Some(value) = self.slot_cache.get(&(address, slot)) {
+            return *value;
+        }
+        if force_cache {
+            return EVMU256::ZERO;
+        }
+
+        let params = format!("[\"0x{:x}\",\"0x{:x}\",\"{}\"]", address, slot, self.block_number);
+        let resp_string = match self._request("eth_getStorageAt".to_string(), params).await {
+            Some(resp) => resp.as_str().unwrap().to_string(),
+            None => "".to_string(),
+        };
+
+        let slot_suffix = resp_string.trim_start_matches("0x");
+        let slot_value = if slot_suffix.is_empty() {
+            EVMU256::ZERO
+        } else {
+            EVMU256::try_from_be_slice(&hex::decode(slot_suffix).unwrap()).unwrap()
+        };
+        self.slot_cache.insert((address, slot), slot_value);
+        slot_value
+    }
+
+    /// Fetch reserves of a Uniswap V2 like pair, returns `None` if the RPC
+    /// returns unexpected data
+    pub async fn fetch_reserve(&self, pair: &str) -> Option<(String, String)> {
+        let params = json!([{
+            "to": pair,
+            "data": "0x0902f1ac",
+            "id": 1
+        }, self.block_number]);
+        debug!("fetching reserve for {pair} {}", self.block_number);
+        let result = match self
+            ._request_with_id("eth_call".to_string(), params.to_string(), 1)
+            .await
+        {
+            Some(resp) => resp.to_string(),
+            None => "".to_string(),
+        };
+
+        if result.len() != 196 {
+            error!("unexpected reserve of {pair}: {result}");
+            return None;
+        }
+
+        Some((result[3..67].to_string(), result[67..131].to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::debug;
+
+    use super::*;
+    use crate::evm::onchain::endpoints::Chain::ETH;
+
+    #[tokio::test]
+    async fn test_get_balance_async() {
+        let mut config = AsyncOnChainConfig::new(ETH, 18168677).await;
+        let v = config
+            .get_balance(EVMAddress::from_str("0x1f9090aaE28b8a3dCeaDf281B0F12828e676c326").unwrap())
+            .await;
+        debug!("{:?}", v);
+        assert!(v == EVMU256::from(439351222497229612i64));
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     env,
     fmt::Debug,
     hash::{Hash, Hasher},
+    mem::size_of,
     panic,
     str::FromStr,
     sync::Arc,
@@ -22,6 +23,7 @@ use tracing::{debug, error, info, warn};
 use crate::{
     cache::{Cache, FileSystemCache},
     evm::{
+        background::BackgroundTxn,
         tokens::TokenContext,
         types::{EVMAddress, EVMU256},
     },
@@ -216,12 +218,35 @@ pub struct GetPairResponseDataPairToken {
     pub id: String,
 }
 
+/// bytes32(uint256(keccak256('eip1967.proxy.implementation')) - 1)
+const EIP1967_IMPLEMENTATION_SLOT: EVMU256 = EVMU256::from_limbs([
+    0x20a3ca505d382bbc,
+    0xca3e2076cc3735a9,
+    0x667c828492db98d,
+    0x360894a13ba1a321,
+]);
+/// bytes32(uint256(keccak256('eip1967.proxy.beacon')) - 1)
+const EIP1967_BEACON_SLOT: EVMU256 = EVMU256::from_limbs([
+    0x6cb3582b35133d50,
+    0x35a9a72aeaee59ff,
+    0xfd80d3ef43465783,
+    0xa3f0ad74e5423aeb,
+]);
+
 #[derive(Clone, Default)]
 pub struct OnChainConfig {
     pub endpoint_url: String,
     pub client: reqwest::blocking::Client,
     pub chain_id: u32,
+    /// Block the fuzzed chain is forked at. Code, storage and balances are
+    /// fetched at this block.
     pub block_number: String,
+    /// Block the reserves of Uniswap pairs are fetched at (Default:
+    /// `block_number`). Only `initial_reserves` of the token contexts, which
+    /// value the tokens when computing profits, come from this block; the
+    /// storage of the pairs, including the reserves they hold during
+    /// execution, is still fetched at `block_number`.
+    pub reserves_block_number: Option<String>,
     pub timestamp: Option<String>,
     pub coinbase: Option<String>,
     pub gaslimit: Option<String>,
@@ -241,6 +266,10 @@ pub struct OnChainConfig {
     abi_cache: HashMap<EVMAddress, Option<String>>,
     storage_dump_cache: HashMap<EVMAddress, Option<Arc<HashMap<EVMU256, EVMU256>>>>,
     uniswap_path_cache: HashMap<EVMAddress, TokenContext>,
+    token_decimals_cache: HashMap<EVMAddress, Option<u8>>,
+    pair_address_cache: HashMap<(EVMAddress, EVMAddress, EVMAddress), Option<EVMAddress>>,
+    proxy_implementation_cache: HashMap<EVMAddress, Option<EVMAddress>>,
+    holders_cache: HashMap<(EVMAddress, usize), Vec<EVMAddress>>,
     rpc_cache: FileSystemCache,
 }
 
@@ -250,6 +279,7 @@ impl Debug for OnChainConfig {
             .field("endpoint_url", &self.endpoint_url)
             .field("chain_id", &self.chain_id)
             .field("block_number", &self.block_number)
+            .field("reserves_block_number", &self.reserves_block_number)
             .field("timestamp", &self.timestamp)
             .field("coinbase", &self.coinbase)
             .field("gaslimit", &self.gaslimit)
@@ -265,6 +295,10 @@ impl Debug for OnChainConfig {
             .field("abi_cache", &self.abi_cache)
             .field("storage_dump_cache", &self.storage_dump_cache)
             .field("uniswap_path_cache", &self.uniswap_path_cache)
+            .field("token_decimals_cache", &self.token_decimals_cache)
+            .field("pair_address_cache", &self.pair_address_cache)
+            .field("proxy_implementation_cache", &self.proxy_implementation_cache)
+            .field("holders_cache", &self.holders_cache)
             .field("rpc_cache", &self.rpc_cache)
             .finish()
     }
@@ -503,11 +537,7 @@ impl OnChainConfig {
             "{}?module=contract&action=getabi&address={:?}&format=json&apikey={}",
             self.etherscan_base,
             address,
-            if !self.etherscan_api_key.is_empty() {
-                self.etherscan_api_key[rand::random::<usize>() % self.etherscan_api_key.len()].clone()
-            } else {
-                "".to_string()
-            }
+            self.random_etherscan_api_key()
         );
         info!("fetching abi from {}", endpoint);
         match self.get(endpoint.clone()) {
@@ -537,6 +567,14 @@ impl OnChainConfig {
         }
     }
 
+    fn random_etherscan_api_key(&self) -> String {
+        if !self.etherscan_api_key.is_empty() {
+            self.etherscan_api_key[rand::random::<usize>() % self.etherscan_api_key.len()].clone()
+        } else {
+            "".to_string()
+        }
+    }
+
     pub fn fetch_abi(&mut self, address: EVMAddress) -> Option<String> {
         if self.abi_cache.contains_key(&address) {
             return self.abi_cache.get(&address).unwrap().clone();
@@ -622,6 +660,41 @@ impl OnChainConfig {
         timestamp
     }
 
+    /// Fetch the transactions calling contracts of the block following the
+    /// fork block, as the fork state already includes the fork block
+    pub fn fetch_next_block_transactions(&self) -> Vec<BackgroundTxn> {
+        let next = u64::from_str_radix(self.block_number.trim_start_matches("0x"), 16).unwrap_or_default() + 1;
+        let params = json!([format!("0x{:x}", next), true]);
+        let block = self
+            ._request("eth_getBlockByNumber".to_string(), params.to_string())
+            .filter(|block| !block.is_null());
+        let Some(block) = block else {
+            warn!("failed to fetch transactions of block {}", next);
+            return vec![];
+        };
+        let parse_address = |v: &Value| v.as_str().and_then(|s| EVMAddress::from_str(s).ok());
+        block["transactions"]
+            .as_array()
+            .map(|txns| {
+                txns.iter()
+                    .filter_map(|txn| {
+                        let data = hex::decode(txn["input"].as_str()?.trim_start_matches("0x")).ok()?;
+                        // skip contract creations and plain transfers
+                        if data.is_empty() {
+                            return None;
+                        }
+                        Some(BackgroundTxn {
+                            from: parse_address(&txn["from"])?,
+                            to: parse_address(&txn["to"])?,
+                            value: EVMU256::from_str(txn["value"].as_str()?).ok()?,
+                            data: Bytes::from(data),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn fetch_blk_coinbase(&mut self) -> EVMAddress {
         if self.coinbase.is_none() {
             self.coinbase = {
@@ -743,6 +816,33 @@ impl OnChainConfig {
         self.slot_cache.insert((address, slot), slot_value);
         slot_value
     }
+
+    /// Estimated bytes held by the caches of the fetched code, slots and
+    /// balances
+    pub fn cache_size(&self) -> usize {
+        let word = size_of::<EVMAddress>() + size_of::<EVMU256>();
+        self.slot_cache.len() * (word + size_of::<EVMU256>()) +
+            self.balance_cache.len() * word +
+            self.code_cache.values().map(|code| code.len()).sum::<usize>() +
+            self.code_cache_analyzed.values().map(|code| code.len()).sum::<usize>() +
+            self.abi_cache.values().flatten().map(|abi| abi.len()).sum::<usize>()
+    }
+
+    /// Drop the cached slots, balances and analyzed code, which are fetched
+    /// (from the RPC cache on disk) or analyzed again when needed. Returns the
+    /// estimated bytes freed.
+    ///
+    /// The raw code is kept, as it is not fetched again when the cache is
+    /// forced. For the same reason, slots are kept with the `force_cache`
+    /// feature.
+    pub fn evict_caches(&mut self) -> usize {
+        let before = self.cache_size();
+        #[cfg(not(feature = "force_cache"))]
+        self.slot_cache.clear();
+        self.balance_cache.clear();
+        self.code_cache_analyzed.clear();
+        before - self.cache_size()
+    }
 }
 
 impl OnChainConfig {
@@ -800,14 +900,25 @@ impl OnChainConfig {
         pairs
     }
 
+    /// Fetch reserves of Uniswap pairs at `block_number` instead of the fork
+    /// block, e.g., the block of an exploit being reproduced
+    pub fn set_reserves_block_number(&mut self, block_number: u64) {
+        self.reserves_block_number = Some(format!("0x{:x}", block_number));
+    }
+
+    /// Block the reserves of Uniswap pairs are fetched at
+    pub fn reserves_block(&self) -> &str {
+        self.reserves_block_number.as_deref().unwrap_or(&self.block_number)
+    }
+
     pub fn fetch_reserve(&self, pair: &str) -> (String, String) {
         let result = {
             let params = json!([{
             "to": pair,
             "data": "0x0902f1ac",
             "id": 1
-        }, self.block_number]);
-            debug!("fetching reserve for {pair} {}", self.block_number);
+        }, self.reserves_block()]);
+            debug!("fetching reserve for {pair} {}", self.reserves_block());
             let resp = self._request_with_id("eth_call".to_string(), params.to_string(), 1);
             match resp {
                 Some(resp) => resp.to_string(),
@@ -827,9 +938,220 @@ impl OnChainConfig {
 
         (reserve1.into(), reserve2.into())
     }
+
+    /// Resolve the implementation of an EIP-1967 proxy, either from the
+    /// implementation slot or, for beacon proxies, from `implementation()` of
+    /// the beacon. Returns `None` if `address` is not such a proxy.
+    pub fn fetch_proxy_implementation(&mut self, address: EVMAddress) -> Option<EVMAddress> {
+        if let Some(implementation) = self.proxy_implementation_cache.get(&address) {
+            return *implementation;
+        }
+        let slot_address = |slot: EVMU256| {
+            let bytes = slot.to_be_bytes::<32>();
+            let addr = EVMAddress::from_slice(&bytes[12..]);
+            (addr != EVMAddress::zero()).then_some(addr)
+        };
+
+        let mut implementation = slot_address(self.get_contract_slot(address, EIP1967_IMPLEMENTATION_SLOT, false));
+        if implementation.is_none() &&
+            let Some(beacon) = slot_address(self.get_contract_slot(address, EIP1967_BEACON_SLOT, false))
+        {
+            // implementation()
+            implementation = self
+                .eth_call(beacon, &[0x5c, 0x60, 0xda, 0x1b])
+                .filter(|ret| ret.len() == 32)
+                .and_then(|ret| slot_address(EVMU256::from_be_slice(&ret)));
+            debug!("{:?} is a beacon proxy of beacon {:?}", address, beacon);
+        }
+        // the slot may be used for something else by a non-proxy contract
+        let implementation = implementation.filter(|imp| !self.get_contract_code(*imp, false).is_empty());
+        if let Some(imp) = implementation {
+            info!("{:?} is a proxy of implementation {:?}", address, imp);
+        }
+        self.proxy_implementation_cache.insert(address, implementation);
+        implementation
+    }
+
+    /// Conduct an `eth_call` at the configured block and return the raw
+    /// return data
+    pub fn eth_call(&self, to: EVMAddress, data: &[u8]) -> Option<Vec<u8>> {
+        let params = json!([{
+            "to": format!("0x{:x}", to),
+            "data": format!("0x{}", hex::encode(data)),
+        }, self.block_number]);
+        let resp = self._request_with_id("eth_call".to_string(), params.to_string(), 1)?;
+        hex::decode(resp.as_str()?.trim_start_matches("0x")).ok()
+    }
+
+    /// Fetch `decimals()` of an ERC20 token, returns `None` if the token does
+    /// not implement it
+    pub fn fetch_token_decimals(&mut self, token: EVMAddress) -> Option<u8> {
+        if let Some(decimals) = self.token_decimals_cache.get(&token) {
+            return *decimals;
+        }
+        // decimals()
+        let decimals = self
+            .eth_call(token, &[0x31, 0x3c, 0xe5, 0x67])
+            .filter(|ret| ret.len() == 32 && ret[..31].iter().all(|b| *b == 0))
+            .map(|ret| ret[31]);
+        self.token_decimals_cache.insert(token, decimals);
+        decimals
+    }
+
+    /// Override the decimals of a token, e.g., for tokens that do not
+    /// implement `decimals()`
+    pub fn set_token_decimals(&mut self, token: EVMAddress, decimals: u8) {
+        self.token_decimals_cache.insert(token, Some(decimals));
+    }
+
+    /// Fetch the `count` largest holders of `token` that are not contracts,
+    /// largest `balanceOf()` at the configured block first. The holders are
+    /// listed by the explorer (at the latest block), so that a few more are
+    /// fetched to make up for the contracts and the holders that sold since.
+    pub fn fetch_top_holders(&mut self, token: EVMAddress, count: usize) -> Vec<EVMAddress> {
+        if let Some(holders) = self.holders_cache.get(&(token, count)) {
+            return holders.clone();
+        }
+        // the holders are kept in the RPC cache, as they are selected with
+        // many calls and the explorer lists the holders at the latest block
+        let mut hasher = DefaultHasher::new();
+        format!("holders_{}_{}_{:?}_{}", self.chain_id, self.block_number, token, count).hash(&mut hasher);
+        let key = hasher.finish().to_string();
+        if let Some(holders) = self
+            .rpc_cache
+            .load(&key)
+            .ok()
+            .and_then(|cached| serde_json::from_str::<Vec<EVMAddress>>(&cached).ok())
+        {
+            self.holders_cache.insert((token, count), holders.clone());
+            return holders;
+        }
+        let endpoint = format!(
+            "{}?module=token&action=tokenholderlist&contractaddress={:?}&page=1&offset={}&apikey={}",
+            self.etherscan_base,
+            token,
+            count * 2,
+            self.random_etherscan_api_key()
+        );
+        info!("fetching token holders from {}", endpoint);
+        let listed = match self.get(endpoint.clone()) {
+            Some(resp) => parse_token_holders(&resp),
+            None => {
+                error!("failed to fetch token holders from {}", endpoint);
+                vec![]
+            }
+        };
+
+        let mut holders = vec![];
+        for holder in listed {
+            if !self.get_contract_code(holder, false).is_empty() {
+                continue;
+            }
+            // balanceOf(address)
+            let mut data = vec![0x70, 0xa0, 0x82, 0x31];
+            data.extend_from_slice(&[0; 12]);
+            data.extend_from_slice(holder.as_bytes());
+            let balance = self
+                .eth_call(token, &data)
+                .filter(|ret| ret.len() == 32)
+                .map(|ret| EVMU256::from_be_slice(&ret))
+                .unwrap_or_default();
+            if balance > EVMU256::ZERO {
+                holders.push((holder, balance));
+            }
+        }
+        holders.sort_by(|a, b| b.1.cmp(&a.1));
+        let holders = holders.into_iter().take(count).map(|(holder, _)| holder).collect_vec();
+        // not cached on disk when the explorer failed, so that it is fetched
+        // again
+        if !holders.is_empty() {
+            self.rpc_cache
+                .save(&key, &serde_json::to_string(&holders).expect("failed to json"))
+                .unwrap();
+        }
+        self.holders_cache.insert((token, count), holders.clone());
+        holders
+    }
+
+    /// Fetch `getPair(token0, token1)` of a Uniswap V2 like factory, returns
+    /// the zero address if the factory has no such pair
+    pub fn fetch_pair_address(
+        &mut self,
+        factory: EVMAddress,
+        token0: EVMAddress,
+        token1: EVMAddress,
+    ) -> Option<EVMAddress> {
+        if let Some(pair) = self.pair_address_cache.get(&(factory, token0, token1)) {
+            return *pair;
+        }
+        // getPair(address,address)
+        let mut data = vec![0xe6, 0xa4, 0x39, 0x05];
+        for token in [token0, token1] {
+            data.extend_from_slice(&[0; 12]);
+            data.extend_from_slice(token.as_bytes());
+        }
+        let pair = self
+            .eth_call(factory, &data)
+            .filter(|ret| ret.len() == 32)
+            .map(|ret| EVMAddress::from_slice(&ret[12..]));
+        self.pair_address_cache.insert((factory, token0, token1), pair);
+        pair
+    }
+
+    /// Fetch `A()` and `fee()` of a Curve stableswap pool
+    pub fn fetch_stableswap_params(&self, pool: EVMAddress) -> Option<(EVMU256, EVMU256)> {
+        // A()
+        let amp = self.eth_call(pool, &[0xf4, 0x46, 0xc1, 0xd0])?;
+        // fee()
+        let fee = self.eth_call(pool, &[0xdd, 0xca, 0x3f, 0x43])?;
+        Some((
+            EVMU256::try_from_be_slice(amp.get(0..32)?)?,
+            EVMU256::try_from_be_slice(fee.get(0..32)?)?,
+        ))
+    }
+
+    /// Fetch `getNormalizedWeights()` and `getSwapFeePercentage()` of a
+    /// Balancer weighted pool
+    pub fn fetch_weighted_params(&self, pool: EVMAddress) -> Option<(Vec<EVMU256>, EVMU256)> {
+        // getNormalizedWeights()
+        let weights = self.eth_call(pool, &[0xf8, 0x9f, 0x27, 0xed])?;
+        // getSwapFeePercentage()
+        let fee = self.eth_call(pool, &[0x55, 0xc6, 0x76, 0x28])?;
+
+        let weights = match ethers::abi::decode(
+            &[ethers::abi::ParamType::Array(Box::new(ethers::abi::ParamType::Uint(
+                256,
+            )))],
+            &weights,
+        )
+        .ok()?
+        .pop()?
+        {
+            ethers::abi::Token::Array(tokens) => tokens
+                .into_iter()
+                .filter_map(|t| t.into_uint().map(|v| EVMU256::from_limbs(v.0)))
+                .collect_vec(),
+            _ => return None,
+        };
+        Some((weights, EVMU256::try_from_be_slice(fee.get(0..32)?)?))
+    }
+}
+
+/// Holders listed in a `tokenholderlist` response of the explorer
+fn parse_token_holders(resp: &str) -> Vec<EVMAddress> {
+    let Ok(json) = serde_json::from_str::<Value>(resp) else {
+        return vec![];
+    };
+    let Some(holders) = json["result"].as_array() else {
+        return vec![];
+    };
+    holders
+        .iter()
+        .filter_map(|holder| EVMAddress::from_str(holder["TokenHolderAddress"].as_str()?).ok())
+        .collect()
 }
 
-fn get_header() -> HeaderMap {
+pub(crate) fn get_header() -> HeaderMap {
     let mut headers = HeaderMap::new();
     headers.insert("authority", "etherscan.io".parse().unwrap());
     headers.insert("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.9".parse().unwrap());
@@ -884,6 +1206,16 @@ mod tests {
         debug!("{:?}", v)
     }
 
+    #[test]
+    fn test_parse_token_holders() {
+        let resp = r#"{"status":"1","message":"OK","result":[
+            {"TokenHolderAddress":"0x0000000000000000000000000000000000000001","TokenHolderQuantity":"2"},
+            {"TokenHolderAddress":"invalid","TokenHolderQuantity":"1"}
+        ]}"#;
+        assert_eq!(parse_token_holders(resp), vec![EVMAddress::from_low_u64_be(1)]);
+        assert!(parse_token_holders(r#"{"status":"0","message":"NOTOK","result":"Invalid API Key"}"#).is_empty());
+    }
+
     #[test]
     fn test_fetch_abi() {
         let mut config = OnChainConfig::new(BSC, 0);
@@ -0,0 +1,115 @@
+//! Transaction fees of findings.
+//!
+//! Each transaction of a finding pays for its execution gas and, on L2s, for
+//! posting its calldata to L1. The fees are subtracted from the net profit of
+//! the finding, so that findings that are only profitable before fees are not
+//! reported. Everything is free by default, as before.
+
+use crate::evm::{onchain::endpoints::Chain, types::EVMU256};
+
+/// Fee model of the campaign, set once before fuzzing
+pub static mut TX_FEE_MODEL: TxFeeModel = TxFeeModel::FREE;
+
+/// Gas of an L1 calldata byte
+const ZERO_BYTE_GAS: u128 = 4;
+const NONZERO_BYTE_GAS: u128 = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TxFeeModel {
+    /// Price of execution gas, in wei
+    pub gas_price: u64,
+    /// L1 base fee the calldata is priced at, in wei
+    pub l1_base_fee: u64,
+    /// Multiplier of the L1 data fee, in millionths (`l1FeeScalar` of OP
+    /// stack chains)
+    pub l1_fee_scalar: u64,
+    /// L1 gas added to each transaction (`l1FeeOverhead` of OP stack chains)
+    pub l1_fee_overhead: u64,
+}
+
+impl TxFeeModel {
+    pub const FREE: Self = Self {
+        gas_price: 0,
+        l1_base_fee: 0,
+        l1_fee_scalar: 0,
+        l1_fee_overhead: 0,
+    };
+
+    /// Fee model of the chain. Prices are zero until configured, so the
+    /// defaults are free, L2s only come with the shape of their L1 data fee.
+    pub fn for_chain(chain: &Chain) -> Self {
+        match chain {
+            Chain::OPTIMISM | Chain::BASE => Self {
+                l1_fee_scalar: 684_000,
+                l1_fee_overhead: 188,
+                ..Self::FREE
+            },
+            Chain::ARBITRUM => Self {
+                l1_fee_scalar: 1_000_000,
+                ..Self::FREE
+            },
+            _ => Self::FREE,
+        }
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.gas_price == 0 && (self.l1_base_fee == 0 || self.l1_fee_scalar == 0)
+    }
+
+    /// Override fields with `key=value` pairs separated by `,`, e.g.,
+    /// `gas_price=1000000,l1_base_fee=20000000000`
+    pub fn apply_overrides(&mut self, overrides: &str) -> Result<(), String> {
+        for item in overrides.split(',').filter(|s| !s.trim().is_empty()) {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {}", item))?;
+            let value = value
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("invalid value of {}: {}", key.trim(), e))?;
+            match key.trim() {
+                "gas_price" => self.gas_price = value,
+                "l1_base_fee" => self.l1_base_fee = value,
+                "l1_fee_scalar" => self.l1_fee_scalar = value,
+                "l1_fee_overhead" => self.l1_fee_overhead = value,
+                key => return Err(format!("unknown fee model field {}", key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// L1 data fee of a transaction with `calldata`, in wei
+    pub fn l1_data_fee(&self, calldata: &[u8]) -> u128 {
+        let zeros = calldata.iter().filter(|b| **b == 0).count() as u128;
+        let l1_gas =
+            zeros * ZERO_BYTE_GAS + (calldata.len() as u128 - zeros) * NONZERO_BYTE_GAS + self.l1_fee_overhead as u128;
+        l1_gas * self.l1_base_fee as u128 * self.l1_fee_scalar as u128 / 1_000_000
+    }
+
+    /// Total fee of a transaction, in wei
+    pub fn fee(&self, gas_used: u64, calldata: &[u8]) -> EVMU256 {
+        EVMU256::from(gas_used as u128 * self.gas_price as u128 + self.l1_data_fee(calldata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tx_fee() {
+        let mut model = TxFeeModel::for_chain(&Chain::OPTIMISM);
+        assert!(model.is_free());
+        assert_eq!(model.fee(100_000, &[1, 0, 2]), EVMU256::ZERO);
+
+        model.apply_overrides("gas_price=10, l1_base_fee=1000000").unwrap();
+        assert!(!model.is_free());
+        // (16 + 4 + 16 + 188) * 1e6 * 0.684
+        assert_eq!(model.l1_data_fee(&[1, 0, 2]), 153_216_000);
+        assert_eq!(model.fee(100_000, &[1, 0, 2]), EVMU256::from(1_000_000 + 153_216_000));
+
+        assert!(model.apply_overrides("gas=1").is_err());
+        assert!(model.apply_overrides("gas_price").is_err());
+        assert_eq!(TxFeeModel::for_chain(&Chain::ETH), TxFeeModel::FREE);
+    }
+}
@@ -6,7 +6,7 @@
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
-    fmt::Debug,
+    fmt::{Debug, Display, Formatter},
     ops::Deref,
     rc::Rc,
     str::FromStr,
@@ -21,21 +21,30 @@ use libafl::{
     schedulers::Scheduler,
     state::HasMetadata,
 };
+use libafl_bolts::impl_serdeany;
 use revm_interpreter::Interpreter;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::{
     evm::{
+        abi::register_interesting_address,
         contract_utils::ABIConfig,
         corpus_initializer::EnvMetadata,
         host::FuzzHost,
-        input::{ConciseEVMInput, EVMInput, EVMInputT, EVMInputTy},
+        input::{CallValueMetadata, ConciseEVMInput, EVMInput, EVMInputT, EVMInputTy},
         middlewares::middleware::{Middleware, MiddlewareType},
         mutator::AccessPattern,
         onchain::endpoints::OnChainConfig,
-        oracles::erc20::IERC20OracleFlashloan,
-        tokens::{uniswap::fetch_uniswap_path, TokenContext},
+        oracles::{erc20::IERC20OracleFlashloan, u512_div_float},
+        tokens::{
+            balance_slot::BalanceSlotMetadata,
+            format_token_amount,
+            parse_token_amount,
+            uniswap::{SwapDiscoveryMetadata, TokenContextRegistry},
+            TokenContext,
+            DEFAULT_DECIMALS,
+        },
         types::{convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256, EVMU512},
     },
     generic_vm::vm_state::VMStateT,
@@ -44,6 +53,9 @@ use crate::{
 };
 
 pub static mut CAN_LIQUIDATE: bool = false;
+/// Assets a single borrow input buys at most, more than 1 lets the attacker
+/// borrow multiple assets atomically (e.g., to imbalance a multi-asset pool)
+pub static mut MAX_BORROWED_ASSETS: usize = 1;
 
 #[macro_export]
 macro_rules! scale {
@@ -59,7 +71,8 @@ pub struct Flashloan {
     pair_address: HashSet<EVMAddress>,
     pub unbound_tracker: HashMap<usize, HashSet<EVMAddress>>, // pc -> [address called]
     pub flashloan_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
-    pub token_context_cache: HashMap<EVMAddress, TokenContext>,
+    /// Token contexts shared by all targets of the campaign
+    pub token_contexts: TokenContextRegistry,
 }
 
 impl Debug for Flashloan {
@@ -70,6 +83,24 @@ impl Debug for Flashloan {
     }
 }
 
+/// Tokens with a discovered swap path, i.e., tokens that can be bought by a
+/// borrow input
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SwapTokenMetadata {
+    pub tokens: Vec<EVMAddress>,
+}
+
+impl_serdeany!(SwapTokenMetadata);
+
+/// Token contexts registered with the flashloan oracle, kept in the fuzzer
+/// state so that campaign checkpoints include them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenContextMetadata {
+    pub contexts: HashMap<EVMAddress, TokenContext>,
+}
+
+impl_serdeany!(TokenContextMetadata);
+
 pub fn register_borrow_txn<VS, I, S, SC>(mut scheduler: SC, state: &mut S, token: EVMAddress)
 where
     I: Input + VMInputT<VS, EVMAddress, EVMAddress, ConciseEVMInput> + EVMInputT + 'static,
@@ -103,6 +134,9 @@ where
                 randomness: vec![0],
                 repeat: 1,
                 swap_data: HashMap::new(),
+                swap_routes: HashMap::new(),
+                co_borrows: vec![],
+                block_advance: None,
             }
         }
         .as_any()
@@ -111,6 +145,11 @@ where
         .clone(),
     ) as Testcase<I>;
     tc.set_exec_time(Duration::from_secs(0));
+    state
+        .metadata_map_mut()
+        .get_or_insert_with(SwapTokenMetadata::default)
+        .tokens
+        .push(token);
     let idx = state.corpus_mut().add(tc).expect("failed to add");
     scheduler.on_add(state, idx).expect("failed to call scheduler on_add");
 }
@@ -128,20 +167,45 @@ impl Flashloan {
             erc20_address: Default::default(),
             pair_address: Default::default(),
             unbound_tracker: Default::default(),
-            token_context_cache: Default::default(),
+            token_contexts: TokenContextRegistry::new(),
             flashloan_oracle,
         }
     }
 
-    fn get_token_context(&mut self, addr: EVMAddress) -> Option<TokenContext> {
-        self.endpoint.as_mut().map(|config| fetch_uniswap_path(config, addr))
+    fn get_token_context(&mut self, addr: EVMAddress, state: &mut EVMFuzzState) -> Option<TokenContext> {
+        let endpoint = self.endpoint.as_mut()?;
+        let (ctx, stats) = self.token_contexts.get_or_fetch_with_stats(endpoint, addr);
+        state
+            .metadata_map_mut()
+            .get_or_insert_with(SwapDiscoveryMetadata::default)
+            .record(stats);
+        // shares of the reserves of the pools are interesting call values
+        if let Some(call_value) = state.metadata_map_mut().get_mut::<CallValueMetadata>() {
+            for reserve in ctx.weth_reserves() {
+                call_value.add_reserve_fractions(reserve);
+            }
+        }
+        Some(ctx)
+    }
+
+    /// Register the token contexts of a resumed campaign with the oracle,
+    /// replacing the ones discovered again on startup
+    pub fn restore_token_contexts(&mut self, state: &EVMFuzzState) {
+        let Some(meta) = state.metadata_map().get::<TokenContextMetadata>() else {
+            return;
+        };
+        let mut oracle = self.flashloan_oracle.deref().borrow_mut();
+        for (token, ctx) in &meta.contexts {
+            oracle.register_token(*token, ctx.clone(), !ctx.swaps.is_empty());
+            self.erc20_address.insert(*token);
+        }
     }
 
     pub fn on_contract_insertion(
         &mut self,
         addr: &EVMAddress,
         abi: &[ABIConfig],
-        _state: &mut EVMFuzzState,
+        state: &mut EVMFuzzState,
     ) -> (bool, bool) {
         // should not happen, just sanity check
         if self.known_addresses.contains(addr) {
@@ -174,13 +238,28 @@ impl Flashloan {
         // check abi_signatures_token is subset of abi.name
         {
             if abi_signatures_token.iter().all(|x| abi_names.contains(x)) {
-                match self.get_token_context(*addr) {
+                match self.get_token_context(*addr, state) {
                     Some(token_ctx) => {
+                        // tokens, pairs and routers are often compared against in checks
+                        register_interesting_address(*addr, state);
+                        for path in &token_ctx.swaps {
+                            for address in path.pair_addresses().into_iter().chain(path.router_addresses()) {
+                                register_interesting_address(address, state);
+                            }
+                        }
                         let oracle = self.flashloan_oracle.deref().try_borrow_mut();
                         // avoid delegate call on token -> make oracle borrow multiple times
                         if oracle.is_ok() {
                             let can_liquidate = !token_ctx.swaps.is_empty(); // if there is more than one liquidation path, we can liquidate
+                            state
+                                .metadata_map_mut()
+                                .get_or_insert_with(TokenContextMetadata::default)
+                                .contexts
+                                .insert(*addr, token_ctx.clone());
                             oracle.unwrap().register_token(*addr, token_ctx, can_liquidate);
+                            if let Some(slots) = state.metadata_map_mut().get_mut::<BalanceSlotMetadata>() {
+                                slots.queue_probe(*addr);
+                            }
                             self.erc20_address.insert(*addr);
                             is_erc20 = true;
                         } else {
@@ -197,6 +276,7 @@ impl Flashloan {
         // if the contract is pair
         if abi_signatures_pair.iter().all(|x| abi_names.contains(x)) {
             self.pair_address.insert(*addr);
+            register_interesting_address(*addr, state);
             debug!("pair detected @ address {:?}", addr);
             is_pair = true;
         }
@@ -284,7 +364,7 @@ where
         // todo: fix for delegatecall
         let call_target: EVMAddress = convert_u256_to_h160(interp.stack.peek(1).unwrap());
 
-        if value_transfer > EVMU256::ZERO && s.has_caller(&call_target) {
+        if value_transfer > EVMU256::ZERO && s.is_attacker(&call_target) {
             host.evmstate.flashloan_data.earned += EVMU512::from(value_transfer) * scale!();
         }
 
@@ -305,11 +385,29 @@ pub struct FlashloanData {
     pub oracle_recheck_balance: HashSet<EVMAddress>,
     pub owed: EVMU512,
     pub earned: EVMU512,
+    /// Transaction fees paid so far, see [`crate::evm::onchain::fee`]
+    pub fees: EVMU512,
     pub prev_reserves: HashMap<EVMAddress, (EVMU256, EVMU256)>,
     pub unliquidated_tokens: HashMap<EVMAddress, EVMU256>,
+    /// WETH owed and earned for each borrowed asset, part of `owed` and
+    /// `earned`
+    pub assets: HashMap<EVMAddress, AssetLoan>,
     pub extra_info: String,
 }
 
+/// WETH owed for buying a borrowed asset and earned from selling it back
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct AssetLoan {
+    pub owed: EVMU512,
+    pub earned: EVMU512,
+}
+
+impl AssetLoan {
+    pub fn net(&self) -> NetProfit {
+        NetProfit::new(self.earned, self.owed)
+    }
+}
+
 impl FlashloanData {
     pub fn new() -> Self {
         Self {
@@ -317,9 +415,201 @@ impl FlashloanData {
             oracle_recheck_balance: HashSet::new(),
             owed: Default::default(),
             earned: Default::default(),
+            fees: Default::default(),
             prev_reserves: Default::default(),
             unliquidated_tokens: Default::default(),
+            assets: Default::default(),
             extra_info: Default::default(),
         }
     }
+
+    /// Net profit of the flashloan after transaction fees, i.e., `earned -
+    /// owed - fees`. `earned` and `owed` add up all borrowed assets, so this
+    /// is the sum of the net of each asset.
+    pub fn net_profit(&self) -> NetProfit {
+        NetProfit::new(self.earned, self.owed + self.fees)
+    }
+
+    /// Transaction fees paid so far in wei of the native currency
+    pub fn fees_wei(&self) -> EVMU512 {
+        self.fees / scale!()
+    }
+
+    /// Attribute the WETH owed and earned since `owed_before` and
+    /// `earned_before` to `asset`, i.e., to buying or selling it
+    pub fn attribute(&mut self, asset: EVMAddress, owed_before: EVMU512, earned_before: EVMU512) {
+        let owed = self.owed.saturating_sub(owed_before);
+        let earned = self.earned.saturating_sub(earned_before);
+        if owed == EVMU512::ZERO && earned == EVMU512::ZERO {
+            return;
+        }
+        let loan = self.assets.entry(asset).or_default();
+        loan.owed += owed;
+        loan.earned += earned;
+    }
+
+    /// Net of each borrowed asset, sorted by address
+    pub fn net_by_asset(&self) -> Vec<(EVMAddress, NetProfit)> {
+        let mut nets = self
+            .assets
+            .iter()
+            .map(|(asset, loan)| (*asset, loan.net()))
+            .collect::<Vec<_>>();
+        nets.sort_by_key(|(asset, _)| *asset);
+        nets
+    }
+}
+
+/// Signed difference between earned and owed, in the same scale as
+/// [`FlashloanData::earned`] (1 ETH = 1e24)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetProfit {
+    Profit(EVMU512),
+    Loss(EVMU512),
+}
+
+impl NetProfit {
+    pub fn new(earned: EVMU512, spent: EVMU512) -> Self {
+        if earned >= spent {
+            NetProfit::Profit(earned - spent)
+        } else {
+            NetProfit::Loss(spent - earned)
+        }
+    }
+
+    pub fn is_profit(&self) -> bool {
+        matches!(self, NetProfit::Profit(v) if *v > EVMU512::ZERO)
+    }
+
+    /// Human readable profit or loss, given the decimals of WETH
+    pub fn describe(&self, quote_decimals: u8) -> String {
+        match self {
+            NetProfit::Profit(_) => format!("~{} WETH profit", self.amount(quote_decimals)),
+            NetProfit::Loss(_) => format!("~{} WETH loss", self.amount(quote_decimals)),
+        }
+    }
+
+    /// Absolute amount with 3 decimals, given the decimals of the quote asset
+    pub fn amount(&self, decimals: u8) -> String {
+        let (NetProfit::Profit(v) | NetProfit::Loss(v)) = self;
+        // amounts are scaled by 1e6, so divide by 10^(decimals + 3) and keep 3
+        // decimals
+        u512_div_float(*v, EVMU512::from(10).pow(EVMU512::from(decimals as u64 + 3)), 3)
+    }
+}
+
+/// Minimum net profit of a finding, smaller profits (e.g., a few wei from
+/// rounding) are noise rather than exploits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MinProfit {
+    /// Amount of WETH in wei (with 18 decimals, rescaled to the decimals of
+    /// the quote asset)
+    Amount(EVMU256),
+    /// Basis points of the amount owed for borrowing
+    BorrowedBps(u64),
+}
+
+impl Default for MinProfit {
+    /// 0.01 WETH
+    fn default() -> Self {
+        MinProfit::Amount(EVMU256::from(10_000_000_000_000_000_u64))
+    }
+}
+
+impl MinProfit {
+    /// Threshold in the same scale as [`FlashloanData::earned`], given the
+    /// amount owed and the decimals of WETH
+    pub fn threshold(&self, owed: EVMU512, quote_decimals: u8) -> EVMU512 {
+        match self {
+            MinProfit::Amount(amount) => {
+                let quote_unit = EVMU512::from(10).pow(EVMU512::from(quote_decimals as u64));
+                let wei_unit = EVMU512::from(10).pow(EVMU512::from(DEFAULT_DECIMALS as u64));
+                EVMU512::from(*amount) * scale!() * quote_unit / wei_unit
+            }
+            MinProfit::BorrowedBps(bps) => owed * EVMU512::from(*bps) / EVMU512::from(10000),
+        }
+    }
+}
+
+impl FromStr for MinProfit {
+    type Err = String;
+
+    /// Format: an amount of WETH (in wei, or in ether with a decimal point),
+    /// or a percentage of the borrowed amount ending with `%`, e.g., `0.5%`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(percent) => {
+                let percent = percent
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid percentage {}", s))?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(format!("percentage {} is not between 0% and 100%", s));
+                }
+                Ok(MinProfit::BorrowedBps((percent * 100.0).round() as u64))
+            }
+            None => Ok(MinProfit::Amount(parse_token_amount(s, DEFAULT_DECIMALS)?)),
+        }
+    }
+}
+
+impl Display for MinProfit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MinProfit::Amount(amount) => write!(f, "{} WETH", format_token_amount(*amount, DEFAULT_DECIMALS, 6)),
+            MinProfit::BorrowedBps(bps) => write!(f, "{}% of the borrowed amount", *bps as f64 / 100.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_net_by_asset() {
+        let eth = |v: u64| EVMU512::from(v) * scale!();
+        let (token_a, token_b) = (EVMAddress::from_slice(&[1; 20]), EVMAddress::from_slice(&[2; 20]));
+        let mut data = FlashloanData::new();
+
+        // borrow both assets in one input
+        data.owed += eth(10);
+        data.attribute(token_a, EVMU512::ZERO, EVMU512::ZERO);
+        data.owed += eth(5);
+        data.attribute(token_b, eth(10), EVMU512::ZERO);
+
+        // sell them back, a earns 3 more than borrowed while b loses 1
+        data.earned += eth(13);
+        data.attribute(token_a, data.owed, EVMU512::ZERO);
+        let earned = data.earned;
+        data.earned += eth(4);
+        data.attribute(token_b, data.owed, earned);
+
+        assert_eq!(
+            data.net_by_asset(),
+            vec![(token_a, NetProfit::Profit(eth(3))), (token_b, NetProfit::Loss(eth(1)))]
+        );
+        assert_eq!(data.net_profit(), NetProfit::Profit(eth(2)));
+    }
+
+    #[test]
+    fn test_min_profit() {
+        let eth = |v: u64| EVMU512::from(v) * scale!() * EVMU512::from(10).pow(EVMU512::from(18));
+        assert_eq!(
+            MinProfit::default().threshold(EVMU512::ZERO, DEFAULT_DECIMALS),
+            eth(1) / EVMU512::from(100)
+        );
+        assert_eq!(MinProfit::from_str("0.01"), Ok(MinProfit::default()));
+        assert_eq!(MinProfit::from_str("0"), Ok(MinProfit::Amount(EVMU256::ZERO)));
+
+        let min_profit = MinProfit::from_str("0.5%").unwrap();
+        assert_eq!(min_profit, MinProfit::BorrowedBps(50));
+        assert_eq!(
+            min_profit.threshold(eth(10), DEFAULT_DECIMALS),
+            eth(1) / EVMU512::from(20)
+        );
+        assert!(MinProfit::from_str("120%").is_err());
+        assert!(MinProfit::from_str("much").is_err());
+    }
 }
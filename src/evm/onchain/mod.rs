@@ -1,11 +1,14 @@
 pub mod abi_decompiler;
+pub mod async_endpoints;
 pub mod endpoints;
+pub mod fee;
 pub mod flashloan;
 
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
     fmt::{Debug, Formatter},
+    mem::size_of,
     ops::Deref,
     rc::Rc,
     str::FromStr,
@@ -24,12 +27,14 @@ use super::{corpus_initializer::EnvMetadata, types::EVMFuzzState};
 use crate::{
     evm::{
         abi::{get_abi_type_boxed, register_abi_instance},
+        arg_pins::ArgPinMetadata,
         blaz::builder::{ArtifactInfoMetadata, BuildJob},
         bytecode_analyzer,
         config::StorageFetchingMode,
         contract_utils::{extract_sig_from_contract, ABIConfig, ContractLoader},
         corpus_initializer::ABIMap,
-        host::FuzzHost,
+        function_filter::FunctionFilterMetadata,
+        host::{FuzzHost, REAL_BALANCE},
         input::{EVMInput, EVMInputTy},
         middlewares::{
             cheatcode::CHEATCODE_ADDRESS,
@@ -37,10 +42,12 @@ use crate::{
         },
         mutator::AccessPattern,
         onchain::{abi_decompiler::fetch_abi_heimdall, endpoints::OnChainConfig, flashloan::register_borrow_txn},
+        oracles::view_probe::register_view_function,
         types::{convert_u256_to_h160, EVMAddress, EVMU256},
         vm::IS_FAST_CALL,
     },
     handle_contract_insertion,
+    memory::MemoryCache,
     state::HasCaller,
     state_input::StagedVMState,
 };
@@ -209,17 +216,15 @@ where
                     }
                 };
             }
-            #[cfg(feature = "real_balance")]
             // BALANCE
-            0x31 => {
+            0x31 if REAL_BALANCE => {
                 let address = convert_u256_to_h160(interp.stack.peek(0).unwrap());
                 debug!("onchain balance for {:?}", address);
                 // std::thread::sleep(std::time::Duration::from_secs(3));
                 host.next_slot = self.endpoint.get_balance(address);
             }
-            #[cfg(feature = "real_balance")]
             // 	SELFBALANCE
-            0x47 => {
+            0x47 if REAL_BALANCE => {
                 let address = interp.contract.address;
                 debug!("onchain selfbalance for {:?}", address);
                 // std::thread::sleep(std::time::Duration::from_secs(3));
@@ -233,8 +238,11 @@ where
             }
             // TIMESTAMP
             0x42 => {
-                if host.env.block.timestamp == EVMU256::from(1) {
-                    host.env.block.timestamp = self.endpoint.fetch_blk_timestamp();
+                // the placeholder timestamp (1) is advanced by the block boundaries
+                // of the sequence, so is the one fetched
+                let advanced = EVMU256::from(host.evmstate.block_offset.seconds);
+                if host.env.block.timestamp == EVMU256::from(1).saturating_add(advanced) {
+                    host.env.block.timestamp = self.endpoint.fetch_blk_timestamp().saturating_add(advanced);
                 }
             }
             // GASLIMIT
@@ -253,8 +261,7 @@ where
                 let address = match *interp.instruction_pointer {
                     0xf1 | 0xf2 => {
                         // CALL | CALLCODE
-                        #[cfg(feature = "real_balance")]
-                        {
+                        if REAL_BALANCE {
                             // Get balance of the callee
                             host.next_slot = self.endpoint.get_balance(caller);
                         }
@@ -292,6 +299,22 @@ where
     }
 }
 
+impl MemoryCache for OnChain {
+    fn name(&self) -> &'static str {
+        "onchain"
+    }
+
+    fn estimated_size(&self) -> usize {
+        let slot = size_of::<EVMAddress>() + size_of::<EVMU256>();
+        let dumped_slots = self.storage_dump.values().map(|dump| dump.len()).sum::<usize>();
+        self.loaded_data.len() * slot + dumped_slots * 2 * size_of::<EVMU256>() + self.endpoint.cache_size()
+    }
+
+    fn evict(&mut self) -> usize {
+        self.endpoint.evict_caches()
+    }
+}
+
 impl OnChain {
     #[allow(clippy::too_many_arguments)]
     pub fn load_code<SC>(
@@ -444,11 +467,18 @@ impl OnChain {
             }
         }
 
+        let filter = state
+            .metadata_map()
+            .get::<FunctionFilterMetadata>()
+            .cloned()
+            .unwrap_or_default();
         parsed_abi
             .iter()
             .filter(|v| !v.is_constructor)
             .filter(|v| abi_hashes_to_add.contains(&v.function))
+            .filter(|v| filter.is_allowed(&target, &v.function))
             .for_each(|abi| {
+                register_view_function(target, abi, state);
                 #[cfg(not(feature = "fuzz_static"))]
                 if abi.is_static {
                     return;
@@ -456,6 +486,9 @@ impl OnChain {
 
                 let mut abi_instance = get_abi_type_boxed(&abi.abi);
                 abi_instance.set_func_with_signature(abi.function, &abi.function_name, &abi.abi);
+                if let Some(pins) = state.metadata_map().get::<ArgPinMetadata>() {
+                    pins.apply(&target, &mut abi_instance);
+                }
                 register_abi_instance(target, abi_instance.clone(), state);
 
                 let input = EVMInput {
@@ -474,6 +507,9 @@ impl OnChain {
                     randomness: vec![0],
                     repeat: 1,
                     swap_data: HashMap::new(),
+                    swap_routes: HashMap::new(),
+                    co_borrows: vec![],
+                    block_advance: None,
                 };
                 add_corpus(host, state, &input);
             });
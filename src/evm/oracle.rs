@@ -1,6 +1,26 @@
 /// Dummy oracle for testing
-use crate::evm::input::ConciseEVMInput;
-use crate::{evm::srcmap::RawSourceMapInfo, fuzzer::ORACLE_OUTPUT};
+use std::ops::Deref;
+
+use bytes::Bytes;
+use libafl::state::HasMetadata;
+use tracing::{debug, info};
+
+use crate::{
+    evm::{
+        input::ConciseEVMInput,
+        solution,
+        srcmap::RawSourceMapInfo,
+        tokens::balance_slot::{known_balance, probe_balance_slot, BalanceSlotMetadata},
+        types::{EVMAddress, EVMOracleCtx, EVMU256},
+        vm::EVMState,
+    },
+    fuzzer::push_oracle_output,
+    generic_vm::vm_executor::GenericVM,
+    state::{HasCaller, HasExecutionResult},
+};
+
+/// balanceOf(address)
+const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
 
 pub struct EVMBugResult {
     pub bug_type: String,
@@ -20,6 +40,7 @@ impl EVMBugResult {
             "sourcemap": self.sourcemap,
             "issue_source": self.issue_source,
             "bug_idx": self.bug_idx,
+            "fork_block": solution::fork_block(),
         })
     }
 
@@ -52,9 +73,151 @@ impl EVMBugResult {
         }
     }
 
-    pub fn push_to_output(&self) {
-        unsafe {
-            ORACLE_OUTPUT.push(self.to_value());
+    /// Report the bug as a finding of the given oracle
+    pub fn push_to_output(&self, oracle: &str) {
+        push_oracle_output(oracle, self.to_value());
+    }
+}
+
+/// Balance and return data views of the execution, so that
+/// oracles need not extract balances from [`crate::evm::vm::EVMState`]
+impl<'a> EVMOracleCtx<'a> {
+    /// Callers registered in the fuzzer, i.e., the accounts of the attacker,
+    /// except the privileged ones (e.g., the detected owners of the targets)
+    /// and the bystanders (e.g., the top holders of tokens)
+    pub fn attackers(&self) -> Vec<EVMAddress> {
+        self.fuzz_state
+            .callers_pool
+            .iter()
+            .filter(|caller| self.fuzz_state.is_attacker(caller))
+            .cloned()
+            .collect()
+    }
+
+    /// Return data of the executed transaction
+    pub fn last_return_data(&self) -> &[u8] {
+        self.fuzz_state.get_execution_result().output.as_slice()
+    }
+
+    /// Return data of the last successful call to `addr` with `selector`
+    /// during the execution, e.g., the price a price feed returned to the
+    /// victim contract
+    pub fn return_data_of(&self, addr: EVMAddress, selector: [u8; 4]) -> Option<&[u8]> {
+        self.post_state.return_data_of(addr, selector)
+    }
+
+    /// Native balance of `addr` before the execution
+    pub fn native_balance_pre(&self, addr: &EVMAddress) -> EVMU256 {
+        self.pre_state.balance.get(addr).cloned().unwrap_or_default()
+    }
+
+    /// Native balance of `addr` after the execution
+    pub fn native_balance_post(&self, addr: &EVMAddress) -> EVMU256 {
+        self.post_state.balance.get(addr).cloned().unwrap_or_default()
+    }
+
+    fn balance_of_calls(queries: &[(EVMAddress, EVMAddress)]) -> Vec<(EVMAddress, Bytes)> {
+        queries
+            .iter()
+            .map(|(token, owner)| {
+                let mut call_data = BALANCE_OF.to_vec();
+                call_data.extend_from_slice(&[0; 12]);
+                call_data.extend_from_slice(owner.0.as_slice());
+                (*token, Bytes::from(call_data))
+            })
+            .collect()
+    }
+
+    fn decode_balances(results: Vec<Vec<u8>>) -> Vec<EVMU256> {
+        results
+            .iter()
+            .map(|res| EVMU256::try_from_be_slice(res.as_slice()).unwrap_or(EVMU256::ZERO))
+            .collect()
+    }
+
+    /// Balances of `queries` read from `vm_state` for the tokens with a known
+    /// balance slot, `None` for the others
+    fn known_balances(&self, vm_state: &EVMState, queries: &[(EVMAddress, EVMAddress)]) -> Vec<Option<EVMU256>> {
+        queries
+            .iter()
+            .map(|(token, owner)| known_balance(&*self.fuzz_state, vm_state, *token, owner))
+            .collect()
+    }
+
+    /// Fill in the balances not known from storage with `balanceOf` calls
+    fn complete_balances(
+        known: Vec<Option<EVMU256>>,
+        queries: &[(EVMAddress, EVMAddress)],
+        call: impl FnOnce(&[(EVMAddress, Bytes)]) -> Vec<Vec<u8>>,
+    ) -> Vec<EVMU256> {
+        let missing = queries
+            .iter()
+            .zip(&known)
+            .filter(|(_, balance)| balance.is_none())
+            .map(|(query, _)| *query)
+            .collect::<Vec<_>>();
+        let mut called = if missing.is_empty() {
+            vec![]
+        } else {
+            Self::decode_balances(call(&Self::balance_of_calls(&missing)))
+        }
+        .into_iter();
+        known
+            .into_iter()
+            .map(|balance| balance.unwrap_or_else(|| called.next().unwrap_or_default()))
+            .collect()
+    }
+
+    /// Probe the balance slots of the tokens discovered while fuzzing, on the
+    /// state after the execution, which has their storage loaded
+    fn probe_pending_balance_slots(&mut self) {
+        let pending = match self.fuzz_state.metadata_map_mut().get_mut::<BalanceSlotMetadata>() {
+            Some(meta) if !meta.pending.is_empty() => std::mem::take(&mut meta.pending),
+            _ => return,
+        };
+        for token in pending {
+            let found = probe_balance_slot(&self.post_state, token, |calls, probed| {
+                self.executor
+                    .deref()
+                    .borrow_mut()
+                    .fast_static_call(calls, probed, self.fuzz_state)
+            });
+            match found {
+                Some(slot) => {
+                    info!("Found the balances slot of {:?}: {:?}", token, slot);
+                    self.fuzz_state
+                        .metadata_map_mut()
+                        .get_mut::<BalanceSlotMetadata>()
+                        .unwrap()
+                        .slots
+                        .insert(token, slot);
+                }
+                None => debug!("No balances slot found for {:?}, using balanceOf", token),
+            }
         }
     }
+
+    /// ERC20 balances of `(token, owner)` pairs before the execution
+    pub fn balance_of_pre_batch(&mut self, queries: &[(EVMAddress, EVMAddress)]) -> Vec<EVMU256> {
+        self.probe_pending_balance_slots();
+        let known = self.known_balances(self.pre_state, queries);
+        Self::complete_balances(known, queries, |calls| self.call_pre_batch(calls))
+    }
+
+    /// ERC20 balances of `(token, owner)` pairs after the execution
+    pub fn balance_of_post_batch(&mut self, queries: &[(EVMAddress, EVMAddress)]) -> Vec<EVMU256> {
+        self.probe_pending_balance_slots();
+        let known = self.known_balances(&self.post_state, queries);
+        Self::complete_balances(known, queries, |calls| self.call_post_batch(calls))
+    }
+
+    /// ERC20 balance of `owner` before the execution
+    pub fn balance_of_pre(&mut self, token: EVMAddress, owner: EVMAddress) -> EVMU256 {
+        self.balance_of_pre_batch(&[(token, owner)])[0]
+    }
+
+    /// ERC20 balance of `owner` after the execution
+    pub fn balance_of_post(&mut self, token: EVMAddress, owner: EVMAddress) -> EVMU256 {
+        self.balance_of_post_batch(&[(token, owner)])[0]
+    }
 }
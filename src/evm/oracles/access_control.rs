@@ -0,0 +1,494 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use bytes::Bytes;
+use crypto::{digest::Digest, sha3::Sha3};
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        host::StorageWrite,
+        input::{ConciseEVMInput, EVMInput},
+        logs::EVMLog,
+        oracle::EVMBugResult,
+        oracles::{ACCESS_CONTROL_BUG_IDX, EIP1967_ADMIN_SLOT, EIP1967_BEACON_SLOT, EIP1967_IMPLEMENTATION_SLOT},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx, OracleDescriptor},
+    oracle_should_skip,
+    state::{HasCaller, HasExecutionResult},
+};
+
+/// owner()
+const OWNER: [u8; 4] = [0x8d, 0xa5, 0xcb, 0x5b];
+/// hasRole(bytes32,address)
+const HAS_ROLE: [u8; 4] = [0x91, 0xd1, 0x48, 0x54];
+
+/// OwnershipTransferred(address,address)
+const OWNERSHIP_TRANSFERRED: &str = "0x8be0079c531659141344cd1fd0a4f28419497f9722a3daafe3b4186f6b6457e0";
+/// RoleGranted(bytes32,address,address)
+const ROLE_GRANTED: &str = "0x2f8788117e7eff1d82e926ec794901d17c78024a50270940304540a733656f0d";
+
+/// Roles of OpenZeppelin's `AccessControl` named in the findings, others are
+/// shown by their hash
+const KNOWN_ROLES: [&str; 7] = [
+    "ADMIN_ROLE",
+    "MINTER_ROLE",
+    "BURNER_ROLE",
+    "PAUSER_ROLE",
+    "UPGRADER_ROLE",
+    "OPERATOR_ROLE",
+    "MANAGER_ROLE",
+];
+
+/// A caller that may have become privileged in a transaction, confirmed by
+/// calling the contract before and after it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Escalation {
+    /// `holder` may be the new `owner()` of `contract`
+    Owner { contract: EVMAddress, holder: EVMAddress },
+    /// `holder` may have been granted `role` of `contract`
+    Role {
+        contract: EVMAddress,
+        role: EVMU256,
+        holder: EVMAddress,
+    },
+}
+
+impl Escalation {
+    fn call(&self) -> (EVMAddress, Bytes) {
+        match self {
+            Self::Owner { contract, .. } => (*contract, Bytes::from(OWNER.to_vec())),
+            Self::Role { contract, role, holder } => {
+                let mut call_data = HAS_ROLE.to_vec();
+                call_data.extend_from_slice(&role.to_be_bytes::<32>());
+                call_data.extend_from_slice(&[0; 12]);
+                call_data.extend_from_slice(holder.0.as_slice());
+                (*contract, Bytes::from(call_data))
+            }
+        }
+    }
+
+    /// Whether `holder` is privileged according to the return data of
+    /// [`Escalation::call`]
+    fn holds(&self, out: &[u8]) -> bool {
+        if out.len() != 32 {
+            return false;
+        }
+        match self {
+            Self::Owner { holder, .. } => out[..12].iter().all(|b| *b == 0) && &out[12..] == holder.0.as_slice(),
+            Self::Role { .. } => out[31] == 1 && out[..31].iter().all(|b| *b == 0),
+        }
+    }
+}
+
+/// A change of the implementation, beacon or admin of an EIP-1967 proxy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyChange {
+    pub proxy: EVMAddress,
+    /// `implementation`, `beacon` or `admin`
+    pub kind: &'static str,
+    pub prev: EVMAddress,
+    pub value: EVMAddress,
+}
+
+/// Reports when a caller of the fuzzer that was not privileged becomes the
+/// owner (`Ownable`) or is granted a role (`AccessControl`) of a contract,
+/// or upgrades a proxy or changes its admin.
+///
+/// Candidates come from `OwnershipTransferred` / `RoleGranted` events and
+/// from storage writes of a caller's address (ownable contracts that do not
+/// emit events), and are confirmed with `owner()` / `hasRole` before and
+/// after the transaction. Proxy changes are the writes to the EIP-1967
+/// slots. Transactions sent by the privileged callers (the owners detected
+/// when the corpus is initialized) are authorized.
+pub struct AccessControlOracle {
+    ownership_transferred: EVMU256,
+    role_granted: EVMU256,
+    known_roles: Vec<(EVMU256, &'static str)>,
+    proxy_slots: Vec<(EVMU256, &'static str)>,
+}
+
+impl Default for AccessControlOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccessControlOracle {
+    pub fn new() -> Self {
+        let mut known_roles = vec![(EVMU256::ZERO, "DEFAULT_ADMIN_ROLE")];
+        known_roles.extend(KNOWN_ROLES.iter().map(|name| {
+            let mut hasher = Sha3::keccak256();
+            hasher.input_str(name);
+            let mut role = [0u8; 32];
+            hasher.result(&mut role);
+            (EVMU256::from_be_bytes(role), *name)
+        }));
+        Self {
+            ownership_transferred: EVMU256::from_str(OWNERSHIP_TRANSFERRED).unwrap(),
+            role_granted: EVMU256::from_str(ROLE_GRANTED).unwrap(),
+            known_roles,
+            proxy_slots: [
+                (EIP1967_IMPLEMENTATION_SLOT, "implementation"),
+                (EIP1967_BEACON_SLOT, "beacon"),
+                (EIP1967_ADMIN_SLOT, "admin"),
+            ]
+            .iter()
+            .map(|(slot, kind)| (EVMU256::from_str(slot).unwrap(), *kind))
+            .collect(),
+        }
+    }
+
+    fn role_name(&self, role: &EVMU256) -> String {
+        self.known_roles
+            .iter()
+            .find(|(hash, _)| hash == role)
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_else(|| format!("role {:#x}", role))
+    }
+
+    fn as_address(word: &EVMU256) -> Option<EVMAddress> {
+        let bytes = word.to_be_bytes::<32>();
+        let address = EVMAddress::from_slice(&bytes[12..]);
+        (bytes[..12].iter().all(|b| *b == 0) && !address.is_zero()).then_some(address)
+    }
+
+    /// Escalations of the accounts for which `is_candidate` holds, suggested
+    /// by the events and storage writes of a transaction
+    pub fn find_candidates(
+        &self,
+        logs: &[EVMLog],
+        writes: &[StorageWrite],
+        is_candidate: impl Fn(&EVMAddress) -> bool,
+    ) -> Vec<Escalation> {
+        let mut candidates = vec![];
+        let mut push = |escalation: Escalation| {
+            if !candidates.contains(&escalation) {
+                candidates.push(escalation);
+            }
+        };
+        for log in logs {
+            match log.topics.as_slice() {
+                [topic, _, new_owner] if *topic == self.ownership_transferred => {
+                    if let Some(holder) = Self::as_address(new_owner).filter(&is_candidate) {
+                        push(Escalation::Owner {
+                            contract: log.address,
+                            holder,
+                        });
+                    }
+                }
+                [topic, role, account, _] if *topic == self.role_granted => {
+                    if let Some(holder) = Self::as_address(account).filter(&is_candidate) {
+                        push(Escalation::Role {
+                            contract: log.address,
+                            role: *role,
+                            holder,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        for write in writes.iter().filter(|write| write.prev != write.value) {
+            if let Some(holder) = Self::as_address(&write.value).filter(&is_candidate) {
+                push(Escalation::Owner {
+                    contract: write.address,
+                    holder,
+                });
+            }
+        }
+        candidates
+    }
+
+    /// Changes of the EIP-1967 slots of proxies in `writes`, the last one per
+    /// (proxy, slot)
+    pub fn find_proxy_changes(&self, writes: &[StorageWrite]) -> Vec<ProxyChange> {
+        let as_address = |word: &EVMU256| EVMAddress::from_slice(&word.to_be_bytes::<32>()[12..]);
+        let mut changes: Vec<ProxyChange> = vec![];
+        for write in writes.iter().filter(|write| write.prev != write.value) {
+            let Some((_, kind)) = self.proxy_slots.iter().find(|(slot, _)| *slot == write.slot) else {
+                continue;
+            };
+            match changes
+                .iter_mut()
+                .find(|change| change.proxy == write.address && change.kind == *kind)
+            {
+                Some(change) => change.value = as_address(&write.value),
+                None => changes.push(ProxyChange {
+                    proxy: write.address,
+                    kind,
+                    prev: as_address(&write.prev),
+                    value: as_address(&write.value),
+                }),
+            }
+        }
+        changes.retain(|change| change.prev != change.value);
+        changes
+    }
+
+    fn report_proxy_change(&self, ctx: &mut EVMOracleCtx<'_>, change: &ProxyChange) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        change.proxy.hash(&mut hasher);
+        change.kind.hash(&mut hasher);
+        let real_bug_idx = (hasher.finish() << 8) + ACCESS_CONTROL_BUG_IDX;
+        if oracle_should_skip!(ctx, real_bug_idx) {
+            return None;
+        }
+
+        let info = format!(
+            "Caller {} changed the {} of proxy {} from {} to {}\n",
+            checksum(&ctx.input.get_caller()),
+            change.kind,
+            checksum(&change.proxy),
+            checksum(&change.prev),
+            checksum(&change.value)
+        );
+        EVMBugResult::new_simple(
+            "Access Control".to_string(),
+            real_bug_idx,
+            info,
+            ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+        )
+        .push_to_output("access_control");
+        Some(real_bug_idx)
+    }
+
+    fn report(&self, ctx: &mut EVMOracleCtx<'_>, escalation: &Escalation) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        let (contract, holder, role) = match escalation {
+            Escalation::Owner { contract, holder } => (contract, holder, "owner".to_string()),
+            Escalation::Role { contract, role, holder } => (contract, holder, self.role_name(role)),
+        };
+        contract.hash(&mut hasher);
+        role.hash(&mut hasher);
+        let real_bug_idx = (hasher.finish() << 8) + ACCESS_CONTROL_BUG_IDX;
+        if oracle_should_skip!(ctx, real_bug_idx) {
+            return None;
+        }
+
+        let sender = ctx.input.get_caller();
+        let info = if sender == *holder {
+            format!(
+                "Caller {} made itself {} of {}\n",
+                checksum(holder),
+                role,
+                checksum(contract)
+            )
+        } else {
+            format!(
+                "Caller {} became {} of {} in a transaction from {}\n",
+                checksum(holder),
+                role,
+                checksum(contract),
+                checksum(&sender)
+            )
+        };
+        EVMBugResult::new_simple(
+            "Access Control".to_string(),
+            real_bug_idx,
+            info,
+            ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+        )
+        .push_to_output("access_control");
+        Some(real_bug_idx)
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for AccessControlOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+        if ctx.fuzz_state.privileged_callers.contains(&ctx.input.get_caller()) {
+            return vec![];
+        }
+        let mut bugs = self
+            .find_proxy_changes(&ctx.post_state.storage_writes)
+            .iter()
+            .filter_map(|change| self.report_proxy_change(ctx, change))
+            .collect::<Vec<_>>();
+
+        let logs = &ctx.fuzz_state.get_execution_result().logs;
+        let candidates = self.find_candidates(logs, &ctx.post_state.storage_writes, |addr| {
+            ctx.fuzz_state.is_attacker(addr)
+        });
+        if candidates.is_empty() {
+            return bugs;
+        }
+
+        let calls = candidates
+            .iter()
+            .map(|escalation| escalation.call())
+            .collect::<Vec<_>>();
+        let pre = ctx.call_pre_batch(&calls);
+        let post = ctx.call_post_batch(&calls);
+        bugs.extend(
+            candidates
+                .iter()
+                .enumerate()
+                .filter(|(idx, escalation)| !escalation.holds(&pre[*idx]) && escalation.holds(&post[*idx]))
+                .filter_map(|(_, escalation)| self.report(ctx, escalation)),
+        );
+        bugs
+    }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("access_control")
+            .with(
+                "roles",
+                self.known_roles.iter().map(|(_, name)| *name).collect::<Vec<_>>(),
+            )
+            .with(
+                "proxy_slots",
+                self.proxy_slots.iter().map(|(_, kind)| *kind).collect::<Vec<_>>(),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_candidates() {
+        let contract = EVMAddress::from_slice(&[1; 20]);
+        let attacker = EVMAddress::from_slice(&[2; 20]);
+        let owner = EVMAddress::from_slice(&[3; 20]);
+        let word = |addr: &EVMAddress| EVMU256::from_be_slice(addr.0.as_slice());
+        let oracle = AccessControlOracle::new();
+        let minter = oracle.known_roles[2].0;
+        assert_eq!(oracle.role_name(&minter), "MINTER_ROLE");
+
+        let logs = vec![
+            EVMLog {
+                address: contract,
+                topics: vec![oracle.ownership_transferred, word(&owner), word(&attacker)],
+                data: vec![],
+            },
+            EVMLog {
+                address: contract,
+                topics: vec![oracle.role_granted, minter, word(&attacker), word(&attacker)],
+                data: vec![],
+            },
+            // granted to a non-caller
+            EVMLog {
+                address: contract,
+                topics: vec![oracle.role_granted, minter, word(&owner), word(&attacker)],
+                data: vec![],
+            },
+        ];
+        let write = |value: EVMU256| StorageWrite {
+            address: contract,
+            code_address: contract,
+            slot: EVMU256::ZERO,
+            prev: word(&owner),
+            value,
+        };
+        let writes = vec![
+            // same as the event
+            write(word(&attacker)),
+            // not an address
+            write(EVMU256::MAX),
+        ];
+
+        let candidates = oracle.find_candidates(&logs, &writes, |addr| *addr == attacker);
+        assert_eq!(
+            candidates,
+            vec![
+                Escalation::Owner {
+                    contract,
+                    holder: attacker
+                },
+                Escalation::Role {
+                    contract,
+                    role: minter,
+                    holder: attacker
+                },
+            ]
+        );
+
+        let mut out = vec![0; 12];
+        out.extend_from_slice(attacker.0.as_slice());
+        assert!(candidates[0].holds(&out));
+        assert!(!candidates[1].holds(&out));
+        assert!(candidates[1].holds(&EVMU256::from(1).to_be_bytes::<32>()));
+    }
+
+    #[test]
+    fn test_find_proxy_changes() {
+        let proxy = EVMAddress::from_slice(&[1; 20]);
+        let implementation = EVMAddress::from_slice(&[2; 20]);
+        let attacker = EVMAddress::from_slice(&[3; 20]);
+        let word = |addr: &EVMAddress| EVMU256::from_be_slice(addr.0.as_slice());
+        let oracle = AccessControlOracle::new();
+        let write = |slot: &str, prev: &EVMAddress, value: &EVMAddress| StorageWrite {
+            address: proxy,
+            code_address: implementation,
+            slot: EVMU256::from_str(slot).unwrap(),
+            prev: word(prev),
+            value: word(value),
+        };
+        let writes = vec![
+            write(EIP1967_IMPLEMENTATION_SLOT, &implementation, &attacker),
+            write(EIP1967_IMPLEMENTATION_SLOT, &attacker, &proxy),
+            // unchanged
+            write(EIP1967_ADMIN_SLOT, &attacker, &attacker),
+            // changed back
+            write(EIP1967_BEACON_SLOT, &implementation, &attacker),
+            write(EIP1967_BEACON_SLOT, &attacker, &implementation),
+            StorageWrite {
+                slot: EVMU256::ZERO,
+                ..write(EIP1967_ADMIN_SLOT, &implementation, &attacker)
+            },
+        ];
+        assert_eq!(
+            oracle.find_proxy_changes(&writes),
+            vec![ProxyChange {
+                proxy,
+                kind: "implementation",
+                prev: implementation,
+                value: proxy,
+            }]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::ALLOWANCE_DRAIN_BUG_IDX,
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
+    state::{HasCaller, HasExecutionResult},
+};
+
+/// Reports when tokens of a monitored victim are moved by `transferFrom`, i.e.,
+/// through an allowance granted by the victim, and either the spender or the
+/// recipient is controlled by the attacker
+pub struct AllowanceDrainOracle {
+    /// Accounts that have granted allowances to the targets
+    pub victims: Vec<EVMAddress>,
+}
+
+impl AllowanceDrainOracle {
+    pub fn new(victims: Vec<EVMAddress>) -> Self {
+        Self { victims }
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for AllowanceDrainOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        let mut bugs = vec![];
+        for call in ctx.post_state.transfer_froms.iter() {
+            if call.amount == EVMU256::ZERO ||
+                !self.victims.contains(&call.from) ||
+                !(ctx.fuzz_state.is_attacker(&call.spender) || ctx.fuzz_state.is_attacker(&call.to))
+            {
+                continue;
+            }
+
+            let mut hasher = DefaultHasher::new();
+            call.token.hash(&mut hasher);
+            call.spender.hash(&mut hasher);
+            call.from.hash(&mut hasher);
+            let real_bug_idx = (hasher.finish() << 8) + ALLOWANCE_DRAIN_BUG_IDX;
+
+            EVMBugResult::new_simple(
+                "Allowance Drain".to_string(),
+                real_bug_idx,
+                format!(
+                    "{} of token {} owned by {} is transferred to {} using the allowance of spender {}\n",
+                    call.amount,
+                    checksum(&call.token),
+                    checksum(&call.from),
+                    checksum(&call.to),
+                    checksum(&call.spender)
+                ),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+            )
+            .push_to_output("allowance_drain");
+            bugs.push(real_bug_idx);
+        }
+        bugs
+    }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("allowance_drain").with("victims", self.victims.iter().map(checksum).collect::<Vec<_>>())
+    }
+}
@@ -15,10 +15,10 @@ use crate::{
         oracle::EVMBugResult,
         oracles::ARB_CALL_BUG_IDX,
         srcmap::SOURCE_MAP_PROVIDER,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -100,21 +100,17 @@ impl
                 pc.hash(&mut hasher);
                 let real_bug_idx = (hasher.finish() << 8) + ARB_CALL_BUG_IDX;
 
-                let name = self
-                    .address_to_name
-                    .get(caller)
-                    .unwrap_or(&format!("{:?}", caller))
-                    .clone();
+                let name = self.address_to_name.get(caller).unwrap_or(&checksum(caller)).clone();
 
                 EVMBugResult::new(
                     "Arbitrary Call".to_string(),
                     real_bug_idx,
-                    format!("Arbitrary call from {:?} to {:?}", name, target),
+                    format!("Arbitrary call from {:?} to {}", name, checksum(target)),
                     ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
                     SOURCE_MAP_PROVIDER.lock().unwrap().get_raw_source_map_info(caller, *pc),
                     Some(name.clone()),
                 )
-                .push_to_output();
+                .push_to_output("arbitrary_call");
                 res.push(real_bug_idx);
             }
             res
@@ -122,4 +118,8 @@ impl
             vec![]
         }
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("arbitrary_call")
+    }
 }
@@ -16,11 +16,10 @@ use crate::{
         input::{ConciseEVMInput, EVMInput},
         oracle::EVMBugResult,
         oracles::{ARB_CALL_BUG_IDX, ARB_TRANSFER_BUG_IDX},
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMU256},
         vm::EVMState,
     },
-    fuzzer::ORACLE_OUTPUT,
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -70,7 +69,7 @@ impl
                 let mut name = self
                     .address_to_name
                     .get(caller)
-                    .unwrap_or(&format!("{:?}", caller))
+                    .unwrap_or(&checksum(caller))
                     .clone();
 
                 EVMBugResult::new(
@@ -81,7 +80,7 @@ impl
                     SOURCE_MAP_PROVIDER.lock().unwrap().get_raw_source_map_info(caller, *pc),
                     Some(name.clone()),
                 )
-                .push_to_output();
+                .push_to_output("arbitrary_transfer");
                 res.push(real_bug_idx);
             }
             res
@@ -89,4 +88,8 @@ impl
             vec![]
         }
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("arbitrary_transfer")
+    }
 }
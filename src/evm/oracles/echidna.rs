@@ -12,7 +12,7 @@ use crate::{
         types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -88,7 +88,7 @@ impl
                         None,
                         Some(name.clone()),
                     )
-                    .push_to_output();
+                    .push_to_output("echidna");
                     bug_idx
                 } else {
                     0
@@ -97,4 +97,8 @@ impl
             .filter(|x| *x != 0)
             .collect_vec()
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("echidna").with("functions", self.names.values().sorted().collect::<Vec<_>>())
+    }
 }
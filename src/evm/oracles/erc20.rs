@@ -5,37 +5,87 @@ use revm_primitives::Bytecode;
 
 use crate::{
     evm::{
+        host::REAL_BALANCE,
         input::{ConciseEVMInput, EVMInput},
-        onchain::flashloan::CAN_LIQUIDATE,
+        onchain::flashloan::{MinProfit, NetProfit, CAN_LIQUIDATE},
         oracle::EVMBugResult,
-        oracles::{u512_div_float, ERC20_BUG_IDX},
+        oracles::ERC20_BUG_IDX,
         producers::erc20::ERC20Producer,
-        tokens::TokenContext,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256, EVMU512},
+        tokens::{TokenContext, DEFAULT_DECIMALS},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256, EVMU512},
         vm::EVMState,
     },
     generic_vm::vm_state::VMStateT,
-    oracle::Oracle,
+    oracle::{Oracle, OracleDescriptor},
     state::HasExecutionResult,
 };
 
+/// How the value gained by the attacker in an execution is measured, e.g.,
+/// in a protocol token or in the floor price of NFTs instead of WETH
+pub trait ProfitModel {
+    /// Net value gained by the attackers ([`EVMOracleCtx::attackers`]) from
+    /// `ctx.pre_state` to `ctx.post_state`, in the same scale as
+    /// [`crate::evm::onchain::flashloan::FlashloanData::earned`]
+    fn net_profit(&self, ctx: &mut EVMOracleCtx<'_>) -> NetProfit;
+
+    /// Unit of the profit shown in reports
+    fn unit(&self) -> String {
+        "WETH".to_string()
+    }
+
+    /// Minimum profit worth reporting, `min_profit` is the one configured
+    /// for the WETH profit, which has `quote_decimals`
+    fn report_threshold(&self, ctx: &EVMOracleCtx<'_>, min_profit: &MinProfit, quote_decimals: u8) -> EVMU512 {
+        min_profit.threshold(ctx.post_state.flashloan_data.owed, quote_decimals)
+    }
+}
+
+/// Profit as WETH earned from selling tokens minus WETH owed for buying them
+pub struct WethProfitModel;
+
+impl ProfitModel for WethProfitModel {
+    fn net_profit(&self, ctx: &mut EVMOracleCtx<'_>) -> NetProfit {
+        ctx.post_state.flashloan_data.net_profit()
+    }
+}
+
 pub struct IERC20OracleFlashloan {
-    pub balance_of: Vec<u8>,
     pub known_tokens: HashMap<EVMAddress, TokenContext>,
     pub known_pair_reserve_slot: HashMap<EVMAddress, EVMU256>,
     pub erc20_producer: Rc<RefCell<ERC20Producer>>,
+    pub profit_model: Box<dyn ProfitModel>,
+    pub min_profit: MinProfit,
+    /// Decimals of WETH, i.e., the asset profits are measured in
+    pub quote_decimals: u8,
 }
 
 impl IERC20OracleFlashloan {
     pub fn new(erc20_producer: Rc<RefCell<ERC20Producer>>) -> Self {
         Self {
-            balance_of: hex::decode("70a08231").unwrap(),
             known_tokens: HashMap::new(),
             known_pair_reserve_slot: HashMap::new(),
             erc20_producer,
+            profit_model: Box::new(WethProfitModel),
+            min_profit: MinProfit::default(),
+            quote_decimals: DEFAULT_DECIMALS,
         }
     }
 
+    /// Measure the attacker's profit with `profit_model` instead of WETH
+    pub fn set_profit_model(&mut self, profit_model: Box<dyn ProfitModel>) {
+        self.profit_model = profit_model;
+    }
+
+    /// Do not report profits below `min_profit`
+    pub fn set_min_profit(&mut self, min_profit: MinProfit) {
+        self.min_profit = min_profit;
+    }
+
+    /// Format profits with the `decimals` of WETH
+    pub fn set_quote_decimals(&mut self, decimals: u8) {
+        self.quote_decimals = decimals;
+    }
+
     pub fn register_token(&mut self, token: EVMAddress, token_ctx: TokenContext, can_liquidate: bool) {
         // setting can_liquidate to true to turn on liquidation
         unsafe {
@@ -84,6 +134,13 @@ impl
             .flashloan_data
             .oracle_recheck_reserve
             .clear();
+
+        // no flashloan when fuzzing with real balances, so there is nothing to
+        // repay and earned / owed are not tracked
+        if unsafe { REAL_BALANCE } {
+            return vec![];
+        }
+
         let liquidation_percent = ctx.input.get_liquidation_percent();
         if liquidation_percent > 0 {
             // println!("Liquidation percent: {}", liquidation_percent);
@@ -94,10 +151,11 @@ impl
                 // println!("token: {:?}, user: {:?}, new_balance: {:?}", token, caller,
                 // new_balance);
                 if *new_balance > EVMU256::ZERO &&
-                    let Some(token_info) = self.known_tokens.get(token)
+                    let Some(token_info) = self.known_tokens.get(token) &&
+                    !token_info.is_unsellable()
                 {
                     let liq_amount = *new_balance * liquidation_percent / EVMU256::from(10);
-                    liquidations_earned.push((*caller, token_info, liq_amount));
+                    liquidations_earned.push((*caller, *token, token_info, liq_amount));
                 }
             }
 
@@ -107,8 +165,9 @@ impl
                 ctx.executor.deref().borrow_mut().host.evmstate = ctx.post_state.clone();
             }
             let mut failed = false;
-            for (caller, _token_info, _amount) in liquidations_earned {
+            for (caller, token, _token_info, _amount) in liquidations_earned {
                 let backup = ctx.executor.deref().borrow_mut().host.evmstate.clone();
+                let (owed, earned) = (backup.flashloan_data.owed, backup.flashloan_data.earned);
                 if _token_info
                     .sell(
                         _amount,
@@ -116,52 +175,104 @@ impl
                         ctx.fuzz_state,
                         &mut *ctx.executor.deref().borrow_mut(),
                         ctx.input.get_randomness().as_slice(),
+                        ctx.input.get_swap_routes().get("sell").map(|x| x.as_slice()),
                     )
                     .is_none()
                 {
                     ctx.executor.deref().borrow_mut().host.evmstate = backup;
                     continue;
                 }
+                ctx.executor
+                    .deref()
+                    .borrow_mut()
+                    .host
+                    .evmstate
+                    .flashloan_data
+                    .attribute(token, owed, earned);
             }
             if !failed {
-                ctx.fuzz_state.get_execution_result_mut().new_state.state =
-                    ctx.executor.deref().borrow_mut().host.evmstate.clone();
+                ctx.post_state = ctx.executor.deref().borrow_mut().host.evmstate.clone();
+                ctx.fuzz_state.get_execution_result_mut().new_state.state = ctx.post_state.clone();
             }
         }
 
-        let exec_res = ctx.fuzz_state.get_execution_result_mut();
-
-        if exec_res.new_state.state.has_post_execution() {
+        if ctx.post_state.has_post_execution() {
             return vec![];
         }
 
-        // println!(
-        //     "balance: {:?} - {:?}",
-        //     exec_res.new_state.state.flashloan_data.earned,
-        // exec_res.new_state.state.flashloan_data.owed );
-
-        if exec_res.new_state.state.flashloan_data.earned > exec_res.new_state.state.flashloan_data.owed &&
-            exec_res.new_state.state.flashloan_data.earned - exec_res.new_state.state.flashloan_data.owed >
-                EVMU512::from(10_000_000_000_000_000_000_000_u128)
-        // > 0.01ETH
+        let net_profit = self.profit_model.net_profit(ctx);
+        if let NetProfit::Profit(net) = net_profit &&
+            net > self
+                .profit_model
+                .report_threshold(ctx, &self.min_profit, self.quote_decimals)
         {
-            let net = exec_res.new_state.state.flashloan_data.earned - exec_res.new_state.state.flashloan_data.owed;
-            // we scaled by 1e24, so divide by 1e24 to get ETH
-            let net_eth = u512_div_float(net, EVMU512::from(1_000_000_000_000_000_000_000_u128), 3);
-
+            let unit = self.profit_model.unit();
+            let swap_impacts = ctx
+                .post_state
+                .swap_impacts
+                .iter()
+                .map(|impact| format!("  {}\n", impact))
+                .collect::<String>();
+            let weth_actions = ctx
+                .post_state
+                .weth_actions
+                .iter()
+                .map(|action| format!("  {}\n", action))
+                .collect::<String>();
+            // the net of each asset of a multi-asset flashloan
+            let asset_nets = ctx.post_state.flashloan_data.net_by_asset();
+            let asset_nets = if asset_nets.len() > 1 {
+                asset_nets
+                    .iter()
+                    .map(|(asset, net)| format!("  {}: {}\n", checksum(asset), net.describe(self.quote_decimals)))
+                    .collect::<String>()
+            } else {
+                String::new()
+            };
             EVMBugResult::new_simple(
                 "Fund Loss".to_string(),
                 ERC20_BUG_IDX,
                 format!(
-                    "Anyone can earn {} ETH by interacting with the provided contracts\n",
-                    net_eth,
+                    "Anyone can earn ~{} {} by interacting with the provided contracts{}\n{}{}{}",
+                    net_profit.amount(self.quote_decimals),
+                    unit,
+                    if ctx.post_state.flashloan_data.fees > EVMU512::ZERO {
+                        // fees are paid in the native currency, whatever the profit is measured in
+                        format!(
+                            " after {} wei of transaction fees",
+                            ctx.post_state.flashloan_data.fees_wei()
+                        )
+                    } else {
+                        String::new()
+                    },
+                    if swap_impacts.is_empty() {
+                        swap_impacts
+                    } else {
+                        format!("Price impact of the swaps through pairs and pools:\n{}", swap_impacts)
+                    },
+                    if weth_actions.is_empty() {
+                        weth_actions
+                    } else {
+                        format!("Native currency wrapped / unwrapped:\n{}", weth_actions)
+                    },
+                    if asset_nets.is_empty() {
+                        asset_nets
+                    } else {
+                        format!("Net of each borrowed asset:\n{}", asset_nets)
+                    }
                 ),
                 ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
             )
-            .push_to_output();
+            .push_to_output("erc20");
             vec![ERC20_BUG_IDX]
         } else {
             vec![]
         }
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("erc20")
+            .with("min_profit", self.min_profit.to_string())
+            .with("profit_unit", self.profit_model.unit())
+    }
 }
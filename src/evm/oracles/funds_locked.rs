@@ -0,0 +1,274 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+use libafl::state::HasMetadata;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        abi::ABIAddressToInstanceMap,
+        host::StorageWrite,
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::FUNDS_LOCKED_BUG_IDX,
+        tokens::{format_token_amount, DEFAULT_DECIMALS},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx, OracleDescriptor},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+/// Functions without arguments through which an owner takes the native
+/// currency held by a contract out
+const WITHDRAWALS: [([u8; 4], &str); 4] = [
+    ([0x3c, 0xcf, 0xd6, 0x0b], "withdraw"),
+    ([0x85, 0x38, 0x28, 0xb6], "withdrawAll"),
+    ([0xdb, 0x2e, 0x21, 0xbc], "emergencyWithdraw"),
+    ([0x4e, 0x71, 0xd9, 0x2d], "claim"),
+];
+
+/// A withdrawal of `contract` by `owner`, expected to succeed whenever the
+/// contract holds native currency
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawalProbe {
+    pub contract: EVMAddress,
+    pub owner: EVMAddress,
+    pub selector: [u8; 4],
+}
+
+impl WithdrawalProbe {
+    fn call(&self) -> (EVMAddress, EVMAddress, Bytes) {
+        (self.owner, self.contract, Bytes::from(self.selector.to_vec()))
+    }
+
+    fn function_name(&self) -> &'static str {
+        WITHDRAWALS
+            .iter()
+            .find(|(selector, _)| *selector == self.selector)
+            .map(|(_, name)| *name)
+            .unwrap_or("unknown")
+    }
+}
+
+/// Reports when the native currency held by a contract can no longer be
+/// withdrawn by its owner, e.g., a transaction of the attacker bricked the
+/// contract (griefing) without taking anything.
+///
+/// The owners are the privileged callers (the owners detected when the
+/// corpus is initialized). For the contracts whose storage a transaction
+/// changed and which still hold native currency afterwards, each of their
+/// withdrawal functions is called by each owner before and after the
+/// transaction: a withdrawal that succeeded before and reverts after is
+/// reported, with the storage changes that caused it. Transactions sent by
+/// the owners are authorized.
+pub struct FundsLockedOracle;
+
+impl Default for FundsLockedOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FundsLockedOracle {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Contracts whose storage changed in `writes`, in order of their first
+    /// change
+    pub fn changed_contracts(writes: &[StorageWrite]) -> Vec<EVMAddress> {
+        let mut contracts = vec![];
+        for write in writes.iter().filter(|write| write.prev != write.value) {
+            if !contracts.contains(&write.address) {
+                contracts.push(write.address);
+            }
+        }
+        contracts
+    }
+
+    /// Withdrawals by each of `owners` of each contract of `contracts` that
+    /// exposes them (given as the selectors of its ABI)
+    pub fn find_probes(contracts: &[(EVMAddress, Vec<[u8; 4]>)], owners: &[EVMAddress]) -> Vec<WithdrawalProbe> {
+        let mut probes = vec![];
+        for (contract, selectors) in contracts {
+            for (selector, _) in WITHDRAWALS.iter().filter(|(selector, _)| selectors.contains(selector)) {
+                probes.extend(owners.iter().map(|owner| WithdrawalProbe {
+                    contract: *contract,
+                    owner: *owner,
+                    selector: *selector,
+                }));
+            }
+        }
+        probes
+    }
+
+    /// Storage changes of `contract` in `writes`, one per line
+    pub fn describe_changes(contract: &EVMAddress, writes: &[StorageWrite]) -> String {
+        writes
+            .iter()
+            .filter(|write| write.address == *contract && write.prev != write.value)
+            .map(|write| format!("  slot {:#x}: {:#x} → {:#x}\n", write.slot, write.prev, write.value))
+            .collect()
+    }
+
+    fn report(&self, ctx: &mut EVMOracleCtx<'_>, probe: &WithdrawalProbe) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        probe.contract.hash(&mut hasher);
+        probe.selector.hash(&mut hasher);
+        let real_bug_idx = (hasher.finish() << 8) + FUNDS_LOCKED_BUG_IDX;
+        if oracle_should_skip!(ctx, real_bug_idx) {
+            return None;
+        }
+
+        let locked = ctx.native_balance_post(&probe.contract);
+        let info = format!(
+            "{} ETH held by {} is locked: {}() reverts for its owner {} after a transaction from {}\nStorage \
+             changes of the contract:\n{}",
+            format_token_amount(locked, DEFAULT_DECIMALS, 6),
+            checksum(&probe.contract),
+            probe.function_name(),
+            checksum(&probe.owner),
+            checksum(&ctx.input.get_caller()),
+            Self::describe_changes(&probe.contract, &ctx.post_state.storage_writes)
+        );
+        EVMBugResult::new_simple(
+            "Funds Locked".to_string(),
+            real_bug_idx,
+            info,
+            ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+        )
+        .push_to_output("funds_locked");
+        Some(real_bug_idx)
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for FundsLockedOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+        let owners = ctx.fuzz_state.privileged_callers.clone();
+        if owners.is_empty() || owners.contains(&ctx.input.get_caller()) {
+            return vec![];
+        }
+        let Some(abis) = ctx.fuzz_state.metadata_map().get::<ABIAddressToInstanceMap>() else {
+            return vec![];
+        };
+        let contracts = Self::changed_contracts(&ctx.post_state.storage_writes)
+            .into_iter()
+            .filter(|contract| ctx.native_balance_post(contract) > EVMU256::ZERO)
+            .filter_map(|contract| {
+                let selectors = abis.map.get(&contract)?.iter().map(|abi| abi.function).collect();
+                Some((contract, selectors))
+            })
+            .collect::<Vec<_>>();
+        let probes = Self::find_probes(&contracts, &owners);
+
+        let mut bugs = vec![];
+        let mut locked = vec![];
+        for probe in probes {
+            // one call per batch, as the calls of a batch share the state
+            if locked.contains(&probe.contract) || !ctx.call_pre_batch_dyn(&[probe.call()]).0[0].1 {
+                continue;
+            }
+            if ctx.call_post_batch_dyn(&[probe.call()]).0[0].1 {
+                continue;
+            }
+            locked.push(probe.contract);
+            bugs.extend(self.report(ctx, &probe));
+        }
+        bugs
+    }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("funds_locked").with("withdrawals", WITHDRAWALS.map(|(_, name)| name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_probes() {
+        let vault = EVMAddress::from_slice(&[1; 20]);
+        let token = EVMAddress::from_slice(&[2; 20]);
+        let owner = EVMAddress::from_slice(&[3; 20]);
+        let write = |address: EVMAddress, prev: u64, value: u64| StorageWrite {
+            address,
+            code_address: address,
+            slot: EVMU256::from(1),
+            prev: EVMU256::from(prev),
+            value: EVMU256::from(value),
+        };
+        let writes = vec![
+            write(token, 1, 1),
+            write(vault, 0, 1),
+            write(token, 1, 2),
+            write(vault, 1, 0),
+        ];
+        assert_eq!(FundsLockedOracle::changed_contracts(&writes), vec![vault, token]);
+        assert_eq!(
+            FundsLockedOracle::describe_changes(&vault, &writes),
+            "  slot 0x1: 0x0 → 0x1\n  slot 0x1: 0x1 → 0x0\n"
+        );
+
+        let transfer = [0xa9, 0x05, 0x9c, 0xbb];
+        let contracts = vec![
+            (vault, vec![transfer, WITHDRAWALS[0].0, WITHDRAWALS[2].0]),
+            (token, vec![transfer]),
+        ];
+        let probes = FundsLockedOracle::find_probes(&contracts, &[owner]);
+        assert_eq!(probes.len(), 2);
+        assert_eq!(
+            probes[1],
+            WithdrawalProbe {
+                contract: vault,
+                owner,
+                selector: WITHDRAWALS[2].0
+            }
+        );
+        assert_eq!(probes[0].function_name(), "withdraw");
+        assert_eq!(probes[0].call().2.as_ref(), &WITHDRAWALS[0].0);
+    }
+}
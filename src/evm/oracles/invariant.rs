@@ -18,7 +18,7 @@ use crate::{
         types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
-    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle::{BugMetadata, Oracle, OracleCtx, OracleDescriptor},
     oracle_should_skip,
     state::HasExecutionResult,
 };
@@ -136,9 +136,16 @@ impl
                 None,
                 Some(name.clone()),
             )
-            .push_to_output();
+            .push_to_output("invariant");
             res.push(bug_idx);
         }
         res
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("invariant").with(
+            "functions",
+            self.names.values().map(|(name, _)| name).sorted().collect::<Vec<_>>(),
+        )
+    }
 }
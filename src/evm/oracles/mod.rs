@@ -1,15 +1,21 @@
 use super::types::EVMU512;
 
+pub mod access_control;
+pub mod allowance;
 pub mod arb_call;
 pub mod echidna;
 pub mod erc20;
 pub mod function;
+pub mod funds_locked;
 pub mod invariant;
+pub mod nft;
 pub mod reentrancy;
 pub mod selfdestruct;
 pub mod state_comp;
+pub mod storage_collision;
 pub mod typed_bug;
 pub mod v2_pair;
+pub mod view_probe;
 
 pub static ERC20_BUG_IDX: u64 = 0;
 pub static FUNCTION_BUG_IDX: u64 = 1;
@@ -22,6 +28,16 @@ pub static ARB_CALL_BUG_IDX: u64 = 8;
 pub static REENTRANCY_BUG_IDX: u64 = 9;
 pub static INVARIANT_BUG_IDX: u64 = 10;
 pub static INTEGER_OVERFLOW_BUG_IDX: u64 = 11;
+pub static NFT_THEFT_BUG_IDX: u64 = 12;
+pub static ALLOWANCE_DRAIN_BUG_IDX: u64 = 13;
+pub static STORAGE_COLLISION_BUG_IDX: u64 = 14;
+pub static ACCESS_CONTROL_BUG_IDX: u64 = 15;
+pub static FUNDS_LOCKED_BUG_IDX: u64 = 16;
+
+/// EIP-1967 slots of proxies
+pub const EIP1967_IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+pub const EIP1967_ADMIN_SLOT: &str = "0xb53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103";
+pub const EIP1967_BEACON_SLOT: &str = "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
 
 /// Divide a U512 by another U512 and return a string with the decimal point at
 /// the correct position For example, 1000 / 3 = 333.333, then a = 1000e6, b =
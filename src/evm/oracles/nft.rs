@@ -0,0 +1,293 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::NFT_THEFT_BUG_IDX,
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    generic_vm::vm_state::VMStateT,
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
+    state::{HasCaller, HasExecutionResult},
+};
+
+/// ownerOf(uint256)
+const OWNER_OF: [u8; 4] = [0x63, 0x52, 0x21, 0x1e];
+/// balanceOf(address,uint256)
+const BALANCE_OF_1155: [u8; 4] = [0x00, 0xfd, 0xd5, 0x8e];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NftStandard {
+    ERC721,
+    ERC1155,
+}
+
+/// A monitored NFT collection and the token ids to track
+#[derive(Clone, Debug)]
+pub struct NftCollection {
+    pub address: EVMAddress,
+    pub standard: NftStandard,
+    pub token_ids: Vec<EVMU256>,
+}
+
+impl FromStr for NftCollection {
+    type Err = String;
+
+    /// Format: `address:erc721:id;id;...` or `address:erc1155:id;id;...`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.trim().split(':').collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(format!("invalid nft collection {}", s));
+        }
+        let address = EVMAddress::from_str(parts[0]).map_err(|_| format!("invalid address {}", parts[0]))?;
+        let standard = match parts[1].to_lowercase().as_str() {
+            "erc721" => NftStandard::ERC721,
+            "erc1155" => NftStandard::ERC1155,
+            other => return Err(format!("unknown nft standard {}", other)),
+        };
+        let token_ids = parts[2]
+            .split(';')
+            .filter(|id| !id.trim().is_empty())
+            .map(|id| EVMU256::from_str(id.trim()).map_err(|_| format!("invalid token id {}", id)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            address,
+            standard,
+            token_ids,
+        })
+    }
+}
+
+/// Reports when an NFT held by a monitored contract ends up owned by one of
+/// the callers (i.e., the attacker) after a transaction
+pub struct NftTheftOracle {
+    pub collections: Vec<NftCollection>,
+    /// Contracts whose NFTs should not move to the attacker
+    pub holders: Vec<EVMAddress>,
+}
+
+impl NftTheftOracle {
+    pub fn new(collections: Vec<NftCollection>, holders: Vec<EVMAddress>) -> Self {
+        Self { collections, holders }
+    }
+
+    fn encode_uint(v: &EVMU256) -> Vec<u8> {
+        v.to_be_bytes::<32>().to_vec()
+    }
+
+    fn encode_address(addr: &EVMAddress) -> Vec<u8> {
+        let mut res = vec![0; 12];
+        res.extend_from_slice(addr.0.as_slice());
+        res
+    }
+
+    fn decode_address(out: &[u8]) -> Option<EVMAddress> {
+        if out.len() < 32 {
+            return None;
+        }
+        Some(EVMAddress::from_slice(&out[12..32]))
+    }
+
+    fn decode_uint(out: &[u8]) -> EVMU256 {
+        EVMU256::try_from_be_slice(out).unwrap_or(EVMU256::ZERO)
+    }
+
+    fn report(&self, ctx: &mut EVMOracleCtx<'_>, collection: &NftCollection, token_id: &EVMU256, info: String) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        collection.address.hash(&mut hasher);
+        token_id.hash(&mut hasher);
+        let real_bug_idx = (hasher.finish() << 8) + NFT_THEFT_BUG_IDX;
+
+        EVMBugResult::new_simple(
+            "NFT Theft".to_string(),
+            real_bug_idx,
+            info,
+            ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+        )
+        .push_to_output("nft_theft");
+        real_bug_idx
+    }
+
+    fn check_erc721(&self, ctx: &mut EVMOracleCtx<'_>, collection: &NftCollection) -> Vec<u64> {
+        let calls = collection
+            .token_ids
+            .iter()
+            .map(|id| {
+                let call_data = Bytes::from([OWNER_OF.to_vec(), Self::encode_uint(id)].concat());
+                (collection.address, call_data)
+            })
+            .collect::<Vec<_>>();
+        let pre_owners = ctx.call_pre_batch(&calls);
+        let post_owners = ctx.call_post_batch(&calls);
+
+        let mut bugs = vec![];
+        for (idx, token_id) in collection.token_ids.iter().enumerate() {
+            let (Some(pre_owner), Some(post_owner)) = (
+                Self::decode_address(&pre_owners[idx]),
+                Self::decode_address(&post_owners[idx]),
+            ) else {
+                continue;
+            };
+            if self.holders.contains(&pre_owner) && ctx.fuzz_state.is_attacker(&post_owner) {
+                let info = format!(
+                    "ERC721 {} #{} held by {} is now owned by {}\n",
+                    checksum(&collection.address),
+                    token_id,
+                    checksum(&pre_owner),
+                    checksum(&post_owner)
+                );
+                bugs.push(self.report(ctx, collection, token_id, info));
+            }
+        }
+        bugs
+    }
+
+    fn check_erc1155(&self, ctx: &mut EVMOracleCtx<'_>, collection: &NftCollection) -> Vec<u64> {
+        let callers = ctx.attackers();
+        let accounts = self.holders.iter().chain(callers.iter()).cloned().collect::<Vec<_>>();
+        let calls = collection
+            .token_ids
+            .iter()
+            .flat_map(|id| {
+                accounts.iter().map(move |account| {
+                    let call_data = Bytes::from(
+                        [
+                            BALANCE_OF_1155.to_vec(),
+                            Self::encode_address(account),
+                            Self::encode_uint(id),
+                        ]
+                        .concat(),
+                    );
+                    (collection.address, call_data)
+                })
+            })
+            .collect::<Vec<_>>();
+        let pre_balances = ctx.call_pre_batch(&calls);
+        let post_balances = ctx.call_post_batch(&calls);
+
+        let mut bugs = vec![];
+        for (id_idx, token_id) in collection.token_ids.iter().enumerate() {
+            let base = id_idx * accounts.len();
+            let delta = |idx: usize| {
+                let pre = Self::decode_uint(&pre_balances[base + idx]);
+                let post = Self::decode_uint(&post_balances[base + idx]);
+                (pre, post)
+            };
+
+            let holder_lost = (0..self.holders.len()).any(|idx| {
+                let (pre, post) = delta(idx);
+                post < pre
+            });
+            if !holder_lost {
+                continue;
+            }
+            let gainer = (0..callers.len()).find_map(|idx| {
+                let (pre, post) = delta(self.holders.len() + idx);
+                if post > pre {
+                    Some((callers[idx], post - pre))
+                } else {
+                    None
+                }
+            });
+            if let Some((caller, amount)) = gainer {
+                let info = format!(
+                    "ERC1155 {} #{}: {} moved from monitored holders to {}\n",
+                    checksum(&collection.address),
+                    token_id,
+                    amount,
+                    checksum(&caller)
+                );
+                bugs.push(self.report(ctx, collection, token_id, info));
+            }
+        }
+        bugs
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for NftTheftOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+        self.collections
+            .iter()
+            .flat_map(|collection| match collection.standard {
+                NftStandard::ERC721 => self.check_erc721(ctx, collection),
+                NftStandard::ERC1155 => self.check_erc1155(ctx, collection),
+            })
+            .collect()
+    }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("nft_theft")
+            .with(
+                "collections",
+                self.collections
+                    .iter()
+                    .map(|collection| format!("{} ({:?})", checksum(&collection.address), collection.standard))
+                    .collect::<Vec<_>>(),
+            )
+            .with("holders", self.holders.iter().map(checksum).collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nft_collection() {
+        let collection = NftCollection::from_str("0x0000000000000000000000000000000000000001:erc721:1;2;3").unwrap();
+        assert_eq!(collection.standard, NftStandard::ERC721);
+        assert_eq!(collection.token_ids.len(), 3);
+
+        let collection = NftCollection::from_str("0x0000000000000000000000000000000000000001:ERC1155:7").unwrap();
+        assert_eq!(collection.standard, NftStandard::ERC1155);
+        assert_eq!(collection.token_ids, vec![EVMU256::from(7)]);
+
+        assert!(NftCollection::from_str("0x0000000000000000000000000000000000000001:erc20:1").is_err());
+    }
+}
@@ -12,11 +12,11 @@ use crate::{
     evm::{
         input::{ConciseEVMInput, EVMInput},
         oracle::EVMBugResult,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
     generic_vm::vm_state::VMStateT,
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -78,23 +78,34 @@ impl
         reetrancy_metadata
             .found
             .iter()
-            .map(|(addr, slot)| {
+            .map(|(addr, slot, selector, depth)| {
                 let mut hasher = DefaultHasher::new();
                 addr.hash(&mut hasher);
+                selector.hash(&mut hasher);
                 let real_bug_idx = (hasher.finish() << 8) + REENTRANCY_BUG_IDX;
 
-                let name = self.address_to_name.get(addr).unwrap_or(&format!("{:?}", addr)).clone();
+                let name = self.address_to_name.get(addr).unwrap_or(&checksum(addr)).clone();
                 EVMBugResult::new(
                     "Reentrancy".to_string(),
                     real_bug_idx,
-                    format!("Reentrancy on {:?} at slot {:?}", name, slot),
+                    format!(
+                        "Reentrancy on {:?} at slot {:?}, reentered function 0x{} at call depth {}",
+                        name,
+                        slot,
+                        hex::encode(selector),
+                        depth
+                    ),
                     ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
                     None,
                     Some(name.clone()),
                 )
-                .push_to_output();
+                .push_to_output("reentrancy");
                 real_bug_idx
             })
             .collect_vec()
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("reentrancy")
+    }
 }
@@ -13,10 +13,10 @@ use crate::{
         oracle::EVMBugResult,
         oracles::SELFDESTRUCT_BUG_IDX,
         srcmap::SOURCE_MAP_PROVIDER,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -76,7 +76,7 @@ impl
                     pc.hash(&mut hasher);
                     let real_bug_idx = (hasher.finish() << 8) + SELFDESTRUCT_BUG_IDX;
 
-                    let name = self.address_to_name.get(addr).unwrap_or(&format!("{:?}", addr)).clone();
+                    let name = self.address_to_name.get(addr).unwrap_or(&checksum(addr)).clone();
 
                     EVMBugResult::new(
                         "Selfdestruct".to_string(),
@@ -86,7 +86,7 @@ impl
                         SOURCE_MAP_PROVIDER.lock().unwrap().get_raw_source_map_info(addr, *pc),
                         Some(name.clone()),
                     )
-                    .push_to_output();
+                    .push_to_output("selfdestruct");
                     real_bug_idx
                 })
                 .collect_vec()
@@ -94,4 +94,8 @@ impl
             vec![]
         }
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("selfdestruct")
+    }
 }
@@ -13,7 +13,7 @@ use crate::{
         vm::EVMState,
     },
     generic_vm::vm_state::VMStateT,
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -103,11 +103,20 @@ impl
                     "Found equivalent state".to_string(),
                     ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
                 )
-                .push_to_output();
+                .push_to_output("state_comparison");
                 vec![STATE_COMP_BUG_IDX]
             } else {
                 vec![]
             }
         }
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        let matching = match self.matching_style {
+            StateCompMatching::Exact => "Exact",
+            StateCompMatching::DesiredContain => "DesiredContain",
+            StateCompMatching::StateContain => "StateContain",
+        };
+        OracleDescriptor::new("state_comparison").with("matching", matching)
+    }
 }
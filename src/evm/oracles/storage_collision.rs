@@ -0,0 +1,169 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        host::StorageWrite,
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::STORAGE_COLLISION_BUG_IDX,
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    oracle::{BugMetadata, Oracle, OracleCtx, OracleDescriptor},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+/// Reports when code running in a delegated frame (i.e., the implementation
+/// behind a proxy) changes a slot that the proxy's own code reads before
+/// delegating, e.g., the slot the proxy keeps its implementation or owner in
+#[derive(Default)]
+pub struct StorageCollisionOracle;
+
+impl StorageCollisionOracle {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Writes of delegated frames to the slots in `delegating_reads`, at most
+    /// one per (proxy, slot)
+    pub fn find_collisions<'a>(
+        &self,
+        writes: &'a [StorageWrite],
+        delegating_reads: &HashSet<(EVMAddress, EVMU256)>,
+    ) -> Vec<&'a StorageWrite> {
+        let mut seen = HashSet::new();
+        writes
+            .iter()
+            .filter(|write| {
+                write.address != write.code_address &&
+                    write.prev != write.value &&
+                    delegating_reads.contains(&(write.address, write.slot)) &&
+                    seen.insert((write.address, write.slot))
+            })
+            .collect()
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for StorageCollisionOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        let collisions = self
+            .find_collisions(&ctx.post_state.storage_writes, &ctx.post_state.delegating_reads)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut bugs = vec![];
+        for write in collisions {
+            let mut hasher = DefaultHasher::new();
+            write.address.hash(&mut hasher);
+            write.slot.hash(&mut hasher);
+            let real_bug_idx = (hasher.finish() << 8) + STORAGE_COLLISION_BUG_IDX;
+            if oracle_should_skip!(ctx, real_bug_idx) {
+                continue;
+            }
+            let info = format!(
+                "{} (running as {}) overwrote slot {:#x} from {:#x} to {:#x}, which {} reads before delegating\n",
+                checksum(&write.code_address),
+                checksum(&write.address),
+                write.slot,
+                write.prev,
+                write.value,
+                checksum(&write.address),
+            );
+            EVMBugResult::new_simple(
+                "Storage Collision".to_string(),
+                real_bug_idx,
+                info,
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+            )
+            .push_to_output("storage_collision");
+            bugs.push(real_bug_idx);
+        }
+        bugs
+    }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("storage_collision")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::evm::oracles::EIP1967_IMPLEMENTATION_SLOT;
+
+    #[test]
+    fn test_find_collisions() {
+        let proxy = EVMAddress::from_slice(&[1; 20]);
+        let implementation = EVMAddress::from_slice(&[2; 20]);
+        let oracle = StorageCollisionOracle::new();
+        let write = |code_address: EVMAddress, slot: EVMU256, value: u64| StorageWrite {
+            address: proxy,
+            code_address,
+            slot,
+            prev: EVMU256::ZERO,
+            value: EVMU256::from(value),
+        };
+        let implementation_slot = EVMU256::from_str(EIP1967_IMPLEMENTATION_SLOT).unwrap();
+        let writes = vec![
+            // the owner kept in slot 0 by the proxy is overwritten
+            write(implementation, EVMU256::ZERO, 1),
+            write(implementation, EVMU256::ZERO, 2),
+            // slot 1 is not read by the proxy
+            write(implementation, EVMU256::from(1), 1),
+            // the implementation slot the proxy delegates with is overwritten
+            write(implementation, implementation_slot, 1),
+            // the proxy writes its own slot
+            write(proxy, EVMU256::ZERO, 3),
+            // unchanged
+            write(implementation, EVMU256::ZERO, 0),
+        ];
+        let reads = HashSet::from([(proxy, EVMU256::ZERO), (proxy, implementation_slot)]);
+
+        let collisions = oracle.find_collisions(&writes, &reads);
+        assert_eq!(collisions, vec![&writes[0], &writes[3]]);
+    }
+}
@@ -13,10 +13,10 @@ use crate::{
         oracle::EVMBugResult,
         oracles::TYPED_BUG_BUG_IDX,
         srcmap::SOURCE_MAP_PROVIDER,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -74,7 +74,7 @@ impl
                     let mut hasher = DefaultHasher::new();
                     bug_id.hash(&mut hasher);
                     pc.hash(&mut hasher);
-                    let name = self.address_to_name.get(addr).unwrap_or(&format!("{:?}", addr)).clone();
+                    let name = self.address_to_name.get(addr).unwrap_or(&checksum(addr)).clone();
 
                     let real_bug_idx = (hasher.finish() << 8) + TYPED_BUG_BUG_IDX;
 
@@ -86,7 +86,7 @@ impl
                         SOURCE_MAP_PROVIDER.lock().unwrap().get_raw_source_map_info(addr, *pc),
                         Some(name.clone()),
                     )
-                    .push_to_output();
+                    .push_to_output("typed_bug");
                     real_bug_idx
                 })
                 .collect_vec()
@@ -94,4 +94,8 @@ impl
             vec![]
         }
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("typed_bug")
+    }
 }
@@ -11,10 +11,10 @@ use crate::{
         input::{ConciseEVMInput, EVMInput},
         oracle::EVMBugResult,
         oracles::V2_PAIR_BUG_IDX,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
-    oracle::{Oracle, OracleCtx},
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
     state::HasExecutionResult,
 };
 
@@ -109,14 +109,14 @@ impl
                         "Imbalanced Uniswap Pair".to_string(),
                         bug_idx,
                         format!(
-                            "In Uniswap pair {:?}, reserves has changed from {:?} to {:?}. It is likely the token contract has incorrectly burned that token in the pair.\n",
-                            addr,
+                            "In Uniswap pair {}, reserves has changed from {:?} to {:?}. It is likely the token contract has incorrectly burned that token in the pair.\n",
+                            checksum(&addr),
                             (r0, r1),
                             (pre_r0, pre_r1)
                         ),
                         ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
                     )
-                    .push_to_output();
+                    .push_to_output("pair");
 
                     violations.push(bug_idx);
                 }
@@ -124,6 +124,10 @@ impl
         }
         violations
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("pair")
+    }
 }
 
 pub fn reserve_parser(reserve_slot: &EVMU256) -> (EVMU256, EVMU256) {
@@ -0,0 +1,334 @@
+use std::{collections::HashMap, fs::OpenOptions, io::Write};
+
+use bytes::Bytes;
+use libafl::state::HasMetadata;
+use libafl_bolts::impl_serdeany;
+use revm_primitives::Bytecode;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    evm::{
+        abi::{get_abi_type_boxed, BoxedABI},
+        arg_pins::ArgPinMetadata,
+        contract_utils::ABIConfig,
+        input::{ConciseEVMInput, EVMInput},
+        oracles::funds_locked::FundsLockedOracle,
+        types::{checksum, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    mutation_utils::ConstantPoolMetadata,
+    oracle::{Oracle, OracleCtx, OracleDescriptor},
+    scheduler::SponsorMetadata,
+};
+
+/// Factor by which the value returned by a view function has to change over
+/// a transaction to be a hint
+pub const ANOMALY_FACTOR: u64 = 10;
+
+/// Votes given to the states reaching a hint
+const HINT_VOTES: usize = 3;
+
+/// View (and pure) functions of the targets, probed with fuzzed arguments
+/// when `--probe-views` is set. View functions are not added to the corpus.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ViewFunctionMetadata {
+    pub map: HashMap<EVMAddress, Vec<BoxedABI>>,
+}
+
+impl_serdeany!(ViewFunctionMetadata);
+
+/// Register the view function `abi` of `address` to be probed, if probing
+/// view functions is enabled
+pub fn register_view_function<S: HasMetadata>(address: EVMAddress, abi: &ABIConfig, state: &mut S) {
+    if !abi.is_static || !state.has_metadata::<ViewFunctionMetadata>() {
+        return;
+    }
+    let mut abi_instance = get_abi_type_boxed(&abi.abi);
+    abi_instance.set_func_with_signature(abi.function, &abi.function_name, &abi.abi);
+    if let Some(pins) = state.metadata_map().get::<ArgPinMetadata>() {
+        pins.apply(&address, &mut abi_instance);
+    }
+    let views = state.metadata_map_mut().get_mut::<ViewFunctionMetadata>().unwrap();
+    let functions = views.map.entry(address).or_default();
+    if !functions
+        .iter()
+        .any(|function| function.function == abi_instance.function)
+    {
+        functions.push(abi_instance);
+    }
+}
+
+/// Factor by which `after` differs from `before`, [`EVMU256::MAX`] from or to
+/// zero
+pub fn change_factor(before: EVMU256, after: EVMU256) -> EVMU256 {
+    let (low, high) = if before <= after {
+        (before, after)
+    } else {
+        (after, before)
+    };
+    if high == EVMU256::ZERO {
+        EVMU256::from(1)
+    } else if low == EVMU256::ZERO {
+        EVMU256::MAX
+    } else {
+        high / low
+    }
+}
+
+/// Largest change of the value returned by a view function over a
+/// transaction hinted so far
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ViewChange {
+    pub contract: EVMAddress,
+    pub selector: [u8; 4],
+    pub factor: EVMU256,
+}
+
+/// A view function whose value changed anomalously over a transaction, not a
+/// finding but a state (and arguments) worth exploring to exploit it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ViewHint {
+    pub contract: EVMAddress,
+    pub function: String,
+    /// Calldata of the calls returning `before` and `after`
+    pub calldata: String,
+    pub before: EVMU256,
+    pub after: EVMU256,
+}
+
+/// The largest changes of the probed view functions and the hints found
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ViewHintMetadata {
+    pub changes: Vec<ViewChange>,
+    pub hints: Vec<ViewHint>,
+}
+
+impl_serdeany!(ViewHintMetadata);
+
+impl ViewHintMetadata {
+    /// Record that the value returned by `selector` of `contract` changed by
+    /// `factor`, returns whether it is a hint: a change by at least
+    /// [`ANOMALY_FACTOR`] larger than the ones hinted for the function
+    pub fn observe(&mut self, contract: EVMAddress, selector: [u8; 4], factor: EVMU256) -> bool {
+        if factor < EVMU256::from(ANOMALY_FACTOR) {
+            return false;
+        }
+        match self
+            .changes
+            .iter_mut()
+            .find(|change| change.contract == contract && change.selector == selector)
+        {
+            Some(change) if change.factor >= factor => false,
+            Some(change) => {
+                change.factor = factor;
+                true
+            }
+            None => {
+                self.changes.push(ViewChange {
+                    contract,
+                    selector,
+                    factor,
+                });
+                true
+            }
+        }
+    }
+}
+
+/// Probes the view functions of the contracts whose storage a transaction
+/// changed with fuzzed arguments, calling them with the same calldata before
+/// and after the transaction. The ones whose value changed by
+/// [`ANOMALY_FACTOR`] times (e.g., a manipulatable price) are hints. Hints
+/// are not findings: they are logged, written to
+/// `<work_dir>/view_hints.jsonl`, their arguments are added to the constants
+/// of the mutator and the states reaching them are sponsored, so that the
+/// fuzzer builds the exploit around them.
+pub struct ViewProbeOracle {
+    work_dir: String,
+}
+
+impl ViewProbeOracle {
+    pub fn new(work_dir: String) -> Self {
+        Self { work_dir }
+    }
+
+    fn report(&self, hint: &ViewHint) {
+        info!(
+            "Hint: {}.{} returned {} after the transaction ({} before) with calldata 0x{}",
+            checksum(&hint.contract),
+            hint.function,
+            hint.after,
+            hint.before,
+            hint.calldata
+        );
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(format!("{}/view_hints.jsonl", self.work_dir))
+            .expect("Unable to open file");
+        f.write_all(format!("{}\n", serde_json::to_string(hint).expect("failed to json")).as_bytes())
+            .expect("Unable to write data");
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for ViewProbeOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+        let Some(views) = ctx.fuzz_state.metadata_map().get::<ViewFunctionMetadata>() else {
+            return vec![];
+        };
+        let mut probes = FundsLockedOracle::changed_contracts(&ctx.post_state.storage_writes)
+            .into_iter()
+            .flat_map(|contract| {
+                views
+                    .map
+                    .get(&contract)
+                    .into_iter()
+                    .flatten()
+                    .map(move |abi| (contract, abi.clone()))
+            })
+            .collect::<Vec<_>>();
+        if probes.is_empty() {
+            return vec![];
+        }
+        for (_, abi) in probes.iter_mut() {
+            abi.mutate::<EVMAddress, EVMAddress, EVMState, EVMFuzzState, ConciseEVMInput>(ctx.fuzz_state);
+        }
+        let calls = probes
+            .iter()
+            .map(|(contract, abi)| (*contract, Bytes::from(abi.get_bytes())))
+            .collect::<Vec<_>>();
+        let pre_returns = ctx.call_pre_batch(&calls);
+        let post_returns = ctx.call_post_batch(&calls);
+
+        let mut hints = vec![];
+        let mut arguments = vec![];
+        let metadata = ctx
+            .fuzz_state
+            .metadata_map_mut()
+            .get_or_insert_with(ViewHintMetadata::default);
+        for ((((contract, abi), (_, calldata)), pre), post) in probes
+            .iter()
+            .zip(calls.iter())
+            .zip(pre_returns.iter())
+            .zip(post_returns.iter())
+        {
+            // reverted, or returns nothing
+            if pre.len() < 32 || post.len() < 32 {
+                continue;
+            }
+            let before = EVMU256::from_be_slice(&pre[..32]);
+            let after = EVMU256::from_be_slice(&post[..32]);
+            if metadata.observe(*contract, abi.function, change_factor(before, after)) {
+                arguments.extend(
+                    calldata
+                        .get(4..)
+                        .unwrap_or_default()
+                        .chunks(32)
+                        .map(|word| word.to_vec()),
+                );
+                hints.push(ViewHint {
+                    contract: *contract,
+                    function: abi.get_func_name(),
+                    calldata: hex::encode(calldata),
+                    before,
+                    after,
+                });
+            }
+        }
+        if hints.is_empty() {
+            return vec![];
+        }
+        for hint in &hints {
+            self.report(hint);
+        }
+        metadata.hints.extend(hints);
+
+        let constants = ctx
+            .fuzz_state
+            .metadata_map_mut()
+            .get_or_insert_with(ConstantPoolMetadata::new);
+        for argument in arguments {
+            if !constants.constants.contains(&argument) {
+                constants.add_constant(argument);
+            }
+        }
+        ctx.fuzz_state
+            .metadata_map_mut()
+            .get_or_insert_with(SponsorMetadata::default)
+            .votes += HINT_VOTES;
+        vec![]
+    }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("view_probe").with("anomaly_factor", ANOMALY_FACTOR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_changes() {
+        assert_eq!(
+            change_factor(EVMU256::from(100), EVMU256::from(1000)),
+            EVMU256::from(10)
+        );
+        assert_eq!(change_factor(EVMU256::from(1000), EVMU256::from(99)), EVMU256::from(10));
+        assert_eq!(change_factor(EVMU256::from(7), EVMU256::from(7)), EVMU256::from(1));
+        assert_eq!(change_factor(EVMU256::ZERO, EVMU256::ZERO), EVMU256::from(1));
+        assert_eq!(change_factor(EVMU256::ZERO, EVMU256::from(1)), EVMU256::MAX);
+
+        let pool = EVMAddress::from_slice(&[1; 20]);
+        let price = [0x98, 0xd5, 0xfd, 0xca];
+        let mut metadata = ViewHintMetadata::default();
+        assert!(!metadata.observe(pool, price, EVMU256::from(9)));
+        assert!(metadata.observe(pool, price, EVMU256::from(10)));
+        // not larger than the change hinted
+        assert!(!metadata.observe(pool, price, EVMU256::from(10)));
+        assert!(metadata.observe(pool, price, EVMU256::from(50)));
+        assert!(metadata.observe(pool, price, EVMU256::MAX));
+        assert!(!metadata.observe(pool, price, EVMU256::MAX));
+        // other functions are hinted on their own
+        assert!(metadata.observe(pool, [0; 4], EVMU256::from(10)));
+        assert_eq!(metadata.changes.len(), 2);
+    }
+}
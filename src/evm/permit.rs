@@ -0,0 +1,212 @@
+//! EIP-2612 `permit` signatures for the callers controlled by the fuzzer.
+//!
+//! Besides the default callers, a caller is derived from a deterministic
+//! private key, so that the `permit` calls whose owner is that caller are
+//! signed when they are generated by the mutator, making permit-gated flows
+//! reachable without a prior `approve` transaction. The domain separator of a
+//! token is fetched the first time a permit is called on it, the nonces are
+//! counted in the VM state.
+
+use std::collections::HashMap;
+
+use crypto::{digest::Digest, sha3::Sha3};
+use ethers::{
+    signers::{LocalWallet, Signer},
+    types::H256,
+};
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+
+use crate::evm::types::{EVMAddress, EVMU256};
+
+/// permit(address,address,uint256,uint256,uint8,bytes32,bytes32)
+pub const PERMIT: [u8; 4] = [0xd5, 0x05, 0xac, 0xcf];
+/// DOMAIN_SEPARATOR()
+pub const DOMAIN_SEPARATOR: [u8; 4] = [0x36, 0x44, 0xe5, 0x15];
+
+const PERMIT_TYPE: &str = "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)";
+
+/// Private keys of the callers controlled by the fuzzer
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CallerKeyMetadata {
+    pub keys: HashMap<EVMAddress, [u8; 32]>,
+    /// Domain separators of the tokens whose permits were called
+    pub domain_separators: HashMap<EVMAddress, [u8; 32]>,
+}
+
+impl_serdeany!(CallerKeyMetadata);
+
+impl CallerKeyMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the key and return the address of the caller
+    pub fn add(&mut self, key: [u8; 32]) -> EVMAddress {
+        let address = key_to_address(&key);
+        self.keys.insert(address, key);
+        address
+    }
+
+    pub fn get(&self, address: &EVMAddress) -> Option<&[u8; 32]> {
+        self.keys.get(address)
+    }
+
+    /// Sign `calldata` if it is a permit of `token` whose owner has a key and
+    /// the domain separator of `token` is known
+    pub fn sign(&self, token: &EVMAddress, calldata: &[u8], nonce: EVMU256) -> Option<Vec<u8>> {
+        let key = self.get(&permit_owner(calldata)?)?;
+        let domain_separator = self.domain_separators.get(token)?;
+        let mut calldata = calldata.to_vec();
+        sign_permit_calldata(&mut calldata, key, domain_separator, nonce);
+        Some(calldata)
+    }
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(data);
+    let mut out = [0u8; 32];
+    hasher.result(&mut out);
+    out
+}
+
+fn wallet(key: &[u8; 32]) -> LocalWallet {
+    LocalWallet::from_bytes(key).expect("invalid caller private key")
+}
+
+/// Deterministic private key of the `idx`-th caller signing permits
+pub fn caller_key(idx: usize) -> [u8; 32] {
+    keccak256(format!("ityfuzz caller {}", idx).as_bytes())
+}
+
+pub fn key_to_address(key: &[u8; 32]) -> EVMAddress {
+    EVMAddress::from_slice(wallet(key).address().as_bytes())
+}
+
+/// EIP-712 digest of a permit
+pub fn permit_digest(
+    domain_separator: &[u8; 32],
+    owner: &EVMAddress,
+    spender: &EVMAddress,
+    value: EVMU256,
+    nonce: EVMU256,
+    deadline: EVMU256,
+) -> [u8; 32] {
+    let address_word = |addr: &EVMAddress| [vec![0; 12], addr.0.to_vec()].concat();
+    let struct_hash = keccak256(
+        &[
+            keccak256(PERMIT_TYPE.as_bytes()).to_vec(),
+            address_word(owner),
+            address_word(spender),
+            value.to_be_bytes::<32>().to_vec(),
+            nonce.to_be_bytes::<32>().to_vec(),
+            deadline.to_be_bytes::<32>().to_vec(),
+        ]
+        .concat(),
+    );
+    keccak256(&[vec![0x19, 0x01], domain_separator.to_vec(), struct_hash.to_vec()].concat())
+}
+
+/// Sign the digest, returning (v, r, s)
+pub fn sign_digest(key: &[u8; 32], digest: [u8; 32]) -> (u8, [u8; 32], [u8; 32]) {
+    let signature = wallet(key)
+        .sign_hash(H256::from(digest))
+        .expect("failed to sign permit");
+    let (mut r, mut s) = ([0u8; 32], [0u8; 32]);
+    signature.r.to_big_endian(&mut r);
+    signature.s.to_big_endian(&mut s);
+    (signature.v as u8, r, s)
+}
+
+/// Owner of a `permit` call, if `calldata` is one
+pub fn permit_owner(calldata: &[u8]) -> Option<EVMAddress> {
+    if calldata.len() < 4 + 7 * 32 || calldata[..4] != PERMIT {
+        return None;
+    }
+    Some(EVMAddress::from_slice(&calldata[16..36]))
+}
+
+/// Replace the signature (v, r, s) of the `permit` call with a valid one
+/// signed by `key`
+pub fn sign_permit_calldata(calldata: &mut [u8], key: &[u8; 32], domain_separator: &[u8; 32], nonce: EVMU256) {
+    let word = |idx: usize| &calldata[4 + idx * 32..4 + (idx + 1) * 32];
+    let owner = EVMAddress::from_slice(&word(0)[12..]);
+    let spender = EVMAddress::from_slice(&word(1)[12..]);
+    let value = EVMU256::from_be_slice(word(2));
+    let deadline = EVMU256::from_be_slice(word(3));
+
+    let digest = permit_digest(domain_separator, &owner, &spender, value, nonce, deadline);
+    let (v, r, s) = sign_digest(key, digest);
+    let mut v_word = [0u8; 32];
+    v_word[31] = v;
+    calldata[4 + 4 * 32..4 + 5 * 32].copy_from_slice(&v_word);
+    calldata[4 + 5 * 32..4 + 6 * 32].copy_from_slice(&r);
+    calldata[4 + 6 * 32..4 + 7 * 32].copy_from_slice(&s);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ethers::types::{RecoveryMessage, Signature, U256};
+
+    use super::*;
+
+    #[test]
+    fn test_key_to_address() {
+        // well-known key of the first account of the default test mnemonic
+        let key: [u8; 32] = hex::decode("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            key_to_address(&key),
+            EVMAddress::from_str("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_sign_permit_calldata() {
+        let mut keys = CallerKeyMetadata::new();
+        let key = caller_key(0);
+        let owner = keys.add(key);
+        assert_eq!(keys.get(&owner), Some(&key));
+
+        let word = |v: u64| EVMU256::from(v).to_be_bytes::<32>().to_vec();
+        let calldata = [
+            PERMIT.to_vec(),
+            [vec![0; 12], owner.0.to_vec()].concat(),
+            word(1),
+            word(100),
+            word(u64::MAX),
+            word(0),
+            word(0),
+            word(0),
+        ]
+        .concat();
+        assert_eq!(permit_owner(&calldata), Some(owner));
+
+        let token = EVMAddress::from_slice(&[2; 20]);
+        let domain_separator = [0x42; 32];
+        assert_eq!(keys.sign(&token, &calldata, EVMU256::ZERO), None);
+        keys.domain_separators.insert(token, domain_separator);
+        let calldata = keys.sign(&token, &calldata, EVMU256::ZERO).unwrap();
+
+        let digest = permit_digest(
+            &domain_separator,
+            &owner,
+            &EVMAddress::from_slice(&word(1)[12..]),
+            EVMU256::from(100),
+            EVMU256::ZERO,
+            EVMU256::from(u64::MAX),
+        );
+        let signature = Signature {
+            r: U256::from_big_endian(&calldata[4 + 5 * 32..4 + 6 * 32]),
+            s: U256::from_big_endian(&calldata[4 + 6 * 32..4 + 7 * 32]),
+            v: calldata[4 + 5 * 32 - 1] as u64,
+        };
+        let signer = signature.recover(RecoveryMessage::Hash(H256::from(digest))).unwrap();
+        assert_eq!(EVMAddress::from_slice(signer.as_bytes()), owner);
+    }
+}
@@ -0,0 +1,201 @@
+//! Predeploys and precompiles of L2s (e.g., the `L1Block` of the OP Stack,
+//! `ArbSys` of Arbitrum), so that calls from the targets to them do not
+//! revert.
+//!
+//! They are modeled by stubs returning sane defaults: each known function
+//! returns a constant or a value of the block (number, timestamp, ...) as the
+//! first word of 8, the others return zeros. Predeploys are contracts on the
+//! chain and are only installed when not forking, precompiles have no EVM
+//! code and are always installed.
+
+use std::str::FromStr;
+
+use crate::evm::{
+    contract_utils::set_hash,
+    onchain::endpoints::Chain,
+    types::{EVMAddress, EVMU256},
+};
+
+/// Words returned by every function of a stub, enough for the static return
+/// types (e.g., `getPricesInWei()` returns 6 words)
+const RETURN_WORDS: u16 = 8;
+
+/// Value returned by a function of a stub
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PredeployValue {
+    Constant(u64),
+    Number,
+    Timestamp,
+    BaseFee,
+    ChainId,
+}
+
+impl PredeployValue {
+    /// Code pushing the value on the stack
+    fn code(&self) -> Vec<u8> {
+        match self {
+            PredeployValue::Constant(value) => {
+                let mut code = vec![0x7f];
+                code.extend_from_slice(&EVMU256::from(*value).to_be_bytes::<32>());
+                code
+            }
+            PredeployValue::Number => vec![0x43],
+            PredeployValue::Timestamp => vec![0x42],
+            PredeployValue::BaseFee => vec![0x48],
+            PredeployValue::ChainId => vec![0x46],
+        }
+    }
+}
+
+/// A predeploy or precompile of a chain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Predeploy {
+    pub name: &'static str,
+    pub address: EVMAddress,
+    /// Has no EVM code on the chain, so it is also installed when forking
+    pub precompile: bool,
+    /// Signatures of the functions not returning zero and their values
+    pub functions: Vec<(&'static str, PredeployValue)>,
+}
+
+impl Predeploy {
+    fn new(
+        name: &'static str,
+        address: &str,
+        precompile: bool,
+        functions: Vec<(&'static str, PredeployValue)>,
+    ) -> Self {
+        Self {
+            name,
+            address: EVMAddress::from_str(address).unwrap(),
+            precompile,
+            functions,
+        }
+    }
+
+    /// Code of the stub: dispatches on the selector to push the value of the
+    /// function (zero if unknown) and returns it
+    pub fn bytecode(&self) -> Vec<u8> {
+        // PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+        let mut code = vec![0x60, 0x00, 0x35, 0x60, 0xe0, 0x1c];
+        let dispatch_end = code.len() + self.functions.len() * 11;
+        // default: PUSH1 0, then falls through to the return
+        let ret = dispatch_end + 2;
+        let mut dest = ret + 10;
+        let mut branches = vec![];
+        for (signature, value) in &self.functions {
+            let mut selector = [0u8; 4];
+            set_hash(signature, &mut selector);
+            // DUP1 PUSH4 selector EQ PUSH2 dest JUMPI
+            code.extend([0x80, 0x63]);
+            code.extend(selector);
+            code.extend([0x14, 0x61]);
+            code.extend((dest as u16).to_be_bytes());
+            code.push(0x57);
+            // JUMPDEST value PUSH2 ret JUMP
+            let mut branch = vec![0x5b];
+            branch.extend(value.code());
+            branch.push(0x61);
+            branch.extend((ret as u16).to_be_bytes());
+            branch.push(0x56);
+            dest += branch.len();
+            branches.extend(branch);
+        }
+        code.extend([0x60, 0x00]);
+        // JUMPDEST PUSH1 0 MSTORE PUSH2 size PUSH1 0 RETURN
+        let size = RETURN_WORDS * 32;
+        code.extend([0x5b, 0x60, 0x00, 0x52, 0x61]);
+        code.extend(size.to_be_bytes());
+        code.extend([0x60, 0x00, 0xf3]);
+        code.extend(branches);
+        code
+    }
+}
+
+/// Predeploys and precompiles of `chain` to install, only the precompiles if
+/// the chain is `forked`
+pub fn chain_predeploys(chain: &Chain, forked: bool) -> Vec<Predeploy> {
+    use PredeployValue::*;
+    let predeploys = match chain {
+        Chain::OPTIMISM | Chain::BASE => vec![
+            Predeploy::new(
+                "L1Block",
+                "0x4200000000000000000000000000000000000015",
+                false,
+                vec![
+                    ("number()", Number),
+                    ("timestamp()", Timestamp),
+                    ("basefee()", BaseFee),
+                    ("blobBaseFee()", Constant(1)),
+                    ("l1FeeOverhead()", Constant(188)),
+                    ("l1FeeScalar()", Constant(684_000)),
+                ],
+            ),
+            Predeploy::new(
+                "GasPriceOracle",
+                "0x420000000000000000000000000000000000000F",
+                false,
+                vec![
+                    ("decimals()", Constant(6)),
+                    ("gasPrice()", BaseFee),
+                    ("baseFee()", BaseFee),
+                    ("l1BaseFee()", Constant(1_000_000_000)),
+                    ("overhead()", Constant(188)),
+                    ("scalar()", Constant(684_000)),
+                ],
+            ),
+        ],
+        Chain::ARBITRUM => vec![
+            Predeploy::new(
+                "ArbSys",
+                "0x0000000000000000000000000000000000000064",
+                true,
+                vec![("arbBlockNumber()", Number), ("arbChainID()", ChainId)],
+            ),
+            Predeploy::new(
+                "ArbGasInfo",
+                "0x000000000000000000000000000000000000006C",
+                true,
+                vec![
+                    ("getL1BaseFeeEstimate()", Constant(1_000_000_000)),
+                    ("getL1GasPriceEstimate()", Constant(1_000_000_000)),
+                    ("getMinimumGasPrice()", Constant(10_000_000)),
+                ],
+            ),
+        ],
+        _ => vec![],
+    };
+    predeploys
+        .into_iter()
+        .filter(|predeploy| predeploy.precompile || !forked)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_predeploy_bytecode() {
+        assert!(chain_predeploys(&Chain::ETH, false).is_empty());
+        assert_eq!(chain_predeploys(&Chain::OPTIMISM, false).len(), 2);
+        assert!(chain_predeploys(&Chain::OPTIMISM, true).is_empty());
+        assert_eq!(chain_predeploys(&Chain::ARBITRUM, true).len(), 2);
+
+        let oracle = chain_predeploys(&Chain::BASE, false).remove(1);
+        let code = oracle.bytecode();
+        // decimals() is dispatched to a jump destination pushing 6
+        let decimals = [0x63, 0x31, 0x3c, 0xe5, 0x67];
+        let at = code.windows(5).position(|w| w == decimals).unwrap();
+        assert_eq!(code[at + 5..at + 7], [0x14, 0x61]);
+        let dest = ((code[at + 7] as usize) << 8) + code[at + 8] as usize;
+        assert_eq!(code[dest], 0x5b);
+        assert_eq!(code[dest + 1], 0x7f);
+        assert_eq!(code[dest + 33], 6);
+        // and jumps back to the return
+        let ret = ((code[dest + 35] as usize) << 8) + code[dest + 36] as usize;
+        assert_eq!(code[dest + 37], 0x56);
+        assert_eq!(code[ret..ret + 4], [0x5b, 0x60, 0x00, 0x52]);
+        assert_eq!(*code.last().unwrap(), 0x56);
+    }
+}
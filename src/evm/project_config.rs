@@ -0,0 +1,184 @@
+//! Project config file, so that a repository can check in its ityfuzz setup
+//! instead of passing a long list of flags.
+//!
+//! The keys are the long flags in snake_case (e.g., `chain_type` for
+//! `--chain-type`), and flags taking a comma separated list also take an
+//! array. The file is parsed by clap like the command line, so every flag of
+//! [`EvmArgs`] but the run modes can be given. Flags given on the command line
+//! override the values of the file.
+//!
+//! ```toml
+//! target = "0x5fbdb2315678afecb367f032d93f642f64180aa3"
+//! chain_type = "ETH"
+//! onchain_block_number = 19044110
+//! detectors = ["high_confidence", "access_control"]
+//! state_overrides = ["balance:0x5fbdb2315678afecb367f032d93f642f64180aa3:10.0"]
+//! max_time = 3600
+//! ```
+
+use std::{fs, path::Path};
+
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::EvmArgs;
+
+/// Flags selecting a run mode (e.g., replaying or resuming a campaign, fuzzing
+/// several fork blocks), only given on the command line
+const CLI_ONLY: [&str; 4] = ["project_file", "replay_file", "resume", "fork_blocks"];
+
+/// Values of the project config file, keyed by the ids of the [`EvmArgs`]
+/// flags, see [`EvmArgs`] for their meaning
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ProjectConfig {
+    pub values: serde_json::Map<String, Value>,
+}
+
+impl ProjectConfig {
+    /// Load a TOML (`.toml`) or JSON (any other extension) project file
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+        let is_toml = Path::new(path).extension().map_or(false, |ext| ext == "toml");
+        if is_toml {
+            toml::from_str(&content).map_err(|e| format!("failed to parse {}: {}", path, e))
+        } else {
+            serde_json::from_str(&content).map_err(|e| format!("failed to parse {}: {}", path, e))
+        }
+    }
+
+    /// The values as command line arguments of [`EvmArgs`], except for the
+    /// flags in `skip`
+    fn to_cli_args(&self, skip: impl Fn(&str) -> bool) -> Result<Vec<String>, String> {
+        let command = EvmArgs::command();
+        let mut cli_args = vec![];
+        for (key, value) in &self.values {
+            let Some(arg) = command.get_arguments().find(|arg| arg.get_id().as_str() == key) else {
+                return Err(format!("unknown key {} in the project file", key));
+            };
+            if CLI_ONLY.contains(&key.as_str()) {
+                return Err(format!("{} is only given on the command line", key));
+            }
+            if skip(key) {
+                continue;
+            }
+            let flag = format!("--{}", arg.get_long().unwrap_or(key.as_str()));
+            match value {
+                // switches, false is their default
+                Value::Bool(enabled) if !arg.get_action().takes_values() => {
+                    if *enabled {
+                        cli_args.push(flag);
+                    }
+                    continue;
+                }
+                Value::String(s) => cli_args.extend([flag, s.clone()]),
+                // comma separated lists
+                Value::Array(items) => {
+                    let items = items
+                        .iter()
+                        .map(|item| match item {
+                            Value::String(s) => s.clone(),
+                            item => item.to_string(),
+                        })
+                        .collect::<Vec<_>>();
+                    cli_args.extend([flag, items.join(",")]);
+                }
+                value => cli_args.extend([flag, value.to_string()]),
+            }
+        }
+        Ok(cli_args)
+    }
+}
+
+/// The flags given on the command line (`matches`) as arguments
+fn given_cli_args(matches: &ArgMatches) -> Vec<String> {
+    let mut cli_args = vec![];
+    for arg in EvmArgs::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        if !from_cli(matches, id) {
+            continue;
+        }
+        let flag = match arg.get_long() {
+            Some(long) => format!("--{}", long),
+            None => format!("-{}", arg.get_short().unwrap()),
+        };
+        if !arg.get_action().takes_values() {
+            cli_args.push(flag);
+            continue;
+        }
+        for value in matches.get_raw(id).into_iter().flatten() {
+            cli_args.extend([flag.clone(), value.to_string_lossy().to_string()]);
+        }
+    }
+    cli_args
+}
+
+fn from_cli(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+impl EvmArgs {
+    /// Fill in the flags not given on the command line (`matches`) from the
+    /// project file, if any
+    pub fn apply_project_file(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        let Some(path) = self.project_file.clone() else {
+            return Ok(());
+        };
+        let config = ProjectConfig::from_file(&path)?;
+        self.apply_project_config(config, matches)
+    }
+
+    /// Parse the values of `config` along with the flags given on the command
+    /// line (`matches`), which override them, as a single command line
+    pub fn apply_project_config(&mut self, config: ProjectConfig, matches: &ArgMatches) -> Result<(), String> {
+        let mut cli_args = vec![String::from("evm")];
+        cli_args.extend(config.to_cli_args(|id| from_cli(matches, id))?);
+        cli_args.extend(given_cli_args(matches));
+        let merged = EvmArgs::command()
+            .try_get_matches_from(cli_args)
+            .map_err(|e| format!("invalid project file: {}", e))?;
+        *self = EvmArgs::from_arg_matches(&merged).map_err(|e| format!("invalid project file: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_overrides_project_config() {
+        let config: ProjectConfig = toml::from_str(
+            r#"
+            target = "0x5fbdb2315678afecb367f032d93f642f64180aa3"
+            chain_type = "ETH"
+            onchain_block_number = 19044110
+            detectors = ["erc20", "access_control"]
+            flashloan = true
+            max_time = 3600
+            "#,
+        )
+        .unwrap();
+        let matches = EvmArgs::command()
+            .try_get_matches_from(["evm", "-b", "1", "--max-time", "60"])
+            .unwrap();
+        let mut args = EvmArgs::from_arg_matches(&matches).unwrap();
+        args.apply_project_config(config, &matches).unwrap();
+
+        assert_eq!(args.target, "0x5fbdb2315678afecb367f032d93f642f64180aa3");
+        assert_eq!(args.chain_type.as_deref(), Some("ETH"));
+        assert_eq!(args.detectors, "erc20,access_control");
+        assert!(args.flashloan);
+        // given on the command line
+        assert_eq!(args.onchain_block_number, Some(1));
+        assert_eq!(args.max_time, Some(60));
+        // in neither
+        assert_eq!(args.work_dir, "work_dir");
+
+        for invalid in [r#"{"chain": "ETH"}"#, r#"{"resume": true}"#, r#"{"max_execs": "soon"}"#] {
+            let config = serde_json::from_str::<ProjectConfig>(invalid).unwrap();
+            assert!(args.apply_project_config(config, &matches).is_err());
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt::Debug, marker::PhantomData};
+use std::{cmp::Reverse, collections::HashMap, fmt::Debug, marker::PhantomData};
 
 /// Corpus schedulers for ItyFuzz
 /// Used to determine which input / VMState to fuzz next
@@ -98,6 +98,19 @@ impl UncoveredBranchesMetadata {
             branch_status: HashMap::new(),
         }
     }
+
+    /// Testcases reaching branches of which only one side is covered, the
+    /// ones reaching the most such branches first
+    pub fn stuck_testcases(&self) -> Vec<CorpusId> {
+        let mut testcases = self
+            .testcase_to_uncovered_branches
+            .iter()
+            .filter(|(_, uncovered)| **uncovered > 0)
+            .map(|(idx, uncovered)| (*idx, *uncovered))
+            .collect::<Vec<_>>();
+        testcases.sort_by_key(|(idx, uncovered)| (Reverse(*uncovered), usize::from(*idx)));
+        testcases.into_iter().map(|(idx, _)| idx).collect()
+    }
 }
 
 impl_serdeany!(UncoveredBranchesMetadata);
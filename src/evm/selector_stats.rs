@@ -0,0 +1,150 @@
+//! Number of executions of each function of the targets, to tell whether a
+//! function never triggers a finding because it is not reached (e.g., a bad
+//! ABI or a guarded caller) or because the oracles do not flag it.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+use libafl_bolts::{bolts_prelude::SerdeAnyMap, impl_serdeany};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::StatsReporter,
+    evm::{
+        contract_utils::ABIConfig,
+        types::{checksum, EVMAddress},
+    },
+};
+
+/// Executions of a function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectorExecutionStats {
+    pub address: EVMAddress,
+    pub selector: [u8; 4],
+    /// Name of the function, if its ABI is known
+    pub function: Option<String>,
+    pub executions: u64,
+}
+
+impl Display for SelectorExecutionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{} (0x{}): {} executions",
+            checksum(&self.address),
+            self.function.as_deref().unwrap_or("unknown"),
+            hex::encode(self.selector),
+            self.executions
+        )
+    }
+}
+
+/// Executions of each (contract address, selector) called by the fuzzer,
+/// counted by the executor for transactions (not their steps), seeded with
+/// the functions of the targets so that the ones never executed show up
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SelectorExecutionMetadata {
+    pub executions: HashMap<(EVMAddress, [u8; 4]), u64>,
+    /// Names of the functions of the targets
+    pub functions: HashMap<(EVMAddress, [u8; 4]), String>,
+}
+
+impl_serdeany!(SelectorExecutionMetadata);
+
+impl SelectorExecutionMetadata {
+    /// Count an execution of `calldata` on `address`, calls without a selector
+    /// (e.g., to `receive()`) are not counted
+    pub fn record(&mut self, address: EVMAddress, calldata: &[u8]) {
+        let Some(selector) = calldata.get(..4) else {
+            return;
+        };
+        *self
+            .executions
+            .entry((address, selector.try_into().unwrap()))
+            .or_default() += 1;
+    }
+
+    /// Register the function `abi` of the target `address`, not executed yet
+    pub fn register(&mut self, address: EVMAddress, abi: &ABIConfig) {
+        self.executions.entry((address, abi.function)).or_default();
+        self.functions
+            .insert((address, abi.function), abi.function_name.clone());
+    }
+
+    /// Executions of each function of the targets, least executed first
+    pub fn stats(&self) -> Vec<SelectorExecutionStats> {
+        let mut stats = self
+            .executions
+            .iter()
+            .map(|((address, selector), executions)| SelectorExecutionStats {
+                address: *address,
+                selector: *selector,
+                function: self.functions.get(&(*address, *selector)).cloned(),
+                executions: *executions,
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|stat| (stat.executions, stat.address, stat.selector));
+        stats
+    }
+}
+
+/// Reports the [`SelectorExecutionMetadata`] in the campaign stats
+pub struct SelectorStatsReporter;
+
+impl StatsReporter for SelectorStatsReporter {
+    fn name(&self) -> &'static str {
+        "executions"
+    }
+
+    fn report(&self, metadata: &SerdeAnyMap) -> Vec<String> {
+        metadata.get::<SelectorExecutionMetadata>().map_or(vec![], |meta| {
+            meta.stats().iter().map(|stats| stats.to_string()).collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector_executions() {
+        let (token, vault) = (EVMAddress::from_slice(&[1; 20]), EVMAddress::from_slice(&[2; 20]));
+        let transfer = [0xa9, 0x05, 0x9c, 0xbb];
+        let mut metadata = SelectorExecutionMetadata::default();
+        metadata.record(token, &[transfer.as_slice(), &[0; 64]].concat());
+        metadata.record(token, &transfer);
+        metadata.record(vault, &[0x12, 0x34, 0x56, 0x78]);
+        // receive()
+        metadata.record(vault, &[]);
+
+        let stats = metadata.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].address, stats[0].executions), (vault, 1));
+        assert_eq!((stats[1].selector, stats[1].executions), (transfer, 2));
+        assert_eq!(
+            stats[1].to_string(),
+            format!("{}.unknown (0xa9059cbb): 2 executions", checksum(&token))
+        );
+
+        // functions never executed are reported too, with their names
+        let approve = ABIConfig {
+            abi: String::from("(address,uint256)"),
+            function: [0x09, 0x5e, 0xa7, 0xb3],
+            function_name: String::from("approve"),
+            is_static: false,
+            is_payable: false,
+            is_constructor: false,
+            should_add_corpus: true,
+        };
+        metadata.register(token, &approve);
+        let stats = metadata.stats();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(
+            stats[0].to_string(),
+            format!("{}.approve (0x095ea7b3): 0 executions", checksum(&token))
+        );
+    }
+}
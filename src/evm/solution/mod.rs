@@ -14,7 +14,7 @@ use serde::Serialize;
 use tracing::{debug, error};
 
 use self::abi::{Abi, DecodedArg};
-use super::{types::EVMU256, utils, OnChainConfig};
+use super::{state_override::StateOverride, types::EVMU256, utils, OnChainConfig};
 use crate::{generic_vm::vm_state::SwapInfo, input::SolutionTx};
 
 /// Template
@@ -23,6 +23,9 @@ const TEMPLATE: &str = include_str!("foundry_test.hbs");
 /// Cli args.
 static CLI_ARGS: OnceLock<CliArgs> = OnceLock::new();
 
+/// Cheatcode calls replaying the state overrides in `setUp()`.
+static STATE_OVERRIDES: OnceLock<Vec<String>> = OnceLock::new();
+
 // Template helpers to compare strings
 handlebars_helper!(is_deposit: |ty: String| ty == "deposit");
 handlebars_helper!(is_buy: |ty: String| ty == "buy");
@@ -53,6 +56,19 @@ pub fn init_cli_args(target: String, work_dir: String, onchain: &Option<OnChainC
     let _ = CLI_ARGS.set(cli_args);
 }
 
+/// Record the state overrides applied before fuzzing.
+pub fn record_state_overrides(overrides: &[StateOverride]) {
+    let _ = STATE_OVERRIDES.set(overrides.iter().map(|o| o.to_cheatcode()).collect());
+}
+
+/// Block the chain is forked at, if fuzzing onchain
+pub fn fork_block() -> Option<String> {
+    CLI_ARGS
+        .get()
+        .filter(|cli_args| cli_args.is_onchain)
+        .map(|cli_args| cli_args.block_number.clone())
+}
+
 /// Generate a foundry test file.
 pub fn generate_test<T: SolutionTx>(solution: String, inputs: Vec<T>) {
     let solution = utils::remove_color(&solution);
@@ -113,7 +129,11 @@ pub struct Tx {
     // A tx can contain both a `buy` and a `sell` operation at the same time.
     buy_type: BuyType,
     sell_type: SellType,
+    // The tx only sells the tokens of `contract`, there is no call
+    sell_only: bool,
     borrow_idx: u32,
+    // Other assets bought along with the token of a borrow
+    co_borrows: Vec<CoBorrow>,
     caller: String,
     contract: String,
     value: String,
@@ -121,17 +141,58 @@ pub struct Tx {
     fn_selector: String,
     fn_args: String,
     calldata: String,
+    is_raw_calldata: bool,
     liq_percent: u8,
     balance_idx: u32,
     // map<type, swap_info>
     swap_data: HashMap<String, SwapInfo>,
+    // Only set when the block number changes (vm.roll)
+    block_number: Option<u64>,
+    // Seconds mined before the transaction, when it starts a new block of the
+    // sequence (vm.warp)
+    warp: Option<u64>,
+    // Blocks mined before the transaction, added to `block_number`
+    #[serde(skip)]
+    advanced_blocks: u64,
+    // EIP-2930 access list, warmed up before the call
+    access_list: Vec<AccessListItem>,
+    // Logs emitted by the transaction, rendered as comments
+    logs: Vec<String>,
+    // Real transactions run before and after the transaction
+    background_before: Option<BackgroundCall>,
+    background_after: Option<BackgroundCall>,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct CoBorrow {
+    token: String,
+    value: String,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct AccessListItem {
+    address: String,
+    slots: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct BackgroundCall {
+    caller: String,
+    contract: String,
+    value: String,
+    calldata: String,
 }
 
 impl<T: SolutionTx> From<&T> for Tx {
     fn from(input: &T) -> Self {
         let (is_borrow, mut liq_percent, swap_data) = (input.is_borrow(), input.liq_percent(), input.swap_data());
         let buy_type = BuyType::new(is_borrow, &swap_data);
-        let sell_type = SellType::new(liq_percent, &swap_data);
+        let sell_only = input.is_sell();
+        let sell_type = if sell_only {
+            SellType::Sell
+        } else {
+            SellType::new(liq_percent, &swap_data)
+        };
 
         // Adjust the liq_percent based on whether the `sell` operation is actually
         // executed.
@@ -139,9 +200,30 @@ impl<T: SolutionTx> From<&T> for Tx {
             liq_percent = 0;
         }
 
+        let (mut background_before, mut background_after) = (None, None);
+        if let Some((before, caller, contract, value, calldata)) = input.background_txn() {
+            let call = BackgroundCall {
+                caller,
+                contract,
+                value: if value == "0" { String::new() } else { value },
+                calldata,
+            };
+            if before {
+                background_before = Some(call);
+            } else {
+                background_after = Some(call);
+            }
+        }
+
         Self {
             buy_type,
             sell_type,
+            sell_only,
+            co_borrows: input
+                .co_borrows()
+                .into_iter()
+                .map(|(token, value)| CoBorrow { token, value })
+                .collect(),
             caller: input.caller(),
             contract: input.contract(),
             value: input.value(),
@@ -149,8 +231,20 @@ impl<T: SolutionTx> From<&T> for Tx {
             fn_selector: input.fn_selector(),
             fn_args: input.fn_args(),
             calldata: input.calldata(),
+            is_raw_calldata: input.is_raw_calldata(),
             liq_percent,
             swap_data,
+            block_number: input.block_number(),
+            warp: input.block_advance().map(|(_, seconds)| seconds),
+            advanced_blocks: input.block_advance().map_or(0, |(blocks, _)| blocks),
+            access_list: input
+                .access_list()
+                .into_iter()
+                .map(|(address, slots)| AccessListItem { address, slots })
+                .collect(),
+            logs: input.logs(),
+            background_before,
+            background_after,
             ..Default::default()
         }
     }
@@ -192,6 +286,7 @@ pub struct TemplateArgs {
     chain: String,
     target: String,
     block_number: String,
+    state_overrides: Vec<String>,
     etherscan_keyname: String,
     solution: String,
     trace: Vec<Tx>,
@@ -211,7 +306,7 @@ impl TemplateArgs {
         let stepping_with_return = trace.iter().any(|tx| tx.fn_selector == "0x00000000");
         let mut trace: Vec<Tx> = trace.into_iter().filter(|tx| tx.fn_selector != "0x00000000").collect();
 
-        setup_trace(&mut trace);
+        setup_trace(&mut trace, cli_args.block_number.parse::<u64>().ok());
         let router = get_router(&trace);
         let contract_name = make_contract_name(cli_args);
         let include_interface = trace
@@ -235,6 +330,7 @@ impl TemplateArgs {
             chain: cli_args.chain.clone(),
             target: cli_args.target.clone(),
             block_number: cli_args.block_number.clone(),
+            state_overrides: STATE_OVERRIDES.get().cloned().unwrap_or_default(),
             etherscan_keyname: format!("{}_ETHERSCAN_API_KEY", cli_args.chain.to_uppercase()),
             solution,
             trace,
@@ -294,15 +390,39 @@ fn make_interface(fn_sig: &str, struct_defs: &HashMap<String, StructDef>) -> Str
     format!("function {} external payable;", res)
 }
 
-fn setup_trace(trace: &mut [Tx]) {
+fn setup_trace(trace: &mut [Tx], initial_block_number: Option<u64>) {
     let (mut borrow_idx, mut balance_idx) = (0, 0);
+    let mut block_number = initial_block_number;
+    // blocks mined by the block boundaries so far, on top of the numbers of
+    // the transactions
+    let mut advanced_blocks = 0;
     for tx in trace.iter_mut() {
+        advanced_blocks += tx.advanced_blocks;
+        let number = tx.block_number.or(initial_block_number).map(|n| n + advanced_blocks);
+        // Roll only when the block number changes
+        match number {
+            Some(number) if Some(number) != block_number => {
+                block_number = Some(number);
+                tx.block_number = Some(number);
+            }
+            _ => tx.block_number = None,
+        }
+
         // Liquidation / Withdraw
         if [SellType::Sell, SellType::Withdraw].contains(&tx.sell_type) {
             tx.balance_idx = balance_idx;
             balance_idx += 1;
         }
 
+        // Raw calldata
+        if tx.is_raw_calldata {
+            tx.interface_calls = vec![format!(
+                "address({}).call{{value: {}}}(hex\"{}\");",
+                tx.contract, tx.value, tx.calldata
+            )];
+            continue;
+        }
+
         // Raw code
         if let Some(call) = make_erc20_calls(tx) {
             tx.interface_calls = vec![call];
@@ -557,4 +677,34 @@ mod tests {
         let solution = String::from("solution");
         generate_test(solution, inputs);
     }
+
+    #[test]
+    fn test_setup_trace_block_number() {
+        let mut trace = [100, 100, 105, 105]
+            .into_iter()
+            .map(|number| Tx {
+                block_number: Some(number),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        setup_trace(&mut trace, Some(100));
+        let rolls = trace.iter().map(|tx| tx.block_number).collect::<Vec<_>>();
+        assert_eq!(rolls, vec![None, None, Some(105), None]);
+    }
+
+    #[test]
+    fn test_setup_trace_block_advance() {
+        let mut trace = [(0, None), (1, Some(12)), (0, None), (300, Some(3600))]
+            .into_iter()
+            .map(|(advanced_blocks, warp)| Tx {
+                block_number: Some(100),
+                warp,
+                advanced_blocks,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        setup_trace(&mut trace, Some(100));
+        let rolls = trace.iter().map(|tx| tx.block_number).collect::<Vec<_>>();
+        assert_eq!(rolls, vec![None, Some(101), None, Some(401)]);
+    }
 }
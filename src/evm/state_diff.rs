@@ -0,0 +1,213 @@
+//! What an execution changed in an [`EVMState`], used to explain findings.
+//!
+//! States built on chain are fetched lazily, so an account or slot missing
+//! before the execution may only have been loaded (not changed) by it. Such
+//! values are reported as unknown instead of zero.
+
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::evm::{
+    types::{checksum, EVMAddress, EVMU256},
+    vm::EVMState,
+};
+
+/// Changes reported by [`StateDiff`]'s `Display`, the others are only counted
+pub const MAX_REPORTED_CHANGES: usize = 20;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub address: EVMAddress,
+    /// `None` if unknown before
+    pub before: Option<EVMU256>,
+    pub after: EVMU256,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageChange {
+    pub address: EVMAddress,
+    pub slot: EVMU256,
+    /// `None` if unknown before
+    pub before: Option<EVMU256>,
+    pub after: EVMU256,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiff {
+    pub balances: Vec<BalanceChange>,
+    pub storage: Vec<StorageChange>,
+    /// Accounts without any storage or balance before, i.e., created or first
+    /// touched by the execution
+    pub new_accounts: Vec<EVMAddress>,
+    /// Contracts self-destructed by the last transaction
+    pub destroyed: Vec<EVMAddress>,
+}
+
+impl StateDiff {
+    /// Changes from `before` to `after`, sorted by address (and slot)
+    pub fn new(before: &EVMState, after: &EVMState) -> Self {
+        let balances = after
+            .balance
+            .iter()
+            .filter(|(address, balance)| before.balance.get(*address) != Some(*balance))
+            .map(|(address, balance)| BalanceChange {
+                address: *address,
+                before: before.balance.get(address).cloned(),
+                after: *balance,
+            })
+            .sorted_by_key(|change| change.address)
+            .collect_vec();
+
+        let storage = after
+            .state
+            .iter()
+            .flat_map(|(address, slots)| {
+                slots.iter().filter_map(|(slot, value)| {
+                    let prev = before.sload(*address, *slot);
+                    // unknown and still zero, nothing was written
+                    (prev != Some(*value) && !(prev.is_none() && *value == EVMU256::ZERO)).then_some(StorageChange {
+                        address: *address,
+                        slot: *slot,
+                        before: prev,
+                        after: *value,
+                    })
+                })
+            })
+            .sorted_by_key(|change| (change.address, change.slot))
+            .collect_vec();
+
+        let new_accounts = after
+            .state
+            .keys()
+            .chain(after.balance.keys())
+            .filter(|address| !before.state.contains_key(*address) && !before.balance.contains_key(*address))
+            .unique()
+            .sorted()
+            .cloned()
+            .collect_vec();
+
+        let destroyed = after
+            .self_destruct
+            .iter()
+            .map(|(address, _)| *address)
+            .unique()
+            .sorted()
+            .collect_vec();
+
+        Self {
+            balances,
+            storage,
+            new_accounts,
+            destroyed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.balances.is_empty() && self.storage.is_empty() && self.new_accounts.is_empty() && self.destroyed.is_empty()
+    }
+
+    /// Lines of the diff, at most `limit` changes per section
+    pub fn summary(&self, limit: usize) -> String {
+        fn section<T>(out: &mut Vec<String>, title: &str, items: &[T], limit: usize, fmt: impl Fn(&T) -> String) {
+            if items.is_empty() {
+                return;
+            }
+            out.push(format!("{} ({}):", title, items.len()));
+            out.extend(items.iter().take(limit).map(|item| format!("  {}", fmt(item))));
+            if items.len() > limit {
+                out.push(format!("  ... {} more", items.len() - limit));
+            }
+        }
+        let value = |value: &Option<EVMU256>| value.map_or("?".to_string(), |value| format!("{:#x}", value));
+
+        let mut out = vec![];
+        section(&mut out, "Balances", &self.balances, limit, |change| {
+            format!(
+                "{}: {} → {:#x}",
+                checksum(&change.address),
+                value(&change.before),
+                change.after
+            )
+        });
+        section(&mut out, "Storage", &self.storage, limit, |change| {
+            format!(
+                "{}[{:#x}]: {} → {:#x}",
+                checksum(&change.address),
+                change.slot,
+                value(&change.before),
+                change.after
+            )
+        });
+        section(&mut out, "New accounts", &self.new_accounts, limit, checksum);
+        section(&mut out, "Self-destructed", &self.destroyed, limit, checksum);
+        out.join("\n")
+    }
+}
+
+impl Display for StateDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No state changes");
+        }
+        write!(f, "{}", self.summary(MAX_REPORTED_CHANGES))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_diff() {
+        let address = |idx: u8| EVMAddress::from_slice(&[idx; 20]);
+        let mut before = EVMState::default();
+        before.set_balance(address(1), EVMU256::from(100));
+        before.set_balance(address(2), EVMU256::from(5));
+        before.sstore(address(1), EVMU256::from(0), EVMU256::from(7));
+        before.sstore(address(1), EVMU256::from(1), EVMU256::from(8));
+
+        let mut after = before.clone();
+        after.set_balance(address(1), EVMU256::from(0));
+        after.sstore(address(1), EVMU256::from(0), EVMU256::from(9));
+        // loaded from the chain, unknown before
+        after.sstore(address(3), EVMU256::from(2), EVMU256::ZERO);
+        after.sstore(address(3), EVMU256::from(4), EVMU256::from(1));
+        after.self_destruct.insert((address(2), 10));
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.balances,
+            vec![BalanceChange {
+                address: address(1),
+                before: Some(EVMU256::from(100)),
+                after: EVMU256::ZERO,
+            }]
+        );
+        assert_eq!(
+            diff.storage,
+            vec![
+                StorageChange {
+                    address: address(1),
+                    slot: EVMU256::from(0),
+                    before: Some(EVMU256::from(7)),
+                    after: EVMU256::from(9),
+                },
+                StorageChange {
+                    address: address(3),
+                    slot: EVMU256::from(4),
+                    before: None,
+                    after: EVMU256::from(1),
+                },
+            ]
+        );
+        assert_eq!(diff.new_accounts, vec![address(3)]);
+        assert_eq!(diff.destroyed, vec![address(2)]);
+
+        let summary = diff.summary(1);
+        assert!(summary.contains("Storage (2):"));
+        assert!(summary.contains("... 1 more"));
+        assert!(before.diff(&before).is_empty());
+    }
+}
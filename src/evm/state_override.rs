@@ -0,0 +1,168 @@
+//! Changes to the state applied before fuzzing, the way Foundry's cheatcodes
+//! (`vm.store`, `vm.deal`, `vm.etch` and `vm.setNonce`) set up a test, e.g.,
+//! to give the attacker capital or to unlock a guarded state.
+//!
+//! Overrides are applied after the fork is set up and before the corpus is
+//! initialized, and the generated Foundry tests replay them in `setUp()`.
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use revm_primitives::Bytecode;
+use tracing::{info, warn};
+
+use crate::evm::{
+    bytecode_analyzer,
+    host::FuzzHost,
+    tokens::{parse_token_amount, DEFAULT_DECIMALS},
+    types::{checksum, EVMAddress, EVMFuzzState, EVMU256},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateOverride {
+    /// Write `value` to the storage `slot` of `address`
+    Storage {
+        address: EVMAddress,
+        slot: EVMU256,
+        value: EVMU256,
+    },
+    /// Set the ETH balance of `address`
+    Balance { address: EVMAddress, balance: EVMU256 },
+    /// Replace the runtime code of `address`
+    Code { address: EVMAddress, code: Vec<u8> },
+    /// Set the nonce of `address`. Nonces are not modeled by the fuzzer (new
+    /// contracts get random addresses), so this only affects the generated
+    /// Foundry tests.
+    Nonce { address: EVMAddress, nonce: u64 },
+}
+
+impl StateOverride {
+    /// Apply the override to the host's state
+    pub fn apply<SC>(&self, host: &mut FuzzHost<SC>, state: &mut EVMFuzzState)
+    where
+        SC: Scheduler<State = EVMFuzzState> + Clone,
+    {
+        info!("Overriding state: {}", self);
+        match self {
+            Self::Storage { address, slot, value } => host.evmstate.sstore(*address, *slot, *value),
+            Self::Balance { address, balance } => host.evmstate.set_balance(*address, *balance),
+            Self::Code { address, code } => {
+                let code = Bytecode::new_raw(Bytes::from(code.clone()));
+                bytecode_analyzer::add_analysis_result_to_state(&code, state);
+                host.set_code(*address, code, state);
+            }
+            Self::Nonce { .. } => warn!("nonces are not modeled, {} only applies to the generated tests", self),
+        }
+    }
+
+    /// The cheatcode call doing the same in a Foundry test
+    pub fn to_cheatcode(&self) -> String {
+        match self {
+            Self::Storage { address, slot, value } => format!(
+                "vm.store({}, bytes32(uint256({})), bytes32(uint256({})));",
+                checksum(address),
+                slot,
+                value
+            ),
+            Self::Balance { address, balance } => format!("vm.deal({}, {});", checksum(address), balance),
+            Self::Code { address, code } => format!("vm.etch({}, hex\"{}\");", checksum(address), hex::encode(code)),
+            Self::Nonce { address, nonce } => format!("vm.setNonce({}, {});", checksum(address), nonce),
+        }
+    }
+}
+
+impl Display for StateOverride {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Storage { address, slot, value } => {
+                write!(f, "storage {}[{}] = {}", checksum(address), slot, value)
+            }
+            Self::Balance { address, balance } => write!(f, "balance {} = {}", checksum(address), balance),
+            Self::Code { address, code } => write!(f, "code {} = {} bytes", checksum(address), code.len()),
+            Self::Nonce { address, nonce } => write!(f, "nonce {} = {}", checksum(address), nonce),
+        }
+    }
+}
+
+impl FromStr for StateOverride {
+    type Err = String;
+
+    /// Format: `storage:address:slot:value`, `balance:address:amount` (in
+    /// wei, or in ether with a decimal point), `code:address:hex` or
+    /// `nonce:address:nonce`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.trim().split(':').map(|part| part.trim()).collect::<Vec<_>>();
+        let address = |s: &str| EVMAddress::from_str(s).map_err(|_| format!("invalid address {}", s));
+        let u256 = |s: &str| EVMU256::from_str(s).map_err(|e| format!("invalid value {}: {}", s, e));
+        match parts.as_slice() {
+            ["storage", addr, slot, value] => Ok(Self::Storage {
+                address: address(addr)?,
+                slot: u256(slot)?,
+                value: u256(value)?,
+            }),
+            ["balance", addr, amount] => Ok(Self::Balance {
+                address: address(addr)?,
+                balance: parse_token_amount(amount, DEFAULT_DECIMALS)?,
+            }),
+            ["code", addr, code] => Ok(Self::Code {
+                address: address(addr)?,
+                code: hex::decode(code.trim_start_matches("0x"))
+                    .map_err(|e| format!("invalid code {}: {}", code, e))?,
+            }),
+            ["nonce", addr, nonce] => Ok(Self::Nonce {
+                address: address(addr)?,
+                nonce: nonce.parse().map_err(|_| format!("invalid nonce {}", nonce))?,
+            }),
+            _ => Err(format!("invalid state override {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state_overrides() {
+        let address = EVMAddress::from_str("0x5fbdb2315678afecb367f032d93f642f64180aa3").unwrap();
+        assert_eq!(
+            StateOverride::from_str("storage:0x5fbdb2315678afecb367f032d93f642f64180aa3:0x1:42"),
+            Ok(StateOverride::Storage {
+                address,
+                slot: EVMU256::from(1),
+                value: EVMU256::from(42),
+            })
+        );
+        assert_eq!(
+            StateOverride::from_str("balance:0x5fbdb2315678afecb367f032d93f642f64180aa3:1.5"),
+            Ok(StateOverride::Balance {
+                address,
+                balance: EVMU256::from(1_500_000_000_000_000_000_u128),
+            })
+        );
+        assert_eq!(
+            StateOverride::from_str("code:0x5fbdb2315678afecb367f032d93f642f64180aa3:0x6000"),
+            Ok(StateOverride::Code {
+                address,
+                code: vec![0x60, 0x00],
+            })
+        );
+        assert_eq!(
+            StateOverride::from_str("nonce:0x5fbdb2315678afecb367f032d93f642f64180aa3:7")
+                .unwrap()
+                .to_cheatcode(),
+            "vm.setNonce(0x5FbDB2315678afecb367f032d93F642f64180aa3, 7);"
+        );
+        assert_eq!(
+            StateOverride::from_str("balance:0x5fbdb2315678afecb367f032d93f642f64180aa3:7")
+                .unwrap()
+                .to_string(),
+            "balance 0x5FbDB2315678afecb367f032d93F642f64180aa3 = 7"
+        );
+        assert!(StateOverride::from_str("store:0x5fbdb2315678afecb367f032d93f642f64180aa3:1").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! On-disk schema of [`EVMState`], so that VM states written by one version
+//! of ityfuzz (in checkpoints and the traces of the corpora) are still read by
+//! the later ones.
+//!
+//! [`EVMState`] is written tagged with [`EVM_STATE_VERSION`] and read through
+//! [`StoredEVMState`], which maps its fields explicitly. When the persisted
+//! fields change, the version is bumped, the new fields get a
+//! `#[serde(default)]` (or an `alias` when renamed) and [`migrate`] upgrades
+//! the states of the older versions. States written before the schema was
+//! versioned have no version and are read as version 0.
+//!
+//! The transactions of the traces ([`crate::evm::input::ConciseEVMInput`])
+//! are tagged with [`TRACE_VERSION`] the same way. Their fields added since
+//! are all optional, so the older traces are read as they are, and the
+//! traces of newer versions are rejected.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::evm::{
+    input::BlockAdvance,
+    types::{EVMAddress, EVMU256},
+    vm::{EVMState, PostExecutionCtx},
+};
+
+/// Version of the schema written
+pub const EVM_STATE_VERSION: u32 = 2;
+
+/// Version of the trace format written, 0 for the traces written before it
+/// was versioned
+pub const TRACE_VERSION: u32 = 1;
+
+/// Read the version of a transaction of a trace, fails for the versions newer
+/// than [`TRACE_VERSION`]
+pub fn deserialize_trace_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if version > TRACE_VERSION {
+        return Err(serde::de::Error::custom(format!(
+            "trace of format version {} is newer than the supported version {}",
+            version, TRACE_VERSION
+        )));
+    }
+    Ok(version)
+}
+
+/// An [`EVMState`] as read from disk, only the fields that persist across
+/// transactions
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct StoredEVMState {
+    /// Version of the schema the state was written with, 0 if it was written
+    /// before the schema was versioned
+    #[serde(default)]
+    pub version: u32,
+    pub state: HashMap<EVMAddress, HashMap<EVMU256, EVMU256>>,
+    pub balance: HashMap<EVMAddress, EVMU256>,
+    #[serde(default)]
+    pub block_offset: BlockAdvance,
+    pub post_execution: Vec<PostExecutionCtx>,
+    /// Since version 2
+    #[serde(default)]
+    pub permit_nonces: Option<HashMap<EVMAddress, HashMap<EVMAddress, EVMU256>>>,
+}
+
+/// Upgrade `stored` to [`EVM_STATE_VERSION`], fails for the versions newer
+/// than it and the states missing fields of their version
+pub fn migrate(mut stored: StoredEVMState) -> Result<StoredEVMState, String> {
+    if stored.version > EVM_STATE_VERSION {
+        return Err(format!(
+            "VM state of schema version {} is newer than the supported version {}",
+            stored.version, EVM_STATE_VERSION
+        ));
+    }
+    // version 0 (unversioned) has the same fields as version 1
+    if stored.version < 2 {
+        // no permits were signed before the permit nonces were persisted
+        stored.permit_nonces.get_or_insert_with(HashMap::new);
+        stored.version = 2;
+    }
+    if stored.permit_nonces.is_none() {
+        return Err(format!(
+            "VM state of schema version {} has no permit nonces",
+            stored.version
+        ));
+    }
+    Ok(stored)
+}
+
+/// [`EVMState`] as written to disk, borrowed so that the state is not cloned
+/// to be written. Has the fields of [`StoredEVMState`].
+#[derive(Serialize)]
+struct StoredEVMStateRef<'a> {
+    version: u32,
+    state: &'a HashMap<EVMAddress, HashMap<EVMU256, EVMU256>>,
+    balance: &'a HashMap<EVMAddress, EVMU256>,
+    block_offset: &'a BlockAdvance,
+    post_execution: &'a Vec<PostExecutionCtx>,
+    permit_nonces: &'a HashMap<EVMAddress, HashMap<EVMAddress, EVMU256>>,
+}
+
+impl Serialize for EVMState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredEVMStateRef {
+            version: EVM_STATE_VERSION,
+            state: &self.state,
+            balance: &self.balance,
+            block_offset: &self.block_offset,
+            post_execution: &self.post_execution,
+            permit_nonces: &self.permit_nonces,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl TryFrom<StoredEVMState> for EVMState {
+    type Error = String;
+
+    fn try_from(stored: StoredEVMState) -> Result<Self, Self::Error> {
+        let stored = migrate(stored)?;
+        Ok(Self {
+            state: stored.state,
+            balance: stored.balance,
+            block_offset: stored.block_offset,
+            post_execution: stored.post_execution,
+            permit_nonces: stored.permit_nonces.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::evm::input::ConciseEVMInput;
+
+    #[test]
+    fn test_evm_state_schema() {
+        let contract = EVMAddress::from_slice(&[1; 20]);
+        let mut state = EVMState::new();
+        state.insert(contract, HashMap::from([(EVMU256::from(1), EVMU256::from(2))]));
+        state.set_balance(contract, EVMU256::from(3));
+
+        let mut json = serde_json::to_value(&state).unwrap();
+        assert_eq!(json["version"], EVM_STATE_VERSION);
+        let read = serde_json::from_value::<EVMState>(json.clone()).unwrap();
+        assert_eq!(read.sload(contract, EVMU256::from(1)), Some(EVMU256::from(2)));
+        assert_eq!(read.get_balance(&contract), Some(&EVMU256::from(3)));
+
+        // written before the schema was versioned
+        json.as_object_mut().unwrap().remove("version");
+        let read = serde_json::from_value::<EVMState>(json.clone()).unwrap();
+        assert_eq!(read.sload(contract, EVMU256::from(1)), Some(EVMU256::from(2)));
+
+        json["version"] = Value::from(EVM_STATE_VERSION + 1);
+        assert!(serde_json::from_value::<EVMState>(json).is_err());
+    }
+
+    #[test]
+    fn test_migrate_permit_nonces() {
+        let token = EVMAddress::from_slice(&[1; 20]);
+        let owner = EVMAddress::from_slice(&[2; 20]);
+        let mut state = EVMState::new();
+        state
+            .permit_nonces
+            .insert(token, HashMap::from([(owner, EVMU256::from(1))]));
+        let mut json = serde_json::to_value(&state).unwrap();
+        let read = serde_json::from_value::<EVMState>(json.clone()).unwrap();
+        assert_eq!(read.permit_nonces, state.permit_nonces);
+
+        // versions 0 and 1 have no permit nonces
+        json.as_object_mut().unwrap().remove("permit_nonces");
+        for version in [0, 1] {
+            json["version"] = Value::from(version);
+            let stored = serde_json::from_value::<StoredEVMState>(json.clone()).unwrap();
+            let migrated = migrate(stored).unwrap();
+            assert_eq!(migrated.version, EVM_STATE_VERSION);
+            assert_eq!(migrated.permit_nonces, Some(HashMap::new()));
+        }
+        json["version"] = Value::from(2);
+        assert!(serde_json::from_value::<EVMState>(json).is_err());
+    }
+
+    #[test]
+    fn test_trace_version() {
+        let json = serde_json::to_value(ConciseEVMInput::default().to_readable()).unwrap();
+        assert_eq!(json["version"], TRACE_VERSION);
+        assert_eq!(
+            serde_json::from_value::<ConciseEVMInput>(json.clone()).unwrap().version,
+            TRACE_VERSION
+        );
+
+        // written before the format was versioned
+        let mut unversioned = json.clone();
+        unversioned.as_object_mut().unwrap().remove("version");
+        assert_eq!(
+            serde_json::from_value::<ConciseEVMInput>(unversioned).unwrap().version,
+            0
+        );
+
+        let mut newer = json;
+        newer["version"] = Value::from(TRACE_VERSION + 1);
+        assert!(serde_json::from_value::<ConciseEVMInput>(newer).is_err());
+    }
+}
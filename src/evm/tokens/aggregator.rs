@@ -0,0 +1,191 @@
+//! Decoders of the swap entrypoints of DEX aggregators. The real swap of an
+//! aggregator call is hidden in its calldata, so it is decoded from the raw
+//! calldata to find the tokens (and amounts) involved.
+
+use std::str::FromStr;
+
+use super::{SwapInfo, SwapType};
+use crate::evm::types::{checksum, EVMAddress, EVMU256};
+
+/// 1inch v5 swap(address,(address,address,address,address,uint256,uint256,
+/// uint256),bytes,bytes)
+const ONEINCH_SWAP: [u8; 4] = [0x12, 0xaa, 0x3c, 0xaf];
+/// 0x transformERC20(address,address,uint256,uint256,(uint32,bytes)[])
+const ZEROX_TRANSFORM_ERC20: [u8; 4] = [0x41, 0x55, 0x65, 0xb0];
+/// Paraswap multiSwap((address,uint256,uint256,uint256,address,(address,
+/// uint256,(address,uint256,uint256,(uint256,address,uint256,bytes,uint256)[])
+/// [])[],address,uint256,bytes,uint256,bytes16))
+const PARASWAP_MULTI_SWAP: [u8; 4] = [0xa9, 0x4e, 0x78, 0xef];
+
+/// Placeholder used by the aggregators for the native token
+const NATIVE_TOKEN: &str = "0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee";
+
+/// A swap decoded from the calldata of an aggregator
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AggregatorSwap {
+    /// Tokens from the input token to the output token
+    pub path: Vec<EVMAddress>,
+    pub amount_in: EVMU256,
+    pub min_amount_out: EVMU256,
+}
+
+impl AggregatorSwap {
+    pub fn token_in(&self) -> EVMAddress {
+        self.path[0]
+    }
+
+    pub fn token_out(&self) -> EVMAddress {
+        *self.path.last().unwrap()
+    }
+
+    /// Decode the calldata of a supported aggregator entrypoint
+    pub fn decode(calldata: &[u8]) -> Option<Self> {
+        if calldata.len() < 4 {
+            return None;
+        }
+        let (selector, args) = calldata.split_at(4);
+        let swap = match selector {
+            s if s == ONEINCH_SWAP => {
+                // the swap description is a static tuple, so it is inlined after the executor
+                Self {
+                    path: vec![read_address(args, 1)?, read_address(args, 2)?],
+                    amount_in: read_u256(args, 5)?,
+                    min_amount_out: read_u256(args, 6)?,
+                }
+            }
+            s if s == ZEROX_TRANSFORM_ERC20 => Self {
+                path: vec![read_address(args, 0)?, read_address(args, 1)?],
+                amount_in: read_u256(args, 2)?,
+                min_amount_out: read_u256(args, 3)?,
+            },
+            s if s == PARASWAP_MULTI_SWAP => {
+                let data = args.get(read_offset(args, 0)?..)?;
+                let mut path = vec![read_address(data, 0)?];
+                let paths = data.get(read_offset(data, 5)?..)?;
+                let paths_len = read_offset(paths, 0)?;
+                let items = paths.get(32..)?;
+                for idx in 0..paths_len {
+                    let item = items.get(read_offset(items, idx)?..)?;
+                    path.push(read_address(item, 0)?);
+                }
+                Self {
+                    path,
+                    amount_in: read_u256(data, 1)?,
+                    min_amount_out: read_u256(data, 2)?,
+                }
+            }
+            _ => return None,
+        };
+        if swap.path.len() < 2 {
+            return None;
+        }
+        Some(swap)
+    }
+
+    /// Swap info of the decoded swap, buying (selling) when the native token is
+    /// the input (output) token
+    pub fn to_swap_info(&self, target: &EVMAddress) -> SwapInfo {
+        let native = EVMAddress::from_str(NATIVE_TOKEN).unwrap();
+        let ty = if self.token_in() == native {
+            SwapType::Buy
+        } else if self.token_out() == native {
+            SwapType::Sell
+        } else {
+            SwapType::Swap
+        };
+        SwapInfo {
+            ty,
+            target: checksum(target),
+            path: self.path.iter().map(checksum).collect(),
+        }
+    }
+}
+
+fn read_word(data: &[u8], idx: usize) -> Option<&[u8]> {
+    data.get(idx * 32..(idx + 1) * 32)
+}
+
+fn read_address(data: &[u8], idx: usize) -> Option<EVMAddress> {
+    Some(EVMAddress::from_slice(&read_word(data, idx)?[12..]))
+}
+
+fn read_u256(data: &[u8], idx: usize) -> Option<EVMU256> {
+    Some(EVMU256::from_be_slice(read_word(data, idx)?))
+}
+
+/// Offsets and lengths, which are bounded by the calldata size
+fn read_offset(data: &[u8], idx: usize) -> Option<usize> {
+    let value = read_u256(data, idx)?;
+    if value > EVMU256::from(data.len()) {
+        return None;
+    }
+    Some(value.as_limbs()[0] as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(value: u64) -> Vec<u8> {
+        EVMU256::from(value).to_be_bytes::<32>().to_vec()
+    }
+
+    fn address_word(addr: &EVMAddress) -> Vec<u8> {
+        [vec![0; 12], addr.0.to_vec()].concat()
+    }
+
+    #[test]
+    fn test_decode_transform_erc20() {
+        let token_in = EVMAddress::from_str(NATIVE_TOKEN).unwrap();
+        let token_out = EVMAddress::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap();
+        let calldata = [
+            ZEROX_TRANSFORM_ERC20.to_vec(),
+            address_word(&token_in),
+            address_word(&token_out),
+            word(100),
+            word(99),
+            word(0xa0),
+            word(0),
+        ]
+        .concat();
+
+        let swap = AggregatorSwap::decode(&calldata).unwrap();
+        assert_eq!(swap.path, vec![token_in, token_out]);
+        assert_eq!(swap.amount_in, EVMU256::from(100));
+        assert_eq!(swap.min_amount_out, EVMU256::from(99));
+        assert_eq!(swap.to_swap_info(&token_out).ty, SwapType::Buy);
+
+        assert!(AggregatorSwap::decode(&calldata[..100]).is_none());
+    }
+
+    #[test]
+    fn test_decode_multi_swap() {
+        let from = EVMAddress::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap();
+        let mid = EVMAddress::from_str("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").unwrap();
+        let to = EVMAddress::from_str("0xdac17f958d2ee523a2206206994597c13d831ec7").unwrap();
+        // path[i] = (to, totalNetworkFee, adapters = [])
+        let path_item = |to: &EVMAddress| [address_word(to), word(0), word(0x60), word(0)].concat();
+        let paths = [word(2), word(0x40), word(0xc0), path_item(&mid), path_item(&to)].concat();
+        let data = [
+            address_word(&from),
+            word(100),
+            word(90),
+            word(95),
+            word(0),
+            word(11 * 32),
+            word(0),
+            word(0),
+            word(0),
+            word(0),
+            word(0),
+            paths,
+        ]
+        .concat();
+        let calldata = [PARASWAP_MULTI_SWAP.to_vec(), word(0x20), data].concat();
+
+        let swap = AggregatorSwap::decode(&calldata).unwrap();
+        assert_eq!(swap.path, vec![from, mid, to]);
+        assert_eq!(swap.amount_in, EVMU256::from(100));
+        assert_eq!(swap.to_swap_info(&to).ty, SwapType::Swap);
+    }
+}
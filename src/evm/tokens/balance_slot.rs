@@ -0,0 +1,215 @@
+//! Balances of tokens read from and written to storage directly.
+//!
+//! When the storage slot of a token's `balances` mapping is known (given on
+//! the command line or found by probing), balances are read from
+//! [`EVMState`] instead of calling `balanceOf`, which is faster and does not
+//! depend on non-standard or proxied `balanceOf` implementations. The slots of
+//! the tokens discovered while fuzzing (e.g., fetched from the chain by the
+//! flashloan middleware) are probed by the oracles with the next execution.
+
+use std::{collections::HashMap, str::FromStr};
+
+use bytes::Bytes;
+use crypto::{digest::Digest, sha3::Sha3};
+use libafl::state::HasMetadata;
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::v2_transformer::balance_of_bytes;
+use crate::evm::{
+    types::{EVMAddress, EVMFuzzState, EVMU256},
+    vm::EVMState,
+};
+
+/// balanceOf(address)
+pub const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Slots probed for the `balances` mapping, Solidity lays out mappings
+/// declared early in the contract at low slots
+const MAX_PROBED_SLOT: u64 = 16;
+
+/// Owner and balance written while probing, unlikely to collide with real
+/// ones
+const PROBE_OWNER: [u8; 20] = [0xba; 20];
+const PROBE_BALANCE: u64 = 0x1337_b33f;
+
+/// Slot of `mapping(address => uint256)` holding the balances of a token
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceSlot {
+    pub index: EVMU256,
+    /// Vyper hashes the slot before the key
+    pub vyper: bool,
+}
+
+impl BalanceSlot {
+    /// Storage key of the balance of `owner`
+    pub fn key(&self, owner: &EVMAddress) -> EVMU256 {
+        let owner = [vec![0; 12], owner.0.to_vec()].concat();
+        let index = self.index.to_be_bytes::<32>().to_vec();
+        let preimage = if self.vyper {
+            [index, owner].concat()
+        } else {
+            [owner, index].concat()
+        };
+        let mut hasher = Sha3::keccak256();
+        hasher.input(&preimage);
+        let mut out = [0u8; 32];
+        hasher.result(&mut out);
+        EVMU256::from_be_bytes(out)
+    }
+
+    /// Balance of `owner`, `None` if the slot is not loaded (e.g., not
+    /// fetched from the chain yet)
+    pub fn read(&self, vm_state: &EVMState, token: EVMAddress, owner: &EVMAddress) -> Option<EVMU256> {
+        vm_state.sload(token, self.key(owner))
+    }
+
+    fn write(&self, vm_state: &mut EVMState, token: EVMAddress, owner: &EVMAddress, amount: EVMU256) {
+        vm_state.sstore(token, self.key(owner), amount);
+    }
+}
+
+/// `<slot>` or `<slot>:vyper`, the slot in hex (0x...) or decimal
+impl FromStr for BalanceSlot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, vyper) = match s.trim().split_once(':') {
+            Some((index, "vyper")) => (index, true),
+            Some((_, layout)) => return Err(format!("unknown storage layout {}", layout)),
+            None => (s.trim(), false),
+        };
+        let index = EVMU256::from_str(index).map_err(|e| format!("invalid balance slot {}: {}", index, e))?;
+        Ok(Self { index, vyper })
+    }
+}
+
+/// Known balance slots of tokens
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BalanceSlotMetadata {
+    pub slots: HashMap<EVMAddress, BalanceSlot>,
+    /// Probe the slots of the tokens whose slot is not given
+    pub probe: bool,
+    /// Tokens discovered while fuzzing whose slot is yet to be probed
+    pub pending: Vec<EVMAddress>,
+}
+
+impl_serdeany!(BalanceSlotMetadata);
+
+impl BalanceSlotMetadata {
+    /// Balance of `owner` read from storage, `None` if the balance slot of the
+    /// token is unknown or not loaded, i.e., `balanceOf` needs to be called
+    pub fn read_balance(&self, vm_state: &EVMState, token: EVMAddress, owner: &EVMAddress) -> Option<EVMU256> {
+        self.slots.get(&token)?.read(vm_state, token, owner)
+    }
+
+    /// Probe the slot of `token` with the next execution, if probing is
+    /// enabled and its slot is unknown
+    pub fn queue_probe(&mut self, token: EVMAddress) {
+        if self.probe && !self.slots.contains_key(&token) && !self.pending.contains(&token) {
+            self.pending.push(token);
+        }
+    }
+}
+
+/// Balance of `owner` from storage, if the balance slot of `token` is known
+pub fn known_balance(
+    state: &EVMFuzzState,
+    vm_state: &EVMState,
+    token: EVMAddress,
+    owner: &EVMAddress,
+) -> Option<EVMU256> {
+    state
+        .metadata_map()
+        .get::<BalanceSlotMetadata>()?
+        .read_balance(vm_state, token, owner)
+}
+
+/// Find the balance slot of `token` the way cheat frameworks implement `deal`:
+/// write a balance to each candidate slot of `vm_state` and check whether
+/// `balanceOf`, statically called on the written state with `call`, returns
+/// it
+pub fn probe_balance_slot(
+    vm_state: &EVMState,
+    token: EVMAddress,
+    mut call: impl FnMut(&[(EVMAddress, Bytes)], &EVMState) -> Vec<Vec<u8>>,
+) -> Option<BalanceSlot> {
+    let owner = EVMAddress::from_slice(&PROBE_OWNER);
+    let probe = [(token, balance_of_bytes(&owner))];
+    for index in 0..MAX_PROBED_SLOT {
+        for vyper in [false, true] {
+            let slot = BalanceSlot {
+                index: EVMU256::from(index),
+                vyper,
+            };
+            let mut probed = vm_state.clone();
+            slot.write(&mut probed, token, &owner, EVMU256::from(PROBE_BALANCE));
+            let res = call(&probe, &probed);
+            if EVMU256::try_from_be_slice(&res[0]) == Some(EVMU256::from(PROBE_BALANCE)) {
+                debug!("balance slot of {:?}: {:?}", token, slot);
+                return Some(slot);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_slot() {
+        // balances[0xf39f...2266] of a mapping at slot 0
+        let owner = EVMAddress::from_str("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266").unwrap();
+        let slot = BalanceSlot::from_str("0").unwrap();
+        assert_eq!(
+            slot.key(&owner),
+            EVMU256::from_str("0x723077b8a1b173adc35e5f0e7e3662fd1208212cb629f9c128551ea7168da722").unwrap()
+        );
+        assert_eq!(
+            BalanceSlot::from_str("0x3:vyper").unwrap(),
+            BalanceSlot {
+                index: EVMU256::from(3),
+                vyper: true
+            }
+        );
+        assert!(BalanceSlot::from_str("3:huff").is_err());
+
+        let token = EVMAddress::from_slice(&[1; 20]);
+        let mut vm_state = EVMState::default();
+        let mut meta = BalanceSlotMetadata::default();
+        assert_eq!(meta.read_balance(&vm_state, token, &owner), None);
+        meta.slots.insert(token, slot);
+        assert_eq!(meta.read_balance(&vm_state, token, &owner), None);
+        slot.write(&mut vm_state, token, &owner, EVMU256::from(42));
+        assert_eq!(meta.read_balance(&vm_state, token, &owner), Some(EVMU256::from(42)));
+    }
+
+    #[test]
+    fn test_probe_balance_slot() {
+        let token = EVMAddress::from_slice(&[1; 20]);
+        let probe_owner = EVMAddress::from_slice(&PROBE_OWNER);
+        // a Vyper token keeping the balances at slot 3
+        let slot = BalanceSlot::from_str("3:vyper").unwrap();
+        let balance_of = |_: &[(EVMAddress, Bytes)], state: &EVMState| {
+            let balance = state.sload(token, slot.key(&probe_owner)).unwrap_or_default();
+            vec![balance.to_be_bytes::<32>().to_vec()]
+        };
+        assert_eq!(probe_balance_slot(&EVMState::default(), token, balance_of), Some(slot));
+        let reverting = |_: &[(EVMAddress, Bytes)], _: &EVMState| vec![vec![]];
+        assert_eq!(probe_balance_slot(&EVMState::default(), token, reverting), None);
+
+        let mut meta = BalanceSlotMetadata::default();
+        meta.queue_probe(token);
+        assert!(meta.pending.is_empty());
+        meta.probe = true;
+        meta.queue_probe(token);
+        meta.queue_probe(token);
+        assert_eq!(meta.pending, vec![token]);
+        meta.slots.insert(EVMAddress::zero(), slot);
+        meta.queue_probe(EVMAddress::zero());
+        assert_eq!(meta.pending, vec![token]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Throughput of [`TokenContext::buy`] / [`TokenContext::sell`] on 1 / 2 / 3
+//! hop routes, built offline with [`TokenContext::from_pairs`].
+//!
+//! Run with `cargo test --release --features bench bench_swaps -- --ignored
+//! --nocapture`.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use criterion::{black_box, Criterion};
+use libafl::{schedulers::StdScheduler, state::HasMetadata};
+use revm_primitives::Bytecode;
+
+use super::*;
+use crate::{
+    evm::{
+        abi::ABIAddressToInstanceMap,
+        corpus_initializer::EnvMetadata,
+        host::FuzzHost,
+        input::ConciseEVMInput,
+        types::{EVMAddress, EVMFuzzState, EVMU256},
+        vm::{EVMExecutor, EVMState},
+    },
+    state::{FuzzState, HasCaller},
+};
+
+/// A token without any checks, balances are kept at slot `address`:
+/// `balanceOf(address)`, `transfer(address,uint256)` and deposits via empty
+/// calldata (as WETH)
+const MOCK_TOKEN: &str = concat!(
+    // dispatch
+    "361560495760003560e01c806370a0823114601f5763a9059cbb14602c5700",
+    // balanceOf
+    "5b6004355460005260206000f3",
+    // transfer
+    "5b602435803354033355600435805482019055600160005260206000f3",
+    // deposit
+    "5b34335401335500"
+);
+
+fn slot_of(addr: &EVMAddress) -> EVMU256 {
+    EVMU256::from_be_slice(addr.0.as_slice())
+}
+
+fn address(idx: u8) -> EVMAddress {
+    EVMAddress::from_slice(&[idx; 20])
+}
+
+type BenchExecutor = EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>>;
+
+/// Route of `hops` pairs from token 1 to WETH, each pair holding 1e24 of both
+/// tokens, and a holder of 1e21 token 1
+fn setup(hops: u8, state: &mut EVMFuzzState) -> (TokenContext, BenchExecutor, EVMAddress) {
+    let reserve = EVMU256::from(10).pow(EVMU256::from(24));
+    let weth = address(0x10);
+    let tokens = (1..=hops).map(address).chain([weth]).collect::<Vec<_>>();
+    let pairs = (0..hops).map(|idx| address(0x80 + idx)).collect::<Vec<_>>();
+    let holder = address(0xff);
+
+    let mut host = FuzzHost::new(StdScheduler::new(), "work_dir".to_string());
+    let code = Bytecode::new_raw(Bytes::from(hex::decode(MOCK_TOKEN).unwrap()));
+    for token in &tokens {
+        host.set_code(*token, code.clone(), state);
+    }
+    for (idx, pair) in pairs.iter().enumerate() {
+        for token in &tokens[idx..idx + 2] {
+            host.evmstate
+                .state
+                .entry(*token)
+                .or_default()
+                .insert(slot_of(pair), reserve);
+        }
+    }
+    host.evmstate
+        .state
+        .entry(tokens[0])
+        .or_default()
+        .insert(slot_of(&holder), EVMU256::from(10).pow(EVMU256::from(21)));
+
+    let route = pairs
+        .iter()
+        .zip(tokens.iter().skip(1))
+        .map(|(pair, next_hop)| (*pair, *next_hop, (reserve, reserve)))
+        .collect::<Vec<_>>();
+    let ctx = TokenContext::from_pairs(
+        tokens[0],
+        weth,
+        &route,
+        UniswapInfo {
+            pool_fee: 30,
+            ..Default::default()
+        },
+    );
+    (ctx, EVMExecutor::new(host, address(0xfe)), holder)
+}
+
+#[test]
+#[ignore]
+fn bench_swaps() {
+    let mut state: EVMFuzzState = FuzzState::new(0);
+    state.add_caller(&address(0xfd));
+    state
+        .metadata_map_mut()
+        .insert::<ABIAddressToInstanceMap>(ABIAddressToInstanceMap::new());
+    state.metadata_map_mut().insert::<EnvMetadata>(EnvMetadata::default());
+
+    let mut criterion = Criterion::default()
+        .warm_up_time(Duration::from_secs(1))
+        .measurement_time(Duration::from_secs(3));
+    let amount = EVMU256::from(10).pow(EVMU256::from(18));
+    for hops in 1..=3 {
+        let (ctx, mut vm, holder) = setup(hops, &mut state);
+        // each swap starts from the initial reserves
+        let initial = vm.host.evmstate.clone();
+
+        criterion.bench_function(&format!("buy {} hop", hops), |b| {
+            b.iter(|| {
+                vm.host.evmstate = initial.clone();
+                let res = ctx.buy(black_box(amount), holder, &mut state, &mut vm, &[0], None);
+                assert!(res.is_some());
+            })
+        });
+        criterion.bench_function(&format!("sell {} hop", hops), |b| {
+            b.iter(|| {
+                vm.host.evmstate = initial.clone();
+                let res = ctx.sell(black_box(amount), holder, &mut state, &mut vm, &[0], None);
+                assert!(res.is_some());
+            })
+        });
+    }
+    criterion.final_summary();
+}
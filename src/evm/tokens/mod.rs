@@ -2,35 +2,47 @@ use std::{
     borrow::BorrowMut,
     cell::RefCell,
     collections::{hash_map, HashMap},
-    fmt::Debug,
+    fmt::{Debug, Display, Formatter},
     ops::Deref,
     rc::Rc,
     str::FromStr,
+    sync::{Arc, RwLock},
 };
 
 use alloy_primitives::hex;
-use libafl::schedulers::Scheduler;
+use crypto::{digest::Digest, sha3::Sha3};
+use lazy_static::lazy_static;
+use libafl::{schedulers::Scheduler, state::HasMetadata};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::debug;
 
 use super::{
-    types::{checksum, EVMFuzzState},
+    types::{checksum, generate_random_address, EVMFuzzState},
     vm::EVMExecutor,
 };
 use crate::{
     evm::{
         abi::{AArray, BoxedABI},
         onchain::endpoints::Chain,
-        types::{EVMAddress, EVMU256},
+        oracles::u512_div_float,
+        tokens::{aggregator::AggregatorSwap, uniswap::SwapDiscoveryMetadata},
+        types::{EVMAddress, EVMU256, EVMU512},
     },
     generic_vm::{
         vm_executor::GenericVM,
         vm_state::{self, VMStateT},
     },
     input::ConciseSerde,
-    state::HasCaller,
 };
 
+pub mod aggregator;
+pub mod balance_slot;
+#[cfg(all(test, feature = "bench"))]
+mod bench;
 pub mod constant_pair;
+pub mod multi_pool_transformer;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
 pub mod uniswap;
 pub mod v2_transformer;
 pub mod weth_transformer;
@@ -43,6 +55,14 @@ const SWAP_WITHDRAW: [u8; 4] = [0x2e, 0x1a, 0x7d, 0x4d];
 const SWAP_BUY: [u8; 4] = [0xb6, 0xf9, 0xde, 0x95];
 // swapExactTokensForETHSupportingFeeOnTransferTokens
 const SWAP_SELL: [u8; 4] = [0x79, 0x1a, 0xc9, 0x47];
+// swapExactETHForTokens
+const SWAP_EXACT_ETH_FOR_TOKENS: [u8; 4] = [0x7f, 0xf3, 0x6a, 0xb5];
+// swapExactTokensForETH
+const SWAP_EXACT_TOKENS_FOR_ETH: [u8; 4] = [0x18, 0xcb, 0xaf, 0xe5];
+// swapTokensForExactETH
+const SWAP_TOKENS_FOR_EXACT_ETH: [u8; 4] = [0x4a, 0x25, 0xd9, 0x4a];
+// swapExactTokensForTokens
+const SWAP_EXACT_TOKENS_FOR_TOKENS: [u8; 4] = [0x38, 0xed, 0x17, 0x39];
 
 #[derive(Clone, Debug)]
 pub enum UniswapProvider {
@@ -73,8 +93,37 @@ macro_rules! get_code_tokens {
     };
 }
 
+impl UniswapProvider {
+    /// Provider names accepted by [`UniswapProvider::from_str`]
+    pub const SUPPORTED: [&'static str; 6] = [
+        "pancakeswap",
+        "pancakeswapv2",
+        "sushiswap",
+        "uniswapv2",
+        "uniswapv3",
+        "biswap",
+    ];
+}
+
+/// Error of parsing an unknown [`UniswapProvider`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownProviderError(pub String);
+
+impl Display for UnknownProviderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown provider '{}', expected one of: {}",
+            self.0,
+            UniswapProvider::SUPPORTED.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownProviderError {}
+
 impl FromStr for UniswapProvider {
-    type Err = ();
+    type Err = UnknownProviderError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "pancakeswap" => Ok(Self::PancakeSwap),
@@ -83,17 +132,56 @@ impl FromStr for UniswapProvider {
             "uniswapv2" => Ok(Self::UniswapV2),
             "uniswapv3" => Ok(Self::UniswapV3),
             "biswap" => Ok(Self::Biswap),
-            _ => Err(()),
+            _ => Err(UnknownProviderError(s.to_string())),
         }
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// How the address of the pair of two tokens is determined
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PairLookup {
+    /// Derive it with CREATE2 from the factory and the init code hash
+    #[default]
+    Create2,
+    /// Query `factory.getPair(token0, token1)`, which does not depend on the
+    /// init code hash (e.g., for forked DEXes), `init_code_hash` may be left
+    /// empty
+    GetPair,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct UniswapInfo {
     pub pool_fee: usize,
     pub router: EVMAddress,
     pub factory: EVMAddress,
     pub init_code_hash: Vec<u8>,
+    pub pair_lookup: PairLookup,
+}
+
+impl UniswapInfo {
+    /// CREATE2 address of the pair of `token_a` and `token_b` deployed by the
+    /// factory, in either token order
+    pub fn pair_for(&self, token_a: EVMAddress, token_b: EVMAddress) -> EVMAddress {
+        let (token0, token1) = if token_a < token_b {
+            (token_a, token_b)
+        } else {
+            (token_b, token_a)
+        };
+        let mut salt = [0u8; 32];
+        let mut hasher = Sha3::keccak256();
+        hasher.input(token0.as_bytes());
+        hasher.input(token1.as_bytes());
+        hasher.result(&mut salt);
+
+        let mut hash = [0u8; 32];
+        let mut hasher = Sha3::keccak256();
+        hasher.input(&[0xff]);
+        hasher.input(self.factory.as_bytes());
+        hasher.input(&salt);
+        hasher.input(&self.init_code_hash);
+        hasher.result(&mut hash);
+        EVMAddress::from_slice(&hash[12..])
+    }
 }
 
 pub trait PairContext {
@@ -115,10 +203,23 @@ pub trait PairContext {
     fn name(&self) -> String;
 }
 
-#[derive(Clone)]
-enum PairContextTy {
+/// A hop of a route
+#[derive(Clone, Serialize, Deserialize)]
+pub enum PairContextTy {
     Uniswap(Rc<RefCell<v2_transformer::UniswapPairContext>>),
     Weth(Rc<RefCell<weth_transformer::WethContext>>),
+    MultiPool(Rc<RefCell<multi_pool_transformer::MultiTokenPoolContext>>),
+}
+
+impl PairContextTy {
+    /// Pair, pool or WETH contract of the hop
+    pub fn address(&self) -> EVMAddress {
+        match self {
+            PairContextTy::Uniswap(ctx) => ctx.borrow().pair_address,
+            PairContextTy::Weth(ctx) => ctx.borrow().weth_address,
+            PairContextTy::MultiPool(ctx) => ctx.borrow().pool_address,
+        }
+    }
 }
 
 impl Debug for PairContextTy {
@@ -126,25 +227,467 @@ impl Debug for PairContextTy {
         match self {
             PairContextTy::Uniswap(ctx) => write!(f, "Uniswap({:?})", ctx.borrow()),
             PairContextTy::Weth(ctx) => write!(f, "Weth({:?})", ctx.borrow()),
+            PairContextTy::MultiPool(ctx) => write!(f, "MultiPool({:?})", ctx.borrow()),
         }
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PathContext {
     pub route: Vec<PairContextTy>,
 }
 
-#[derive(Clone, Debug, Default)]
+impl PathContext {
+    /// Addresses of the pairs / pools on this route, weth hops are skipped
+    pub fn pair_addresses(&self) -> Vec<EVMAddress> {
+        self.route
+            .iter()
+            .filter_map(|pair| match pair {
+                PairContextTy::Uniswap(ctx) => Some(ctx.borrow().pair_address),
+                PairContextTy::MultiPool(ctx) => Some(ctx.borrow().pool_address),
+                PairContextTy::Weth(_) => None,
+            })
+            .collect()
+    }
+
+    /// Whether a pair on this route is known to block transfers of the
+    /// token, so that swapping through it always reverts
+    pub fn is_blocked(&self) -> bool {
+        self.route.iter().any(|pair| match pair {
+            PairContextTy::Uniswap(ctx) => ctx.borrow().blocked.get().is_some(),
+            _ => false,
+        })
+    }
+
+    /// Addresses of the routers of the uniswap pairs on this route
+    pub fn router_addresses(&self) -> Vec<EVMAddress> {
+        self.route
+            .iter()
+            .filter_map(|pair| match pair {
+                PairContextTy::Uniswap(ctx) => Some(ctx.borrow().uniswap_info.router),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Display for PairContextTy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PairContextTy::Uniswap(ctx) => {
+                let ctx = ctx.borrow();
+                write!(
+                    f,
+                    "pair {}: {} → {} (reserves {} / {}",
+                    checksum(&ctx.pair_address),
+                    checksum(&ctx.in_token_address),
+                    checksum(&ctx.next_hop),
+                    ctx.initial_reserves.0,
+                    ctx.initial_reserves.1
+                )?;
+                if let Some(blocked) = ctx.blocked.get() {
+                    write!(f, ", blocked: {:?}", blocked)?;
+                }
+                write!(f, ")")
+            }
+            PairContextTy::Weth(ctx) => write!(f, "unwrap {}", checksum(&ctx.borrow().weth_address)),
+            PairContextTy::MultiPool(ctx) => {
+                let ctx = ctx.borrow();
+                write!(
+                    f,
+                    "pool {}: {} → {}",
+                    checksum(&ctx.pool_address),
+                    checksum(&ctx.tokens[ctx.token_in]),
+                    checksum(&ctx.tokens[ctx.token_out])
+                )
+            }
+        }
+    }
+}
+
+/// One hop per line, in swap order
+impl Display for PathContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (idx, pair) in self.route.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "  {}. {}", idx + 1, pair)?;
+        }
+        Ok(())
+    }
+}
+
+/// Price impact of a swap through a pair or pool, i.e., how much less the
+/// swap outputs than trading at its spot rate before the trade
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct SwapImpact {
+    pub pair: EVMAddress,
+    pub amount_in: EVMU256,
+    pub amount_out: EVMU256,
+    /// Price impact in basis points
+    pub impact_bps: u64,
+}
+
+impl SwapImpact {
+    /// `reserve_in` and `reserve_out` give the spot rate before the trade
+    pub fn new(
+        pair: EVMAddress,
+        amount_in: EVMU256,
+        amount_out: EVMU256,
+        reserve_in: EVMU256,
+        reserve_out: EVMU256,
+    ) -> Self {
+        let spot_out = if reserve_in == EVMU256::ZERO {
+            EVMU512::ZERO
+        } else {
+            EVMU512::from(amount_in) * EVMU512::from(reserve_out) / EVMU512::from(reserve_in)
+        };
+        let amount_out_512 = EVMU512::from(amount_out);
+        let impact_bps = if spot_out == EVMU512::ZERO || amount_out_512 >= spot_out {
+            0
+        } else {
+            ((spot_out - amount_out_512) * EVMU512::from(10000) / spot_out).as_limbs()[0]
+        };
+        Self {
+            pair,
+            amount_in,
+            amount_out,
+            impact_bps,
+        }
+    }
+}
+
+impl Display for SwapImpact {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} → {} (price impact {}.{:02}%)",
+            checksum(&self.pair),
+            self.amount_in,
+            self.amount_out,
+            self.impact_bps / 100,
+            self.impact_bps % 100
+        )
+    }
+}
+
+/// Native currency wrapped or unwrapped through WETH in a swap
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WethAction {
+    /// ETH deposited for WETH
+    Deposit(EVMU256),
+    /// WETH withdrawn for ETH
+    Withdraw(EVMU256),
+}
+
+impl Display for WethAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Deposit(amount) => write!(
+                f,
+                "deposit {} ETH → WETH",
+                format_token_amount(*amount, DEFAULT_DECIMALS, 6)
+            ),
+            Self::Withdraw(amount) => write!(
+                f,
+                "withdraw {} WETH → ETH",
+                format_token_amount(*amount, DEFAULT_DECIMALS, 6)
+            ),
+        }
+    }
+}
+
+/// Decimals assumed for tokens whose `decimals()` is unknown
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+/// Format a raw token amount with the token's decimals, keeping `fp`
+/// fractional digits, e.g., 1500000 with 6 decimals and fp = 3 is "1.500"
+pub fn format_token_amount(amount: EVMU256, decimals: u8, fp: usize) -> String {
+    let amount = EVMU512::from(amount);
+    let decimals = decimals as usize;
+    if decimals >= fp {
+        u512_div_float(amount, EVMU512::from(10).pow(EVMU512::from(decimals - fp)), fp)
+    } else {
+        u512_div_float(
+            amount * EVMU512::from(10).pow(EVMU512::from(fp - decimals)),
+            EVMU512::from(1),
+            fp,
+        )
+    }
+}
+
+/// Parse a token amount given by the user. An amount with a decimal point is
+/// in units of the token and scaled by its decimals, e.g., "2.0" with 18
+/// decimals is 2000000000000000000; any other amount is raw (in wei).
+pub fn parse_token_amount(s: &str, decimals: u8) -> Result<EVMU256, String> {
+    let s = s.trim().replace('_', "");
+    let Some((int, frac)) = s.split_once('.') else {
+        return EVMU256::from_str(&s).map_err(|_| format!("invalid amount {}", s));
+    };
+    let decimals = decimals as usize;
+    let frac = frac.trim_end_matches('0');
+    if frac.len() > decimals {
+        return Err(format!("amount {} has more than {} decimals", s, decimals));
+    }
+    if !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) || (int.is_empty() && frac.is_empty()) {
+        return Err(format!("invalid amount {}", s));
+    }
+    let digits = format!("0{}{}{}", int, frac, "0".repeat(decimals - frac.len()));
+    EVMU256::from_str_radix(&digits, 10).map_err(|_| format!("amount {} is too large", s))
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TokenContext {
     pub swaps: Vec<PathContext>,
     pub is_weth: bool,
     pub weth_address: EVMAddress,
+    /// `decimals()` of the token, `None` if unknown
+    pub decimals: Option<u8>,
+    /// `decimals()` of WETH, i.e., the asset profits are measured in
+    pub weth_decimals: Option<u8>,
+    /// Share of the tokens bought that is taxed on the transfer out of the
+    /// pair, in basis points, `None` until measured by
+    /// [`TokenContext::measure_taxes`]
+    pub buy_tax_bps: Option<u64>,
+    /// Share of the tokens sold that is taxed on the transfer into the pair,
+    /// in basis points
+    pub sell_tax_bps: Option<u64>,
+}
+
+/// Tokens losing this share (in basis points) of a round trip to taxes are
+/// reported as honeypots
+pub const HONEYPOT_TAX_BPS: u64 = 1000;
+
+/// e.g., 1234 bps is "12.34%"
+fn format_bps(bps: u64) -> String {
+    format!("{}.{:02}%", bps / 100, bps % 100)
 }
 
 static mut WETH_MAX: EVMU256 = EVMU256::ZERO;
 
+/// The routes to WETH, in the order they are selected by seed
+impl Display for TokenContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_weth {
+            return write!(f, "token is WETH ({}), no swap needed", checksum(&self.weth_address));
+        }
+        write!(
+            f,
+            "{} route(s) to WETH ({})",
+            self.swaps.len(),
+            checksum(&self.weth_address)
+        )?;
+        if let Some(decimals) = self.decimals {
+            write!(f, ", {} decimals", decimals)?;
+        }
+        if let Some(bps) = self.buy_tax_bps {
+            write!(f, ", buy tax {}", format_bps(bps))?;
+        }
+        if let Some(bps) = self.sell_tax_bps {
+            write!(f, ", sell tax {}", format_bps(bps))?;
+        }
+        for (idx, path) in self.swaps.iter().enumerate() {
+            write!(f, "\nroute #{}", idx)?;
+            if path.is_blocked() {
+                write!(f, " (blocked)")?;
+            }
+            write!(f, ":\n{}", path)?;
+        }
+        Ok(())
+    }
+}
+
 impl TokenContext {
+    /// Build the context of `token` without fetching anything, from a single
+    /// route of Uniswap V2 like pairs towards WETH. Each hop is given as
+    /// (pair, token out, reserves of the pair), and the token out of the
+    /// last hop must be `weth`.
+    pub fn from_pairs(
+        token: EVMAddress,
+        weth: EVMAddress,
+        hops: &[(EVMAddress, EVMAddress, (EVMU256, EVMU256))],
+        uniswap_info: UniswapInfo,
+    ) -> Self {
+        let uniswap_info = Arc::new(uniswap_info);
+        let mut path = PathContext::default();
+        let mut in_token = token;
+        for (pair, next_hop, reserves) in hops {
+            path.route.push(PairContextTy::Uniswap(Rc::new(RefCell::new(
+                v2_transformer::UniswapPairContext {
+                    pair_address: *pair,
+                    in_token_address: in_token,
+                    next_hop: *next_hop,
+                    // token0 is the one with the smaller address
+                    side: if in_token < *next_hop { 0 } else { 1 },
+                    uniswap_info: uniswap_info.clone(),
+                    initial_reserves: *reserves,
+                    blocked: Default::default(),
+                    last_failure: Default::default(),
+                },
+            ))));
+            in_token = *next_hop;
+        }
+        assert_eq!(in_token, weth, "the last hop should swap to WETH");
+        path.route.push(PairContextTy::Weth(Rc::new(RefCell::new(
+            weth_transformer::WethContext { weth_address: weth },
+        ))));
+
+        Self {
+            swaps: vec![path],
+            is_weth: token == weth,
+            weth_address: weth,
+            decimals: None,
+            weth_decimals: None,
+            buy_tax_bps: None,
+            sell_tax_bps: None,
+        }
+    }
+
+    /// Initial WETH reserves of the Uniswap pairs swapping to or from WETH
+    pub fn weth_reserves(&self) -> Vec<EVMU256> {
+        self.swaps
+            .iter()
+            .flat_map(|path| path.route.iter())
+            .filter_map(|pair| match pair {
+                PairContextTy::Uniswap(ctx) => {
+                    let ctx = ctx.borrow();
+                    let (reserve_in, reserve_out) = if ctx.side == 0 {
+                        ctx.initial_reserves
+                    } else {
+                        (ctx.initial_reserves.1, ctx.initial_reserves.0)
+                    };
+                    if ctx.next_hop == self.weth_address {
+                        Some(reserve_out)
+                    } else if ctx.in_token_address == self.weth_address {
+                        Some(reserve_in)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Select the path to swap through. A pinned route (e.g., recorded in a
+    /// replayed input) takes precedence over the seed, so that replaying does
+    /// not depend on the order paths are discovered.
+    pub fn select_path(&self, seed: &[u8], pinned_route: Option<&[EVMAddress]>) -> Option<&PathContext> {
+        if self.swaps.is_empty() {
+            return None;
+        }
+        if let Some(pinned) = pinned_route {
+            if let Some(path) = self.swaps.iter().find(|path| path.pair_addresses() == pinned) {
+                return Some(path);
+            }
+        }
+        // routes known to revert are skipped
+        let paths = self.swaps.iter().filter(|path| !path.is_blocked()).collect::<Vec<_>>();
+        if paths.is_empty() {
+            return None;
+        }
+        Some(paths[seed[0] as usize % paths.len()])
+    }
+
+    /// The token blocks transfers on all of its routes, e.g., a honeypot
+    /// blacklisting its pairs, so it can not be sold
+    pub fn is_unsellable(&self) -> bool {
+        !self.is_weth &&
+            !self.swaps.is_empty() &&
+            (self.swaps.iter().all(|path| path.is_blocked()) || self.sell_tax_bps.map_or(false, |bps| bps >= 10000))
+    }
+
+    /// Share of the tokens lost to taxes by buying them and selling them
+    /// back, in basis points, `None` if the taxes are not measured
+    pub fn round_trip_tax_bps(&self) -> Option<u64> {
+        let kept = |bps: Option<u64>| Some(10000 - bps?.min(10000));
+        Some(10000 - kept(self.buy_tax_bps)? * kept(self.sell_tax_bps)? / 10000)
+    }
+
+    /// Why the token is a honeypot, i.e., it can not be sold or a round trip
+    /// loses at least [`HONEYPOT_TAX_BPS`] to taxes
+    pub fn honeypot_reason(&self) -> Option<String> {
+        if self.is_unsellable() {
+            return Some("the token can not be sold on any route".to_string());
+        }
+        let loss = self.round_trip_tax_bps()?;
+        (loss >= HONEYPOT_TAX_BPS).then(|| {
+            format!(
+                "a round trip loses {} to taxes (buy tax {}, sell tax {})",
+                format_bps(loss),
+                format_bps(self.buy_tax_bps.unwrap_or_default()),
+                format_bps(self.sell_tax_bps.unwrap_or_default())
+            )
+        })
+    }
+
+    /// Measure the buy and sell taxes independently by buying the token with
+    /// `amount` of ETH for a fresh account through the first route and
+    /// selling it all back. The buy tax is what the account receives less
+    /// than the pair sends, and the sell tax is what the pair receives less
+    /// than the account sends. The VM state is restored afterwards, and the
+    /// pairs blocking the transfers are recorded.
+    pub fn measure_taxes<VS, CI, SC>(
+        &mut self,
+        amount: EVMU256,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+    ) where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        if self.is_weth {
+            return;
+        }
+        // taxes are taken on transfers from / to the pair next to the token
+        let Some(path) = self.select_path(&[0], None) else {
+            return;
+        };
+        let PairContextTy::Uniswap(pair) = &path.route[0] else {
+            return;
+        };
+        let token = pair.borrow().in_token_address;
+        let route = path.pair_addresses();
+
+        let tax_bps = |sent: EVMU256, received: EVMU256| {
+            if sent == EVMU256::ZERO || received >= sent {
+                0
+            } else {
+                ((sent - received) * EVMU256::from(10000) / sent).as_limbs()[0]
+            }
+        };
+        let backup = vm.host.evmstate.clone();
+        let account = generate_random_address(state);
+        let balance_before = v2_transformer::balance_of(&token, &account, state, vm).unwrap_or_default();
+        unsafe {
+            v2_transformer::VALIDATING_ROUTES = true;
+        }
+
+        // the pair of the token is the last hop of a buy
+        vm.host.evmstate.swap_impacts.clear();
+        if self.buy(amount, account, state, vm, &[0], Some(&route)).is_some() &&
+            let Some(sent) = vm.host.evmstate.swap_impacts.last().map(|impact| impact.amount_out) &&
+            let Some(balance) = v2_transformer::balance_of(&token, &account, state, vm)
+        {
+            let bought = balance.saturating_sub(balance_before);
+            self.buy_tax_bps = Some(tax_bps(sent, bought));
+
+            // and the first hop of a sell
+            vm.host.evmstate.swap_impacts.clear();
+            if self.sell(bought, account, state, vm, &[0], Some(&route)).is_some() &&
+                let Some(received) = vm.host.evmstate.swap_impacts.first().map(|impact| impact.amount_in)
+            {
+                self.sell_tax_bps = Some(tax_bps(bought, received));
+            }
+        }
+        unsafe {
+            v2_transformer::VALIDATING_ROUTES = false;
+        }
+        vm.host.evmstate = backup;
+    }
+
     pub fn buy<VS, CI, SC>(
         &self,
         amount_in: EVMU256,
@@ -152,7 +695,8 @@ impl TokenContext {
         state: &mut EVMFuzzState,
         vm: &mut EVMExecutor<VS, CI, SC>,
         seed: &[u8],
-    ) -> Option<()>
+        pinned_route: Option<&[EVMAddress]>,
+    ) -> Option<Vec<HopResult>>
     where
         VS: VMStateT + Default + 'static,
         CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
@@ -160,84 +704,26 @@ impl TokenContext {
     {
         if self.is_weth {
             let ctx = &self.swaps[0].route[0];
-            if let PairContextTy::Weth(ctx) = ctx {
-                ctx.deref().borrow_mut().transform(&to, &to, amount_in, state, vm, true);
-            } else {
+            let PairContextTy::Weth(weth) = ctx else {
                 panic!("Invalid weth context");
-            }
+            };
+            weth.deref()
+                .borrow_mut()
+                .transform(&to, &to, amount_in, state, vm, true);
+            Some(vec![HopResult {
+                pair: ctx.address(),
+                amount_in,
+                amount_out: amount_in,
+                receiver: to,
+            }])
         } else {
-            if self.swaps.is_empty() {
-                return None;
-            }
-            let mut current_amount_in = amount_in;
-            let mut current_sender = None;
-            let path_ctx = &self.swaps[seed[0] as usize % self.swaps.len()];
-            let path_len = path_ctx.route.len();
-            for (nth, pair) in path_ctx.route.iter().rev().enumerate() {
-                let is_final = nth == path_len - 1;
-
-                let next = if is_final {
-                    to
-                } else {
-                    match &path_ctx.route[path_len - nth - 2] {
-                        PairContextTy::Uniswap(ctx) => ctx.borrow().pair_address,
-                        PairContextTy::Weth(_ctx) => panic!("Invalid weth context"),
-                    }
-                };
-
-                match pair {
-                    PairContextTy::Uniswap(ctx) => {
-                        #[cfg(test)]
-                        {
-                            println!("======== Uniswap ========");
-                            println!("pair = {:?}", ctx.borrow().pair_address);
-                            println!(
-                                "{:?} => {:?} ({}/{:?})",
-                                current_sender, next, current_amount_in, current_amount_in
-                            );
-                        }
-                        if let Some((receiver, amount)) = ctx.deref().borrow_mut().transform(
-                            &current_sender.unwrap(),
-                            &next,
-                            current_amount_in,
-                            state,
-                            vm,
-                            true,
-                        ) {
-                            #[cfg(test)]
-                            {
-                                println!("Hop out = {}/{:?}", amount, amount);
-                            }
-                            current_amount_in = amount;
-                            current_sender = Some(receiver);
-                        } else {
-                            #[cfg(test)]
-                            {
-                                println!("!!! Uniswap Failed !!!");
-                            }
-                            return None;
-                        }
-                    }
-                    PairContextTy::Weth(ctx) => {
-                        #[cfg(test)]
-                        {
-                            println!("======== Weth ========");
-                            println!(
-                                "{:?} => {:?} ({}/{:?})",
-                                current_sender, next, current_amount_in, current_amount_in
-                            );
-                        }
-                        assert!(current_sender.is_none());
-                        ctx.deref()
-                            .borrow_mut()
-                            .transform(&to, &next, amount_in, state, vm, true)
-                            .expect("Weth failed");
-                        current_sender = Some(to);
-                    }
-                }
-            }
+            let path_ctx = self.select_path(seed, pinned_route)?;
+            vm.host
+                .evmstate
+                .swap_routes
+                .insert(String::from(SwapType::Buy), path_ctx.pair_addresses());
+            simulate_path(&path_ctx.route, amount_in, SwapDirection::Buy { to }, state, vm)
         }
-        Some(())
     }
 
     // swapExactTokensForETHSupportingFeeOnTransferTokens
@@ -248,131 +734,431 @@ impl TokenContext {
         state: &mut EVMFuzzState,
         vm: &mut EVMExecutor<VS, CI, SC>,
         seed: &[u8],
-    ) -> Option<()>
+        pinned_route: Option<&[EVMAddress]>,
+    ) -> Option<Vec<HopResult>>
     where
         VS: VMStateT + Default + 'static,
         CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
         SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
     {
         if self.is_weth {
-            if let PairContextTy::Weth(ctx) = &self.swaps[0].route[0] {
-                ctx.deref()
-                    .borrow_mut()
-                    .transform(&src, &EVMAddress::zero(), amount_in, state, vm, false)
-                    .map(|_| ());
-            } else {
+            let ctx = &self.swaps[0].route[0];
+            let PairContextTy::Weth(weth) = ctx else {
                 panic!("Invalid weth context");
-            }
+            };
+            // the seller holds the WETH and unwraps it itself
+            weth.deref()
+                .borrow_mut()
+                .transform(&src, &src, amount_in, state, vm, false);
+            Some(vec![HopResult {
+                pair: ctx.address(),
+                amount_in,
+                amount_out: amount_in,
+                receiver: src,
+            }])
+        } else {
+            let path_ctx = self.select_path(seed, pinned_route)?;
+            vm.host
+                .evmstate
+                .swap_routes
+                .insert(String::from(SwapType::Sell), path_ctx.pair_addresses());
+            simulate_path(&path_ctx.route, amount_in, SwapDirection::Sell { from: src }, state, vm)
+        }
+    }
+}
+
+/// Direction of a swap through a route, see [`simulate_path`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapDirection {
+    /// ETH for the token, sent to `to`. Routes lead from the token to WETH,
+    /// so they are walked from the last hop.
+    Buy { to: EVMAddress },
+    /// The token held by `from` for ETH
+    Sell { from: EVMAddress },
+}
+
+/// Where the output of a hop of a sell goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Destination {
+    /// The output is unwrapped to ETH for the account, i.e., it is the last
+    /// hop or the next hop is WETH
+    NativeRecipient(EVMAddress),
+    /// The output is swapped further by the next pair or pool of the route
+    Pair(EVMAddress),
+}
+
+impl Destination {
+    /// Destination of the output of the hop `nth` of a sell of `seller`
+    /// through `route`. The hop before WETH sends the WETH to the seller, so
+    /// that it is the seller unwrapping it.
+    pub fn of_sell_hop(route: &[PairContextTy], nth: usize, seller: EVMAddress) -> Self {
+        match route.get(nth + 1) {
+            None | Some(PairContextTy::Weth(_)) => Destination::NativeRecipient(seller),
+            Some(hop) => Destination::Pair(hop.address()),
+        }
+    }
+
+    pub fn address(&self) -> EVMAddress {
+        match self {
+            Destination::NativeRecipient(address) | Destination::Pair(address) => *address,
+        }
+    }
+}
+
+/// Amounts swapped by a hop of a route, in swap order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HopResult {
+    /// Pair, pool or WETH contract of the hop
+    pub pair: EVMAddress,
+    pub amount_in: EVMU256,
+    pub amount_out: EVMU256,
+    /// Where the output of the hop is sent
+    pub receiver: EVMAddress,
+}
+
+/// Swap `amount_in` through `route` on the VM's current state, hop by hop,
+/// the way [`TokenContext::buy`] and [`TokenContext::sell`] do once a route
+/// is selected. Returns the hops in swap order, `None` if a hop fails (the
+/// state is then left as it is after the failed hop).
+pub fn simulate_path<VS, CI, SC>(
+    route: &[PairContextTy],
+    amount_in: EVMU256,
+    direction: SwapDirection,
+    state: &mut EVMFuzzState,
+    vm: &mut EVMExecutor<VS, CI, SC>,
+) -> Option<Vec<HopResult>>
+where
+    VS: VMStateT + Default + 'static,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+    SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+{
+    match direction {
+        SwapDirection::Buy { to } => simulate_buy(route, amount_in, to, state, vm),
+        SwapDirection::Sell { from } => simulate_sell(route, amount_in, from, state, vm),
+    }
+}
+
+fn simulate_buy<VS, CI, SC>(
+    route: &[PairContextTy],
+    amount_in: EVMU256,
+    to: EVMAddress,
+    state: &mut EVMFuzzState,
+    vm: &mut EVMExecutor<VS, CI, SC>,
+) -> Option<Vec<HopResult>>
+where
+    VS: VMStateT + Default + 'static,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+    SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+{
+    let mut hops = vec![];
+    let mut current_amount_in = amount_in;
+    let mut current_sender = None;
+    let path_len = route.len();
+    for (nth, pair) in route.iter().rev().enumerate() {
+        let is_final = nth == path_len - 1;
+
+        let next = if is_final {
+            to
         } else {
-            if self.swaps.is_empty() {
-                return None;
+            match &route[path_len - nth - 2] {
+                PairContextTy::Weth(_ctx) => panic!("Invalid weth context"),
+                hop => hop.address(),
+            }
+        };
+
+        match pair {
+            PairContextTy::Uniswap(ctx) => {
+                #[cfg(test)]
+                {
+                    println!("======== Uniswap ========");
+                    println!("pair = {:?}", ctx.borrow().pair_address);
+                    println!(
+                        "{:?} => {:?} ({}/{:?})",
+                        current_sender, next, current_amount_in, current_amount_in
+                    );
+                }
+                let swapped =
+                    ctx.borrow()
+                        .transform(&current_sender.unwrap(), &next, current_amount_in, state, vm, true);
+                if let Some((receiver, amount)) = swapped {
+                    #[cfg(test)]
+                    {
+                        println!("Hop out = {}/{:?}", amount, amount);
+                    }
+                    hops.push(HopResult {
+                        pair: pair.address(),
+                        amount_in: current_amount_in,
+                        amount_out: amount,
+                        receiver,
+                    });
+                    current_amount_in = amount;
+                    current_sender = Some(receiver);
+                } else {
+                    record_swap_failure(&ctx.borrow(), state);
+                    return None;
+                }
             }
-            let mut current_amount_in = amount_in;
-            let mut current_sender = src;
-            let path_ctx = &self.swaps[seed[0] as usize % self.swaps.len()];
-            let mut is_first = true;
-            let path_len = path_ctx.route.len();
-            for (nth, pair) in path_ctx.route.iter().enumerate() {
-                let is_final = nth == path_len - 1;
-                let next = if is_final {
-                    EVMAddress::zero()
+            PairContextTy::MultiPool(ctx) => {
+                #[cfg(test)]
+                {
+                    println!("======== MultiPool ========");
+                    println!("pool = {:?}", ctx.borrow().pool_address);
+                    println!(
+                        "{:?} => {:?} ({}/{:?})",
+                        current_sender, next, current_amount_in, current_amount_in
+                    );
+                }
+                if let Some((receiver, amount)) = ctx.deref().borrow_mut().transform(
+                    &current_sender.unwrap(),
+                    &next,
+                    current_amount_in,
+                    state,
+                    vm,
+                    true,
+                ) {
+                    hops.push(HopResult {
+                        pair: pair.address(),
+                        amount_in: current_amount_in,
+                        amount_out: amount,
+                        receiver,
+                    });
+                    current_amount_in = amount;
+                    current_sender = Some(receiver);
                 } else {
-                    match &path_ctx.route[nth + 1] {
-                        PairContextTy::Uniswap(ctx) => ctx.borrow().pair_address,
-                        PairContextTy::Weth(_ctx) => state.get_rand_caller(),
+                    #[cfg(test)]
+                    {
+                        println!("!!! MultiPool Failed !!!");
                     }
-                };
-                match pair {
-                    PairContextTy::Uniswap(ctx) => {
-                        #[cfg(test)]
-                        {
-                            println!("======== Uniswap ========");
-                            println!("pair = {:?}", ctx.borrow().pair_address);
-                            println!(
-                                "{:?} => {:?} ({}/{:?})",
-                                current_sender, next, current_amount_in, current_amount_in
-                            );
-                        }
-
-                        let pair_address = ctx.deref().borrow_mut().pair_address;
-
-                        if is_first {
-                            ctx.deref().borrow_mut().initial_transfer(
-                                &current_sender,
-                                &pair_address,
-                                current_amount_in,
-                                state,
-                                vm,
-                            );
-                            is_first = false;
-                        }
-
-                        if let Some((receiver, amount)) = ctx.deref().borrow_mut().transform(
-                            &current_sender,
-                            &next,
-                            current_amount_in,
-                            state,
-                            vm,
-                            false,
-                        ) {
-                            #[cfg(test)]
-                            {
-                                println!("Hop out = {}/{:?}", amount, amount);
-                            }
-                            current_amount_in = amount;
-                            current_sender = receiver;
-                        } else {
-                            #[cfg(test)]
-                            {
-                                println!("!!! Uniswap Failed !!!");
-                            }
-                            return None;
-                        }
+                    return None;
+                }
+            }
+            PairContextTy::Weth(ctx) => {
+                #[cfg(test)]
+                {
+                    println!("======== Weth ========");
+                    println!(
+                        "{:?} => {:?} ({}/{:?})",
+                        current_sender, next, current_amount_in, current_amount_in
+                    );
+                }
+                assert!(current_sender.is_none());
+                ctx.deref()
+                    .borrow_mut()
+                    .transform(&to, &next, amount_in, state, vm, true)
+                    .expect("Weth failed");
+                // wrapped 1:1
+                hops.push(HopResult {
+                    pair: pair.address(),
+                    amount_in,
+                    amount_out: amount_in,
+                    receiver: next,
+                });
+                current_sender = Some(to);
+            }
+        }
+    }
+    Some(hops)
+}
+
+fn simulate_sell<VS, CI, SC>(
+    route: &[PairContextTy],
+    amount_in: EVMU256,
+    src: EVMAddress,
+    state: &mut EVMFuzzState,
+    vm: &mut EVMExecutor<VS, CI, SC>,
+) -> Option<Vec<HopResult>>
+where
+    VS: VMStateT + Default + 'static,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+    SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+{
+    let mut hops = vec![];
+    let mut current_amount_in = amount_in;
+    let mut current_sender = src;
+    let mut is_first = true;
+    for (nth, pair) in route.iter().enumerate() {
+        let destination = Destination::of_sell_hop(route, nth, src);
+        let next = destination.address();
+        match pair {
+            PairContextTy::Uniswap(ctx) => {
+                #[cfg(test)]
+                {
+                    println!("======== Uniswap ========");
+                    println!("pair = {:?}", ctx.borrow().pair_address);
+                    println!(
+                        "{:?} => {:?} ({}/{:?})",
+                        current_sender, next, current_amount_in, current_amount_in
+                    );
+                }
+
+                let pair_address = ctx.deref().borrow_mut().pair_address;
+
+                if is_first {
+                    let transferred = ctx.deref().borrow_mut().initial_transfer(
+                        &current_sender,
+                        &pair_address,
+                        current_amount_in,
+                        state,
+                        vm,
+                    );
+                    if transferred.is_none() {
+                        record_swap_failure(&ctx.borrow(), state);
+                        return None;
                     }
-                    PairContextTy::Weth(ctx) => {
-                        #[cfg(test)]
-                        {
-                            assert!(!is_first);
-                            println!("======== Weth ========");
-                            println!(
-                                "{:?} => {:?} ({}/{:?})",
-                                current_sender, next, current_amount_in, current_amount_in
-                            );
-                        }
-                        ctx.deref()
-                            .borrow_mut()
-                            .transform(&current_sender, &next, current_amount_in, state, vm, false)
-                            .expect("Weth failed");
+                    is_first = false;
+                }
+
+                let swapped = ctx
+                    .borrow()
+                    .transform(&current_sender, &next, current_amount_in, state, vm, false);
+                if let Some((receiver, amount)) = swapped {
+                    #[cfg(test)]
+                    {
+                        println!("Hop out = {}/{:?}", amount, amount);
+                    }
+                    hops.push(HopResult {
+                        pair: pair_address,
+                        amount_in: current_amount_in,
+                        amount_out: amount,
+                        receiver,
+                    });
+                    current_amount_in = amount;
+                    current_sender = receiver;
+                } else {
+                    record_swap_failure(&ctx.borrow(), state);
+                    return None;
+                }
+            }
+            PairContextTy::MultiPool(ctx) => {
+                #[cfg(test)]
+                {
+                    println!("======== MultiPool ========");
+                    println!("pool = {:?}", ctx.borrow().pool_address);
+                    println!(
+                        "{:?} => {:?} ({}/{:?})",
+                        current_sender, next, current_amount_in, current_amount_in
+                    );
+                }
+
+                if is_first {
+                    ctx.deref()
+                        .borrow_mut()
+                        .initial_transfer(&current_sender, current_amount_in, state, vm)?;
+                    is_first = false;
+                }
+
+                if let Some((receiver, amount)) =
+                    ctx.deref()
+                        .borrow_mut()
+                        .transform(&current_sender, &next, current_amount_in, state, vm, false)
+                {
+                    hops.push(HopResult {
+                        pair: pair.address(),
+                        amount_in: current_amount_in,
+                        amount_out: amount,
+                        receiver,
+                    });
+                    current_amount_in = amount;
+                    current_sender = receiver;
+                } else {
+                    #[cfg(test)]
+                    {
+                        println!("!!! MultiPool Failed !!!");
                     }
+                    return None;
                 }
             }
+            PairContextTy::Weth(ctx) => {
+                #[cfg(test)]
+                {
+                    assert!(!is_first);
+                    println!("======== Weth ========");
+                    println!(
+                        "{:?} => {:?} ({}/{:?})",
+                        current_sender, next, current_amount_in, current_amount_in
+                    );
+                }
+                let Destination::NativeRecipient(recipient) = destination else {
+                    panic!("WETH is not the last hop of the sell");
+                };
+                // the previous hop sent the WETH to the recipient, which unwraps it
+                assert_eq!(current_sender, recipient);
+                ctx.deref()
+                    .borrow_mut()
+                    .transform(&recipient, &recipient, current_amount_in, state, vm, false)
+                    .expect("Weth failed");
+                // unwrapped 1:1
+                hops.push(HopResult {
+                    pair: pair.address(),
+                    amount_in: current_amount_in,
+                    amount_out: current_amount_in,
+                    receiver: recipient,
+                });
+            }
         }
-        Some(())
     }
+    Some(hops)
+}
+
+/// Log why the last swap through `pair` failed and keep it in the
+/// [`SwapDiscoveryMetadata`]
+fn record_swap_failure(pair: &v2_transformer::UniswapPairContext, state: &mut EVMFuzzState) {
+    let Some(failure) = pair.last_failure.take() else {
+        return;
+    };
+    debug!("swap through pair {:?} failed: {}", pair.pair_address, failure);
+    state
+        .metadata_map_mut()
+        .get_or_insert_with(SwapDiscoveryMetadata::default)
+        .record_failure(pair.pair_address, failure);
 }
 
 pub fn get_uniswap_info(provider: &UniswapProvider, chain: &Chain) -> UniswapInfo {
-    match (provider, chain) {
+    try_get_uniswap_info(provider, chain)
+        .unwrap_or_else(|| panic!("Uniswap provider {:?} @ chain {:?} not supported", provider, chain))
+}
+
+/// Same as [`get_uniswap_info`], but returns `None` for unsupported
+/// provider / chain combinations
+pub fn try_get_uniswap_info(provider: &UniswapProvider, chain: &Chain) -> Option<UniswapInfo> {
+    Some(match (provider, chain) {
         (&UniswapProvider::UniswapV2, &Chain::BSC) => UniswapInfo {
             pool_fee: 25,
             router: EVMAddress::from_str("0x10ed43c718714eb63d5aa57b78b54704e256024e").unwrap(),
             factory: EVMAddress::from_str("0xca143ce32fe78f1f7019d7d551a6402fc5350c73").unwrap(),
             init_code_hash: hex::decode("00fb7f630766e6a796048ea87d01acd3068e8ff67d078148a3fa3f4a84f69bd5").unwrap(),
+            pair_lookup: PairLookup::Create2,
         },
         (&UniswapProvider::PancakeSwap, &Chain::BSC) => UniswapInfo {
             pool_fee: 25,
             router: EVMAddress::from_str("0x10ed43c718714eb63d5aa57b78b54704e256024e").unwrap(),
             factory: EVMAddress::from_str("0xca143ce32fe78f1f7019d7d551a6402fc5350c73").unwrap(),
             init_code_hash: hex::decode("00fb7f630766e6a796048ea87d01acd3068e8ff67d078148a3fa3f4a84f69bd5").unwrap(),
+            pair_lookup: PairLookup::Create2,
         },
         (&UniswapProvider::UniswapV2, &Chain::ETH) => UniswapInfo {
             pool_fee: 30,
             router: EVMAddress::from_str("0x7a250d5630b4cf539739df2c5dacb4c659f2488d").unwrap(),
             factory: EVMAddress::from_str("0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f").unwrap(),
             init_code_hash: hex::decode("96e8ac4277198ff8b6f785478aa9a39f403cb768dd02cbee326c3e7da348845f").unwrap(),
+            pair_lookup: PairLookup::Create2,
         },
-        _ => panic!("Uniswap provider {:?} @ chain {:?} not supported", provider, chain),
-    }
+        // pairs are looked up with the factory, no init code hash needed
+        (&UniswapProvider::SushiSwap, &Chain::ETH) => UniswapInfo {
+            pool_fee: 30,
+            router: EVMAddress::from_str("0xd9e1ce17f2641f24ae83637ab66a2cca9c378b9f").unwrap(),
+            factory: EVMAddress::from_str("0xc0aee478e3658e2610c5f7a4a2e1777ce9e4f2ac").unwrap(),
+            init_code_hash: vec![],
+            pair_lookup: PairLookup::GetPair,
+        },
+        _ => return None,
+    })
+}
+
+lazy_static! {
+    /// Swap functions registered with [`SwapData::register_selector`], with
+    /// their type and the index of their path argument
+    static ref CUSTOM_SWAP_SELECTORS: RwLock<HashMap<[u8; 4], (SwapType, usize)>> = RwLock::new(HashMap::new());
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
@@ -385,14 +1171,37 @@ impl SwapData {
         Default::default()
     }
 
+    /// Recognize the calls to `selector` as swaps of type `ty`, besides the
+    /// router functions, e.g., the swap entrypoint of a reflection token.
+    /// The token path is the `path_index`-th argument, which is ignored for
+    /// deposits and withdrawals.
+    pub fn register_selector(selector: [u8; 4], ty: SwapType, path_index: usize) {
+        CUSTOM_SWAP_SELECTORS
+            .write()
+            .unwrap()
+            .insert(selector, (ty, path_index));
+    }
+
     pub fn push(&mut self, addr: &EVMAddress, abi: &mut BoxedABI) {
         if let Some(new) = SwapInfo::try_new(addr, abi) {
-            // swap_infos with same type will be merged
-            if let hash_map::Entry::Vacant(e) = self.inner.entry(new.ty) {
-                e.insert(new);
-            } else {
-                self.inner.get_mut(&new.ty).unwrap().concat_path(new.path);
-            }
+            self.insert(new);
+        }
+    }
+
+    /// Same as [`SwapData::push`], but for swaps through an aggregator, which
+    /// are decoded from the raw calldata
+    pub fn push_calldata(&mut self, addr: &EVMAddress, calldata: &[u8]) {
+        if let Some(swap) = AggregatorSwap::decode(calldata) {
+            self.insert(swap.to_swap_info(addr));
+        }
+    }
+
+    fn insert(&mut self, new: SwapInfo) {
+        // swap_infos with same type will be merged
+        if let hash_map::Entry::Vacant(e) = self.inner.entry(new.ty) {
+            e.insert(new);
+        } else {
+            self.inner.get_mut(&new.ty).unwrap().concat_path(new.path);
         }
     }
 
@@ -412,6 +1221,22 @@ pub enum SwapType {
     Buy,
     Withdraw,
     Sell,
+    /// Token to token swap, e.g., through an aggregator
+    Swap,
+}
+
+impl FromStr for SwapType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deposit" => Ok(Self::Deposit),
+            "buy" => Ok(Self::Buy),
+            "withdraw" => Ok(Self::Withdraw),
+            "sell" => Ok(Self::Sell),
+            "swap" => Ok(Self::Swap),
+            _ => Err(format!("unknown swap type: {}", s)),
+        }
+    }
 }
 
 impl From<SwapType> for String {
@@ -421,6 +1246,7 @@ impl From<SwapType> for String {
             SwapType::Buy => "buy".to_string(),
             SwapType::Withdraw => "withdraw".to_string(),
             SwapType::Sell => "sell".to_string(),
+            SwapType::Swap => "swap".to_string(),
         }
     }
 }
@@ -435,28 +1261,33 @@ pub struct SwapInfo {
 impl SwapInfo {
     pub fn try_new(target: &EVMAddress, abi: &mut BoxedABI) -> Option<Self> {
         let get_path = |abi: &mut BoxedABI, idx: usize| -> Option<Vec<String>> {
-            if let Some(args) = abi.b.as_any().downcast_mut::<AArray>() {
-                let path = args.data[idx]
-                    .b
-                    .as_any()
-                    .downcast_ref::<AArray>()
-                    .unwrap()
-                    .data
-                    .iter()
-                    .map(|x| x.b.to_string())
-                    .collect::<Vec<_>>();
-                Some(path)
-            } else {
-                None
-            }
+            let args = abi.b.as_any().downcast_mut::<AArray>()?;
+            let path = args
+                .data
+                .get(idx)?
+                .b
+                .as_any()
+                .downcast_ref::<AArray>()?
+                .data
+                .iter()
+                .map(|x| x.b.to_string())
+                .collect::<Vec<_>>();
+            Some(path)
         };
 
         let (ty, path) = match abi.function {
-            SWAP_BUY => (SwapType::Buy, get_path(abi, 1)),
-            SWAP_SELL => (SwapType::Sell, get_path(abi, 2)),
+            SWAP_BUY | SWAP_EXACT_ETH_FOR_TOKENS => (SwapType::Buy, get_path(abi, 1)),
+            SWAP_SELL | SWAP_EXACT_TOKENS_FOR_ETH | SWAP_TOKENS_FOR_EXACT_ETH => (SwapType::Sell, get_path(abi, 2)),
+            SWAP_EXACT_TOKENS_FOR_TOKENS => (SwapType::Swap, get_path(abi, 2)),
             SWAP_DEPOSIT => (SwapType::Deposit, Some(vec![])),
             SWAP_WITHDRAW => (SwapType::Withdraw, Some(vec![])),
-            _ => return None,
+            selector => {
+                let (ty, path_index) = *CUSTOM_SWAP_SELECTORS.read().unwrap().get(&selector)?;
+                match ty {
+                    SwapType::Deposit | SwapType::Withdraw => (ty, Some(vec![])),
+                    _ => (ty, get_path(abi, path_index)),
+                }
+            }
         };
 
         if let Some(path) = path {
@@ -494,120 +1325,42 @@ impl From<SwapInfo> for vm_state::SwapInfo {
 
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, rc::Rc};
-
-    use libafl::{schedulers::StdScheduler, state::HasMetadata};
+    use ethers::abi::{encode, Token};
 
     use super::*;
-    use crate::{
-        evm::{
-            abi::ABIAddressToInstanceMap,
-            config::StorageFetchingMode,
-            corpus_initializer::EnvMetadata,
-            host::FuzzHost,
-            input::ConciseEVMInput,
-            onchain::{
-                endpoints::{Chain, OnChainConfig},
-                OnChain,
-            },
-            oracles::v2_pair::reserve_parser,
-            tokens::uniswap::{fetch_uniswap_path, CODE_REGISTRY},
-            types::{generate_random_address, EVMAddress, EVMFuzzState, EVMU256},
-            vm::{EVMExecutor, EVMState},
-        },
-        state::{FuzzState, HasCaller},
+    use crate::evm::{
+        abi::get_abi_type_boxed,
+        onchain::endpoints::Chain,
+        tokens::testing::{trade, TradeDirection},
+        types::{EVMAddress, EVMU256},
     };
 
-    macro_rules! wrap {
-        ($x: expr) => {
-            Rc::new(RefCell::new($x))
-        };
-    }
-
-    fn trade(direction: &str, token: EVMAddress, amount: EVMU256, nth: usize, block: u64, src: &EVMAddress) {
-        let mut state = FuzzState::new(0);
-        let dummy_caller = generate_random_address(&mut state);
-        state.add_caller(&dummy_caller);
-        state
-            .metadata_map_mut()
-            .insert::<ABIAddressToInstanceMap>(ABIAddressToInstanceMap::new());
-        state.metadata_map_mut().insert::<EnvMetadata>(EnvMetadata::default());
-
-        let mut fuzz_host = FuzzHost::new(StdScheduler::new(), "work_dir".to_string());
-
-        let mut onchain = OnChainConfig::new(Chain::ETH, block);
-        let onchain_mid = OnChain::new(onchain.clone(), StorageFetchingMode::OneByOne);
-        let onchain_mid_ptr = wrap!(onchain_mid);
-        fuzz_host.add_middlewares(onchain_mid_ptr);
-        let vm_state = EVMState::default();
-
-        fuzz_host.evmstate = vm_state;
-
-        CODE_REGISTRY
-            .lock()
-            .unwrap()
-            .insert(token, onchain.get_contract_code_analyzed(token, false));
-
-        let token_ctx = fetch_uniswap_path(&mut onchain, token);
-
-        println!("======== Token Swaps ========");
-        token_ctx.swaps.iter().for_each(|x| {
-            println!("route: {:?}", x.route.iter());
-        });
-        println!("selected route: {:?}", token_ctx.swaps[nth].route);
-
-        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> =
-            EVMExecutor::new(fuzz_host, generate_random_address(&mut state));
-
-        let res = if direction == "buy" {
-            token_ctx.buy(
-                amount,
-                generate_random_address(&mut state),
-                &mut state,
-                &mut evm_executor,
-                &[nth as u8],
-            )
-        } else {
-            token_ctx.sell(amount, *src, &mut state, &mut evm_executor, &[nth as u8])
-        };
-
-        if res.is_none() {
-            println!("failed");
-            return;
-        }
-
-        let result_state = evm_executor.host.evmstate;
-
-        // print reserve change
-        println!("======== Reserve Changes ========");
-
-        token_ctx.swaps[nth].route.iter().for_each(|x| match x {
-            PairContextTy::Uniswap(ctx) => {
-                let pair_addr = ctx.borrow().pair_address;
-                let (r0, r1) = reserve_parser(&result_state.state[&pair_addr][&EVMU256::from(8)]);
-                println!(
-                    "{:?} ({}, {}) => ({}, {}), slot = {:?}",
-                    ctx.borrow().pair_address,
-                    ctx.borrow().initial_reserves.0,
-                    ctx.borrow().initial_reserves.1,
-                    r0,
-                    r1,
-                    result_state.state[&pair_addr][&EVMU256::from(8)]
-                );
-            }
-            _ => {}
-        });
-
-        // print flashloan data
-        println!("======== Flashloan Data ========");
-        println!(
-            "owed: {}/{:?}",
-            result_state.flashloan_data.owed, result_state.flashloan_data.owed
+    #[test]
+    fn test_swap_impact() {
+        let pair = EVMAddress::zero();
+        // spot rate is 1:2, so 100 in is worth 200 out
+        let impact = SwapImpact::new(
+            pair,
+            EVMU256::from(100),
+            EVMU256::from(150),
+            EVMU256::from(1000),
+            EVMU256::from(2000),
         );
-        println!(
-            "earned: {}/{:?}",
-            result_state.flashloan_data.earned, result_state.flashloan_data.earned
+        assert_eq!(impact.impact_bps, 2500);
+        assert!(impact.to_string().ends_with("(price impact 25.00%)"));
+
+        let no_impact = SwapImpact::new(
+            pair,
+            EVMU256::from(100),
+            EVMU256::from(200),
+            EVMU256::from(1000),
+            EVMU256::from(2000),
         );
+        assert_eq!(no_impact.impact_bps, 0);
+    }
+
+    fn trade_eth(direction: TradeDirection, token: EVMAddress, amount: EVMU256, route: usize) {
+        println!("{}", trade(Chain::ETH, 19044110, direction, token, amount, route));
     }
 
     #[test]
@@ -615,7 +1368,7 @@ mod tests {
         let token = EVMAddress::from_str("0xf3ae5d769e153ef72b4e3591ac004e89f48107a1").unwrap();
         let amount = EVMU256::from_str("2000000000000000000").unwrap();
         // dpr => weth
-        trade("buy", token, amount, 1, 19044110, &EVMAddress::zero());
+        trade_eth(TradeDirection::Buy, token, amount, 1);
     }
 
     const DPR_RICH: &str = "0x1959f0401e101620dd7e2ab5456f4b4a6e289aaf";
@@ -625,14 +1378,8 @@ mod tests {
         let token = EVMAddress::from_str("0xf3ae5d769e153ef72b4e3591ac004e89f48107a1").unwrap();
         let amount = EVMU256::from_str("20000000000000000000000").unwrap();
         // dpr => weth
-        trade(
-            "sell",
-            token,
-            amount,
-            1,
-            19044110,
-            &EVMAddress::from_str(DPR_RICH).unwrap(),
-        );
+        let from = EVMAddress::from_str(DPR_RICH).unwrap();
+        trade_eth(TradeDirection::Sell { from }, token, amount, 1);
     }
 
     #[test]
@@ -640,7 +1387,62 @@ mod tests {
         let token = EVMAddress::from_str("0xf3ae5d769e153ef72b4e3591ac004e89f48107a1").unwrap();
         let amount = EVMU256::from_str("2000000000000000000").unwrap();
         // dpr => usdc => weth
-        trade("buy", token, amount, 0, 19044110, &EVMAddress::zero());
+        trade_eth(TradeDirection::Buy, token, amount, 0);
+    }
+
+    #[test]
+    fn test_simulate_path_hops() {
+        let token = EVMAddress::from_str("0xf3ae5d769e153ef72b4e3591ac004e89f48107a1").unwrap();
+        let amount = EVMU256::from_str("2000000000000000000").unwrap();
+        // dpr => usdc => weth
+        let outcome = trade(Chain::ETH, 19044110, TradeDirection::Buy, token, amount, 0);
+        assert!(outcome.state.is_some());
+        assert_eq!(outcome.hops.first().map(|hop| hop.amount_in), Some(amount));
+        // each hop swaps what the previous one sent
+        for hops in outcome.hops.windows(2) {
+            assert_eq!(hops[0].amount_out, hops[1].amount_in);
+            assert_eq!(hops[0].receiver, hops[1].pair);
+        }
+    }
+
+    #[test]
+    fn test_sell_destinations() {
+        let seller = EVMAddress::from_slice(&[1; 20]);
+        let pair = |address: u8| {
+            PairContextTy::Uniswap(Rc::new(RefCell::new(v2_transformer::UniswapPairContext {
+                pair_address: EVMAddress::from_slice(&[address; 20]),
+                ..Default::default()
+            })))
+        };
+        let weth = PairContextTy::Weth(Rc::new(RefCell::new(weth_transformer::WethContext {
+            weth_address: EVMAddress::from_slice(&[9; 20]),
+        })));
+        // token => pair 2 => pair 3 => WETH => ETH
+        let route = vec![pair(2), pair(3), weth];
+        assert_eq!(
+            Destination::of_sell_hop(&route, 0, seller),
+            Destination::Pair(EVMAddress::from_slice(&[3; 20]))
+        );
+        assert_eq!(
+            Destination::of_sell_hop(&route, 1, seller),
+            Destination::NativeRecipient(seller)
+        );
+        assert_eq!(
+            Destination::of_sell_hop(&route, 2, seller),
+            Destination::NativeRecipient(seller)
+        );
+    }
+
+    #[test]
+    fn test_sell_unwraps_to_seller() {
+        let token = EVMAddress::from_str("0xf3ae5d769e153ef72b4e3591ac004e89f48107a1").unwrap();
+        let amount = EVMU256::from_str("20000000000000000000000").unwrap();
+        // dpr => weth => ETH
+        let from = EVMAddress::from_str(DPR_RICH).unwrap();
+        let outcome = trade(Chain::ETH, 19044110, TradeDirection::Sell { from }, token, amount, 1);
+        assert!(outcome.state.is_some());
+        // the WETH goes to the seller, which unwraps it
+        assert!(outcome.hops.iter().rev().take(2).all(|hop| hop.receiver == from));
     }
 
     // https://www.tdly.co/shared/simulation/c1d5d70f-8718-4740-961a-3f789a0834c1
@@ -649,7 +1451,7 @@ mod tests {
         let token = EVMAddress::from_str("0x72e4f9F808C49A2a61dE9C5896298920Dc4EEEa9").unwrap();
         let amount = EVMU256::from_str("2000000000000000000").unwrap();
         // HarryPotterObamaSonic10Inu => weth
-        trade("buy", token, amount, 0, 19044110, &EVMAddress::zero());
+        trade_eth(TradeDirection::Buy, token, amount, 0);
     }
 
     // https://www.tdly.co/shared/simulation/83d283d4-b367-4893-85a4-4af19fc9a80b
@@ -658,7 +1460,7 @@ mod tests {
         let token = EVMAddress::from_str("0x72e4f9F808C49A2a61dE9C5896298920Dc4EEEa9").unwrap();
         let amount = EVMU256::from_str("2000000000000000000").unwrap();
         // HarryPotterObamaSonic10Inu => OSAK => weth
-        trade("buy", token, amount, 1, 19044110, &EVMAddress::zero());
+        trade_eth(TradeDirection::Buy, token, amount, 1);
     }
 
     #[test]
@@ -667,7 +1469,247 @@ mod tests {
         let token = EVMAddress::from_str("0x72e4f9F808C49A2a61dE9C5896298920Dc4EEEa9").unwrap();
         let amount = EVMU256::from_str("2000000000000000000").unwrap();
         // HarryPotterObamaSonic10Inu => weth
-        trade("buy", token, amount, 2, 19044110, &EVMAddress::zero());
+        trade_eth(TradeDirection::Buy, token, amount, 2);
+    }
+
+    #[test]
+    fn test_pair_for() {
+        // WBNB / BUSD on PancakeSwap
+        let info = get_uniswap_info(&UniswapProvider::PancakeSwap, &Chain::BSC);
+        let wbnb = EVMAddress::from_str("0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c").unwrap();
+        let busd = EVMAddress::from_str("0xe9e7cea3dedca5984780bafc599bd69add087d56").unwrap();
+        let pair = EVMAddress::from_str("0x58f876857a02d6762e0101bb5c46a8c1ed44dc16").unwrap();
+        assert_eq!(info.pair_for(wbnb, busd), pair);
+        assert_eq!(info.pair_for(busd, wbnb), pair);
+
+        // USDC / WETH and DAI / WETH on Uniswap V2
+        let info = get_uniswap_info(&UniswapProvider::UniswapV2, &Chain::ETH);
+        let weth = EVMAddress::from_str("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").unwrap();
+        let usdc = EVMAddress::from_str("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").unwrap();
+        let dai = EVMAddress::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap();
+        assert_eq!(
+            info.pair_for(weth, usdc),
+            EVMAddress::from_str("0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc").unwrap()
+        );
+        assert_eq!(
+            info.pair_for(dai, weth),
+            EVMAddress::from_str("0xa478c2975ab1ea89e8196811f51a7b7ade33eb11").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_select_path_skips_blocked() {
+        let address = |idx: u8| EVMAddress::from_slice(&[idx; 20]);
+        let reserves = (EVMU256::from(1), EVMU256::from(1));
+        let mut ctx = TokenContext::from_pairs(
+            address(1),
+            address(0x10),
+            &[(address(0x80), address(0x10), reserves)],
+            UniswapInfo::default(),
+        );
+        let other = TokenContext::from_pairs(
+            address(1),
+            address(0x10),
+            &[(address(0x81), address(0x10), reserves)],
+            UniswapInfo::default(),
+        );
+        ctx.swaps.extend(other.swaps);
+        assert!(!ctx.is_unsellable());
+
+        let block = |ctx: &TokenContext, idx: usize, block| match &ctx.swaps[idx].route[0] {
+            PairContextTy::Uniswap(pair) => pair.borrow().blocked.set(Some(block)),
+            _ => unreachable!(),
+        };
+        block(&ctx, 0, v2_transformer::TransferBlock::Pair);
+        for seed in 0..4 {
+            let path = ctx.select_path(&[seed], None).unwrap();
+            assert_eq!(path.pair_addresses(), vec![address(0x81)]);
+        }
+        // a pinned route is still replayed
+        let pinned = [address(0x80)];
+        assert_eq!(
+            ctx.select_path(&[0], Some(&pinned)).unwrap().pair_addresses(),
+            pinned.to_vec()
+        );
+
+        block(&ctx, 1, v2_transformer::TransferBlock::Caller);
+        assert!(ctx.select_path(&[0], None).is_none());
+        assert!(ctx.is_unsellable());
+    }
+
+    #[test]
+    fn test_honeypot_taxes() {
+        let address = |idx: u8| EVMAddress::from_slice(&[idx; 20]);
+        let reserves = (EVMU256::from(1), EVMU256::from(1));
+        let mut ctx = TokenContext::from_pairs(
+            address(1),
+            address(0x10),
+            &[(address(0x80), address(0x10), reserves)],
+            UniswapInfo::default(),
+        );
+        assert_eq!(ctx.round_trip_tax_bps(), None);
+        assert_eq!(ctx.honeypot_reason(), None);
+
+        // taxed on sells only, a round trip loses the sell tax
+        ctx.buy_tax_bps = Some(0);
+        ctx.sell_tax_bps = Some(1000);
+        assert_eq!(ctx.round_trip_tax_bps(), Some(1000));
+        assert_eq!(
+            ctx.honeypot_reason().unwrap(),
+            "a round trip loses 10.00% to taxes (buy tax 0.00%, sell tax 10.00%)"
+        );
+        assert!(!ctx.is_unsellable());
+
+        // 3% each way loses 5.91%
+        ctx.buy_tax_bps = Some(300);
+        ctx.sell_tax_bps = Some(300);
+        assert_eq!(ctx.round_trip_tax_bps(), Some(591));
+        assert_eq!(ctx.honeypot_reason(), None);
+
+        // everything sold is taxed
+        ctx.sell_tax_bps = Some(10000);
+        assert!(ctx.is_unsellable());
+    }
+
+    #[test]
+    fn test_token_context_display() {
+        let address = |idx: u8| EVMAddress::from_slice(&[idx; 20]);
+        let ctx = TokenContext::from_pairs(
+            address(1),
+            address(0x10),
+            &[
+                (address(0x80), address(2), (EVMU256::from(100), EVMU256::from(200))),
+                (address(0x81), address(0x10), (EVMU256::from(300), EVMU256::from(400))),
+            ],
+            UniswapInfo::default(),
+        );
+        let expected = [
+            format!("1 route(s) to WETH ({})", checksum(&address(0x10))),
+            "route #0:".to_string(),
+            format!(
+                "  1. pair {}: {} → {} (reserves 100 / 200)",
+                checksum(&address(0x80)),
+                checksum(&address(1)),
+                checksum(&address(2))
+            ),
+            format!(
+                "  2. pair {}: {} → {} (reserves 300 / 400)",
+                checksum(&address(0x81)),
+                checksum(&address(2)),
+                checksum(&address(0x10))
+            ),
+            format!("  3. unwrap {}", checksum(&address(0x10))),
+        ];
+        assert_eq!(ctx.to_string(), expected.join("\n"));
+    }
+
+    #[test]
+    fn test_format_token_amount() {
+        // 1.5 USDC
+        assert_eq!(format_token_amount(EVMU256::from(1_500_000), 6, 3), "1.500");
+        // 2 WETH
+        assert_eq!(
+            format_token_amount(EVMU256::from(2_000_000_000_000_000_000_u128), 18, 3),
+            "2.000"
+        );
+        assert_eq!(format_token_amount(EVMU256::from(12), 0, 3), "12.000");
+    }
+
+    #[test]
+    fn test_weth_action_display() {
+        let amount = EVMU256::from(1_250_000_000_000_000_000_u128);
+        assert_eq!(WethAction::Deposit(amount).to_string(), "deposit 1.250000 ETH → WETH");
+        assert_eq!(WethAction::Withdraw(amount).to_string(), "withdraw 1.250000 WETH → ETH");
+    }
+
+    #[test]
+    fn test_parse_token_amount() {
+        assert_eq!(
+            parse_token_amount("2.0", 18).unwrap(),
+            EVMU256::from(2_000_000_000_000_000_000_u128)
+        );
+        assert_eq!(parse_token_amount("1.5", 6).unwrap(), EVMU256::from(1_500_000));
+        assert_eq!(parse_token_amount("0.000001", 6).unwrap(), EVMU256::from(1));
+        assert_eq!(parse_token_amount("1_000", 6).unwrap(), EVMU256::from(1000));
+        assert_eq!(parse_token_amount("0x10", 6).unwrap(), EVMU256::from(16));
+        assert!(parse_token_amount("0.0000001", 6).is_err());
+        assert!(parse_token_amount("1.2.3", 6).is_err());
+        assert!(parse_token_amount(".", 6).is_err());
+    }
+
+    fn decode_swap(function: [u8; 4], types: &str, args: &[Token]) -> Option<SwapInfo> {
+        let mut abi = get_abi_type_boxed(types);
+        abi.function = function;
+        assert!(abi.set_bytes([function.to_vec(), encode(args)].concat()));
+        SwapInfo::try_new(&EVMAddress::zero(), &mut abi)
+    }
+
+    #[test]
+    fn test_swap_info_non_fee_selectors() {
+        let path = [EVMAddress::from_slice(&[1; 20]), EVMAddress::from_slice(&[2; 20])];
+        let path_token = Token::Array(path.iter().map(|addr| Token::Address(addr.0.into())).collect());
+        let expected = path.iter().map(checksum).collect::<Vec<_>>();
+        let uint = |v: u64| Token::Uint(v.into());
+        let to = Token::Address(EVMAddress::from_slice(&[3; 20]).0.into());
+
+        // swapExactETHForTokens(amountOutMin, path, to, deadline)
+        let info = decode_swap(
+            SWAP_EXACT_ETH_FOR_TOKENS,
+            "(uint256,address[],address,uint256)",
+            &[uint(1), path_token.clone(), to.clone(), uint(2)],
+        )
+        .unwrap();
+        assert_eq!(info.ty, SwapType::Buy);
+        assert_eq!(info.path, expected);
+
+        // swapExactTokensForETH / swapTokensForExactETH / swapExactTokensForTokens
+        // (amount, amount, path, to, deadline)
+        for (function, ty) in [
+            (SWAP_EXACT_TOKENS_FOR_ETH, SwapType::Sell),
+            (SWAP_TOKENS_FOR_EXACT_ETH, SwapType::Sell),
+            (SWAP_EXACT_TOKENS_FOR_TOKENS, SwapType::Swap),
+        ] {
+            let info = decode_swap(
+                function,
+                "(uint256,uint256,address[],address,uint256)",
+                &[uint(1), uint(2), path_token.clone(), to.clone(), uint(3)],
+            )
+            .unwrap();
+            assert_eq!(info.ty, ty);
+            assert_eq!(info.path, expected);
+        }
+    }
+
+    #[test]
+    fn test_custom_swap_selector() {
+        let path = [EVMAddress::from_slice(&[1; 20]), EVMAddress::from_slice(&[2; 20])];
+        let path_token = Token::Array(path.iter().map(|addr| Token::Address(addr.0.into())).collect());
+        // swapETHForTokensWithReflection(address[] path, uint256 amountOutMin)
+        let selector = [0xde, 0xad, 0xbe, 0xef];
+        let types = "(address[],uint256)";
+        let args = [path_token, Token::Uint(1.into())];
+        assert!(decode_swap(selector, types, &args).is_none());
+
+        SwapData::register_selector(selector, SwapType::from_str("buy").unwrap(), 0);
+        let info = decode_swap(selector, types, &args).unwrap();
+        assert_eq!(info.ty, SwapType::Buy);
+        assert_eq!(info.path, path.iter().map(checksum).collect::<Vec<_>>());
+
+        // the path argument is not an array
+        SwapData::register_selector(selector, SwapType::Sell, 1);
+        assert!(decode_swap(selector, types, &args).is_none());
+        assert!(SwapType::from_str("borrow").is_err());
+    }
+
+    #[test]
+    fn test_unknown_provider() {
+        assert!(UniswapProvider::from_str("sushiswap").is_ok());
+        let err = UniswapProvider::from_str("sushi").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown provider 'sushi', expected one of: pancakeswap, pancakeswapv2, sushiswap, uniswapv2, uniswapv3, \
+             biswap"
+        );
     }
 }
 //     use std::str::FromStr;
@@ -0,0 +1,492 @@
+use std::fmt::Debug;
+
+use libafl::schedulers::Scheduler;
+use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    uniswap::CODE_REGISTRY,
+    v2_transformer::{balance_of_bytes, transfer_bytes},
+    PairContext,
+    SwapImpact,
+};
+use crate::{
+    evm::{
+        onchain::endpoints::OnChainConfig,
+        types::{EVMAddress, EVMFuzzState, EVMU256},
+        vm::{EVMExecutor, MEM_LIMIT},
+    },
+    generic_vm::vm_state::VMStateT,
+    get_code_tokens,
+    input::ConciseSerde,
+    is_call_success,
+};
+
+/// Curve fees are expressed with 10 decimals
+const STABLESWAP_FEE_DENOMINATOR: u64 = 10_000_000_000;
+/// Balancer weights and fees are expressed with 18 decimals
+const WEIGHTED_ONE: u128 = 1_000_000_000_000_000_000;
+/// Newton iterations used by Curve before giving up
+const STABLESWAP_MAX_ITERATIONS: usize = 255;
+/// The spot rate of a pool is measured by swapping this fraction of the
+/// balance of the in token
+const SPOT_RATE_PROBE_FRACTION: u64 = 1_000_000;
+
+/// The invariant a multi token pool maintains between its balances
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PoolInvariant {
+    /// Curve stableswap, `amp` is the raw `A()` of the pool and `fee` uses
+    /// 1e10 as denominator
+    StableSwap { amp: EVMU256, fee: EVMU256 },
+    /// Balancer weighted pool, `weights` are normalized weights (1e18 = 100%)
+    /// and `fee` is the swap fee percentage (1e18 = 100%)
+    Weighted { weights: Vec<EVMU256>, fee: EVMU256 },
+}
+
+impl Default for PoolInvariant {
+    fn default() -> Self {
+        PoolInvariant::StableSwap {
+            amp: EVMU256::ZERO,
+            fee: EVMU256::ZERO,
+        }
+    }
+}
+
+/// A hop through a pool holding more than two tokens (Curve / Balancer).
+///
+/// Unlike [`super::v2_transformer::UniswapPairContext`], the direction of the
+/// swap is given by explicit indices into `tokens`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MultiTokenPoolContext {
+    pub pool_address: EVMAddress,
+    pub tokens: Vec<EVMAddress>,
+    /// Multipliers normalizing each token balance to 18 decimals
+    pub precision_multipliers: Vec<EVMU256>,
+    pub token_in: usize,
+    pub token_out: usize,
+    pub invariant: PoolInvariant,
+}
+
+impl MultiTokenPoolContext {
+    /// Build a Curve stableswap hop, the amplification and fee are read from
+    /// the pool
+    pub fn new_stableswap(
+        onchain: &OnChainConfig,
+        pool_address: EVMAddress,
+        tokens: Vec<EVMAddress>,
+        precision_multipliers: Vec<EVMU256>,
+        token_in: usize,
+        token_out: usize,
+    ) -> Option<Self> {
+        let (amp, fee) = onchain.fetch_stableswap_params(pool_address)?;
+        if amp == EVMU256::ZERO {
+            return None;
+        }
+        Some(Self {
+            pool_address,
+            tokens,
+            precision_multipliers,
+            token_in,
+            token_out,
+            invariant: PoolInvariant::StableSwap { amp, fee },
+        })
+    }
+
+    /// Build a Balancer weighted pool hop, the weights and fee are read from
+    /// the pool
+    pub fn new_weighted(
+        onchain: &OnChainConfig,
+        pool_address: EVMAddress,
+        tokens: Vec<EVMAddress>,
+        token_in: usize,
+        token_out: usize,
+    ) -> Option<Self> {
+        let (weights, fee) = onchain.fetch_weighted_params(pool_address)?;
+        if weights.len() != tokens.len() {
+            return None;
+        }
+        Some(Self {
+            pool_address,
+            tokens,
+            precision_multipliers: vec![],
+            token_in,
+            token_out,
+            invariant: PoolInvariant::Weighted { weights, fee },
+        })
+    }
+
+    pub fn in_token_address(&self) -> EVMAddress {
+        self.tokens[self.token_in]
+    }
+
+    pub fn out_token_address(&self) -> EVMAddress {
+        self.tokens[self.token_out]
+    }
+
+    /// Calculate the amount of `j` received for `amount_in` of `i` given the
+    /// balances of the pool before the swap
+    pub fn calculate_amounts_out(&self, i: usize, j: usize, amount_in: EVMU256, balances: &[EVMU256]) -> EVMU256 {
+        match &self.invariant {
+            PoolInvariant::StableSwap { amp, fee } => {
+                let rates = self.rates(balances.len());
+                let xp = balances
+                    .iter()
+                    .zip(rates.iter())
+                    .map(|(b, r)| *b * *r)
+                    .collect::<Vec<_>>();
+                let dy = match stableswap_get_dy(*amp, &xp, i, j, amount_in * rates[i]) {
+                    Some(dy) => dy,
+                    None => return EVMU256::ZERO,
+                };
+                let dy_fee = dy * *fee / EVMU256::from(STABLESWAP_FEE_DENOMINATOR);
+                (dy - dy_fee) / rates[j]
+            }
+            PoolInvariant::Weighted { weights, fee } => {
+                let one = EVMU256::from(WEIGHTED_ONE);
+                let amount_in = amount_in * (one - *fee) / one;
+                weighted_get_out(balances[i], weights[i], balances[j], weights[j], amount_in)
+            }
+        }
+    }
+
+    /// Spot rate of the pool as (amount of `i` in, amount of `j` out) of a
+    /// swap too small to move the price, the fee included
+    pub fn spot_rate(&self, i: usize, j: usize, balances: &[EVMU256]) -> (EVMU256, EVMU256) {
+        let probe = (balances[i] / EVMU256::from(SPOT_RATE_PROBE_FRACTION)).max(EVMU256::from(1));
+        (probe, self.calculate_amounts_out(i, j, probe, balances))
+    }
+
+    /// Transfer the in token of the first hop into the pool
+    pub fn initial_transfer<VS, CI, SC>(
+        &self,
+        src: &EVMAddress,
+        amount: EVMU256,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+    ) -> Option<()>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        let in_token_address = self.in_token_address();
+        let call = Contract::new_with_context_analyzed(
+            transfer_bytes(&self.pool_address, amount),
+            get_code_tokens!(in_token_address, vm, state),
+            &CallContext {
+                address: in_token_address,
+                caller: *src,
+                code_address: in_token_address,
+                apparent_value: EVMU256::ZERO,
+                scheme: CallScheme::Call,
+            },
+        );
+
+        let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+        let ir = vm.host.run_inspect(&mut interp, state);
+        if !is_call_success!(ir) {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    fn rates(&self, n: usize) -> Vec<EVMU256> {
+        if self.precision_multipliers.len() == n {
+            self.precision_multipliers.clone()
+        } else {
+            vec![EVMU256::from(1); n]
+        }
+    }
+}
+
+/// Curve `get_D`: the stableswap invariant of the normalized balances, `None`
+/// if it does not converge or the amplification is zero
+pub fn stableswap_get_d(amp: EVMU256, xp: &[EVMU256]) -> Option<EVMU256> {
+    if amp == EVMU256::ZERO {
+        return None;
+    }
+    let n = EVMU256::from(xp.len());
+    let s = xp.iter().fold(EVMU256::ZERO, |acc, x| acc + *x);
+    if s == EVMU256::ZERO {
+        return Some(EVMU256::ZERO);
+    }
+    if xp.iter().any(|x| *x == EVMU256::ZERO) {
+        return None;
+    }
+
+    let ann = amp * n;
+    let mut d = s;
+    for _ in 0..STABLESWAP_MAX_ITERATIONS {
+        let mut d_p = d;
+        for x in xp {
+            d_p = d_p * d / (*x * n);
+        }
+        let d_prev = d;
+        d = (ann * s + d_p * n) * d / ((ann - EVMU256::from(1)) * d + (n + EVMU256::from(1)) * d_p);
+        let diff = if d > d_prev { d - d_prev } else { d_prev - d };
+        if diff <= EVMU256::from(1) {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Curve `get_y`: the new balance of `j` once `i` is set to `x`
+pub fn stableswap_get_y(amp: EVMU256, xp: &[EVMU256], i: usize, j: usize, x: EVMU256) -> Option<EVMU256> {
+    if i == j || i >= xp.len() || j >= xp.len() || amp == EVMU256::ZERO {
+        return None;
+    }
+    let n = EVMU256::from(xp.len());
+    let d = stableswap_get_d(amp, xp)?;
+    let ann = amp * n;
+
+    let mut c = d;
+    let mut s = EVMU256::ZERO;
+    for (k, balance) in xp.iter().enumerate() {
+        let x_k = if k == i {
+            x
+        } else if k != j {
+            *balance
+        } else {
+            continue;
+        };
+        if x_k == EVMU256::ZERO {
+            return None;
+        }
+        s += x_k;
+        c = c * d / (x_k * n);
+    }
+    c = c * d / (ann * n);
+    let b = s + d / ann;
+
+    let mut y = d;
+    for _ in 0..STABLESWAP_MAX_ITERATIONS {
+        let y_prev = y;
+        let denominator = EVMU256::from(2) * y + b;
+        if denominator <= d {
+            return None;
+        }
+        y = (y * y + c) / (denominator - d);
+        let diff = if y > y_prev { y - y_prev } else { y_prev - y };
+        if diff <= EVMU256::from(1) {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// Amount of `j` (normalized, before fee) received for `dx` (normalized) of `i`
+pub fn stableswap_get_dy(amp: EVMU256, xp: &[EVMU256], i: usize, j: usize, dx: EVMU256) -> Option<EVMU256> {
+    let y = stableswap_get_y(amp, xp, i, j, xp[i] + dx)?;
+    // -1 just in case there were some rounding errors, same as curve
+    if xp[j] <= y + EVMU256::from(1) {
+        return None;
+    }
+    Some(xp[j] - y - EVMU256::from(1))
+}
+
+/// Balancer `calcOutGivenIn`, the power is approximated with floats
+pub fn weighted_get_out(
+    balance_in: EVMU256,
+    weight_in: EVMU256,
+    balance_out: EVMU256,
+    weight_out: EVMU256,
+    amount_in: EVMU256,
+) -> EVMU256 {
+    if weight_out == EVMU256::ZERO || balance_in + amount_in == EVMU256::ZERO {
+        return EVMU256::ZERO;
+    }
+    let as_f64 = |v: EVMU256| -> f64 { v.to_string().parse::<f64>().unwrap_or(f64::MAX) };
+
+    let base = as_f64(balance_in) / as_f64(balance_in + amount_in);
+    let exponent = as_f64(weight_in) / as_f64(weight_out);
+    let power = base.powf(exponent);
+    if !power.is_finite() || power >= 1.0 {
+        return EVMU256::ZERO;
+    }
+
+    let one = EVMU256::from(WEIGHTED_ONE);
+    let power = EVMU256::from((power * WEIGHTED_ONE as f64) as u128);
+    balance_out * (one - power) / one
+}
+
+impl PairContext for MultiTokenPoolContext {
+    fn transform<VS, CI, SC>(
+        &self,
+        _src: &EVMAddress,
+        next: &EVMAddress,
+        amount: EVMU256,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+        reverse: bool,
+    ) -> Option<(EVMAddress, EVMU256)>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        let (token_in, token_out) = if reverse {
+            (self.token_out, self.token_in)
+        } else {
+            (self.token_in, self.token_out)
+        };
+
+        // 1. get balances of the pool, the in token is already transferred to the pool
+        let mut balances = Vec::with_capacity(self.tokens.len());
+        for token in &self.tokens {
+            let call = Contract::new_with_context_analyzed(
+                balance_of_bytes(&self.pool_address),
+                get_code_tokens!(*token, vm, state),
+                &CallContext {
+                    address: *token,
+                    caller: EVMAddress::default(),
+                    code_address: *token,
+                    apparent_value: EVMU256::ZERO,
+                    scheme: CallScheme::Call,
+                },
+            );
+            let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+            let ir = vm.host.run_inspect(&mut interp, state);
+            if !is_call_success!(ir) {
+                return None;
+            }
+            balances.push(EVMU256::try_from_be_slice(interp.return_value().to_vec().as_slice())?);
+        }
+        if balances[token_in] < amount {
+            return None;
+        }
+        balances[token_in] -= amount;
+
+        // 2. calculate amount out
+        let amount_out = self.calculate_amounts_out(token_in, token_out, amount, &balances);
+        if amount_out == EVMU256::ZERO {
+            return None;
+        }
+
+        // 3. transfer out token
+        let out_token_address = self.tokens[token_out];
+        let call = Contract::new_with_context_analyzed(
+            transfer_bytes(next, amount_out),
+            get_code_tokens!(out_token_address, vm, state),
+            &CallContext {
+                address: out_token_address,
+                caller: self.pool_address,
+                code_address: out_token_address,
+                apparent_value: EVMU256::ZERO,
+                scheme: CallScheme::Call,
+            },
+        );
+        let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+        let ir = vm.host.run_inspect(&mut interp, state);
+        if !is_call_success!(ir) {
+            return None;
+        }
+
+        // 4. setup flashloan data
+        vm.host
+            .evmstate
+            .flashloan_data
+            .oracle_recheck_balance
+            .insert(self.tokens[token_in]);
+        vm.host
+            .evmstate
+            .flashloan_data
+            .oracle_recheck_balance
+            .insert(out_token_address);
+
+        // 5. record the price impact against the spot rate before the trade
+        let (spot_in, spot_out) = self.spot_rate(token_in, token_out, &balances);
+        vm.host.evmstate.swap_impacts.push(SwapImpact::new(
+            self.pool_address,
+            amount,
+            amount_out,
+            spot_in,
+            spot_out,
+        ));
+        Some((*next, amount_out))
+    }
+
+    fn name(&self) -> String {
+        match self.invariant {
+            PoolInvariant::StableSwap { .. } => "curve_stableswap".to_string(),
+            PoolInvariant::Weighted { .. } => "balancer_weighted".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_stableswap_3pool_exchange() {
+        // DAI / USDC / USDT, USDC and USDT have 6 decimals
+        let e18 = EVMU256::from_str("1000000000000000000").unwrap();
+        let e6 = EVMU256::from(1_000_000u64);
+        let ctx = MultiTokenPoolContext {
+            tokens: vec![EVMAddress::zero(); 3],
+            precision_multipliers: vec![EVMU256::from(1), e18 / e6, e18 / e6],
+            token_in: 0,
+            token_out: 1,
+            invariant: PoolInvariant::StableSwap {
+                amp: EVMU256::from(2000),
+                // 0.01%
+                fee: EVMU256::from(1_000_000),
+            },
+            ..Default::default()
+        };
+        let balances = vec![
+            EVMU256::from(100_000_000u64) * e18,
+            EVMU256::from(100_000_000u64) * e6,
+            EVMU256::from(100_000_000u64) * e6,
+        ];
+
+        // 1000 DAI => USDC
+        let amount_in = EVMU256::from(1000) * e18;
+        let out = ctx.calculate_amounts_out(0, 1, amount_in, &balances);
+        // balanced pool with a high A: ~1:1 minus the 0.01% fee
+        assert!(out < EVMU256::from(1000) * e6);
+        assert!(out > EVMU256::from(999_800_000u64));
+        // trading 1000 DAI barely moves the price of a 100M pool
+        let (spot_in, spot_out) = ctx.spot_rate(0, 1, &balances);
+        let impact = SwapImpact::new(EVMAddress::zero(), amount_in, out, spot_in, spot_out);
+        assert!(impact.impact_bps <= 1);
+
+        // invariant should not decrease after the swap
+        let rates = ctx.rates(3);
+        let xp_before = balances
+            .iter()
+            .zip(rates.iter())
+            .map(|(b, r)| *b * *r)
+            .collect::<Vec<_>>();
+        let mut after = balances.clone();
+        after[0] += amount_in;
+        after[1] -= out;
+        let xp_after = after.iter().zip(rates.iter()).map(|(b, r)| *b * *r).collect::<Vec<_>>();
+        let amp = EVMU256::from(2000);
+        assert!(stableswap_get_d(amp, &xp_after).unwrap() >= stableswap_get_d(amp, &xp_before).unwrap());
+
+        // a pool reporting A() = 0 is not swapped through
+        assert_eq!(stableswap_get_d(EVMU256::ZERO, &xp_before), None);
+        assert_eq!(stableswap_get_dy(EVMU256::ZERO, &xp_before, 0, 1, amount_in), None);
+    }
+
+    #[test]
+    fn test_weighted_exchange() {
+        let e18 = EVMU256::from_str("1000000000000000000").unwrap();
+        // 80/20 pool
+        let out = weighted_get_out(
+            EVMU256::from(800) * e18,
+            EVMU256::from(8) * e18 / EVMU256::from(10),
+            EVMU256::from(200) * e18,
+            EVMU256::from(2) * e18 / EVMU256::from(10),
+            e18,
+        );
+        // spot price is 1:1, so the out amount is slightly less than 1
+        assert!(out < e18);
+        assert!(out > e18 * EVMU256::from(99) / EVMU256::from(100));
+    }
+}
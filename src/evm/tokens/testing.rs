@@ -0,0 +1,170 @@
+//! Helpers to test swapping a token on a fork, e.g., that a token can be
+//! bought and sold back through its discovered routes.
+//!
+//! Compiled with the `test-utils` feature (and in tests of this crate):
+//!
+//! ```ignore
+//! let outcome = trade(Chain::ETH, 19044110, TradeDirection::Buy, token, amount, 0);
+//! println!("{}", outcome);
+//! assert!(outcome.state.is_some());
+//! ```
+
+use std::{
+    cell::RefCell,
+    fmt::{Display, Formatter},
+    rc::Rc,
+};
+
+use libafl::{schedulers::StdScheduler, state::HasMetadata};
+
+use super::{uniswap::fetch_uniswap_path, HopResult, PairContextTy, TokenContext};
+use crate::{
+    evm::{
+        abi::ABIAddressToInstanceMap,
+        config::StorageFetchingMode,
+        corpus_initializer::EnvMetadata,
+        host::FuzzHost,
+        input::ConciseEVMInput,
+        onchain::{
+            endpoints::{Chain, OnChainConfig},
+            OnChain,
+        },
+        oracles::v2_pair::reserve_parser,
+        tokens::{uniswap::CODE_REGISTRY, DEFAULT_DECIMALS},
+        types::{generate_random_address, EVMAddress, EVMFuzzState, EVMU256},
+        vm::{EVMExecutor, EVMState},
+    },
+    state::{FuzzState, HasCaller},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeDirection {
+    /// Buy the token with ETH, for a fresh account
+    Buy,
+    /// Sell the token held by `from` for ETH
+    Sell { from: EVMAddress },
+}
+
+/// A trade executed by [`trade`]
+pub struct TradeOutcome {
+    /// Discovered routes of the token
+    pub token_ctx: TokenContext,
+    /// Index of the route taken in `token_ctx.swaps`
+    pub route: usize,
+    /// Hops of the route taken, in swap order
+    pub hops: Vec<HopResult>,
+    /// State after the trade, `None` if the swap failed
+    pub state: Option<EVMState>,
+}
+
+impl TradeOutcome {
+    /// Reserves of each Uniswap pair of the route taken, before and after the
+    /// trade
+    pub fn reserve_changes(&self) -> Vec<(EVMAddress, (EVMU256, EVMU256), (EVMU256, EVMU256))> {
+        let (Some(state), Some(path)) = (&self.state, self.token_ctx.swaps.get(self.route)) else {
+            return vec![];
+        };
+        path.route
+            .iter()
+            .filter_map(|pair| match pair {
+                PairContextTy::Uniswap(ctx) => {
+                    let ctx = ctx.borrow();
+                    let reserves = state.sload(ctx.pair_address, EVMU256::from(8))?;
+                    Some((ctx.pair_address, ctx.initial_reserves, reserve_parser(&reserves)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Display for TradeOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "======== Token Swaps ========")?;
+        writeln!(f, "{}", self.token_ctx)?;
+        writeln!(f, "selected route: #{}", self.route)?;
+        let Some(state) = &self.state else {
+            return write!(f, "failed");
+        };
+
+        writeln!(f, "======== Hops ========")?;
+        for hop in &self.hops {
+            writeln!(
+                f,
+                "{:?}: {} => {} (to {:?})",
+                hop.pair, hop.amount_in, hop.amount_out, hop.receiver
+            )?;
+        }
+
+        writeln!(f, "======== Reserve Changes ========")?;
+        for (pair, (r0, r1), (new_r0, new_r1)) in self.reserve_changes() {
+            writeln!(f, "{:?} ({}, {}) => ({}, {})", pair, r0, r1, new_r0, new_r1)?;
+        }
+
+        let flashloan_data = &state.flashloan_data;
+        writeln!(f, "======== Flashloan Data ========")?;
+        writeln!(f, "owed: {}", flashloan_data.owed)?;
+        writeln!(f, "earned: {}", flashloan_data.earned)?;
+        write!(f, "net: {}", flashloan_data.net_profit().describe(DEFAULT_DECIMALS))
+    }
+}
+
+/// Fork `chain` at `block`, discover the swap routes (and measure the taxes)
+/// of `token` and trade `amount` of it (in wei when buying, in the token when
+/// selling) through the `route`-th route
+pub fn trade(
+    chain: Chain,
+    block: u64,
+    direction: TradeDirection,
+    token: EVMAddress,
+    amount: EVMU256,
+    route: usize,
+) -> TradeOutcome {
+    let mut state = FuzzState::new(0);
+    let dummy_caller = generate_random_address(&mut state);
+    state.add_caller(&dummy_caller);
+    state
+        .metadata_map_mut()
+        .insert::<ABIAddressToInstanceMap>(ABIAddressToInstanceMap::new());
+    state.metadata_map_mut().insert::<EnvMetadata>(EnvMetadata::default());
+
+    let mut fuzz_host = FuzzHost::new(StdScheduler::new(), "work_dir".to_string());
+    let mut onchain = OnChainConfig::new(chain, block);
+    let onchain_mid = OnChain::new(onchain.clone(), StorageFetchingMode::OneByOne);
+    fuzz_host.add_middlewares(Rc::new(RefCell::new(onchain_mid)));
+    fuzz_host.evmstate = EVMState::default();
+
+    CODE_REGISTRY
+        .lock()
+        .unwrap()
+        .insert(token, onchain.get_contract_code_analyzed(token, false));
+    let mut token_ctx = fetch_uniswap_path(&mut onchain, token);
+
+    let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> =
+        EVMExecutor::new(fuzz_host, generate_random_address(&mut state));
+    // with 0.1 ETH, the state is restored afterwards
+    token_ctx.measure_taxes(
+        EVMU256::from(100_000_000_000_000_000_u128),
+        &mut state,
+        &mut evm_executor,
+    );
+    let seed = [route as u8];
+    let res = match direction {
+        TradeDirection::Buy => {
+            let to = generate_random_address(&mut state);
+            token_ctx.buy(amount, to, &mut state, &mut evm_executor, &seed, None)
+        }
+        TradeDirection::Sell { from } => token_ctx.sell(amount, from, &mut state, &mut evm_executor, &seed, None),
+    };
+
+    let (hops, state) = match res {
+        Some(hops) => (hops, Some(evm_executor.host.evmstate)),
+        None => (vec![], None),
+    };
+    TradeOutcome {
+        token_ctx,
+        route,
+        hops,
+        state,
+    }
+}
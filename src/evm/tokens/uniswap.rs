@@ -1,6 +1,7 @@
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
+    fmt::{Display, Formatter},
     rc::Rc,
     str::FromStr,
     sync::{Arc, Mutex},
@@ -8,20 +9,29 @@ use std::{
 
 use itertools::Itertools;
 use lazy_static::lazy_static;
+use libafl_bolts::{bolts_prelude::SerdeAnyMap, impl_serdeany};
 use revm_primitives::Bytecode;
-use tracing::{info, warn};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
 
 use super::{
     get_uniswap_info,
-    v2_transformer::UniswapPairContext,
+    try_get_uniswap_info,
+    v2_transformer::{SwapFailure, UniswapPairContext},
     weth_transformer::WethContext,
+    PairLookup,
     PathContext,
     TokenContext,
+    UniswapInfo,
     UniswapProvider,
 };
-use crate::evm::{
-    onchain::endpoints::{Chain, OnChainConfig, PairData},
-    types::{EVMAddress, EVMU256},
+use crate::{
+    events::StatsReporter,
+    evm::{
+        onchain::endpoints::{Chain, OnChainConfig, PairData},
+        types::{EVMAddress, EVMU256},
+    },
+    memory::MemoryCache,
 };
 
 pub struct Info {
@@ -36,22 +46,297 @@ pub struct BasicInfo {
 
 const MAX_HOPS: u32 = 2; // Assuming the value of MAX_HOPS
 
+/// Number of most liquid pairs kept for each token
+const MAX_PAIRS_PER_TOKEN: usize = 3;
+
+/// Why a candidate pair was dropped during path discovery, or what looks off
+/// about a kept one. The sanity checks are only logged, a pair is dropped only
+/// when it is not among the most liquid ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PairVerdict {
+    Kept,
+    /// One side of the pair has no reserve
+    Illiquid,
+    /// The swap side of the pair does not match the token0 / token1 ordering
+    WrongToken,
+    /// The pair address is not the pair of the factory of its provider, i.e.,
+    /// neither the CREATE2 address derived from the init code hash nor
+    /// `getPair`, depending on the provider's [`PairLookup`]
+    InitCodeHashMismatch,
+    /// Not among the `MAX_PAIRS_PER_TOKEN` most liquid pairs, dropped
+    LowLiquidityRank,
+}
+
+impl PairVerdict {
+    /// Whether the pair is used in the routes
+    pub fn is_kept(&self) -> bool {
+        *self != PairVerdict::LowLiquidityRank
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CandidatePair {
+    /// Token whose pairs were being fetched
+    pub token: String,
+    /// Pair data, with reserves filled in
+    pub pair: PairData,
+    pub verdict: PairVerdict,
+}
+
+/// Everything considered by [`fetch_uniswap_path_verbose`] while building
+/// the swap routes of a token
+#[derive(Clone, Debug, Default)]
+pub struct DiscoveryLog {
+    pub candidates: Vec<CandidatePair>,
+    pub routes: Vec<Vec<PairData>>,
+}
+
+impl DiscoveryLog {
+    fn record(&mut self, token: &str, pair: PairData, verdict: PairVerdict) {
+        self.candidates.push(CandidatePair {
+            token: token.to_string(),
+            pair,
+            verdict,
+        });
+    }
+}
+
+impl Display for DiscoveryLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "candidate pairs:")?;
+        for candidate in &self.candidates {
+            let pair = &candidate.pair;
+            writeln!(
+                f,
+                "  {} {} -> {} via {} ({}), reserves 0x{} / 0x{}: {:?}",
+                candidate.token,
+                pair.in_token,
+                pair.next,
+                pair.pair,
+                pair.src_exact,
+                pair.initial_reserves_0.trim_start_matches('0'),
+                pair.initial_reserves_1.trim_start_matches('0'),
+                candidate.verdict
+            )?;
+        }
+        writeln!(f, "routes:")?;
+        for route in &self.routes {
+            let hops = route
+                .iter()
+                .map(|pair| format!("{}({})", pair.pair, pair.src))
+                .join(" -> ");
+            writeln!(f, "  {}", hops)?;
+        }
+        Ok(())
+    }
+}
+
+/// Swap routes discovered for a token, telling "no liquidity found" apart
+/// from "routes found but no exploit"
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenDiscoveryStats {
+    pub token: EVMAddress,
+    /// Routes to WETH kept in the token context
+    pub paths: usize,
+    /// Distinct pairs on those routes
+    pub pairs: usize,
+    /// Candidate pairs dropped for not being among the most liquid ones
+    pub pruned_pairs: usize,
+}
+
+impl TokenDiscoveryStats {
+    pub fn new(token: EVMAddress, ctx: &TokenContext, log: &DiscoveryLog) -> Self {
+        Self {
+            token,
+            paths: ctx.swaps.len(),
+            pairs: ctx.swaps.iter().flat_map(|path| path.pair_addresses()).unique().count(),
+            pruned_pairs: log
+                .candidates
+                .iter()
+                .filter(|candidate| !candidate.verdict.is_kept())
+                .count(),
+        }
+    }
+}
+
+impl Display for TokenDiscoveryStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?}: {} path(s) over {} pair(s), {} candidate pair(s) pruned",
+            self.token, self.paths, self.pairs, self.pruned_pairs
+        )
+    }
+}
+
+/// Discovery stats of the tokens of the campaign, surfaced in the campaign
+/// stats and the final summary
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SwapDiscoveryMetadata {
+    pub tokens: Vec<TokenDiscoveryStats>,
+    /// Pairs swaps failed through, with the latest failure and how many swaps
+    /// failed
+    #[serde(default)]
+    pub failures: HashMap<EVMAddress, (SwapFailure, usize)>,
+}
+
+impl_serdeany!(SwapDiscoveryMetadata);
+
+impl SwapDiscoveryMetadata {
+    /// Record the stats of a token, replacing earlier ones of the same token
+    pub fn record(&mut self, stats: TokenDiscoveryStats) {
+        self.tokens.retain(|known| known.token != stats.token);
+        self.tokens.push(stats);
+    }
+
+    /// Record why a swap through `pair` failed
+    pub fn record_failure(&mut self, pair: EVMAddress, failure: SwapFailure) {
+        let entry = self.failures.entry(pair).or_insert((failure.clone(), 0));
+        entry.0 = failure;
+        entry.1 += 1;
+    }
+}
+
+/// Reports the [`SwapDiscoveryMetadata`] in the campaign stats
+pub struct SwapDiscoveryReporter;
+
+impl StatsReporter for SwapDiscoveryReporter {
+    fn name(&self) -> &'static str {
+        "swap discovery"
+    }
+
+    fn report(&self, metadata: &SerdeAnyMap) -> Vec<String> {
+        let Some(discovery) = metadata.get::<SwapDiscoveryMetadata>() else {
+            return vec![];
+        };
+        discovery
+            .tokens
+            .iter()
+            .map(|stats| stats.to_string())
+            .chain(discovery.failures.iter().map(|(pair, (failure, count))| {
+                format!("{} swap(s) through {:?} failed, last: {}", count, pair, failure)
+            }))
+            .collect()
+    }
+}
+
 lazy_static! {
     pub static ref CODE_REGISTRY: Mutex<HashMap<EVMAddress, Bytecode>> = Mutex::new(HashMap::new());
 }
 
+/// Memory accounting of [`CODE_REGISTRY`]. The code of the pairs is executed
+/// by the swaps, so it is never evicted.
+pub struct CodeRegistryCache;
+
+impl MemoryCache for CodeRegistryCache {
+    fn name(&self) -> &'static str {
+        "code registry"
+    }
+
+    fn estimated_size(&self) -> usize {
+        CODE_REGISTRY.lock().unwrap().values().map(|code| code.len()).sum()
+    }
+
+    fn evict(&mut self) -> usize {
+        0
+    }
+}
+
 pub fn fetch_uniswap_path(onchain: &mut OnChainConfig, token_address: EVMAddress) -> TokenContext {
+    fetch_uniswap_path_verbose(onchain, token_address).0
+}
+
+/// Discover the routes of each of `tokens` and describe them, i.e., the
+/// pairs, their reserves and the hop order, without fuzzing anything
+pub fn describe_uniswap_paths(onchain: &mut OnChainConfig, tokens: &[EVMAddress]) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            let (ctx, log) = fetch_uniswap_path_verbose(onchain, *token);
+            format!("{}\n{}", TokenDiscoveryStats::new(*token, &ctx, &log), ctx)
+        })
+        .join("\n\n")
+}
+
+/// Token contexts discovered in a campaign, keyed by (token, chain, block),
+/// so that a token shared by several targets is only fetched once
+#[derive(Debug, Default)]
+pub struct TokenContextRegistry {
+    contexts: HashMap<(EVMAddress, String, String), (TokenContext, TokenDiscoveryStats)>,
+}
+
+impl TokenContextRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Get the context of the token at the block of `onchain`, fetching it if
+    /// it is not known yet
+    pub fn get_or_fetch(&mut self, onchain: &mut OnChainConfig, token_address: EVMAddress) -> TokenContext {
+        self.get_or_fetch_with_stats(onchain, token_address).0
+    }
+
+    /// Same as [`Self::get_or_fetch`], but also returns how many routes and
+    /// pairs were discovered for the token
+    pub fn get_or_fetch_with_stats(
+        &mut self,
+        onchain: &mut OnChainConfig,
+        token_address: EVMAddress,
+    ) -> (TokenContext, TokenDiscoveryStats) {
+        let key = (token_address, onchain.chain_name.clone(), onchain.block_number.clone());
+        if let Some(entry) = self.contexts.get(&key) {
+            debug!("reusing token context of {:?}", token_address);
+            return entry.clone();
+        }
+
+        // contexts fetched at other blocks of the same chain are stale
+        self.contexts
+            .retain(|(_, chain, block), _| chain != &key.1 || block == &key.2);
+
+        let (ctx, log) = fetch_uniswap_path_verbose(onchain, token_address);
+        let stats = TokenDiscoveryStats::new(token_address, &ctx, &log);
+        info!("Swap discovery of {}", stats);
+        self.contexts.insert(key, (ctx.clone(), stats.clone()));
+        (ctx, stats)
+    }
+
+    pub fn len(&self) -> usize {
+        self.contexts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contexts.is_empty()
+    }
+}
+
+/// Same as [`fetch_uniswap_path`], but also returns the candidate pairs
+/// considered and why each of them was kept or dropped
+pub fn fetch_uniswap_path_verbose(
+    onchain: &mut OnChainConfig,
+    token_address: EVMAddress,
+) -> (TokenContext, DiscoveryLog) {
     let token = format!("{:?}", token_address);
-    let info: Info = find_path_subgraph(onchain, &token);
+    let mut log = DiscoveryLog::default();
+    let info: Info = find_path_subgraph(onchain, &token, &mut log);
+    log.routes = info.routes.clone();
 
     let basic_info = info.basic_info;
     if basic_info.weth.is_empty() {
         warn!("failed to find weth address");
-        return TokenContext::default();
+        return (TokenContext::default(), log);
     }
     let weth = EVMAddress::from_str(&basic_info.weth).unwrap();
     let is_weth = basic_info.is_weth;
 
+    let decimals = onchain.fetch_token_decimals(token_address);
+    if decimals.is_none() {
+        warn!(
+            "failed to fetch decimals of {:?}, assuming 18 (override with --token-decimals)",
+            token_address
+        );
+    }
+    let weth_decimals = onchain.fetch_token_decimals(weth);
+
     let routes: Vec<Vec<PairData>> = info.routes;
 
     macro_rules! register_code {
@@ -74,7 +359,7 @@ pub fn fetch_uniswap_path(onchain: &mut OnChainConfig, token_address: EVMAddress
                         next_hop: EVMAddress::from_str(pair.next.as_str()).expect("failed to parse pair"),
                         side: pair.in_ as u8,
                         uniswap_info: Arc::new(get_uniswap_info(
-                            &UniswapProvider::from_str(pair.src_exact.as_str()).unwrap(),
+                            &UniswapProvider::from_str(pair.src_exact.as_str()).unwrap_or_else(|e| panic!("{}", e)),
                             &Chain::from_str(&onchain.chain_name).unwrap(),
                         )),
                         initial_reserves: (
@@ -82,6 +367,8 @@ pub fn fetch_uniswap_path(onchain: &mut OnChainConfig, token_address: EVMAddress
                             EVMU256::try_from_be_slice(&hex::decode(&pair.initial_reserves_1).unwrap()).unwrap(),
                         ),
                         in_token_address: EVMAddress::from_str(pair.in_token.as_str()).unwrap(),
+                        blocked: Default::default(),
+                        last_failure: Default::default(),
                     }));
                     register_code!(inner.borrow().next_hop);
                     path_parsed.route.push(super::PairContextTy::Uniswap(inner));
@@ -92,7 +379,7 @@ pub fn fetch_uniswap_path(onchain: &mut OnChainConfig, token_address: EVMAddress
                         next_hop: EVMAddress::from_str(pair.next.as_str()).expect("failed to parse pair"),
                         side: pair.in_ as u8,
                         uniswap_info: Arc::new(get_uniswap_info(
-                            &UniswapProvider::from_str(pair.src_exact.as_str()).unwrap(),
+                            &UniswapProvider::from_str(pair.src_exact.as_str()).unwrap_or_else(|e| panic!("{}", e)),
                             &Chain::from_str(&onchain.chain_name).unwrap(),
                         )),
                         initial_reserves: (
@@ -100,6 +387,8 @@ pub fn fetch_uniswap_path(onchain: &mut OnChainConfig, token_address: EVMAddress
                             EVMU256::try_from_be_slice(&hex::decode(&pair.initial_reserves_1).unwrap()).unwrap(),
                         ),
                         in_token_address: EVMAddress::from_str(pair.in_token.as_str()).unwrap(),
+                        blocked: Default::default(),
+                        last_failure: Default::default(),
                     }));
                     register_code!(inner_pair.borrow().next_hop);
                     path_parsed.route.push(super::PairContextTy::Uniswap(inner_pair));
@@ -121,11 +410,18 @@ pub fn fetch_uniswap_path(onchain: &mut OnChainConfig, token_address: EVMAddress
         })
         .collect();
 
-    TokenContext {
-        swaps: paths_parsed,
-        is_weth,
-        weth_address: weth,
-    }
+    (
+        TokenContext {
+            swaps: paths_parsed,
+            is_weth,
+            weth_address: weth,
+            decimals,
+            weth_decimals,
+            buy_tax_bps: None,
+            sell_tax_bps: None,
+        },
+        log,
+    )
 }
 
 pub fn get_weth(network: &str) -> String {
@@ -192,7 +488,13 @@ fn get_pegged_token(network: &str) -> HashMap<String, String> {
     }
 }
 
-fn get_pair(onchain: &mut OnChainConfig, token: &str, network: &str, is_pegged: bool) -> Vec<PairData> {
+fn get_pair(
+    onchain: &mut OnChainConfig,
+    token: &str,
+    network: &str,
+    is_pegged: bool,
+    log: &mut DiscoveryLog,
+) -> Vec<PairData> {
     let token = token.to_lowercase();
     info!("fetching pairs for {token}");
     if token == get_weth(network) {
@@ -200,26 +502,74 @@ fn get_pair(onchain: &mut OnChainConfig, token: &str, network: &str, is_pegged:
     }
     let weth = get_weth(network);
     let pegged_tokens = get_pegged_token(network);
-    let mut pairs = onchain.get_pair(
+    let candidates = onchain.get_pair(
         token.as_str(),
         network,
         is_pegged || pegged_tokens.values().contains(&token),
         weth,
     );
 
-    for pair in &mut pairs {
-        add_reserve_info(onchain, pair);
+    let mut pairs = vec![];
+    for mut pair in candidates {
+        add_reserve_info(onchain, &mut pair);
+        let verdict = check_pair(onchain, &pair, network);
+        pairs.push((pair, verdict));
     }
-    pairs.sort_by(|a, b| {
+    pairs.sort_by(|(a, _), (b, _)| {
         let a = get_liquidity_cmp(a);
         let b = get_liquidity_cmp(b);
         b.cmp(&a)
     });
 
-    if pairs.len() > 3 {
-        pairs = pairs[0..3].to_vec();
+    for (idx, (pair, verdict)) in pairs.iter().enumerate() {
+        let verdict = if idx < MAX_PAIRS_PER_TOKEN {
+            verdict.clone()
+        } else {
+            PairVerdict::LowLiquidityRank
+        };
+        log.record(&token, pair.clone(), verdict);
+    }
+    pairs.truncate(MAX_PAIRS_PER_TOKEN);
+    pairs.into_iter().map(|(pair, _)| pair).collect()
+}
+
+/// Sanity checks a pair returned by the pair API, for the discovery log
+fn check_pair(onchain: &mut OnChainConfig, pair: &PairData, network: &str) -> PairVerdict {
+    let is_empty = |reserve: &str| reserve.trim_start_matches('0').is_empty();
+    if is_empty(&pair.initial_reserves_0) || is_empty(&pair.initial_reserves_1) {
+        return PairVerdict::Illiquid;
+    }
+
+    let (Ok(token), Ok(next)) = (EVMAddress::from_str(&pair.in_token), EVMAddress::from_str(&pair.next)) else {
+        return PairVerdict::WrongToken;
+    };
+    let (token0, token1) = if token < next { (token, next) } else { (next, token) };
+    if (pair.in_ == 0) != (token == token0) {
+        return PairVerdict::WrongToken;
+    }
+
+    if let (Ok(provider), Ok(chain)) = (UniswapProvider::from_str(&pair.src_exact), Chain::from_str(network)) &&
+        let Some(info) = try_get_uniswap_info(&provider, &chain) &&
+        let Some(expected) = expected_pair_address(onchain, &info, token0, token1) &&
+        EVMAddress::from_str(&pair.pair).ok() != Some(expected)
+    {
+        return PairVerdict::InitCodeHashMismatch;
+    }
+    PairVerdict::Kept
+}
+
+/// Address of the pair of `token0` and `token1` of the factory of `info`,
+/// looked up as configured by [`UniswapInfo::pair_lookup`]
+fn expected_pair_address(
+    onchain: &mut OnChainConfig,
+    info: &UniswapInfo,
+    token0: EVMAddress,
+    token1: EVMAddress,
+) -> Option<EVMAddress> {
+    match info.pair_lookup {
+        PairLookup::Create2 => Some(info.pair_for(token0, token1)),
+        PairLookup::GetPair => onchain.fetch_pair_address(info.factory, token0, token1),
     }
-    pairs
 }
 
 fn get_all_hops(
@@ -228,6 +578,7 @@ fn get_all_hops(
     network: &str,
     hop: u32,
     known: &mut HashSet<String>,
+    log: &mut DiscoveryLog,
 ) -> HashMap<String, Vec<PairData>> {
     known.insert(token.to_string());
 
@@ -236,7 +587,7 @@ fn get_all_hops(
     }
 
     let mut hops: HashMap<String, Vec<PairData>> = HashMap::new();
-    hops.insert(token.to_string(), get_pair(onchain, token, network, false));
+    hops.insert(token.to_string(), get_pair(onchain, token, network, false, log));
 
     let pegged_tokens = get_pegged_token(network);
 
@@ -244,16 +595,22 @@ fn get_all_hops(
         if pegged_tokens.values().any(|v| v == &i.next) || known.contains(&i.next) {
             continue;
         }
-        let next_hops = get_all_hops(onchain, &i.next, network, hop + 1, known);
+        let next_hops = get_all_hops(onchain, &i.next, network, hop + 1, known, log);
         hops.extend(next_hops);
     }
 
     hops
 }
 
-fn get_pegged_next_hop(onchain: &mut OnChainConfig, token: &str, network: &str) -> PairData {
+/// Hop from the pegged `token` to WETH, `None` if it has no pair
+fn get_pegged_next_hop(
+    onchain: &mut OnChainConfig,
+    token: &str,
+    network: &str,
+    log: &mut DiscoveryLog,
+) -> Option<PairData> {
     if token == get_weth(network) {
-        return PairData {
+        return Some(PairData {
             src: "pegged_weth".to_string(),
             rate: 1_000_000,
             in_: 0,
@@ -265,12 +622,12 @@ fn get_pegged_next_hop(onchain: &mut OnChainConfig, token: &str, network: &str)
             decimals_0: 0,
             decimals_1: 0,
             in_token: token.to_string(),
-        };
+        });
     }
-    let mut peg_info = get_pair(onchain, token, network, true)
-        .first()
-        .expect("Unexpected RPC error, consider setting env <ETH_RPC_URL> ")
-        .clone();
+    let Some(mut peg_info) = get_pair(onchain, token, network, true, log).into_iter().next() else {
+        warn!("no pair found for pegged token {token}, is <ETH_RPC_URL> set?");
+        return None;
+    };
 
     add_reserve_info(onchain, &mut peg_info);
     let p0 = i128::from_str_radix(&peg_info.initial_reserves_0, 16).unwrap();
@@ -282,10 +639,10 @@ fn get_pegged_next_hop(onchain: &mut OnChainConfig, token: &str, network: &str)
         peg_info.rate = (p0 as f64 / p1 as f64 * 1_000_000.0).round() as u32;
     }
 
-    PairData {
+    Some(PairData {
         src: "pegged".to_string(),
         ..peg_info.clone()
-    }
+    })
 }
 
 /// returns whether the pair is significant
@@ -351,11 +708,14 @@ fn dfs(
     pegged_tokens: &HashMap<String, String>,
     hops: &HashMap<String, Vec<PairData>>,
     routes: &mut Vec<Vec<PairData>>,
+    log: &mut DiscoveryLog,
 ) {
     if pegged_tokens.values().any(|v| v == token) {
-        let mut new_path = path.clone();
-        new_path.push(get_pegged_next_hop(onchain, token, network));
-        routes.push(new_path);
+        if let Some(hop) = get_pegged_next_hop(onchain, token, network, log) {
+            let mut new_path = path.clone();
+            new_path.push(hop);
+            routes.push(new_path);
+        }
         return;
     }
     visited.insert(token.to_string());
@@ -367,22 +727,34 @@ fn dfs(
             continue;
         }
         path.push(hop.clone());
-        dfs(onchain, &hop.next, network, path, visited, pegged_tokens, hops, routes);
+        dfs(
+            onchain,
+            &hop.next,
+            network,
+            path,
+            visited,
+            pegged_tokens,
+            hops,
+            routes,
+            log,
+        );
         path.pop();
     }
 }
 
-fn find_path_subgraph(onchain: &mut OnChainConfig, token: &str) -> Info {
+fn find_path_subgraph(onchain: &mut OnChainConfig, token: &str, log: &mut DiscoveryLog) -> Info {
     let network = onchain.chain_name.clone();
     let pegged_tokens = get_pegged_token(network.as_str());
 
     if pegged_tokens.values().any(|v| v == token) {
-        let hop = get_pegged_next_hop(onchain, token, network.as_str());
-        return with_info(vec![vec![hop]], network.as_str(), token);
+        let routes = get_pegged_next_hop(onchain, token, network.as_str(), log)
+            .map(|hop| vec![vec![hop]])
+            .unwrap_or_default();
+        return with_info(routes, network.as_str(), token);
     }
 
     let mut known: HashSet<String> = HashSet::new();
-    let hops = get_all_hops(onchain, token, network.as_str(), 0, &mut known);
+    let hops = get_all_hops(onchain, token, network.as_str(), 0, &mut known, log);
 
     let mut routes: Vec<Vec<PairData>> = vec![];
 
@@ -395,6 +767,7 @@ fn find_path_subgraph(onchain: &mut OnChainConfig, token: &str) -> Info {
         &pegged_tokens,
         &hops,
         &mut routes,
+        log,
     );
 
     with_info(routes, network.as_str(), token)
@@ -411,7 +784,7 @@ mod tests {
     fn test_get_pegged_next_hop() {
         let mut config = OnChainConfig::new(BSC, 22055611);
         let token = "0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c";
-        let v = get_pegged_next_hop(&mut config, token, "bsc");
+        let v = get_pegged_next_hop(&mut config, token, "bsc", &mut DiscoveryLog::default()).unwrap();
         assert!(v.src == "pegged_weth");
     }
 
@@ -425,6 +798,7 @@ mod tests {
             "bsc",
             0,
             &mut known,
+            &mut DiscoveryLog::default(),
         );
         assert!(!v.is_empty());
     }
@@ -432,7 +806,13 @@ mod tests {
     #[test]
     fn test_get_pair() {
         let mut config = OnChainConfig::new(ETH, 19021411);
-        let v = get_pair(&mut config, "0x06450dEe7FD2Fb8E39061434BAbCFC05599a6Fb8", "eth", false);
+        let v = get_pair(
+            &mut config,
+            "0x06450dEe7FD2Fb8E39061434BAbCFC05599a6Fb8",
+            "eth",
+            false,
+            &mut DiscoveryLog::default(),
+        );
         assert!(!v.is_empty());
         for p in v {
             println!("pair: {:?}", p);
@@ -460,4 +840,69 @@ mod tests {
         assert!(!v.swaps.is_empty());
         assert!(!v.weth_address.is_zero());
     }
+
+    #[test]
+    fn test_token_context_registry() {
+        let mut config = OnChainConfig::new(BSC, 22055611);
+        let mut registry = TokenContextRegistry::new();
+        let token = EVMAddress::from_str("0xcff086ead392ccb39c49ecda8c974ad5238452ac").unwrap();
+        let first = registry.get_or_fetch(&mut config, token);
+        let second = registry.get_or_fetch(&mut config, token);
+        assert_eq!(first.swaps.len(), second.swaps.len());
+        assert_eq!(registry.len(), 1);
+
+        // re-forking at another block invalidates the context
+        config.block_number = format!("0x{:x}", 22055612);
+        registry.get_or_fetch(&mut config, token);
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_fetch_uniswap_path_verbose() {
+        let mut config = OnChainConfig::new(BSC, 22055611);
+        let (v, log) = fetch_uniswap_path_verbose(
+            &mut config,
+            EVMAddress::from_str("0xcff086ead392ccb39c49ecda8c974ad5238452ac").unwrap(),
+        );
+        assert_eq!(v.swaps.len(), log.routes.len());
+        assert!(log.candidates.iter().any(|c| c.verdict == PairVerdict::Kept));
+    }
+
+    #[test]
+    fn test_token_discovery_stats() {
+        let address = |idx: u8| EVMAddress::from_slice(&[idx; 20]);
+        let reserves = (EVMU256::from(1), EVMU256::from(1));
+        let mut ctx = TokenContext::from_pairs(
+            address(1),
+            address(0x10),
+            &[(address(0x80), address(0x10), reserves)],
+            UniswapInfo::default(),
+        );
+        // a second route through the same pair and another one
+        ctx.swaps.extend(
+            TokenContext::from_pairs(
+                address(1),
+                address(0x10),
+                &[
+                    (address(0x80), address(2), reserves),
+                    (address(0x81), address(0x10), reserves),
+                ],
+                UniswapInfo::default(),
+            )
+            .swaps,
+        );
+        let mut log = DiscoveryLog::default();
+        for verdict in [PairVerdict::Kept, PairVerdict::Illiquid, PairVerdict::LowLiquidityRank] {
+            log.record("token", PairData::default(), verdict);
+        }
+
+        // illiquid pairs are only logged
+        let stats = TokenDiscoveryStats::new(address(1), &ctx, &log);
+        assert_eq!((stats.paths, stats.pairs, stats.pruned_pairs), (2, 2, 1));
+
+        let mut meta = SwapDiscoveryMetadata::default();
+        meta.record(stats.clone());
+        meta.record(stats);
+        assert_eq!(meta.tokens.len(), 1);
+    }
 }
@@ -1,11 +1,17 @@
-use std::{collections::HashMap, fmt::Debug, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter},
+    sync::Arc,
+};
 
 use bytes::Bytes;
 use libafl::schedulers::Scheduler;
 use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::warn;
 
-use super::{uniswap::CODE_REGISTRY, PairContext, UniswapInfo};
+use super::{balance_slot::known_balance, uniswap::CODE_REGISTRY, PairContext, SwapImpact, UniswapInfo};
 use crate::{
     evm::{
         types::{EVMAddress, EVMFuzzState, EVMU256},
@@ -16,7 +22,71 @@ use crate::{
     input::ConciseSerde,
     is_call_success,
 };
-#[derive(Clone, Debug, Default)]
+/// Set while the routes of a token are validated at setup. Only then the
+/// transfers found blocked are recorded in the pairs: during fuzzing a transfer
+/// reverts in the state of one input, which says nothing about the others.
+pub static mut VALIDATING_ROUTES: bool = false;
+
+/// Transfers of a token through a pair that revert although the sender has
+/// enough balance, i.e., the token blacklists (or anti-bots) the pair or the
+/// caller
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferBlock {
+    /// Transfers out of the pair revert, so swaps into the token fail
+    Pair,
+    /// Transfers from the caller to the pair revert, so the token cannot be
+    /// sold
+    Caller,
+}
+
+/// Why a swap through a pair failed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SwapFailure {
+    /// The pair is locked, i.e., in the middle of another swap
+    Locked,
+    /// `balanceOf` of the token reverted or returned garbage
+    BalanceOf { token: EVMAddress },
+    /// The transfer of the token into / out of the pair reverted
+    Transfer { token: EVMAddress, reason: String },
+    /// The reserves of the pair would not fit in uint112
+    ReserveOverflow,
+}
+
+impl Display for SwapFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwapFailure::Locked => write!(f, "pair is locked"),
+            SwapFailure::BalanceOf { token } => write!(f, "balanceOf of token {:?} failed", token),
+            SwapFailure::Transfer { token, reason } => {
+                write!(f, "transfer of token {:?} reverted: {}", token, reason)
+            }
+            SwapFailure::ReserveOverflow => write!(f, "reserves overflow uint112"),
+        }
+    }
+}
+
+/// Human readable revert reason of `data`, decoding `Error(string)` and
+/// `Panic(uint256)`
+pub fn decode_revert_reason(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "no revert data".to_string();
+    }
+    match data.split_at(4.min(data.len())) {
+        // Error(string)
+        ([0x08, 0xc3, 0x79, 0xa0], args) if args.len() >= 64 => {
+            let len = EVMU256::from_be_slice(&args[32..64]);
+            let len = usize::try_from(len).unwrap_or(usize::MAX).min(args.len() - 64);
+            String::from_utf8_lossy(&args[64..64 + len]).to_string()
+        }
+        // Panic(uint256)
+        ([0x4e, 0x48, 0x7b, 0x71], args) if args.len() >= 32 => {
+            format!("panic {:#x}", EVMU256::from_be_slice(&args[..32]))
+        }
+        _ => format!("0x{}", hex::encode(data)),
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct UniswapPairContext {
     pub pair_address: EVMAddress,
     pub in_token_address: EVMAddress,
@@ -24,10 +94,20 @@ pub struct UniswapPairContext {
     pub side: u8,
     pub uniswap_info: Arc<UniswapInfo>,
     pub initial_reserves: (EVMU256, EVMU256),
+    /// Set once a transfer through the pair is found to be blocked when the
+    /// routes are validated, see [`VALIDATING_ROUTES`]
+    pub blocked: Cell<Option<TransferBlock>>,
+    /// Why the last swap through the pair failed
+    pub last_failure: RefCell<Option<SwapFailure>>,
 }
 
 const MAX_RESERVE: u128 = 1 << 112;
 
+/// Amounts out computed by the pairs in the current transaction, keyed by
+/// (pair, side, amount in, reserve in, reserve out), so that a swap is not
+/// computed again on the same reserves
+pub type SwapAmountCache = HashMap<(EVMAddress, u8, EVMU256, EVMU256, EVMU256), EVMU256>;
+
 impl UniswapPairContext {
     pub fn calculate_amounts_out(&self, amount_in: EVMU256, reserve_in: EVMU256, reserve_out: EVMU256) -> EVMU256 {
         // println!("fee: {}", self.uniswap_info.pool_fee);
@@ -40,6 +120,38 @@ impl UniswapPairContext {
 
         numerator / denominator
     }
+
+    /// [`UniswapPairContext::calculate_amounts_out`] through `cache`, the
+    /// reserves in the key invalidate the amounts once they change
+    pub fn cached_amounts_out(
+        &self,
+        cache: &mut SwapAmountCache,
+        side: u8,
+        amount_in: EVMU256,
+        reserve_in: EVMU256,
+        reserve_out: EVMU256,
+    ) -> EVMU256 {
+        *cache
+            .entry((self.pair_address, side, amount_in, reserve_in, reserve_out))
+            .or_insert_with(|| self.calculate_amounts_out(amount_in, reserve_in, reserve_out))
+    }
+
+    /// Record that transfers of `token` through the pair are blocked
+    fn mark_blocked(&self, token: &EVMAddress, block: TransferBlock) {
+        if unsafe { VALIDATING_ROUTES } && self.blocked.get().is_none() {
+            warn!(
+                "token {:?} blocks transfers ({:?}) through pair {:?}, routes through the pair are skipped",
+                token, block, self.pair_address
+            );
+            self.blocked.set(Some(block));
+        }
+    }
+
+    /// Record why the current swap failed, always returns `None`
+    fn fail<T>(&self, failure: SwapFailure) -> Option<T> {
+        *self.last_failure.borrow_mut() = Some(failure);
+        None
+    }
 }
 
 pub fn reserve_parser(reserve_slot: &EVMU256) -> (EVMU256, EVMU256) {
@@ -74,6 +186,37 @@ pub fn balance_of_bytes(addr: &EVMAddress) -> Bytes {
     Bytes::from(ret)
 }
 
+/// `balanceOf(who)` of `token`, `None` if the call fails
+pub fn balance_of<VS, CI, SC>(
+    token: &EVMAddress,
+    who: &EVMAddress,
+    state: &mut EVMFuzzState,
+    vm: &mut EVMExecutor<VS, CI, SC>,
+) -> Option<EVMU256>
+where
+    VS: VMStateT + Default + 'static,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+    SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+{
+    let call = Contract::new_with_context_analyzed(
+        balance_of_bytes(who),
+        get_code_tokens!(*token, vm, state),
+        &CallContext {
+            address: *token,
+            caller: EVMAddress::default(),
+            code_address: *token,
+            apparent_value: EVMU256::ZERO,
+            scheme: CallScheme::Call,
+        },
+    );
+    let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+    let ir = vm.host.run_inspect(&mut interp, state);
+    if !is_call_success!(ir) {
+        return None;
+    }
+    EVMU256::try_from_be_slice(interp.return_value().to_vec().as_slice())
+}
+
 impl UniswapPairContext {
     pub fn initial_transfer<VS, CI, SC>(
         &self,
@@ -103,9 +246,15 @@ impl UniswapPairContext {
         let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
         let ir = vm.host.run_inspect(&mut interp, state);
         if !is_call_success!(ir) {
-            // println!("transfer failed1");
-            // println!("return value: {:?}", interp.return_value());
-            None
+            let reason = decode_revert_reason(&interp.return_value());
+            // the caller could afford the transfer, so the token blocks it
+            if balance_of(&self.in_token_address, src, state, vm) >= Some(amount) {
+                self.mark_blocked(&self.in_token_address, TransferBlock::Caller);
+            }
+            self.fail(SwapFailure::Transfer {
+                token: self.in_token_address,
+                reason,
+            })
         } else {
             // println!("transfer success");
             Some(())
@@ -141,74 +290,39 @@ impl PairContext for UniswapPairContext {
         macro_rules! balanceof_token {
             ($dir: expr, $who: expr) => {{
                 let addr = if $dir { in_token_address } else { out_token_address };
-                let call = Contract::new_with_context_analyzed(
-                    balance_of_bytes($who),
-                    if $dir {
-                        in_token_code.clone()
-                    } else {
-                        out_token_code.clone()
-                    },
-                    &CallContext {
-                        address: addr,
-                        caller: EVMAddress::default(),
-                        code_address: addr,
-                        apparent_value: EVMU256::ZERO,
-                        scheme: CallScheme::Call,
-                    },
-                );
-                let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
-                let ir = vm.host.run_inspect(&mut interp, state);
-                if !is_call_success!(ir) {
-                    return None;
-                }
-                let in_balance =
-                    if let Some(num) = EVMU256::try_from_be_slice(interp.return_value().to_vec().as_slice()) {
-                        num
-                    } else {
-                        // println!("balance of failed");
-                        // println!("return value: {:?}", interp.return_value());
-                        return None;
-                    };
-
-                // println!("balance of {:?}@{:?}: {:?}", $who, addr, in_balance);
-                in_balance
-            }};
-        }
-
-        // transfer in token to pair
-        macro_rules! transfer_token {
-            ($dir: expr, $who: expr, $dst: expr, $amt: expr) => {{
-                let addr = if $dir { in_token_address } else { out_token_address };
-                let call = Contract::new_with_context_analyzed(
-                    transfer_bytes($dst, $amt),
-                    if $dir {
-                        in_token_code.clone()
-                    } else {
-                        out_token_code.clone()
-                    },
-                    &CallContext {
-                        address: addr,
-                        caller: $who,
-                        code_address: addr,
-                        apparent_value: EVMU256::ZERO,
-                        scheme: CallScheme::Call,
-                    },
-                );
-
-                // println!("transfer {:?}@{:?} for {:?} => {:?}", $amt, addr, $who, $dst);
-                // println!("pre_vm_state: {:?}", vm.host.evmstate.state);
-
-                let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
-
-                let ir = vm.host.run_inspect(&mut interp, state);
-                // println!("bytes: {:?}", transfer_bytes($dst, $amt));
-                // println!("from: {:?} => {:?}, {:?}", $who, $dst, addr);
-                if !is_call_success!(ir) {
-                    // println!("transfer failed2");
-                    // println!("return value: {:?}", interp.return_value());
-                    return None;
+                if let Some(balance) = known_balance(state, &vm.host.evmstate, addr, $who) {
+                    balance
+                } else {
+                    let call = Contract::new_with_context_analyzed(
+                        balance_of_bytes($who),
+                        if $dir {
+                            in_token_code.clone()
+                        } else {
+                            out_token_code.clone()
+                        },
+                        &CallContext {
+                            address: addr,
+                            caller: EVMAddress::default(),
+                            code_address: addr,
+                            apparent_value: EVMU256::ZERO,
+                            scheme: CallScheme::Call,
+                        },
+                    );
+                    let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+                    let ir = vm.host.run_inspect(&mut interp, state);
+                    if !is_call_success!(ir) {
+                        return self.fail(SwapFailure::BalanceOf { token: addr });
+                    }
+                    let in_balance =
+                        if let Some(num) = EVMU256::try_from_be_slice(interp.return_value().to_vec().as_slice()) {
+                            num
+                        } else {
+                            return self.fail(SwapFailure::BalanceOf { token: addr });
+                        };
+
+                    // println!("balance of {:?}@{:?}: {:?}", $who, addr, in_balance);
+                    in_balance
                 }
-                // println!("transfer success");
             }};
         }
 
@@ -216,8 +330,7 @@ impl PairContext for UniswapPairContext {
         if let Some(slots) = vm.host.evmstate.state.get(&self.pair_address) {
             if let Some(slot) = slots.get(&EVMU256::from(0xc)) {
                 if *slot == EVMU256::ZERO {
-                    // locked
-                    return None;
+                    return self.fail(SwapFailure::Locked);
                 }
             }
         }
@@ -240,7 +353,7 @@ impl PairContext for UniswapPairContext {
 
         // 2. get balance of pair's token
         let new_balance = balanceof_token!(true, &self.pair_address);
-        balanceof_token!(false, &self.pair_address);
+        let out_balance = balanceof_token!(false, &self.pair_address);
 
         // 3. calculate amount out
         let amount_in = new_balance - reserve_in;
@@ -248,10 +361,38 @@ impl PairContext for UniswapPairContext {
         // println!("original balance: {:?}", reserve_in);
         // println!("new balance: {:?}", new_balance);
 
-        let amount_out = self.calculate_amounts_out(amount_in, reserve_in, reserve_out);
+        let amount_out = self.cached_amounts_out(
+            &mut vm.host.evmstate.swap_amount_cache,
+            side,
+            amount_in,
+            reserve_in,
+            reserve_out,
+        );
 
-        // 3.5 transfer out token
-        transfer_token!(false, self.pair_address, next, amount_out);
+        // 3.5 transfer out token, the pair holds enough, so a revert means the
+        // token blocks transfers out of the pair
+        let call = Contract::new_with_context_analyzed(
+            transfer_bytes(next, amount_out),
+            out_token_code.clone(),
+            &CallContext {
+                address: out_token_address,
+                caller: self.pair_address,
+                code_address: out_token_address,
+                apparent_value: EVMU256::ZERO,
+                scheme: CallScheme::Call,
+            },
+        );
+        let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+        let ir = vm.host.run_inspect(&mut interp, state);
+        if !is_call_success!(ir) {
+            if amount_out <= out_balance {
+                self.mark_blocked(&out_token_address, TransferBlock::Pair);
+            }
+            return self.fail(SwapFailure::Transfer {
+                token: out_token_address,
+                reason: decode_revert_reason(&interp.return_value()),
+            });
+        }
 
         // 4. update reserve
         let new_reserve_0 = if side == 0 {
@@ -267,7 +408,7 @@ impl PairContext for UniswapPairContext {
 
         let max_reserve = EVMU256::from(MAX_RESERVE);
         if new_reserve_0 > max_reserve || new_reserve_1 > max_reserve {
-            return None;
+            return self.fail(SwapFailure::ReserveOverflow);
         }
 
         // #[cfg(test)]
@@ -302,6 +443,20 @@ impl PairContext for UniswapPairContext {
             .flashloan_data
             .oracle_recheck_reserve
             .insert(self.pair_address);
+
+        // 6. record the price impact against the spot rate of the initial reserves
+        let (initial_in, initial_out) = if side == 0 {
+            (self.initial_reserves.0, self.initial_reserves.1)
+        } else {
+            (self.initial_reserves.1, self.initial_reserves.0)
+        };
+        vm.host.evmstate.swap_impacts.push(SwapImpact::new(
+            self.pair_address,
+            amount_in,
+            amount_out,
+            initial_in,
+            initial_out,
+        ));
         Some((*next, amount_out))
     }
 
@@ -309,3 +464,60 @@ impl PairContext for UniswapPairContext {
         "uniswap_v2".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_amounts_out() {
+        let pair = UniswapPairContext {
+            pair_address: EVMAddress::from_slice(&[1; 20]),
+            ..Default::default()
+        };
+        let mut cache = SwapAmountCache::new();
+        let (amount_in, reserve_in, reserve_out) = (EVMU256::from(10), EVMU256::from(100), EVMU256::from(200));
+        let amount_out = pair.cached_amounts_out(&mut cache, 0, amount_in, reserve_in, reserve_out);
+        assert_eq!(
+            amount_out,
+            pair.calculate_amounts_out(amount_in, reserve_in, reserve_out)
+        );
+        assert_eq!(cache.len(), 1);
+
+        // the second lookup with the same reserves is served from the cache
+        let key = (pair.pair_address, 0, amount_in, reserve_in, reserve_out);
+        cache.insert(key, EVMU256::from(42));
+        assert_eq!(
+            pair.cached_amounts_out(&mut cache, 0, amount_in, reserve_in, reserve_out),
+            EVMU256::from(42)
+        );
+        // other reserves are computed
+        assert_eq!(
+            pair.cached_amounts_out(&mut cache, 0, amount_in, EVMU256::from(1000), reserve_out),
+            pair.calculate_amounts_out(amount_in, EVMU256::from(1000), reserve_out)
+        );
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_decode_revert_reason() {
+        // Error("UniswapV2: K")
+        let error = hex::decode(concat!(
+            "08c379a0",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "000000000000000000000000000000000000000000000000000000000000000c",
+            "556e697377617056323a204b0000000000000000000000000000000000000000"
+        ))
+        .unwrap();
+        assert_eq!(decode_revert_reason(&error), "UniswapV2: K");
+        // Panic(0x11), i.e., arithmetic overflow
+        let panic = hex::decode(concat!(
+            "4e487b71",
+            "0000000000000000000000000000000000000000000000000000000000000011"
+        ))
+        .unwrap();
+        assert_eq!(decode_revert_reason(&panic), "panic 0x11");
+        assert_eq!(decode_revert_reason(&[]), "no revert data");
+        assert_eq!(decode_revert_reason(&[0xde, 0xad]), "0xdead");
+    }
+}
@@ -4,9 +4,9 @@ use alloy_primitives::hex;
 use bytes::Bytes;
 use libafl::schedulers::Scheduler;
 use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use super::{uniswap::CODE_REGISTRY, PairContext};
+use super::{uniswap::CODE_REGISTRY, PairContext, WethAction};
 use crate::{
     evm::{
         types::{EVMAddress, EVMFuzzState, EVMU256, EVMU512},
@@ -19,7 +19,7 @@ use crate::{
     scale,
 };
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct WethContext {
     pub weth_address: EVMAddress,
 }
@@ -56,6 +56,7 @@ impl PairContext for WethContext {
                 .flashloan_data
                 .oracle_recheck_balance
                 .insert(self.weth_address);
+            vm.host.evmstate.weth_actions.push(WethAction::Deposit(amount));
         } else {
             // println!("sold {:?} weth", amount);
             // sell
@@ -65,6 +66,7 @@ impl PairContext for WethContext {
                 .flashloan_data
                 .oracle_recheck_balance
                 .insert(self.weth_address);
+            vm.host.evmstate.weth_actions.push(WethAction::Withdraw(amount));
         }
 
         // todo: fix real balance
@@ -4,11 +4,13 @@ use libafl::prelude::HasRand;
 use libafl_bolts::bolts_prelude::{Rand, RomuDuoJrRand};
 use primitive_types::H160;
 use revm_primitives::{ruint::aliases::U512, Bytecode, B160, U256};
+use serde::Serializer;
 
 /// Common generic types for EVM fuzzing
 use crate::evm::input::{ConciseEVMInput, EVMInput};
 use crate::{
     evm::{
+        config::Config,
         mutator::FuzzMutator,
         scheduler::PowerABIScheduler,
         vm::{EVMExecutor, EVMState},
@@ -69,6 +71,20 @@ pub type EVMFuzzExecutor<OT> = FuzzExecutor<
 
 pub type EVMQueueExecutor = EVMExecutor<EVMState, ConciseEVMInput, PowerABIScheduler<EVMFuzzState>>;
 
+pub type EVMConfig = Config<
+    EVMState,
+    EVMAddress,
+    Bytecode,
+    Bytes,
+    EVMAddress,
+    EVMU256,
+    Vec<u8>,
+    EVMInput,
+    EVMFuzzState,
+    ConciseEVMInput,
+    EVMQueueExecutor,
+>;
+
 /// convert array of 20x u8 to H160
 pub fn convert_h160(v: [u8; 20]) -> H160 {
     v.into()
@@ -151,12 +167,33 @@ pub fn checksum(address: &EVMAddress) -> String {
         })
 }
 
+/// Serialize an address as a checksum address. Addresses are deserialized
+/// case-insensitively, so non-checksum addresses are still accepted.
+pub fn serialize_checksum<S>(address: &EVMAddress, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&checksum(address))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::evm::types::{as_u64, EVMU256};
+    use std::str::FromStr;
+
+    use crate::evm::types::{as_u64, checksum, EVMAddress, EVMU256};
 
     #[test]
     fn test_as_u64() {
         assert_eq!(as_u64(EVMU256::from(100)), 100)
     }
+
+    #[test]
+    fn test_checksum() {
+        let address = EVMAddress::from_str("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        assert_eq!(checksum(&address), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        assert_eq!(
+            EVMAddress::from_str("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap(),
+            address
+        );
+    }
 }
@@ -7,6 +7,7 @@ use std::{
     fmt::Debug,
     hash::{Hash, Hasher},
     marker::PhantomData,
+    mem::size_of,
     ops::Deref,
     rc::Rc,
     sync::Arc,
@@ -15,7 +16,7 @@ use std::{
 use bytes::Bytes;
 /// EVM executor implementation
 use itertools::Itertools;
-use libafl::schedulers::Scheduler;
+use libafl::{prelude::HasMetadata, schedulers::Scheduler};
 use revm_interpreter::{
     BytecodeLocked,
     CallContext,
@@ -33,15 +34,29 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{debug, error};
 
 use super::{input::EVMInput, middlewares::reentrancy::ReentrancyData, types::EVMFuzzState};
-use crate::{evm::tokens::SwapData, generic_vm::vm_state};
 #[allow(unused_imports)]
 use crate::{
     evm::{
+        background::{BackgroundPosition, BackgroundTxn, BackgroundTxnProvider},
         bytecode_analyzer,
-        host::{FuzzHost, CMP_MAP, COVERAGE_NOT_CHANGED, JMP_MAP, READ_MAP, STATE_CHANGE, WRITE_MAP},
-        input::{ConciseEVMInput, EVMInputT, EVMInputTy},
+        host::{
+            CallReturn,
+            FuzzHost,
+            StorageWrite,
+            TransferFromCall,
+            CMP_MAP,
+            COVERAGE_NOT_CHANGED,
+            JMP_MAP,
+            READ_MAP,
+            REAL_BALANCE,
+            STATE_CHANGE,
+            WRITE_MAP,
+        },
+        input::{BlockAdvance, ConciseEVMInput, EVMInputT, EVMInputTy},
+        logs::EVMLog,
         middlewares::middleware::Middleware,
-        onchain::flashloan::FlashloanData,
+        onchain::{fee::TX_FEE_MODEL, flashloan::FlashloanData},
+        permit::{permit_owner, CallerKeyMetadata, DOMAIN_SEPARATOR},
         types::{float_scale_to_u512, EVMAddress, EVMU256, EVMU512},
         vm::Constraint::{NoLiquidation, Value},
     },
@@ -51,9 +66,25 @@ use crate::{
     },
     input::{ConciseSerde, VMInputT},
     invoke_middlewares,
+    scale,
     state::{HasCaller, HasCurrentInputIdx, HasItyState},
     state_input::StagedVMState,
 };
+use crate::{
+    evm::{
+        selector_stats::SelectorExecutionMetadata,
+        state_diff::StateDiff,
+        state_schema::StoredEVMState,
+        tokens::{
+            v2_transformer::{balance_of, SwapAmountCache},
+            SwapData,
+            SwapImpact,
+            SwapType,
+            WethAction,
+        },
+    },
+    generic_vm::vm_state,
+};
 
 pub const MEM_LIMIT: u64 = 500 * 1024;
 const MAX_POST_EXECUTION: usize = 10;
@@ -226,7 +257,10 @@ impl Hash for PostExecutionCtx {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+/// Serialized with a versioned schema of the fields that persist across
+/// transactions, see [`crate::evm::state_schema`]
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(try_from = "StoredEVMState")]
 pub struct EVMState {
     /// State of the EVM, which is mapping of EVMU256 slot to EVMU256 value for
     /// each contract
@@ -235,6 +269,16 @@ pub struct EVMState {
     /// Balance of addresses
     pub balance: HashMap<EVMAddress, EVMU256>,
 
+    /// Blocks mined since the fork by the block boundaries of the
+    /// transactions leading to this state
+    #[serde(default)]
+    pub block_offset: BlockAdvance,
+
+    /// Nonces of the permits signed for the callers with known private keys,
+    /// by token and owner
+    #[serde(default)]
+    pub permit_nonces: HashMap<EVMAddress, HashMap<EVMAddress, EVMU256>>,
+
     /// Post execution context
     /// If control leak happens, we add the post execution context to the VM
     /// state, which contains all information needed to continue execution.
@@ -259,6 +303,20 @@ pub struct EVMState {
     pub typed_bug: HashSet<(String, (EVMAddress, usize))>,
     #[serde(skip)]
     pub arbitrary_calls: HashSet<(EVMAddress, EVMAddress, usize)>,
+    /// Successful `transferFrom` calls in the current transaction
+    #[serde(skip)]
+    pub transfer_froms: Vec<TransferFromCall>,
+    /// Return data of the calls in the current transaction, in the order
+    /// they returned
+    #[serde(skip)]
+    pub call_returns: Vec<CallReturn>,
+    /// Storage writes in the current transaction
+    #[serde(skip)]
+    pub storage_writes: Vec<StorageWrite>,
+    /// Slots read by accounts' own code before they delegatecall in the
+    /// current transaction, e.g., the implementation slot of a proxy
+    #[serde(skip)]
+    pub delegating_reads: HashSet<(EVMAddress, EVMU256)>,
     // integer overflow in sol
     #[serde(skip)]
     pub integer_overflow: HashSet<(EVMAddress, usize, &'static str)>,
@@ -266,6 +324,22 @@ pub struct EVMState {
     pub reentrancy_metadata: ReentrancyData,
     #[serde(skip)]
     pub swap_data: SwapData,
+    /// Pair addresses of the swap route taken by each swap type (buy / sell)
+    /// in the current transaction
+    #[serde(skip)]
+    pub swap_routes: HashMap<String, Vec<EVMAddress>>,
+    /// Amounts out computed by the pairs in the current transaction
+    #[serde(skip)]
+    pub swap_amount_cache: SwapAmountCache,
+    /// Price impact of each hop of the swaps in the current transaction
+    #[serde(skip)]
+    pub swap_impacts: Vec<SwapImpact>,
+    /// ETH wrapped or unwrapped through WETH in the current transaction
+    #[serde(skip)]
+    pub weth_actions: Vec<WethAction>,
+    /// Background transaction run with the current transaction
+    #[serde(skip)]
+    pub background_txn: Option<(BackgroundPosition, BackgroundTxn)>,
 }
 
 pub trait EVMStateT {
@@ -282,12 +356,17 @@ impl EVMStateT for EVMState {
 }
 
 impl VMStateT for EVMState {
+    type Log = EVMLog;
+
     /// Calculate the hash of the VM state
     fn get_hash(&self) -> u64 {
         let mut s = DefaultHasher::new();
         for i in self.post_execution.iter() {
             i.hash(&mut s);
         }
+        if !self.block_offset.is_zero() {
+            self.block_offset.hash(&mut s);
+        }
         for i in self.state.iter().sorted_by_key(|k| k.0) {
             i.0 .0.hash(&mut s);
             for j in i.1.iter() {
@@ -328,8 +407,10 @@ impl VMStateT for EVMState {
     #[cfg(feature = "full_trace")]
     fn get_flashloan(&self) -> String {
         format!(
-            "earned: {:?}, owed: {:?}",
-            self.flashloan_data.earned, self.flashloan_data.owed
+            "earned: {:?}, owed: {:?}, net: {:?}",
+            self.flashloan_data.earned,
+            self.flashloan_data.owed,
+            self.flashloan_data.net_profit()
         )
     }
 
@@ -353,6 +434,18 @@ impl VMStateT for EVMState {
     fn get_swap_data(&self) -> HashMap<String, vm_state::SwapInfo> {
         self.swap_data.to_generic()
     }
+
+    fn describe_changes(&self, after: &Self) -> String {
+        self.diff(after).to_string()
+    }
+
+    fn estimated_size(&self) -> usize {
+        let slots = self.state.values().map(|slots| slots.len()).sum::<usize>();
+        size_of::<Self>() +
+            self.state.len() * (size_of::<EVMAddress>() + size_of::<HashMap<EVMU256, EVMU256>>()) +
+            slots * 2 * size_of::<EVMU256>() +
+            self.balance.len() * (size_of::<EVMAddress>() + size_of::<EVMU256>())
+    }
 }
 
 impl EVMState {
@@ -372,6 +465,16 @@ impl EVMState {
         self.state.get_mut(address)
     }
 
+    /// Return data of the last successful call to `addr` with `selector` in
+    /// the current transaction
+    pub fn return_data_of(&self, addr: EVMAddress, selector: [u8; 4]) -> Option<&[u8]> {
+        self.call_returns
+            .iter()
+            .rev()
+            .find(|ret| ret.address == addr && ret.selector == selector)
+            .map(|ret| ret.data.as_ref())
+    }
+
     /// Insert all storage slots of a specific contract
     pub fn insert(&mut self, address: EVMAddress, storage: HashMap<EVMU256, EVMU256>) {
         self.state.insert(address, storage);
@@ -387,6 +490,15 @@ impl EVMState {
         self.balance.insert(address, balance);
     }
 
+    /// Nonce of the next permit of `owner` on `token`
+    pub fn permit_nonce(&self, token: &EVMAddress, owner: &EVMAddress) -> EVMU256 {
+        self.permit_nonces
+            .get(token)
+            .and_then(|nonces| nonces.get(owner))
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// Loads a storage slot from an address.
     pub fn sload(&self, address: EVMAddress, slot: EVMU256) -> Option<EVMU256> {
         self.state.get(&address).and_then(|slots| slots.get(&slot).cloned())
@@ -396,6 +508,44 @@ impl EVMState {
     pub fn sstore(&mut self, address: EVMAddress, slot: EVMU256, value: EVMU256) {
         self.state.entry(address).or_default().insert(slot, value);
     }
+
+    /// Balances, storage slots and accounts changed from this state to `other`
+    pub fn diff(&self, other: &EVMState) -> StateDiff {
+        StateDiff::new(self, other)
+    }
+
+    /// Take a snapshot of the state, which can be restored later (possibly
+    /// multiple times) with [`EVMState::restore`]
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            inner: Rc::new(self.clone()),
+        }
+    }
+
+    /// Restore the state to a snapshot
+    pub fn restore(&mut self, snapshot: StateSnapshot) {
+        *self = snapshot.into_state();
+    }
+}
+
+/// Snapshot of an [`EVMState`]. Cloning a snapshot is cheap, the state is only
+/// copied when a snapshot shared with other clones is restored.
+#[derive(Clone, Debug)]
+pub struct StateSnapshot {
+    inner: Rc<EVMState>,
+}
+
+impl StateSnapshot {
+    /// Get the state of the snapshot without copying it
+    pub fn state(&self) -> &EVMState {
+        &self.inner
+    }
+
+    /// Convert the snapshot back into a state, only copies the state when
+    /// other clones of the snapshot exist
+    pub fn into_state(self) -> EVMState {
+        Rc::try_unwrap(self.inner).unwrap_or_else(|shared| (*shared).clone())
+    }
 }
 
 /// Is current EVM execution fast call
@@ -419,6 +569,10 @@ where
     pub deployer: EVMAddress,
     /// Known arbitrary (caller,pc)
     pub _known_arbitrary: HashSet<(EVMAddress, usize)>,
+    /// Real transactions interleaved with the fuzzed transactions
+    pub background_txns: BackgroundTxnProvider,
+    /// Gas used by the last executed transaction
+    pub last_gas_used: u64,
     phandom: PhantomData<(EVMInput, VS, CI)>,
 }
 
@@ -451,6 +605,12 @@ macro_rules! init_host {
     ($host:expr) => {
         $host.current_self_destructs = vec![];
         $host.current_arbitrary_calls = vec![];
+        $host.current_transfer_froms = vec![];
+        $host.current_logs = vec![];
+        $host.current_call_returns = vec![];
+        $host.call_frames = vec![];
+        $host.current_storage_writes = vec![];
+        $host.current_delegating_reads = HashSet::new();
         $host.call_count = 0;
         $host.jumpi_trace = 37;
         $host.current_typed_bug = vec![];
@@ -521,6 +681,8 @@ where
             host: fuzz_host,
             deployer,
             _known_arbitrary: Default::default(),
+            background_txns: Default::default(),
+            last_gas_used: 0,
             phandom: PhantomData,
         }
     }
@@ -556,6 +718,14 @@ where
             self.host.jumpi_trace = 37;
             self.host.current_self_destructs = vec![];
             self.host.current_arbitrary_calls = vec![];
+            self.host.current_transfer_froms = vec![];
+            self.host.current_logs = vec![];
+            self.host.current_call_returns = vec![];
+            self.host.call_frames = vec![];
+            self.host.current_storage_writes = vec![];
+            self.host.current_delegating_reads = HashSet::new();
+            self.host.gas_spent = 0;
+            self.host.gas_forwarded = 0;
             // Initially, there is no state change
             unsafe {
                 STATE_CHANGE = false;
@@ -569,6 +739,17 @@ where
         } else {
             input.get_origin() // vm.prank; concolic
         };
+        // a block boundary before the transaction, the rest of the sequence
+        // runs in the new block too
+        if post_exec.is_none() &&
+            let Some(advance) = input.get_block_advance()
+        {
+            self.host.evmstate.block_offset.add(advance);
+        }
+        let offset = self.host.evmstate.block_offset;
+        let block = &mut self.host.env.block;
+        block.number = block.number.saturating_add(EVMU256::from(offset.blocks));
+        block.timestamp = block.timestamp.saturating_add(EVMU256::from(offset.seconds));
         self.host.access_pattern = input.get_access_pattern().clone();
         self.host.call_count = 0;
         self.host.randomness = input.get_randomness();
@@ -629,6 +810,7 @@ where
         if is_call_success!(r) {
             r = self.host.run_inspect(&mut interp, state);
         }
+        self.host.gas_spent += interp.gas.spent();
 
         // Build the result
         let mut result = IntermediateExecutionResult {
@@ -664,13 +846,20 @@ where
         &mut self,
         input: &EVMInput,
         state: &mut EVMFuzzState,
-    ) -> ExecutionResult<EVMAddress, EVMAddress, VS, Vec<u8>, CI> {
+    ) -> ExecutionResult<EVMAddress, EVMAddress, VS, Vec<u8>, CI>
+    where
+        VS: VMStateT<Log = EVMLog>,
+    {
         // Get necessary info from input
         let mut vm_state = unsafe { input.get_state().as_any().downcast_ref_unchecked::<EVMState>().clone() };
+        vm_state.swap_routes.clear();
+        vm_state.swap_amount_cache.clear();
+        vm_state.swap_impacts.clear();
+        vm_state.weth_actions.clear();
+        vm_state.background_txn = None;
 
         // check balance
-        #[cfg(feature = "real_balance")]
-        {
+        if unsafe { REAL_BALANCE } {
             let tx_value = input.get_txn_value().unwrap_or_default();
             if tx_value > EVMU256::ZERO {
                 let caller_balance = *vm_state.get_balance(&input.get_caller()).unwrap_or(&EVMU256::ZERO);
@@ -681,6 +870,7 @@ where
                         reverted: true,
                         new_state: StagedVMState::new_uninitialized(),
                         additional_info: None,
+                        logs: vec![],
                     };
                 }
                 vm_state.set_balance(input.get_caller(), caller_balance - tx_value);
@@ -697,6 +887,38 @@ where
         }
 
         let mut cleanup = true;
+        // steps continue a transaction, which pays its fees when first executed
+        let tx_calldata = if is_step { None } else { Some(data.clone()) };
+        if !is_step {
+            state
+                .metadata_map_mut()
+                .get_or_insert_with(SelectorExecutionMetadata::default)
+                .record(input.get_contract(), &data);
+        }
+
+        let background = if is_step {
+            None
+        } else {
+            self.background_txns
+                .pick(&input.get_caller(), &data)
+                .map(|(position, txn)| (position, txn.clone()))
+        };
+        let mut background_ran = match &background {
+            Some((BackgroundPosition::Before, txn)) => self.run_background_txn(txn, &mut vm_state, state),
+            _ => false,
+        };
+
+        // permit of a caller with a known private key
+        let permit_signer = permit_owner(&data).filter(|owner| {
+            !is_step &&
+                state
+                    .metadata_map()
+                    .get::<CallerKeyMetadata>()
+                    .map_or(false, |keys| keys.get(owner).is_some())
+        });
+        if let Some(owner) = permit_signer {
+            self.record_domain_separator(input.get_contract(), owner, &vm_state, state);
+        }
 
         loop {
             unsafe {
@@ -760,6 +982,13 @@ where
             }
         }
         let mut r = r.unwrap();
+        self.last_gas_used = self.host.gas_used();
+        if let Some(calldata) = &tx_calldata &&
+            unsafe { !TX_FEE_MODEL.is_free() }
+        {
+            let fee = unsafe { TX_FEE_MODEL.fee(self.last_gas_used, calldata) };
+            r.new_state.flashloan_data.fees += EVMU512::from(fee) * scale!();
+        }
         match r.ret {
             ControlLeak |
             InstructionResult::ArbitraryExternalCallAddressBounded(_, _, _) |
@@ -770,6 +999,7 @@ where
                         reverted: true,
                         new_state: StagedVMState::new_uninitialized(),
                         additional_info: None,
+                        logs: vec![],
                     };
                 }
                 let leak_ctx = self.host.leak_ctx.clone();
@@ -823,6 +1053,10 @@ where
                 .cloned()
                 .chain(self.host.current_arbitrary_calls.iter().cloned()),
         );
+        r.new_state.transfer_froms = self.host.current_transfer_froms.clone();
+        r.new_state.call_returns = self.host.current_call_returns.clone();
+        r.new_state.storage_writes = self.host.current_storage_writes.clone();
+        r.new_state.delegating_reads = self.host.current_delegating_reads.clone();
 
         r.new_state.integer_overflow = HashSet::from_iter(
             vm_state
@@ -832,18 +1066,44 @@ where
                 .chain(self.host.current_integer_overflow.iter().cloned()),
         );
 
+        // bugs found in the background transaction are not attributed to the input
+        if let Some((BackgroundPosition::After, txn)) = &background &&
+            matches!(r.ret, InstructionResult::Return | InstructionResult::Stop)
+        {
+            background_ran = self.run_background_txn(txn, &mut r.new_state, state);
+        }
+        r.new_state.background_txn = background.filter(|_| background_ran);
+
+        if let Some(owner) = permit_signer &&
+            matches!(r.ret, InstructionResult::Return | InstructionResult::Stop)
+        {
+            *r.new_state
+                .permit_nonces
+                .entry(input.get_contract())
+                .or_default()
+                .entry(owner)
+                .or_default() += EVMU256::from(1);
+        }
+
+        let reverted = !matches!(
+            r.ret,
+            InstructionResult::Return |
+                InstructionResult::Stop |
+                InstructionResult::ControlLeak |
+                InstructionResult::SelfDestruct |
+                InstructionResult::AddressUnboundedStaticCall |
+                InstructionResult::ArbitraryExternalCallAddressBounded(_, _, _)
+        );
+        // a reverted transaction emits no logs
+        let logs = if reverted {
+            vec![]
+        } else {
+            self.host.current_logs.clone()
+        };
         unsafe {
             ExecutionResult {
                 output: r.output.to_vec(),
-                reverted: !matches!(
-                    r.ret,
-                    InstructionResult::Return |
-                        InstructionResult::Stop |
-                        InstructionResult::ControlLeak |
-                        InstructionResult::SelfDestruct |
-                        InstructionResult::AddressUnboundedStaticCall |
-                        InstructionResult::ArbitraryExternalCallAddressBounded(_, _, _)
-                ),
+                reverted,
                 new_state: StagedVMState::new_with_state(
                     VMStateT::as_any(&r.new_state).downcast_ref_unchecked::<VS>().clone(),
                 ),
@@ -852,16 +1112,74 @@ where
                 } else {
                     None
                 },
+                logs,
             }
         }
     }
 
+    /// Run a background transaction on `vm_state` and keep its changes unless
+    /// it reverts, returns whether they are kept. Transactions to contracts
+    /// not loaded are skipped.
+    fn run_background_txn(&mut self, txn: &BackgroundTxn, vm_state: &mut EVMState, state: &mut EVMFuzzState) -> bool {
+        if !self.host.code.contains_key(&txn.to) {
+            return false;
+        }
+        let backup = vm_state.clone();
+        let (_, ret) = self.fast_call_(txn.to, txn.data.clone(), vm_state, state, txn.value, txn.from);
+        if is_reverted_or_control_leak(&ret) {
+            *vm_state = backup;
+            return false;
+        }
+        true
+    }
+
+    /// Record the domain separator of `token` in `vm_state`, so that the
+    /// mutator signs the permits of `owner` on it, unless already recorded
+    fn record_domain_separator(
+        &mut self,
+        token: EVMAddress,
+        owner: EVMAddress,
+        vm_state: &EVMState,
+        state: &mut EVMFuzzState,
+    ) {
+        let recorded = state
+            .metadata_map()
+            .get::<CallerKeyMetadata>()
+            .map_or(true, |keys| keys.domain_separators.contains_key(&token));
+        if recorded || !self.host.code.contains_key(&token) {
+            return;
+        }
+        let mut view_state = vm_state.clone();
+        let (domain_separator, ret) = self.fast_call_(
+            token,
+            Bytes::from(DOMAIN_SEPARATOR.to_vec()),
+            &mut view_state,
+            state,
+            EVMU256::ZERO,
+            owner,
+        );
+        if is_reverted_or_control_leak(&ret) {
+            return;
+        }
+        let Ok(domain_separator) = <[u8; 32]>::try_from(domain_separator.as_ref()) else {
+            return;
+        };
+        state
+            .metadata_map_mut()
+            .get_mut::<CallerKeyMetadata>()
+            .unwrap()
+            .domain_separators
+            .insert(token, domain_separator);
+    }
+
     pub fn reexecute_with_middleware(
         &mut self,
         input: &EVMInput,
         state: &mut EVMFuzzState,
         middleware: Rc<RefCell<dyn Middleware<SC>>>,
-    ) {
+    ) where
+        VS: VMStateT<Log = EVMLog>,
+    {
         self.host.add_middlewares(middleware.clone());
         self.execute(input, state);
         self.host.remove_middlewares(middleware);
@@ -928,6 +1246,8 @@ where
     CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
     SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
 {
+    type Log = EVMLog;
+
     /// Deploy a contract
     fn deploy(
         &mut self,
@@ -985,12 +1305,107 @@ where
         &mut self,
         input: &EVMInput,
         state: &mut EVMFuzzState,
-    ) -> ExecutionResult<EVMAddress, EVMAddress, VS, Vec<u8>, CI> {
+    ) -> ExecutionResult<EVMAddress, EVMAddress, VS, Vec<u8>, CI>
+    where
+        VS: VMStateT<Log = Self::Log>,
+    {
         use super::host::clear_branch_status;
         clear_branch_status();
         match input.get_input_type() {
             // buy (borrow because we have infinite ETH) tokens with ETH using uniswap
             EVMInputTy::Borrow => {
+                // the token of the input and the other assets of a multi-asset
+                // borrow
+                let borrows = std::iter::once((input.get_contract(), input.get_txn_value().unwrap()))
+                    .chain(input.get_co_borrows())
+                    .collect::<Vec<_>>();
+                let token_ctxs = {
+                    let flashloan_mid = self.host.flashloan_middleware.as_ref().unwrap().deref().borrow();
+                    let flashloan_oracle = flashloan_mid.flashloan_oracle.deref().borrow();
+                    borrows
+                        .iter()
+                        .map(|(token, _)| {
+                            flashloan_oracle
+                                .known_tokens
+                                .get(token)
+                                .unwrap_or_else(|| panic!("unknown token : {:?}", token))
+                                .clone()
+                        })
+                        .collect::<Vec<_>>()
+                };
+                self.host.evmstate = unsafe {
+                    VMStateT::as_any(input.get_state())
+                        .downcast_ref_unchecked::<EVMState>()
+                        .clone()
+                };
+                self.host.evmstate.swap_routes.clear();
+                self.host.evmstate.swap_amount_cache.clear();
+                self.host.evmstate.swap_impacts.clear();
+                self.host.evmstate.weth_actions.clear();
+                let pinned_route = input.get_swap_routes().get("buy").cloned();
+                let mut buy_route = None;
+                // the assets are borrowed atomically, the input reverts if any
+                // of them can not be bought
+                let mut bought = Some(vec![]);
+                for (nth, ((token, amount), token_ctx)) in borrows.iter().zip(token_ctxs).enumerate() {
+                    let owed = self.host.evmstate.flashloan_data.owed;
+                    let earned = self.host.evmstate.flashloan_data.earned;
+                    bought = token_ctx.buy(
+                        *amount,
+                        input.get_caller(),
+                        state,
+                        self,
+                        input.get_randomness().as_slice(),
+                        // only the route of the token of the input is pinned
+                        if nth == 0 { pinned_route.as_deref() } else { None },
+                    );
+                    if bought.is_none() {
+                        break;
+                    }
+                    self.host.evmstate.flashloan_data.attribute(*token, owed, earned);
+                    if nth == 0 {
+                        buy_route = self.host.evmstate.swap_routes.get("buy").cloned();
+                    }
+                }
+                if let Some(route) = buy_route {
+                    self.host
+                        .evmstate
+                        .swap_routes
+                        .insert(String::from(SwapType::Buy), route);
+                }
+                match bought {
+                    Some(_) => unsafe {
+                        ExecutionResult {
+                            output: vec![],
+                            reverted: false,
+                            new_state: StagedVMState::new_with_state(
+                                VMStateT::as_any(&self.host.evmstate.clone())
+                                    .downcast_ref_unchecked::<VS>()
+                                    .clone(),
+                            ),
+                            additional_info: None,
+                            logs: vec![],
+                        }
+                    },
+                    None => {
+                        ExecutionResult {
+                            // we don't have enough liquidity to buy the token
+                            output: vec![],
+                            reverted: true,
+                            new_state: StagedVMState::new_with_state(unsafe {
+                                VMStateT::as_any(input.get_state())
+                                    .downcast_ref_unchecked::<VS>()
+                                    .clone()
+                            }),
+                            additional_info: None,
+                            logs: vec![],
+                        }
+                    }
+                }
+            }
+            // sell all the tokens held by the caller using uniswap, i.e., a
+            // flipped borrow
+            EVMInputTy::Liquidate => {
                 let token = input.get_contract();
                 let token_ctx = {
                     let flashloan_mid = self.host.flashloan_middleware.as_ref().unwrap().deref().borrow();
@@ -1006,14 +1421,27 @@ where
                         .downcast_ref_unchecked::<EVMState>()
                         .clone()
                 };
-                match token_ctx.buy(
-                    input.get_txn_value().unwrap(),
-                    input.get_caller(),
-                    state,
-                    self,
-                    input.get_randomness().as_slice(),
-                ) {
-                    Some(()) => unsafe {
+                self.host.evmstate.swap_routes.clear();
+                self.host.evmstate.swap_amount_cache.clear();
+                self.host.evmstate.swap_impacts.clear();
+                self.host.evmstate.weth_actions.clear();
+                let owed = self.host.evmstate.flashloan_data.owed;
+                let earned = self.host.evmstate.flashloan_data.earned;
+                let sold = balance_of(&token, &input.get_caller(), state, self)
+                    .filter(|balance| *balance > EVMU256::ZERO)
+                    .and_then(|balance| {
+                        token_ctx.sell(
+                            balance,
+                            input.get_caller(),
+                            state,
+                            self,
+                            input.get_randomness().as_slice(),
+                            input.get_swap_routes().get("sell").map(|route| route.as_slice()),
+                        )
+                    });
+                match sold {
+                    Some(_) => unsafe {
+                        self.host.evmstate.flashloan_data.attribute(token, owed, earned);
                         ExecutionResult {
                             output: vec![],
                             reverted: false,
@@ -1023,11 +1451,12 @@ where
                                     .clone(),
                             ),
                             additional_info: None,
+                            logs: vec![],
                         }
                     },
                     None => {
                         ExecutionResult {
-                            // we don't have enough liquidity to buy the token
+                            // nothing to sell or no liquidity to sell the token
                             output: vec![],
                             reverted: true,
                             new_state: StagedVMState::new_with_state(unsafe {
@@ -1036,13 +1465,11 @@ where
                                     .clone()
                             }),
                             additional_info: None,
+                            logs: vec![],
                         }
                     }
                 }
             }
-            EVMInputTy::Liquidate => {
-                unreachable!("liquidate should be handled by middleware");
-            }
             EVMInputTy::ABI => self.execute_abi(input, state),
             EVMInputTy::ArbitraryCallBoundedAddr => self.execute_abi(input, state),
         }
@@ -1060,6 +1487,12 @@ where
             self.host.evmstate = vm_state.as_any().downcast_ref_unchecked::<EVMState>().clone();
             self.host.current_self_destructs = vec![];
             self.host.current_arbitrary_calls = vec![];
+            self.host.current_transfer_froms = vec![];
+            self.host.current_logs = vec![];
+            self.host.current_call_returns = vec![];
+            self.host.call_frames = vec![];
+            self.host.current_storage_writes = vec![];
+            self.host.current_delegating_reads = HashSet::new();
             self.host.call_count = 0;
             self.host.jumpi_trace = 37;
             self.host.current_typed_bug = vec![];
@@ -1168,7 +1601,7 @@ mod tests {
             host::{FuzzHost, JMP_MAP},
             input::{ConciseEVMInput, EVMInput, EVMInputTy},
             mutator::AccessPattern,
-            types::{generate_random_address, EVMFuzzState, EVMU256},
+            types::{generate_random_address, EVMAddress, EVMFuzzState, EVMU256},
             vm::{EVMExecutor, EVMState},
         },
         generic_vm::vm_executor::{GenericVM, MAP_SIZE},
@@ -1234,6 +1667,9 @@ mod tests {
             randomness: vec![],
             repeat: 1,
             swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
         };
 
         let mut state = FuzzState::new(0);
@@ -1272,6 +1708,9 @@ mod tests {
             randomness: vec![],
             repeat: 1,
             swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
         };
 
         let execution_result_5 = evm_executor.execute(&input_5, &mut state);
@@ -1289,4 +1728,138 @@ mod tests {
         assert!(cov_changed);
         assert!(execution_result_5.reverted);
     }
+
+    #[test]
+    fn test_max_call_depth() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let path = Path::new("work_dir");
+        if !path.exists() {
+            std::fs::create_dir(path).unwrap();
+        }
+        let mut host = FuzzHost::new(StdScheduler::new(), "work_dir".to_string());
+        host.set_max_call_depth(8);
+        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> =
+            EVMExecutor::new(host, generate_random_address(&mut state));
+
+        // counts its frames in slot 0 and calls itself until the call fails:
+        // sstore(0, sload(0) + 1); call(gas(), address(), 0, 0, 0, 0, 0)
+        let address = generate_random_address(&mut state);
+        let code = hex::decode("60005460010160005560006000600060006000305af15000").unwrap();
+        evm_executor
+            .host
+            .set_code(address, Bytecode::new_raw(Bytes::from(code)), &mut state);
+
+        let input = EVMInput {
+            caller: generate_random_address(&mut state),
+            contract: address,
+            data: None,
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: Some(EVMU256::ZERO),
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            direct_data: Bytes::new(),
+            input_type: EVMInputTy::ABI,
+            randomness: vec![],
+            repeat: 1,
+            swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
+        };
+        let res = evm_executor.execute(&input, &mut state);
+        assert!(!res.reverted);
+        // the transaction frame and 8 nested calls
+        assert_eq!(
+            res.new_state.state.sload(address, EVMU256::ZERO),
+            Some(EVMU256::from(9))
+        );
+        assert_eq!(evm_executor.host.call_depth, 0);
+        // the gas forwarded to the rejected call is not used
+        assert!(evm_executor.host.gas_used() < 1_000_000);
+    }
+
+    #[test]
+    fn test_nested_call_return_data() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let path = Path::new("work_dir");
+        if !path.exists() {
+            std::fs::create_dir(path).unwrap();
+        }
+        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> = EVMExecutor::new(
+            FuzzHost::new(StdScheduler::new(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+
+        // returns 42: mstore(0, 0x2a); return(0, 0x20)
+        let callee = EVMAddress::from_slice(&[0x11; 20]);
+        let callee_code = hex::decode("602a60005260206000f3").unwrap();
+        evm_executor
+            .host
+            .set_code(callee, Bytecode::new_raw(Bytes::from(callee_code)), &mut state);
+
+        // calls the callee with selector 0x12345678:
+        // mstore(0, shl(0xe0, 0x12345678)); call(gas(), callee, 0, 0, 4, 0, 0x20)
+        let caller = generate_random_address(&mut state);
+        let caller_code =
+            hex::decode("631234567860e01b600052602060006004600060007311111111111111111111111111111111111111115af15000")
+                .unwrap();
+        evm_executor
+            .host
+            .set_code(caller, Bytecode::new_raw(Bytes::from(caller_code)), &mut state);
+
+        let input = EVMInput {
+            caller: generate_random_address(&mut state),
+            contract: caller,
+            data: None,
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: Some(EVMU256::ZERO),
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            direct_data: Bytes::new(),
+            input_type: EVMInputTy::ABI,
+            randomness: vec![],
+            repeat: 1,
+            swap_data: HashMap::new(),
+            swap_routes: HashMap::new(),
+            co_borrows: vec![],
+            block_advance: None,
+        };
+        let res = evm_executor.execute(&input, &mut state);
+        assert!(!res.reverted);
+        let data = res.new_state.return_data_of(callee, [0x12, 0x34, 0x56, 0x78]).unwrap();
+        assert_eq!(EVMU256::from_be_slice(data), EVMU256::from(42));
+        assert!(res.new_state.return_data_of(callee, [0; 4]).is_none());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut fuzz_state: EVMFuzzState = FuzzState::new(0);
+        let address = generate_random_address(&mut fuzz_state);
+        let mut state = EVMState::new();
+        state.sstore(address, EVMU256::from(1), EVMU256::from(1));
+        state.set_balance(address, EVMU256::from(100));
+
+        let snapshot = state.snapshot();
+        let fork = snapshot.clone();
+
+        state.sstore(address, EVMU256::from(1), EVMU256::from(2));
+        state.set_balance(address, EVMU256::from(0));
+        assert_eq!(
+            snapshot.state().sload(address, EVMU256::from(1)),
+            Some(EVMU256::from(1))
+        );
+
+        state.restore(snapshot);
+        assert_eq!(state.sload(address, EVMU256::from(1)), Some(EVMU256::from(1)));
+        assert_eq!(state.get_balance(&address), Some(&EVMU256::from(100)));
+
+        let forked = fork.into_state();
+        assert_eq!(forked.sload(address, EVMU256::from(1)), Some(EVMU256::from(1)));
+    }
 }
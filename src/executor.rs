@@ -37,7 +37,7 @@ where
     CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
 {
     /// The VM executor
-    pub vm: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI>>>,
+    pub vm: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI, Log = VS::Log>>>,
     /// Observers (e.g., coverage)
     observers: OT,
     phantom: PhantomData<(I, S, Addr, Out)>,
@@ -104,7 +104,7 @@ where
     /// Create a new [`FuzzExecutor`]
     #[allow(clippy::type_complexity)]
     pub fn new(
-        vm_executor: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI>>>,
+        vm_executor: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI, Log = VS::Log>>>,
         observers: OT,
     ) -> Self {
         Self {
@@ -25,7 +25,8 @@ use tracing::debug;
 /// Implements Oracle, Comparison, Dataflow feedbacks.
 use crate::generic_vm::vm_executor::{GenericVM, MAP_SIZE};
 use crate::{
-    fuzzer::ORACLE_OUTPUT,
+    checkpoint::CampaignPart,
+    fuzzer::{oracle_output, set_oracle_output},
     generic_vm::vm_state::VMStateT,
     input::{ConciseSerde, VMInputT},
     oracle::{BugMetadata, Oracle, OracleCtx, Producer},
@@ -127,7 +128,7 @@ where
     /// Determines whether the current execution reproduces the bug
     /// specified in the bug_idx.
     pub fn reproduces(&mut self, state: &mut S, input: &S::Input, bug_idx: &[u64]) -> bool {
-        let initial_oracle_output = unsafe { ORACLE_OUTPUT.clone() };
+        let initial_oracle_output = oracle_output();
         if state.get_execution_result().reverted {
             return false;
         }
@@ -138,9 +139,7 @@ where
         // cleanup producers by calling `notify_end` hooks
         macro_rules! before_exit {
             () => {
-                unsafe {
-                    ORACLE_OUTPUT = initial_oracle_output;
-                }
+                set_oracle_output(initial_oracle_output);
                 self.producers.iter().for_each(|producer| {
                     producer.deref().borrow_mut().notify_end(&mut oracle_ctx);
                 });
@@ -355,6 +354,21 @@ impl<'a, VS, Loc, Addr, Out, CI> DataflowFeedback<'a, VS, Loc, Addr, Out, CI> {
     }
 }
 
+#[cfg(feature = "dataflow")]
+impl<'a, VS, Loc, Addr, Out, CI> CampaignPart for DataflowFeedback<'a, VS, Loc, Addr, Out, CI> {
+    type Snapshot = Vec<[bool; 4]>;
+
+    fn snapshot(&self) -> Self::Snapshot {
+        self.global_write_map.to_vec()
+    }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) {
+        if snapshot.len() == MAP_SIZE {
+            self.global_write_map.copy_from_slice(&snapshot);
+        }
+    }
+}
+
 #[cfg(feature = "dataflow")]
 impl<'a, VS, Loc, Addr, S, Out, CI, I> Feedback<S> for DataflowFeedback<'a, VS, Loc, Addr, Out, CI>
 where
@@ -438,7 +452,10 @@ where
 /// VMState is more likely to be selected for fuzzing.
 #[allow(clippy::type_complexity)]
 #[cfg(feature = "cmp")]
-pub struct CmpFeedback<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI> {
+pub struct CmpFeedback<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI>
+where
+    VS: VMStateT,
+{
     /// global min map recording the minimum distance for each comparison
     min_map: [SlotTy; MAP_SIZE],
     /// min map recording the minimum distance for each comparison in the
@@ -451,7 +468,7 @@ pub struct CmpFeedback<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI> {
     /// not
     scheduler: SC,
     /// the VM providing information about the current execution
-    vm: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI>>>,
+    vm: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI, Log = VS::Log>>>,
     phantom: PhantomData<(Addr, Out)>,
 }
 
@@ -472,7 +489,7 @@ where
     pub(crate) fn new(
         current_map: &'a mut [SlotTy],
         scheduler: SC,
-        vm: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI>>>,
+        vm: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI, Log = VS::Log>>>,
     ) -> Self {
         Self {
             min_map: [SlotTy::try_from(u128::MAX).expect(""); MAP_SIZE],
@@ -485,9 +502,33 @@ where
     }
 }
 
+#[cfg(feature = "cmp")]
+impl<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI> CampaignPart
+    for CmpFeedback<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI>
+where
+    VS: VMStateT,
+    SlotTy: Copy + Serialize + DeserializeOwned,
+{
+    /// The min map and the hashes of the known states
+    type Snapshot = (Vec<SlotTy>, HashSet<u64>);
+
+    fn snapshot(&self) -> Self::Snapshot {
+        (self.min_map.to_vec(), self.known_states.clone())
+    }
+
+    fn restore(&mut self, (min_map, known_states): Self::Snapshot) {
+        if min_map.len() == MAP_SIZE {
+            self.min_map.copy_from_slice(&min_map);
+        }
+        self.known_states = known_states;
+    }
+}
+
 #[cfg(feature = "cmp")]
 impl<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI> Named
     for CmpFeedback<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI>
+where
+    VS: VMStateT,
 {
     fn name(&self) -> &str {
         "CmpFeedback"
@@ -497,6 +538,8 @@ impl<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI> Named
 #[cfg(feature = "cmp")]
 impl<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI> Debug
     for CmpFeedback<'a, VS, Addr, Code, By, Loc, SlotTy, Out, I, S, SC, CI>
+where
+    VS: VMStateT,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CmpFeedback").finish()
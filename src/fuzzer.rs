@@ -1,13 +1,15 @@
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     env,
-    fmt::Debug,
+    fmt::{Debug, Display, Formatter},
     fs::{File, OpenOptions},
     hash::{Hash, Hasher},
     io::Write,
     marker::PhantomData,
     path::Path,
-    process::exit,
+    rc::Rc,
+    sync::mpsc::{channel, Receiver, Sender},
     time::Duration,
 };
 
@@ -39,20 +41,111 @@ use libafl::{
 };
 use libafl_bolts::current_time;
 use serde::{de::DeserializeOwned, Serialize};
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
+    checkpoint::{load_campaign, save_campaign, Campaign, CampaignPart},
+    events::{CampaignEvent, CampaignFinding, CampaignResult, CampaignStats, ReportedStats, StatsReporter, StopReason},
     evm::{host::JMP_MAP, solution, utils::prettify_concise_inputs},
     generic_vm::{vm_executor::MAP_SIZE, vm_state::VMStateT},
     input::{ConciseSerde, SolutionTx, VMInputT},
+    memory::{MemoryBudget, MemoryCache, Pressure, MIB},
     minimizer::SequentialMinimizer,
-    oracle::BugMetadata,
-    scheduler::HasReportCorpus,
-    state::{HasCurrentInputIdx, HasExecutionResult, HasInfantStateState, HasItyState, InfantStateState},
+    oracle::{BugMetadata, OracleDescriptor},
+    scheduler::{HasReportCorpus, SponsorMetadata, VoteData},
+    state::{
+        HasCurrentInputIdx,
+        HasExecutionResult,
+        HasInfantStateState,
+        HasItyState,
+        InfantStateState,
+        RngSeedMetadata,
+    },
 };
 
 pub static mut RUN_FOREVER: bool = false;
-pub static mut ORACLE_OUTPUT: Vec<serde_json::Value> = vec![];
+/// Quit once this many distinct bugs (by bug index) are found, with a nonzero
+/// status
+pub static mut STOP_AFTER_FINDINGS: Option<usize> = None;
+
+/// A finding reported by an oracle
+#[derive(Clone, Debug)]
+pub struct OracleFinding {
+    /// Name of the oracle (detector) that reported the finding
+    pub oracle: String,
+    pub message: serde_json::Value,
+}
+
+impl OracleFinding {
+    /// The message, annotated with the name of the oracle
+    pub fn to_value(&self) -> serde_json::Value {
+        let mut value = self.message.clone();
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("oracle".to_string(), serde_json::Value::String(self.oracle.clone()));
+        }
+        value
+    }
+
+    pub fn bug_idx(&self) -> Option<u64> {
+        self.message["bug_idx"].as_u64()
+    }
+}
+
+impl Display for OracleFinding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] [{}]: {}",
+            self.oracle,
+            self.message["bug_type"].as_str().unwrap_or_default(),
+            self.message["bug_info"].as_str().unwrap_or_default()
+        )
+    }
+}
+
+/// Findings reported by the oracles on the current execution
+#[derive(Clone, Debug, Default)]
+pub struct OracleOutput {
+    findings: Vec<OracleFinding>,
+}
+
+impl OracleOutput {
+    const fn new() -> Self {
+        Self { findings: vec![] }
+    }
+
+    pub fn findings(&self) -> &[OracleFinding] {
+        &self.findings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+static mut ORACLE_OUTPUT: OracleOutput = OracleOutput::new();
+
+/// Record a finding of an oracle. All oracles report findings through this.
+pub fn push_oracle_output(oracle: &str, message: serde_json::Value) {
+    unsafe {
+        ORACLE_OUTPUT.findings.push(OracleFinding {
+            oracle: oracle.to_string(),
+            message,
+        });
+    }
+}
+
+/// Findings reported on the current execution so far
+pub fn oracle_output() -> OracleOutput {
+    unsafe { ORACLE_OUTPUT.clone() }
+}
+
+/// Replace the findings, e.g., to restore them after a re-execution
+pub fn set_oracle_output(output: OracleOutput) {
+    unsafe {
+        ORACLE_OUTPUT = output;
+    }
+}
 
 /// A fuzzer that implements ItyFuzz logic using LibAFL's [`Fuzzer`] trait
 ///
@@ -104,6 +197,28 @@ where
     phantom: PhantomData<(I, S, OT, VS, Loc, Addr, Out, CI, SM)>,
     /// work dir path
     work_dir: String,
+    /// How often the campaign is saved to the work dir, if at all
+    checkpoint_interval: Option<Duration>,
+    /// Stop fuzzing after this long
+    max_time: Option<Duration>,
+    /// Stop fuzzing after this many executions
+    max_execs: Option<usize>,
+    /// Receivers of the campaign events
+    subscribers: Vec<Sender<CampaignEvent>>,
+    /// Branches covered by the input corpus
+    covered_branches: HashSet<usize>,
+    /// When the fuzz loop started
+    start_time: Option<Duration>,
+    /// Why the campaign stopped, the fuzz loop returns once set
+    stop_reason: Option<StopReason>,
+    /// Findings so far, with the traces reaching them
+    found: Vec<CampaignFinding>,
+    /// Caches accounted for in the memory usage and its soft cap
+    memory: MemoryBudget,
+    /// The registered oracles and their configuration, recorded with findings
+    oracles: Vec<OracleDescriptor>,
+    /// Reporters of the stats kept by the VM
+    stats_reporters: Vec<Box<dyn StatsReporter>>,
 }
 
 impl<VS, Loc, Addr, Out, CS, IS, F, IF, IFR, I, OF, S, OT, CI, SM>
@@ -142,15 +257,120 @@ where
             infant_scheduler,
             objective,
             work_dir,
+            checkpoint_interval: None,
+            max_time: None,
+            max_execs: None,
+            subscribers: vec![],
+            covered_branches: HashSet::new(),
+            start_time: None,
+            stop_reason: None,
+            found: vec![],
+            memory: MemoryBudget::default(),
+            oracles: vec![],
+            stats_reporters: vec![],
             minimizer_map: Default::default(),
             sequential_minimizer,
             phantom: PhantomData,
         }
     }
 
+    /// Save the campaign to the work dir every `interval`, see
+    /// [`crate::checkpoint`]
+    pub fn set_checkpoint_interval(&mut self, interval: Duration) {
+        self.checkpoint_interval = Some(interval);
+    }
+
+    /// Resume the campaign saved in the work dir into `state`, returns
+    /// whether there was one to resume
+    pub fn resume_campaign(&mut self, state: &mut S) -> bool
+    where
+        S: DeserializeOwned,
+        IF: CampaignPart,
+        IFR: CampaignPart,
+    {
+        let Some(campaign) = load_campaign::<Campaign<S, IF::Snapshot, IFR::Snapshot>>(&self.work_dir) else {
+            return false;
+        };
+        *state = campaign.state;
+        self.minimizer_map = campaign.minimizer_map;
+        self.covered_branches = campaign.covered_branches;
+        self.infant_feedback.restore(campaign.infant_feedback);
+        self.infant_result_feedback.restore(campaign.infant_result_feedback);
+        true
+    }
+
+    /// Stop fuzzing after `max_time`
+    pub fn set_max_time(&mut self, max_time: Duration) {
+        self.max_time = Some(max_time);
+    }
+
+    /// Stop fuzzing after `max_execs` executions. Together with
+    /// [`ItyFuzzer::set_max_time`], whichever budget is used up first ends the
+    /// campaign.
+    pub fn set_max_execs(&mut self, max_execs: usize) {
+        self.max_execs = Some(max_execs);
+    }
+
+    /// Account for `cache` in the memory usage, see [`crate::memory`]
+    pub fn register_cache(&mut self, cache: Rc<RefCell<dyn MemoryCache>>) {
+        self.memory.register(cache);
+    }
+
+    /// Surface the stats of `reporter` in the campaign stats and the final
+    /// summary
+    pub fn register_stats_reporter(&mut self, reporter: Box<dyn StatsReporter>) {
+        self.stats_reporters.push(reporter);
+    }
+
+    /// Stats of the registered reporters
+    fn reported_stats(&self, state: &S) -> Vec<ReportedStats> {
+        self.stats_reporters
+            .iter()
+            .map(|reporter| ReportedStats {
+                name: reporter.name(),
+                lines: reporter.report(state.metadata_map()),
+            })
+            .collect()
+    }
+
+    /// Evict the caches, then infant states, whenever their estimated memory
+    /// usage is above `max_bytes`
+    pub fn set_max_memory(&mut self, max_bytes: usize) {
+        self.memory.set_max_bytes(max_bytes);
+    }
+
+    /// Record the registered oracles and their configuration in the reports
+    /// of the findings
+    pub fn set_oracle_descriptors(&mut self, oracles: Vec<OracleDescriptor>) {
+        self.oracles = oracles;
+    }
+
+    pub fn oracle_descriptors(&self) -> &[OracleDescriptor] {
+        &self.oracles
+    }
+
+    /// Stream the campaign events (periodic stats and findings) to the
+    /// returned receiver. Events are dropped once the receiver is gone.
+    pub fn subscribe(&mut self) -> Receiver<CampaignEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    fn emit(&mut self, event: CampaignEvent) {
+        self.subscribers.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+
     /// Called every time a new testcase is added to the corpus
     /// Setup the minimizer map
     pub fn on_add_corpus(&mut self, input: &I, coverage: &[u8; MAP_SIZE], testcase_idx: usize) {
+        self.covered_branches.extend(
+            coverage
+                .iter()
+                .enumerate()
+                .filter(|(_, hits)| **hits > 0)
+                .map(|(idx, _)| idx),
+        );
         let mut hasher = DefaultHasher::new();
         coverage.hash(&mut hasher);
         let hash = hasher.finish();
@@ -186,6 +406,106 @@ where
     }
 }
 
+impl<VS, Loc, Addr, Out, CS, IS, F, IF, IFR, I, OF, S, OT, CI, SM>
+    ItyFuzzer<VS, Loc, Addr, Out, CS, IS, F, IF, IFR, I, OF, S, OT, CI, SM>
+where
+    CS: Scheduler<State = S>,
+    IS: Scheduler<State = InfantStateState<Loc, Addr, VS, CI>> + HasReportCorpus<InfantStateState<Loc, Addr, VS, CI>>,
+    F: Feedback<S>,
+    IF: Feedback<S>,
+    IFR: Feedback<S>,
+    I: VMInputT<VS, Loc, Addr, CI>,
+    OF: Feedback<S>,
+    S: HasClientPerfMonitor
+        + HasCorpus
+        + HasRand
+        + HasMetadata
+        + HasExecutions
+        + HasInfantStateState<Loc, Addr, VS, CI>
+        + UsesInput<Input = I>,
+    VS: Default + VMStateT,
+    Addr: Serialize + DeserializeOwned + Debug + Clone,
+    Loc: Serialize + DeserializeOwned + Debug + Clone,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
+{
+    /// Why the campaign stopped, `None` while it is running
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// Progress of the campaign so far
+    pub fn campaign_stats(&self, state: &mut S) -> CampaignStats {
+        let elapsed = self
+            .start_time
+            .map_or(Duration::ZERO, |start_time| current_time() - start_time);
+        let executions = *state.executions();
+        let (infant_states, evicted_infant_states) = infant_state_stats::<Loc, Addr, VS, CI, S>(state);
+        CampaignStats {
+            elapsed,
+            executions: executions as u64,
+            execs_per_sec: executions as f64 / elapsed.as_secs_f64().max(1e-3),
+            corpus_size: state.corpus().count(),
+            coverage: self.covered_branches.len(),
+            findings: state
+                .metadata_map()
+                .get::<BugMetadata>()
+                .map_or(0, |meta| meta.known_bugs.len()),
+            reported: self.reported_stats(state),
+            infant_states,
+            evicted_infant_states,
+            memory: self.memory.usage(infant_state_memory::<Loc, Addr, VS, CI, S>(state)),
+        }
+    }
+
+    /// Evict the caches when their estimated memory usage crosses the cap,
+    /// and half of the infant states if it still is above. The infant states
+    /// are no longer limited once the usage falls well below the cap.
+    fn enforce_memory_budget(&mut self, state: &mut S) {
+        if self.memory.max_bytes().is_none() {
+            return;
+        }
+        let usage = self.memory.usage(infant_state_memory::<Loc, Addr, VS, CI, S>(state));
+        match self.memory.check(&usage) {
+            Some(Pressure::Crossed) => {}
+            Some(Pressure::Released) => {
+                if let Some(data) = state.get_infant_state_state().metadata_map_mut().get_mut::<VoteData>() {
+                    if data.limit.is_some() {
+                        info!(
+                            "Estimated memory usage {} is back under the cap, restoring the infant states",
+                            usage
+                        );
+                        data.restore_limit();
+                    }
+                }
+                return;
+            }
+            None => return,
+        }
+        let freed = self.memory.evict();
+        warn!(
+            "Estimated memory usage {} is above the cap, evicted {:.1} MiB of caches",
+            usage,
+            freed as f64 / MIB
+        );
+        if !self.memory.exceeded(&self.memory.usage(usage.infant_states)) {
+            return;
+        }
+        if let Some(data) = state.get_infant_state_state().metadata_map_mut().get_mut::<VoteData>() {
+            warn!("Keeping at most {} infant states", data.halve_limit());
+        }
+    }
+
+    /// Results of the campaign, once it stopped for `stop_reason`
+    pub fn campaign_result(&self, state: &mut S, stop_reason: StopReason) -> CampaignResult<I> {
+        CampaignResult {
+            stop_reason,
+            stats: self.campaign_stats(state),
+            findings: self.found.clone(),
+            corpus: corpus_inputs(state).into_iter().map(|(_, input)| input).collect(),
+        }
+    }
+}
+
 impl<VS, Loc, Addr, Out, CS, IS, F, IF, IFR, I, OF, S, OT, CI, SM> UsesState
     for ItyFuzzer<VS, Loc, Addr, Out, CS, IS, F, IF, IFR, I, OF, S, OT, CI, SM>
 where
@@ -214,8 +534,8 @@ where
     E: Executor<EM, Self, State = S>,
     EM: EventManager<E, Self, State = S>,
     F: Feedback<S>,
-    IF: Feedback<S>,
-    IFR: Feedback<S>,
+    IF: Feedback<S> + CampaignPart,
+    IFR: Feedback<S> + CampaignPart,
     I: VMInputT<VS, Loc, Addr, CI>,
     OF: Feedback<S>,
     S: HasClientPerfMonitor
@@ -225,7 +545,9 @@ where
         + HasRand
         + HasCorpus
         + HasLastReportTime
-        + UsesInput<Input = I>,
+        + HasInfantStateState<Loc, Addr, VS, CI>
+        + UsesInput<Input = I>
+        + Serialize,
     ST: StagesTuple<E, EM, S, Self>,
     VS: Default + VMStateT,
     Addr: Serialize + DeserializeOwned + Debug + Clone,
@@ -268,11 +590,148 @@ where
                 .parse::<u64>()
                 .unwrap(),
         );
+        let start_time = current_time();
+        self.start_time = Some(start_time);
+        let mut last_checkpoint = start_time;
+        let mut last_stats = start_time;
+        let mut last_memory_check = start_time;
         loop {
-            self.fuzz_one(stages, executor, state, manager)?;
+            let idx = self.fuzz_one(stages, executor, state, manager)?;
+            // stopped on findings
+            if self.stop_reason.is_some() {
+                return Ok(idx);
+            }
             manager.maybe_report_progress(state, reporting_interval)?;
+
+            let elapsed = current_time() - start_time;
+            let executions = *state.executions();
+            if !self.subscribers.is_empty() && current_time() - last_stats >= reporting_interval {
+                let stats = self.campaign_stats(state);
+                self.emit(CampaignEvent::Stats(stats));
+                last_stats = current_time();
+            }
+            if current_time() - last_memory_check >= reporting_interval {
+                self.enforce_memory_budget(state);
+                last_memory_check = current_time();
+            }
+            let time_out = self.max_time.is_some_and(|max_time| elapsed >= max_time);
+            let execs_out = self.max_execs.is_some_and(|max_execs| executions >= max_execs);
+            if time_out || execs_out {
+                info!(
+                    "Fuzzing budget used up: {:.2}s elapsed, {} executions",
+                    elapsed.as_secs_f64(),
+                    executions
+                );
+                let found = state
+                    .metadata_map()
+                    .get::<BugMetadata>()
+                    .map_or(0, |meta| meta.known_bugs.len());
+                if found == 0 {
+                    info!("No violations found in {} executions", executions);
+                }
+                let (infant_states, evicted_infant_states) = infant_state_stats::<Loc, Addr, VS, CI, S>(state);
+                if evicted_infant_states > 0 {
+                    info!(
+                        "{} infant states kept, {} evicted",
+                        infant_states, evicted_infant_states
+                    );
+                }
+                for stats in self.reported_stats(state) {
+                    for line in &stats.lines {
+                        info!("{}: {}", stats.name, line);
+                    }
+                }
+                let infant_state_memory = infant_state_memory::<Loc, Addr, VS, CI, S>(state);
+                info!("Estimated memory usage: {}", self.memory.usage(infant_state_memory));
+                self.stop_reason = Some(StopReason::BudgetUsedUp);
+                return Ok(idx);
+            }
+
+            if let Some(interval) = self.checkpoint_interval &&
+                current_time() - last_checkpoint >= interval
+            {
+                let campaign = Campaign {
+                    state: &*state,
+                    minimizer_map: self.minimizer_map.clone(),
+                    covered_branches: self.covered_branches.clone(),
+                    infant_feedback: self.infant_feedback.snapshot(),
+                    infant_result_feedback: self.infant_result_feedback.snapshot(),
+                };
+                if let Err(e) = save_campaign(&self.work_dir, &campaign) {
+                    warn!("Failed to save campaign checkpoint: {}", e);
+                }
+                last_checkpoint = current_time();
+            }
+        }
+    }
+}
+
+/// Inputs of the testcases in the corpus of `state`, in order
+pub fn corpus_inputs<S: HasCorpus>(state: &S) -> Vec<(CorpusId, S::Input)> {
+    let mut inputs = vec![];
+    let mut current_idx = state.corpus().first();
+    while let Some(idx) = current_idx {
+        if let Some(input) = state
+            .corpus()
+            .get(idx)
+            .ok()
+            .and_then(|testcase| testcase.borrow().input().clone())
+        {
+            inputs.push((idx, input));
         }
+        current_idx = state.corpus().next(idx);
     }
+    inputs
+}
+
+/// Why the campaign stops once `found` distinct bugs are found, stopping after
+/// `limit` of them, on the first one, or never if `run_forever`
+fn stop_on_finding(found: usize, limit: Option<usize>, run_forever: bool) -> Option<StopReason> {
+    match limit {
+        Some(limit) => (found >= limit).then_some(StopReason::FindingLimit),
+        None => (!run_forever).then_some(StopReason::Found),
+    }
+}
+
+/// Number of infant states kept for scheduling and evicted so far
+fn infant_state_stats<Loc, Addr, VS, CI, S>(state: &mut S) -> (usize, usize)
+where
+    S: HasInfantStateState<Loc, Addr, VS, CI>,
+    VS: Default + VMStateT,
+    Addr: Serialize + DeserializeOwned + Debug + Clone,
+    Loc: Serialize + DeserializeOwned + Debug + Clone,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
+{
+    state
+        .get_infant_state_state()
+        .metadata_map()
+        .get::<VoteData>()
+        .map_or((0, 0), |data| (data.votes_and_visits.len(), data.evicted))
+}
+
+/// Estimated bytes held by the VM states of the infant state corpus
+fn infant_state_memory<Loc, Addr, VS, CI, S>(state: &mut S) -> usize
+where
+    S: HasInfantStateState<Loc, Addr, VS, CI>,
+    VS: Default + VMStateT,
+    Addr: Serialize + DeserializeOwned + Debug + Clone,
+    Loc: Serialize + DeserializeOwned + Debug + Clone,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
+{
+    let corpus = state.get_infant_state_state().corpus();
+    let mut bytes = 0;
+    let mut current_idx = corpus.first();
+    while let Some(idx) = current_idx {
+        bytes += corpus.get(idx).map_or(0, |testcase| {
+            testcase
+                .borrow()
+                .input()
+                .as_ref()
+                .map_or(0, |input| input.state.estimated_size())
+        });
+        current_idx = corpus.next(idx);
+    }
+    bytes
 }
 
 #[cfg(feature = "print_txn_corpus")]
@@ -389,6 +848,10 @@ where
         input: <Self::State as UsesInput>::Input,
         send_events: bool,
     ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error> {
+        // the campaign stopped on findings, the rest of the stage is skipped
+        if self.stop_reason.is_some() {
+            return Ok((ExecuteInputResult::None, None));
+        }
         start_timer!(state);
         executor.observers_mut().pre_exec_all(state, &input)?;
         mark_feature_time!(state, PerfFeature::PreExecObservers);
@@ -449,6 +912,17 @@ where
             }
         }
 
+        // votes requested during the execution go to its state (or the one it
+        // is derived from, if not added)
+        let votes = state
+            .metadata_map_mut()
+            .get_mut::<SponsorMetadata>()
+            .map_or(0, |sponsor| std::mem::take(&mut sponsor.votes));
+        if votes > 0 && !reverted {
+            self.infant_scheduler
+                .sponsor_state(state.get_infant_state_state(), state_idx, votes)
+        }
+
         let mut res = ExecuteInputResult::None;
         if is_solution && !reverted {
             res = ExecuteInputResult::Solution;
@@ -553,15 +1027,88 @@ where
                     .join("\n");
 
                 println!("\n\n\n😊😊 Found vulnerabilities! \n\n");
-                let cur_report =
-                    format!(
+                let findings = oracle_output();
+                let vulns_dir = format!("{}/vulnerabilities", self.work_dir.as_str());
+                let bug_idxs = findings
+                    .findings()
+                    .iter()
+                    .map(|finding| finding.bug_idx().unwrap())
+                    .join(",");
+                // replaying the written trace catches inputs it does not reproduce,
+                // e.g., because of fields that are not serialized. A replayed
+                // finding is reproduced from its replay file already.
+                let verified = unsafe { REPLAY } || {
+                    let replay_file = write_replayable(&vulns_dir, &bug_idxs, &txn_json);
+                    self.sequential_minimizer.verify(
+                        state,
+                        executor,
+                        &replay_file,
+                        &mut self.objective,
+                        corpus_idx.into(),
+                    )
+                };
+                let mut cur_report = String::new();
+                if !self.oracles.is_empty() {
+                    cur_report.push_str(&format!(
+                        "================ Oracles ================\n{}\n",
+                        self.oracles.iter().join("\n")
+                    ));
+                }
+                cur_report.push_str(&format!(
                     "================ Description ================\n{}\n================ Trace ================\n{}\n",
-                    unsafe { ORACLE_OUTPUT.iter().map(|v| {
-                        format!("[{}]: {}", v["bug_type"].as_str().unwrap(), v["bug_info"].as_str().unwrap())
-                     }).join("\n") },
+                    findings.findings().iter().join("\n"),
                     txn_text
-                );
+                ));
+                // the random bytes of the transactions (e.g., selecting the swap
+                // paths) are part of the trace, the seed reruns the campaign
+                let rng_seed = state
+                    .metadata_map()
+                    .get::<RngSeedMetadata>()
+                    .map(|metadata| metadata.seed);
+                let randomness = minimized
+                    .iter()
+                    .map(|ci| hex::encode(ci.randomness()))
+                    .collect::<Vec<_>>();
+                if let Some(seed) = rng_seed {
+                    cur_report.push_str(&format!(
+                        "================ Seed ================\nRNG seed: {} (--seed {})\n",
+                        seed, seed
+                    ));
+                }
+                if !verified {
+                    warn!("Replaying the trace does not reproduce the finding, it is reported as unverified");
+                    cur_report.push_str(
+                        "================ Unverified ================\nReplaying the trace does not reproduce the finding\n",
+                    );
+                }
+                // changes from the initial state, the first one of the infant state corpus
+                let initial_state = state
+                    .get_infant_state_state()
+                    .corpus()
+                    .get(CorpusId::from(0usize))
+                    .ok()
+                    .and_then(|testcase| testcase.borrow().input().as_ref().map(|input| input.state.clone()));
+                if let Some(initial_state) = initial_state {
+                    let changes = initial_state.describe_changes(&state.get_execution_result().new_state.state);
+                    if !changes.is_empty() {
+                        cur_report.push_str(&format!(
+                            "================ State Changes ================\n{}\n",
+                            changes
+                        ));
+                    }
+                }
                 println!("{}", cur_report);
+                self.emit(CampaignEvent::Finding {
+                    findings: findings.findings().to_vec(),
+                    trace: txn_text.clone(),
+                });
+                self.found.push(CampaignFinding {
+                    findings: findings.findings().to_vec(),
+                    trace: txn_text.clone(),
+                    replayable: txn_json.clone(),
+                    verified,
+                    rng_seed,
+                });
 
                 solution::generate_test(cur_report.clone(), minimized);
 
@@ -571,23 +1118,41 @@ where
                     .append(true)
                     .open(vuln_file)
                     .expect("Unable to open file");
-                f.write_all(unsafe {
-                    ORACLE_OUTPUT
+                f.write_all(
+                    findings
+                        .findings()
                         .iter()
-                        .map(|v| serde_json::to_string(v).expect("failed to json"))
+                        .map(|finding| {
+                            let mut value = finding.to_value();
+                            if let Some(obj) = value.as_object_mut() {
+                                obj.insert("verified".to_string(), serde_json::Value::Bool(verified));
+                                obj.insert(
+                                    "rng_seed".to_string(),
+                                    serde_json::to_value(rng_seed).expect("failed to json"),
+                                );
+                                obj.insert(
+                                    "randomness".to_string(),
+                                    serde_json::to_value(&randomness).expect("failed to json"),
+                                );
+                                if let Some(oracle) = self.oracles.iter().find(|oracle| oracle.name == finding.oracle) {
+                                    obj.insert(
+                                        "oracle_config".to_string(),
+                                        serde_json::to_value(&oracle.config).expect("failed to json"),
+                                    );
+                                }
+                            }
+                            serde_json::to_string(&value).expect("failed to json")
+                        })
                         .join("\n")
-                        .as_bytes()
-                })
+                        .as_bytes(),
+                )
                 .expect("Unable to write data");
                 f.write_all(b"\n").expect("Unable to write data");
+                f.flush().expect("Unable to write data");
 
                 #[cfg(feature = "print_txn_corpus")]
                 {
-                    let vulns_dir = format!("{}/vulnerabilities", self.work_dir.as_str());
-
                     if !unsafe { REPLAY } {
-                        let bug_idxs =
-                            unsafe { ORACLE_OUTPUT.iter().map(|v| v["bug_idx"].as_u64().unwrap()).join(",") };
                         let data = format!(
                             "Reverted? {} \n Txn: {}",
                             state.get_execution_result().reverted,
@@ -600,23 +1165,20 @@ where
                         }
                         let mut file = File::create(format!("{}/{}", vulns_dir, bug_idxs.clone())).unwrap();
                         file.write_all(data.as_bytes()).unwrap();
-                        let mut replayable_file =
-                            File::create(format!("{}/{}_replayable", vulns_dir, bug_idxs)).unwrap();
-                        replayable_file.write_all(txn_json.as_bytes()).unwrap();
                     }
                     // dump_file!(state, vulns_dir, false);
                 }
 
-                if !unsafe { RUN_FOREVER } {
-                    exit(0);
+                let found = state.metadata_map().get::<BugMetadata>().unwrap().known_bugs.len();
+                self.stop_reason = stop_on_finding(found, unsafe { STOP_AFTER_FINDINGS }, unsafe { RUN_FOREVER });
+                if self.stop_reason == Some(StopReason::FindingLimit) {
+                    info!("Found {} distinct bug(s), stopping", found);
                 }
 
                 return Ok((res, None));
             }
         };
-        unsafe {
-            ORACLE_OUTPUT.clear();
-        }
+        set_oracle_output(OracleOutput::default());
         final_res
     }
 
@@ -631,3 +1193,30 @@ where
         todo!()
     }
 }
+
+/// Write the replay file of the finding of `bug_idxs` to `vulns_dir`,
+/// returns its path
+fn write_replayable(vulns_dir: &str, bug_idxs: &str, txn_json: &str) -> String {
+    std::fs::create_dir_all(vulns_dir).unwrap();
+    let path = format!("{}/{}_replayable", vulns_dir, bug_idxs);
+    let mut replayable_file = File::create(&path).unwrap();
+    replayable_file.write_all(txn_json.as_bytes()).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_on_finding() {
+        // stops at the finding limit, not before
+        assert_eq!(stop_on_finding(1, Some(3), false), None);
+        assert_eq!(stop_on_finding(2, Some(3), true), None);
+        assert_eq!(stop_on_finding(3, Some(3), false), Some(StopReason::FindingLimit));
+        // several findings of the last execution may exceed the limit
+        assert_eq!(stop_on_finding(4, Some(3), false), Some(StopReason::FindingLimit));
+        assert_eq!(stop_on_finding(1, None, false), Some(StopReason::Found));
+        assert_eq!(stop_on_finding(5, None, true), None);
+    }
+}
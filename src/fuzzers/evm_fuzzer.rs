@@ -1,31 +1,34 @@
-use std::{cell::RefCell, collections::HashMap, fs::File, io::Read, ops::Deref, path::Path, process::exit, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fs::File, io::Read, ops::Deref, path::Path, rc::Rc, time::Duration};
 
 use bytes::Bytes;
 use glob::glob;
 use itertools::Itertools;
 use libafl::{
+    feedback_or,
     feedbacks::Feedback,
     prelude::{HasMetadata, MaxMapFeedback, SimpleEventManager, SimpleMonitor, StdMapObserver},
+    Error,
     Evaluator,
     Fuzzer,
 };
 use libafl_bolts::tuples::tuple_list;
 use revm_primitives::Bytecode;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
+    events::{CampaignResult, StopReason},
     evm::{
-        abi::{ABIAddressToInstanceMap, BoxedABI},
+        abi::{register_interesting_address, set_interesting_address_percent, ABIAddressToInstanceMap, BoxedABI},
+        background::BackgroundTxnProvider,
         blaz::builder::ArtifactInfoMetadata,
         concolic::{
             concolic_host::CONCOLIC_TIMEOUT,
             concolic_stage::{ConcolicFeedbackWrapper, ConcolicStage},
         },
-        config::Config,
         contract_utils::FIX_DEPLOYER,
         corpus_initializer::EVMCorpusInitializer,
         cov_stage::CoverageStage,
-        feedbacks::Sha3WrappedFeedback,
+        feedbacks::{MaxGasFeedback, Sha3WrappedFeedback},
         host::{
             FuzzHost,
             ACTIVE_MATCH_EXT_CALL,
@@ -34,10 +37,11 @@ use crate::{
             JMP_MAP,
             PANIC_ON_BUG,
             READ_MAP,
+            REAL_BALANCE,
             WRITE_MAP,
             WRITE_RELATIONSHIPS,
         },
-        input::{ConciseEVMInput, EVMInput},
+        input::{ConciseEVMInput, EVMInput, MULTI_BLOCK},
         middlewares::{
             call_printer::CallPrinter,
             cheatcode::Cheatcode,
@@ -48,45 +52,48 @@ use crate::{
         },
         minimizer::EVMMinimizer,
         mutator::FuzzMutator,
-        onchain::{flashloan::Flashloan, OnChain, WHITELIST_ADDR},
+        onchain::{
+            fee::TX_FEE_MODEL,
+            flashloan::{Flashloan, MAX_BORROWED_ASSETS},
+            OnChain,
+            WHITELIST_ADDR,
+        },
         oracles::{
+            access_control::AccessControlOracle,
+            allowance::AllowanceDrainOracle,
             arb_call::ArbitraryCallOracle,
             echidna::EchidnaOracle,
+            funds_locked::FundsLockedOracle,
             invariant::InvariantOracle,
+            nft::NftTheftOracle,
             reentrancy::ReentrancyOracle,
             selfdestruct::SelfdestructOracle,
+            storage_collision::StorageCollisionOracle,
             typed_bug::TypedBugOracle,
+            view_probe::{ViewFunctionMetadata, ViewProbeOracle},
         },
         presets::ExploitTemplate,
         scheduler::{PowerABIMutationalStage, PowerABIScheduler, UncoveredBranchesMetadata},
-        types::{fixed_address, EVMAddress, EVMFuzzMutator, EVMFuzzState, EVMQueueExecutor, EVMU256},
+        selector_stats::SelectorStatsReporter,
+        solution,
+        tokens::{
+            balance_slot::BalanceSlotMetadata,
+            uniswap::{CodeRegistryCache, SwapDiscoveryReporter},
+        },
+        types::{fixed_address, EVMAddress, EVMConfig, EVMFuzzMutator, EVMFuzzState, EVMQueueExecutor, EVMU256},
         vm::{EVMExecutor, EVMState},
     },
     executor::FuzzExecutor,
     feedback::{CmpFeedback, DataflowFeedback, OracleFeedback},
-    fuzzer::{ItyFuzzer, REPLAY, RUN_FOREVER},
+    fuzzer::{ItyFuzzer, REPLAY, RUN_FOREVER, STOP_AFTER_FINDINGS},
+    mutation_utils::ConstantPoolMetadata,
     oracle::BugMetadata,
     scheduler::SortedDroppingScheduler,
     state::{FuzzState, HasCaller, HasExecutionResult, HasPresets},
 };
 
 #[allow(clippy::type_complexity)]
-pub fn evm_fuzzer(
-    config: Config<
-        EVMState,
-        EVMAddress,
-        Bytecode,
-        Bytes,
-        EVMAddress,
-        EVMU256,
-        Vec<u8>,
-        EVMInput,
-        EVMFuzzState,
-        ConciseEVMInput,
-        EVMQueueExecutor,
-    >,
-    state: &mut EVMFuzzState,
-) {
+pub fn evm_fuzzer(config: EVMConfig, state: &mut EVMFuzzState) -> Result<CampaignResult<EVMInput>, Error> {
     info!("\n\n ================ EVM Fuzzer Start ===================\n\n");
 
     // create work dir if not exists
@@ -94,7 +101,7 @@ pub fn evm_fuzzer(
 
     let monitor = SimpleMonitor::new(|s| info!("{}", s));
     let mut mgr = SimpleEventManager::new(monitor);
-    let infant_scheduler = SortedDroppingScheduler::new();
+    let infant_scheduler = SortedDroppingScheduler::with_max_size(config.infant_state_limit);
     let scheduler = PowerABIScheduler::new();
 
     let jmps = unsafe { &mut JMP_MAP };
@@ -106,6 +113,12 @@ pub fn evm_fuzzer(
     let deployer = fixed_address(FIX_DEPLOYER);
     let mut fuzz_host = FuzzHost::new(scheduler.clone(), config.work_dir.clone());
     fuzz_host.set_spec_id(config.spec_id);
+    fuzz_host.set_max_call_depth(config.max_call_depth);
+    fuzz_host.record_storage_writes = config.storage_collision_oracle ||
+        config.access_control_oracle ||
+        config.funds_locked_oracle ||
+        config.probe_views;
+    fuzz_host.record_delegating_reads = config.storage_collision_oracle;
 
     // **Note**: cheatcode should be the first middleware because it consumes the
     // step if it is a call to cheatcode_address, and this step should not be
@@ -151,8 +164,18 @@ pub fn evm_fuzzer(
         }
     }
 
+    unsafe {
+        // any violation fails the assertion
+        STOP_AFTER_FINDINGS = if config.assert_invariants {
+            Some(1)
+        } else {
+            config.stop_after_findings
+        };
+    }
+
     unsafe {
         PANIC_ON_BUG = config.panic_on_bug;
+        TX_FEE_MODEL = config.tx_fee_model;
     }
 
     if !config.only_fuzz.is_empty() {
@@ -161,12 +184,24 @@ pub fn evm_fuzzer(
         }
     }
 
+    if config.caller_balance.is_some() {
+        unsafe {
+            REAL_BALANCE = true;
+        }
+    }
+
     if config.flashloan {
         // we should use real balance of tokens in the contract instead of providing
         // flashloan to contract as well for on chain env
         {
             fuzz_host.add_flashloan_middleware(Flashloan::new(true, config.onchain.clone(), config.flashloan_oracle));
         }
+        unsafe {
+            MAX_BORROWED_ASSETS = config.max_borrowed_assets;
+        }
+    }
+    unsafe {
+        MULTI_BLOCK = config.multi_block;
     }
     let sha3_taint = Rc::new(RefCell::new(Sha3TaintAnalysis::new()));
 
@@ -194,6 +229,42 @@ pub fn evm_fuzzer(
     if !state.has_metadata::<ArtifactInfoMetadata>() {
         state.add_metadata(ArtifactInfoMetadata::new());
     }
+    // consulted by the corpus initializer, onchain middleware and mutator
+    state.metadata_map_mut().insert(config.function_filter.clone());
+    state.metadata_map_mut().insert(config.arg_pins.clone());
+    state.metadata_map_mut().insert(config.call_value.clone());
+    if config.probe_views {
+        state.metadata_map_mut().insert(ViewFunctionMetadata::default());
+    }
+    for address in &config.interesting_addresses {
+        register_interesting_address(*address, state);
+    }
+    set_interesting_address_percent(config.interesting_address_percent, state);
+    state.metadata_map_mut().insert(BalanceSlotMetadata {
+        slots: config.balance_slots.clone(),
+        probe: config.probe_balance_slots,
+        pending: vec![],
+    });
+    if !config.dictionary_values.is_empty() {
+        if !state.has_metadata::<ConstantPoolMetadata>() {
+            state.add_metadata(ConstantPoolMetadata::new());
+        }
+        let pool = state.metadata_map_mut().get_mut::<ConstantPoolMetadata>().unwrap();
+        for value in &config.dictionary_values {
+            // trimmed as the constants of PUSH instructions, so that small
+            // values also fit into narrow inputs
+            let constant = value.to_be_bytes_trimmed_vec();
+            if !pool.constants.contains(&constant) {
+                pool.add_constant(constant);
+            }
+        }
+    }
+    // after the fork is set up, so that the overrides take precedence over the
+    // fetched state
+    for state_override in &config.state_overrides {
+        state_override.apply(&mut evm_executor.host, state);
+    }
+    solution::record_state_overrides(&config.state_overrides);
     let mut corpus_initializer = EVMCorpusInitializer::new(
         &mut evm_executor,
         scheduler.clone(),
@@ -201,8 +272,33 @@ pub fn evm_fuzzer(
         state,
         config.work_dir.clone(),
     );
+    if let Some(balance) = config.caller_balance {
+        corpus_initializer.set_caller_balance(balance);
+    }
+    corpus_initializer.set_top_holders(config.top_holders.clone());
+    corpus_initializer.set_predeploys(config.predeploys.clone());
+    corpus_initializer.set_probe_balance_slots(config.probe_balance_slots);
+    corpus_initializer.set_dump_caller_keys(config.dump_caller_keys);
+    corpus_initializer.set_raw_calldata_targets(config.raw_calldata_targets.clone(), config.raw_calldata_max_len);
+    if config.fuzz_fallback {
+        corpus_initializer.set_fuzz_fallback(&config.call_value);
+    }
+    if let Some(attacker) = config.attacker_contract {
+        corpus_initializer.set_attacker_contract(attacker);
+    }
 
     let mut artifacts = corpus_initializer.initialize(&mut config.contract_loader.clone());
+    if config.dump_recovered_abi {
+        let abi_dir = format!("{}/recovered_abi", config.work_dir);
+        std::fs::create_dir_all(&abi_dir).expect("Failed to create recovered abi dir");
+        for addr in corpus_initializer.recovered_abi_addresses() {
+            let path = format!("{}/{:?}.json", abi_dir, addr);
+            match corpus_initializer.dump_recovered_abi(&addr, &path) {
+                Ok(()) => info!("Recovered ABI of {:?} is dumped to {}", addr, path),
+                Err(e) => warn!("Failed to dump recovered ABI of {:?}: {}", addr, e),
+            }
+        }
+    }
 
     let mut instance_map = ABIAddressToInstanceMap::new();
     artifacts.address_to_abi_object.iter().for_each(|(addr, abi)| {
@@ -264,6 +360,21 @@ pub fn evm_fuzzer(
 
     evm_executor.host.initialize(state);
 
+    if config.background_txns {
+        match config.onchain.as_ref() {
+            Some(onchain) => {
+                let txns = onchain.fetch_next_block_transactions();
+                info!(
+                    "Interleaving {} transactions of the block after {} with fuzzed transactions",
+                    txns.len(),
+                    onchain.block_number
+                );
+                evm_executor.background_txns = BackgroundTxnProvider::new(txns);
+            }
+            None => warn!("background transactions are only available when fuzzing onchain"),
+        }
+    }
+
     // now evm executor is ready, we can clone it
 
     let evm_executor_ref = Rc::new(RefCell::new(evm_executor));
@@ -287,20 +398,31 @@ pub fn evm_fuzzer(
 
     let mut feedback = MaxMapFeedback::new(&jmp_observer);
     feedback.init_state(state).expect("Failed to init state");
+    let feedback = feedback_or!(
+        feedback,
+        MaxGasFeedback::new(evm_executor_ref.clone(), config.gas_feedback)
+    );
     // let calibration = CalibrationStage::new(&feedback);
     if config.concolic {
         unsafe { CONCOLIC_TIMEOUT = config.concolic_timeout };
     }
 
-    let concolic_stage = ConcolicStage::new(
+    let mut concolic_stage = ConcolicStage::new(
         config.concolic,
         config.concolic_caller,
         evm_executor_ref.clone(),
         config.concolic_num_threads,
     );
+    if let Some(plateau) = config.concolic_plateau {
+        concolic_stage.set_plateau(Duration::from_secs(plateau));
+    }
     let mutator: EVMFuzzMutator = FuzzMutator::new(infant_scheduler.clone());
 
     state.metadata_map_mut().insert(UncoveredBranchesMetadata::new());
+    #[cfg(feature = "cmp_dictionary")]
+    state
+        .metadata_map_mut()
+        .insert(crate::mutation_utils::CmpDictionaryMetadata::new());
     let std_stage = PowerABIMutationalStage::new(mutator);
 
     let call_printer_mid = Rc::new(RefCell::new(CallPrinter::new(artifacts.address_to_name.clone())));
@@ -418,10 +540,52 @@ pub fn evm_fuzzer(
         ))));
     }
 
+    if config.storage_collision_oracle {
+        oracles.push(Rc::new(RefCell::new(StorageCollisionOracle::new())));
+    }
+
+    if config.access_control_oracle {
+        oracles.push(Rc::new(RefCell::new(AccessControlOracle::new())));
+    }
+
+    if config.funds_locked_oracle {
+        oracles.push(Rc::new(RefCell::new(FundsLockedOracle::new())));
+    }
+
+    if config.probe_views {
+        oracles.push(Rc::new(RefCell::new(ViewProbeOracle::new(config.work_dir.clone()))));
+    }
+
+    if config.allowance_drain_oracle {
+        if config.allowance_victims.is_empty() {
+            warn!("allowance_drain detector is enabled but no victim is given (--allowance-victims)");
+        }
+        oracles.push(Rc::new(RefCell::new(AllowanceDrainOracle::new(
+            config.allowance_victims.clone(),
+        ))));
+    }
+
+    if config.nft_theft_oracle {
+        if config.nft_collections.is_empty() {
+            warn!("nft_theft detector is enabled but no NFT collection is given (--nft-collections)");
+        }
+        let holders = if config.nft_holders.is_empty() {
+            artifacts.address_to_name.keys().cloned().collect()
+        } else {
+            config.nft_holders.clone()
+        };
+        oracles.push(Rc::new(RefCell::new(NftTheftOracle::new(
+            config.nft_collections.clone(),
+            holders,
+        ))));
+    }
+
     if let Some(m) = onchain_middleware.clone() {
         m.borrow_mut().add_abi(artifacts.address_to_abi.clone());
     }
 
+    let oracle_descriptors = oracles.iter().map(|oracle| oracle.borrow().describe()).collect_vec();
+    info!("Oracles: {}", oracle_descriptors.iter().join(", "));
     let mut producers = config.producers;
 
     let objective: OracleFeedback<
@@ -453,8 +617,27 @@ pub fn evm_fuzzer(
         infant_result_feedback,
         objective,
         EVMMinimizer::new(evm_executor_ref.clone()),
-        config.work_dir,
+        config.work_dir.clone(),
     );
+    if let Some(interval) = config.checkpoint_interval {
+        fuzzer.set_checkpoint_interval(Duration::from_secs(interval));
+    }
+    if let Some(max_time) = config.max_time {
+        fuzzer.set_max_time(Duration::from_secs(max_time));
+    }
+    if let Some(max_execs) = config.max_execs {
+        fuzzer.set_max_execs(max_execs);
+    }
+    fuzzer.set_oracle_descriptors(oracle_descriptors);
+    fuzzer.register_cache(Rc::new(RefCell::new(CodeRegistryCache)));
+    fuzzer.register_stats_reporter(Box::new(SwapDiscoveryReporter));
+    fuzzer.register_stats_reporter(Box::new(SelectorStatsReporter));
+    if let Some(onchain_mid) = onchain_middleware.clone() {
+        fuzzer.register_cache(onchain_mid);
+    }
+    if let Some(max_memory) = config.max_memory {
+        fuzzer.set_max_memory(max_memory as usize * (1 << 20));
+    }
 
     let initial_vm_state = artifacts.initial_state.clone();
     let mut testcases = vec![];
@@ -490,21 +673,35 @@ pub fn evm_fuzzer(
     macro_rules! load_code {
         ($txn: expr) => {
             if let Some(onchain_mid) = onchain_middleware.clone() {
-                onchain_mid.borrow_mut().load_code(
-                    $txn.contract,
-                    &mut evm_executor_ref.clone().deref().borrow_mut().host,
-                    false,
-                    true,
-                    false,
-                    $txn.caller,
-                    state,
-                );
+                // accounts in the access list are made available before the call
+                let addresses = std::iter::once($txn.contract).chain($txn.access_list.iter().map(|(addr, _)| *addr));
+                for address in addresses {
+                    onchain_mid.borrow_mut().load_code(
+                        address,
+                        &mut evm_executor_ref.clone().deref().borrow_mut().host,
+                        false,
+                        true,
+                        false,
+                        $txn.caller,
+                        state,
+                    );
+                }
             }
         };
     }
 
     match config.replay_file {
         None => {
+            if config.resume && fuzzer.resume_campaign(state) {
+                // the corpus of the saved campaign already contains the initial corpus
+                testcases.clear();
+                if let Some(flashloan) = evm_executor_ref.deref().borrow().host.flashloan_middleware.clone() {
+                    flashloan.deref().borrow_mut().restore_token_contexts(state);
+                }
+            } else if config.resume {
+                warn!("No campaign to resume in {}, starting over", config.work_dir);
+            }
+
             // load initial corpus
             for testcase in testcases {
                 let mut vm_state = initial_vm_state.clone();
@@ -520,18 +717,18 @@ pub fn evm_fuzzer(
                     vm_state = state.get_execution_result().new_state.clone();
                 }
             }
-            let res = fuzzer.fuzz_loop(&mut stages, &mut executor, state, &mut mgr);
-
-            // it is not possible to reach here unless an exception is thrown
-            let rv = res.err().unwrap().to_string();
-            if rv == "No items in No entries in corpus" {
-                error!("There is nothing to fuzz. Please check the target you provided.");
-                return;
-            } else {
-                error!("{}", rv);
-            }
-
-            exit(1);
+            let stop_reason = match fuzzer.fuzz_loop(&mut stages, &mut executor, state, &mut mgr) {
+                Ok(_) => fuzzer.stop_reason().expect("the fuzz loop returns once stopped"),
+                Err(e) if e.to_string() == "No items in No entries in corpus" => {
+                    error!("There is nothing to fuzz. Please check the target you provided.");
+                    StopReason::NothingToFuzz
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    return Err(e);
+                }
+            };
+            Ok(fuzzer.campaign_result(state, stop_reason))
         }
         Some(_) => {
             unsafe {
@@ -564,6 +761,18 @@ pub fn evm_fuzzer(
                     info!("reverted: {:?}", state.get_execution_result().clone().reverted);
                     info!("call trace:\n{}", printer.deref().borrow().get_trace());
                     info!("output: {:?}", hex::encode(state.get_execution_result().clone().output));
+                    if config.flashloan {
+                        info!(
+                            "flashloan: {}",
+                            state
+                                .get_execution_result()
+                                .new_state
+                                .state
+                                .flashloan_data
+                                .net_profit()
+                                .describe(config.quote_decimals)
+                        );
+                    }
 
                     // debug!(
                     //     "new_state: {:?}",
@@ -585,6 +794,7 @@ pub fn evm_fuzzer(
             // fuzzer
             //     .fuzz_loop(&mut stages, &mut executor, state, &mut mgr)
             //     .expect("Fuzzing failed");
+            Ok(fuzzer.campaign_result(state, StopReason::Replayed))
         }
     }
 }
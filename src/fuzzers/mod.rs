@@ -1,2 +1,3 @@
 pub mod evm_fuzzer;
+#[cfg(feature = "sui_support")]
 pub mod move_fuzzer;
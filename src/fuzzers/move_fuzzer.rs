@@ -9,30 +9,24 @@ use libafl::{
 use libafl_bolts::tuples::tuple_list;
 use tracing::info;
 
-#[cfg(feature = "sui_support")]
-use crate::r#move::corpus_initializer::MoveCorpusInitializer;
-#[cfg(feature = "sui_support")]
-use crate::r#move::input::MoveFunctionInput;
-#[cfg(feature = "sui_support")]
-use crate::r#move::minimizer::MoveMinimizer;
-#[cfg(feature = "sui_support")]
-use crate::r#move::movevm::MoveVM;
-#[cfg(feature = "sui_support")]
-use crate::r#move::mutator::MoveFuzzMutator;
-#[cfg(feature = "sui_support")]
-use crate::r#move::oracles::typed_bug::TypedBugOracle;
-#[cfg(feature = "sui_support")]
-use crate::r#move::scheduler::{MoveTestcaseScheduler, MoveVMStateScheduler};
-#[cfg(feature = "sui_support")]
-use crate::r#move::types::MoveFuzzState;
-#[cfg(feature = "sui_support")]
-use crate::scheduler::SortedDroppingScheduler;
 use crate::{
     executor::FuzzExecutor,
     feedback::{CmpFeedback, DataflowFeedback, OracleFeedback},
     fuzzer::ItyFuzzer,
     generic_vm::vm_executor::GenericVM,
     oracle::Oracle,
+    r#move::{
+        corpus_initializer::{MoveCorpusInitializer, MoveFunctionFilter},
+        input::MoveFunctionInput,
+        loader::MoveModuleFormat,
+        minimizer::MoveMinimizer,
+        movevm::MoveVM,
+        mutator::MoveFuzzMutator,
+        oracles::typed_bug::TypedBugOracle,
+        scheduler::{MoveTestcaseScheduler, MoveVMStateScheduler},
+        types::MoveFuzzState,
+    },
+    scheduler::SortedDroppingScheduler,
     state::FuzzState,
 };
 
@@ -40,11 +34,11 @@ pub struct MoveFuzzConfig {
     pub target: String,
     pub work_dir: String,
     pub seed: u64,
+    pub function_filter: MoveFunctionFilter,
+    pub module_format: MoveModuleFormat,
+    pub max_type_arg_combinations: usize,
 }
 
-pub static mut MOVE_ENABLED: bool = cfg!(feature = "move_support");
-
-#[cfg(feature = "sui_support")]
 pub fn move_fuzzer(config: &MoveFuzzConfig) {
     let mut state: MoveFuzzState = FuzzState::new(config.seed);
     let mut vm: MoveVM<MoveFunctionInput, MoveFuzzState> = MoveVM::new();
@@ -59,8 +53,11 @@ pub fn move_fuzzer(config: &MoveFuzzConfig) {
     };
 
     {
-        MoveCorpusInitializer::new(&mut state, &mut vm, scheduler.clone(), infant_scheduler.clone())
-            .setup(vec![config.target.clone()]);
+        let mut corpus_initializer =
+            MoveCorpusInitializer::new(&mut state, &mut vm, scheduler.clone(), infant_scheduler.clone());
+        corpus_initializer.set_function_filter(config.function_filter.clone());
+        corpus_initializer.set_max_type_arg_combinations(config.max_type_arg_combinations);
+        corpus_initializer.setup_with_loader(vec![config.target.clone()], config.module_format.loader().as_ref());
     }
 
     let vm_ref = Rc::new(RefCell::new(vm));
@@ -81,6 +78,7 @@ pub fn move_fuzzer(config: &MoveFuzzConfig) {
 
     let mut oracles: Vec<Rc<RefCell<dyn Oracle<_, _, _, _, _, _, _, _, _, _, _>>>> =
         vec![Rc::new(RefCell::new(TypedBugOracle::new()))];
+    let oracle_descriptors = oracles.iter().map(|oracle| oracle.borrow().describe()).collect();
     let mut producers = vec![];
 
     let objective = OracleFeedback::new(&mut oracles, &mut producers, vm_ref.clone());
@@ -96,12 +94,9 @@ pub fn move_fuzzer(config: &MoveFuzzConfig) {
         MoveMinimizer,
         config.work_dir.clone(),
     );
+    fuzzer.set_oracle_descriptors(oracle_descriptors);
     fuzzer
         .fuzz_loop(&mut stages, &mut executor, &mut state, &mut mgr)
         .expect("Fuzzing failed");
-}
-
-#[cfg(not(feature = "sui_support"))]
-pub fn move_fuzzer(_config: &MoveFuzzConfig) {
-    panic!("Move fuzzer is not enabled");
+    std::process::exit(fuzzer.stop_reason().map_or(0, |reason| reason.exit_code()));
 }
@@ -20,6 +20,9 @@ where
     #[serde(deserialize_with = "StagedVMState::deserialize")]
     pub new_state: StagedVMState<Loc, Addr, VS, CI>,
     pub additional_info: Option<Vec<u8>>,
+    /// Logs emitted by the execution, except the ones of reverted calls
+    #[serde(skip)]
+    pub logs: Vec<VS::Log>,
 }
 
 impl<Loc, Addr, VS, Out, CI> ExecutionResult<Loc, Addr, VS, Out, CI>
@@ -36,11 +39,16 @@ where
             reverted: false,
             new_state: StagedVMState::new_uninitialized(),
             additional_info: None,
+            logs: vec![],
         }
     }
 }
 
 pub trait GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI> {
+    /// Log (event) emitted by an execution, the [`VMStateT::Log`] of the
+    /// states of the VM
+    type Log: Clone + Debug + 'static;
+
     fn deploy(
         &mut self,
         code: Code,
@@ -50,7 +58,7 @@ pub trait GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI> {
     ) -> Option<Addr>;
     fn execute(&mut self, input: &I, state: &mut S) -> ExecutionResult<Loc, Addr, VS, Out, CI>
     where
-        VS: VMStateT,
+        VS: VMStateT<Log = Self::Log>,
         Addr: Serialize + DeserializeOwned + Debug,
         Loc: Serialize + DeserializeOwned + Debug,
         Out: Default + Into<Vec<u8>> + Clone,
@@ -3,6 +3,9 @@ use std::{collections::HashMap, fmt::Debug};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 pub trait VMStateT: Clone + Debug + Default + Serialize + DeserializeOwned {
+    /// Log (event) emitted by an execution
+    type Log: Clone + Debug + 'static;
+
     fn get_hash(&self) -> u64;
     fn has_post_execution(&self) -> bool;
     fn get_post_execution_needed_len(&self) -> usize;
@@ -20,6 +23,17 @@ pub trait VMStateT: Clone + Debug + Default + Serialize + DeserializeOwned {
     fn get_swap_data(&self) -> HashMap<String, SwapInfo> {
         HashMap::new()
     }
+
+    /// Human readable summary of what changed from this state to `after`,
+    /// empty if not supported
+    fn describe_changes(&self, _after: &Self) -> String {
+        String::new()
+    }
+
+    /// Estimated bytes held by the state, for memory accounting
+    fn estimated_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
 }
 
 /// Generic swap info.
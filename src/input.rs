@@ -116,6 +116,11 @@ pub trait ConciseSerde {
     fn is_step(&self) -> bool {
         false
     }
+    /// Random bytes of the input consumed when it is executed (e.g., to select
+    /// the swap paths of a flashloan)
+    fn randomness(&self) -> Vec<u8> {
+        vec![]
+    }
 }
 
 /// SolutionTx for generating a test file.
@@ -141,6 +146,19 @@ pub trait SolutionTx {
     fn is_borrow(&self) -> bool {
         false
     }
+    /// Whether the transaction only sells the tokens of the contract held by
+    /// the caller
+    fn is_sell(&self) -> bool {
+        false
+    }
+    /// Other assets bought along with the token of a borrow, as (token, value)
+    fn co_borrows(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+    /// Whether the calldata is raw bytes instead of an ABI-encoded call
+    fn is_raw_calldata(&self) -> bool {
+        false
+    }
     fn liq_percent(&self) -> u8 {
         0
     }
@@ -150,4 +168,25 @@ pub trait SolutionTx {
     fn calldata(&self) -> String {
         String::from("")
     }
+    fn block_number(&self) -> Option<u64> {
+        None
+    }
+    /// Blocks and seconds mined before the transaction, if it starts a new
+    /// block of the sequence
+    fn block_advance(&self) -> Option<(u64, u64)> {
+        None
+    }
+    /// EIP-2930 access list as (address, storage slots) pairs
+    fn access_list(&self) -> Vec<(String, Vec<String>)> {
+        vec![]
+    }
+    /// Logs emitted by the transaction, decoded when possible
+    fn logs(&self) -> Vec<String> {
+        vec![]
+    }
+    /// Real transaction run before (`true`) or after the transaction, as
+    /// (before, caller, contract, value, calldata)
+    fn background_txn(&self) -> Option<(bool, String, String, String, String)> {
+        None
+    }
 }
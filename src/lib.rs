@@ -0,0 +1,36 @@
+//! ItyFuzz, a hybrid fuzzer for smart contracts.
+//!
+//! The `ityfuzz` binary is a thin CLI over this library, a campaign can also be
+//! run programmatically with [`evm::run_campaign`].
+
+#![feature(downcast_unchecked)]
+#![feature(let_chains)]
+#![feature(unchecked_math)]
+#![feature(trait_alias)]
+
+extern crate core;
+
+pub mod cache;
+pub mod checkpoint;
+pub mod r#const;
+pub mod events;
+pub mod evm;
+pub mod executor;
+pub mod feedback;
+pub mod fuzzer;
+pub mod fuzzers;
+pub mod generic_vm;
+pub mod indexed_corpus;
+pub mod input;
+pub mod memory;
+pub mod minimizer;
+pub mod mutation_utils;
+pub mod oracle;
+pub mod power_sched;
+pub mod scheduler;
+pub mod state;
+pub mod state_input;
+pub mod tracer;
+
+#[cfg(feature = "sui_support")]
+pub mod r#move;
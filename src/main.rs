@@ -1,40 +1,10 @@
-#![feature(downcast_unchecked)]
-#![feature(let_chains)]
-#![feature(unchecked_math)]
-#![feature(trait_alias)]
-
-extern crate core;
-
-pub mod cache;
-pub mod r#const;
-pub mod evm;
-pub mod executor;
-pub mod feedback;
-pub mod fuzzer;
-pub mod fuzzers;
-pub mod generic_vm;
-pub mod indexed_corpus;
-pub mod input;
-pub mod minimizer;
-pub mod mutation_utils;
-pub mod oracle;
-pub mod power_sched;
-pub mod scheduler;
-pub mod state;
-pub mod state_input;
-pub mod tracer;
-
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use ityfuzz::evm::{evm_main, EvmArgs};
 #[cfg(feature = "sui_support")]
-pub mod r#move;
-
-use clap::{Parser, Subcommand};
-use evm::{evm_main, EvmArgs};
-use tracing::Level;
+use ityfuzz::r#move::{move_main, MoveArgs};
+use tracing::{error, Level};
 use tracing_subscriber::FmtSubscriber;
 
-#[cfg(feature = "sui_support")]
-use crate::r#move::{move_main, MoveArgs};
-
 pub fn init_sentry() {
     let _guard = sentry::init((
         "https://96f3517bd77346ea835d28f956a84b9d@o4504503751344128.ingest.sentry.io/4504503752523776",
@@ -72,10 +42,16 @@ fn main() {
 
     tracing::subscriber::set_global_default(subscriber).expect("failed to initialize logger");
 
-    let args = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
     match args.command {
-        Commands::Evm(args) => {
-            evm_main(args);
+        Commands::Evm(mut args) => {
+            let (_, evm_matches) = matches.subcommand().expect("no subcommand");
+            if let Err(e) = args.apply_project_file(evm_matches) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+            std::process::exit(evm_main(args));
         }
         #[cfg(feature = "sui_support")]
         Commands::Move(args) => {
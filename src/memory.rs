@@ -0,0 +1,184 @@
+//! Memory accounting of the caches that grow over a campaign (code and
+//! storage fetched from the chain, the infant state corpus, ...), so that
+//! their footprint can be reported and kept under a soft cap.
+//!
+//! The sizes are estimates from the number and size of the entries, not the
+//! memory actually allocated.
+
+use std::{
+    cell::RefCell,
+    fmt::{Display, Formatter},
+    rc::Rc,
+};
+
+pub const MIB: f64 = (1 << 20) as f64;
+
+/// Fraction of the soft cap the usage has to fall below for the pressure to
+/// be released, so that it does not flip at every check around the cap
+pub const RELEASE_RATIO: f64 = 0.75;
+
+/// A cache accounted for by [`MemoryBudget`]
+pub trait MemoryCache {
+    /// Name shown in the stats
+    fn name(&self) -> &'static str;
+
+    /// Estimated bytes held by the cache
+    fn estimated_size(&self) -> usize;
+
+    /// Drop the entries that can be rebuilt (e.g., fetched again), returns the
+    /// estimated bytes freed
+    fn evict(&mut self) -> usize;
+}
+
+/// Estimated footprint of each cache
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes held by each cache, by name
+    pub caches: Vec<(&'static str, usize)>,
+    /// Bytes held by the VM states of the infant state corpus
+    pub infant_states: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.caches.iter().map(|(_, bytes)| bytes).sum::<usize>() + self.infant_states
+    }
+}
+
+impl Display for MemoryUsage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} MiB (", self.total() as f64 / MIB)?;
+        for (name, bytes) in &self.caches {
+            write!(f, "{}: {:.1} MiB, ", name, *bytes as f64 / MIB)?;
+        }
+        write!(f, "infant states: {:.1} MiB)", self.infant_states as f64 / MIB)
+    }
+}
+
+/// Change of the memory pressure, see [`MemoryBudget::check`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pressure {
+    /// The usage went above the soft cap
+    Crossed,
+    /// The usage fell below [`RELEASE_RATIO`] of the soft cap since
+    Released,
+}
+
+/// The caches accounted for and the soft cap on their total size
+#[derive(Default)]
+pub struct MemoryBudget {
+    caches: Vec<Rc<RefCell<dyn MemoryCache>>>,
+    max_bytes: Option<usize>,
+    /// The usage went above the soft cap and was not released since
+    under_pressure: bool,
+}
+
+impl MemoryBudget {
+    pub fn register(&mut self, cache: Rc<RefCell<dyn MemoryCache>>) {
+        self.caches.push(cache);
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = Some(max_bytes);
+    }
+
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_bytes
+    }
+
+    /// Estimated footprint of the caches, with `infant_states` bytes held by
+    /// the infant state corpus
+    pub fn usage(&self, infant_states: usize) -> MemoryUsage {
+        MemoryUsage {
+            caches: self
+                .caches
+                .iter()
+                .map(|cache| {
+                    let cache = cache.borrow();
+                    (cache.name(), cache.estimated_size())
+                })
+                .collect(),
+            infant_states,
+        }
+    }
+
+    /// Whether `usage` is above the soft cap
+    pub fn exceeded(&self, usage: &MemoryUsage) -> bool {
+        self.max_bytes.is_some_and(|max_bytes| usage.total() > max_bytes)
+    }
+
+    /// Track `usage` against the soft cap, returns the change of pressure if
+    /// it crossed the cap or was released since the last check
+    pub fn check(&mut self, usage: &MemoryUsage) -> Option<Pressure> {
+        let max_bytes = self.max_bytes?;
+        if !self.under_pressure && self.exceeded(usage) {
+            self.under_pressure = true;
+            return Some(Pressure::Crossed);
+        }
+        if self.under_pressure && (usage.total() as f64) < max_bytes as f64 * RELEASE_RATIO {
+            self.under_pressure = false;
+            return Some(Pressure::Released);
+        }
+        None
+    }
+
+    /// Evict the caches, returns the estimated bytes freed
+    pub fn evict(&self) -> usize {
+        self.caches.iter().map(|cache| cache.borrow_mut().evict()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Cache(usize);
+
+    impl MemoryCache for Cache {
+        fn name(&self) -> &'static str {
+            "cache"
+        }
+
+        fn estimated_size(&self) -> usize {
+            self.0
+        }
+
+        fn evict(&mut self) -> usize {
+            std::mem::take(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut budget = MemoryBudget::default();
+        budget.register(Rc::new(RefCell::new(Cache(3 << 20))));
+        let usage = budget.usage(1 << 20);
+        assert_eq!(usage.total(), 4 << 20);
+        assert_eq!(usage.to_string(), "4.0 MiB (cache: 3.0 MiB, infant states: 1.0 MiB)");
+        assert!(!budget.exceeded(&usage));
+
+        budget.set_max_bytes(2 << 20);
+        assert!(budget.exceeded(&usage));
+        assert_eq!(budget.evict(), 3 << 20);
+        assert!(budget.exceeded(&budget.usage(3 << 20)));
+        assert!(!budget.exceeded(&budget.usage(1 << 20)));
+    }
+
+    #[test]
+    fn test_memory_pressure() {
+        let mut budget = MemoryBudget::default();
+        assert_eq!(budget.check(&budget.usage(8 << 20)), None);
+
+        budget.set_max_bytes(4 << 20);
+        assert_eq!(budget.check(&budget.usage(3 << 20)), None);
+        assert_eq!(budget.check(&budget.usage(5 << 20)), Some(Pressure::Crossed));
+        // once per crossing
+        assert_eq!(budget.check(&budget.usage(6 << 20)), None);
+        // below the cap, but not enough to release
+        assert_eq!(budget.check(&budget.usage(7 << 19)), None);
+        assert_eq!(budget.check(&budget.usage(5 << 20)), None);
+        assert_eq!(budget.check(&budget.usage(2 << 20)), Some(Pressure::Released));
+        assert_eq!(budget.check(&budget.usage(2 << 20)), None);
+        assert_eq!(budget.check(&budget.usage(5 << 20)), Some(Pressure::Crossed));
+    }
+}
@@ -16,4 +16,19 @@ where
         objective: &mut OF,
         corpus_id: usize,
     ) -> Vec<CI>;
+
+    /// Read the replay file `replay_file` of the finding of `corpus_id` (one
+    /// serialized input per line) back, execute it from the initial state and
+    /// tell whether the finding is reproduced. Minimizers of VMs without a
+    /// replay path do not verify findings.
+    fn verify(
+        &mut self,
+        _state: &mut S,
+        _executor: &mut E,
+        _replay_file: &str,
+        _objective: &mut OF,
+        _corpus_id: usize,
+    ) -> bool {
+        true
+    }
 }
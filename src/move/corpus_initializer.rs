@@ -4,31 +4,38 @@ use itertools::Itertools;
 use libafl::{
     corpus::{Corpus, Testcase},
     schedulers::Scheduler,
-    state::{HasCorpus, HasMetadata},
+    state::{HasCorpus, HasMetadata, HasRand},
+};
+use libafl_bolts::prelude::Rand;
+use move_binary_format::{
+    access::ModuleAccess,
+    file_format::{AbilitySet, Bytecode},
+    CompiledModule,
 };
-use move_binary_format::{access::ModuleAccess, file_format::Bytecode, CompiledModule};
 use move_core_types::{
     account_address::AccountAddress,
     language_storage::{ModuleId, StructTag},
-    u256::U256,
 };
 use move_vm_runtime::loader::Function;
 use move_vm_types::{
     loaded_data::runtime_types::Type,
-    values::{Container, ContainerRef, IndexedRef, Value, ValueImpl},
+    values::{Container, Value, ValueImpl},
 };
 use revm_primitives::HashSet;
 use sui_types::base_types::{TX_CONTEXT_MODULE_NAME, TX_CONTEXT_STRUCT_NAME};
+use tracing::{info, warn};
 
 use crate::{
     generic_vm::vm_executor::GenericVM,
     mutation_utils::ConstantPoolMetadata,
     r#move::{
         input::{CloneableValue, FunctionDefaultable, MoveFunctionInput, StructAbilities},
+        loader::{BuildDirModuleLoader, LoadedModules, MoveModuleLoader},
         movevm,
         movevm::TypeTagInfoMeta,
         scheduler::MoveSchedulerMeta,
         types::{MoveFuzzState, MoveInfantStateState, MoveStagedVMState},
+        value_generator::{DefaultMoveValueGenerator, MoveValueGenerator},
         vm_state::MoveVMState,
     },
     state::HasCaller,
@@ -40,6 +47,55 @@ pub enum MoveInputStatus {
     DependentOnStructs(Value, Vec<Type>),
 }
 
+/// A function that could not be turned into an initial input, together with
+/// the reason why it is not being fuzzed.
+#[derive(Debug, Clone)]
+pub struct SkippedFunction {
+    pub module: ModuleId,
+    pub name: String,
+    pub reason: String,
+}
+
+/// A module that could not be deployed, so neither it nor the modules
+/// depending on it are fuzzed
+#[derive(Debug, Clone)]
+pub struct DeployFailure {
+    pub module: ModuleId,
+    pub reason: String,
+}
+
+/// Allowlist / denylist of target functions. Each pattern is either
+/// `module::function` or just `function` (matching any module).
+#[derive(Debug, Clone, Default)]
+pub struct MoveFunctionFilter {
+    pub allow: Vec<(Option<String>, String)>,
+    pub deny: Vec<(Option<String>, String)>,
+}
+
+impl MoveFunctionFilter {
+    /// Parse a comma separated list of patterns
+    pub fn parse_patterns(s: &str) -> Vec<(Option<String>, String)> {
+        s.split(',')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.split_once("::") {
+                Some((module, function)) => (Some(module.to_string()), function.to_string()),
+                None => (None, p.to_string()),
+            })
+            .collect()
+    }
+
+    pub fn is_allowed(&self, module: &ModuleId, function: &str) -> bool {
+        let matches = |(m, f): &(Option<String>, String)| {
+            f == function && m.as_ref().map_or(true, |m| m.as_str() == module.name().as_str())
+        };
+        if self.deny.iter().any(matches) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(matches)
+    }
+}
+
 pub struct MoveCorpusInitializer<'a, SC, ISC>
 where
     SC: Scheduler<State = MoveFuzzState>,
@@ -50,8 +106,34 @@ where
     pub scheduler: SC,
     pub infant_scheduler: ISC,
     pub default_state: MoveStagedVMState,
+    pub skipped_functions: Vec<SkippedFunction>,
+    pub deploy_failures: Vec<DeployFailure>,
+    pub function_filter: MoveFunctionFilter,
+    /// Maximum number of type argument instantiations built per generic
+    /// function, unbounded if 0
+    pub max_type_arg_combinations: usize,
+    /// Generator of the arguments of the initial inputs
+    pub value_generator: Box<dyn MoveValueGenerator>,
 }
 
+/// Default of `MoveCorpusInitializer::max_type_arg_combinations`
+pub const DEFAULT_MAX_TYPE_ARG_COMBINATIONS: usize = 4;
+
+/// Primitive types tried as type arguments, most useful first
+const PRIMITIVE_TYPE_ARGS: [Type; 8] = [
+    Type::U64,
+    Type::U8,
+    Type::Bool,
+    Type::Address,
+    Type::U128,
+    Type::U256,
+    Type::U16,
+    Type::U32,
+];
+
+/// Name of the function Sui runs once when a module is published
+pub const MODULE_INITIALIZER_NAME: &str = "init";
+
 pub fn is_tx_context(struct_tag: &StructTag) -> bool {
     struct_tag.address ==
         AccountAddress::new(
@@ -81,12 +163,38 @@ where
             scheduler,
             infant_scheduler,
             default_state: MoveStagedVMState::new_with_state(MoveVMState::new()),
+            skipped_functions: vec![],
+            deploy_failures: vec![],
+            function_filter: MoveFunctionFilter::default(),
+            max_type_arg_combinations: DEFAULT_MAX_TYPE_ARG_COMBINATIONS,
+            value_generator: Box::new(DefaultMoveValueGenerator),
         }
     }
 
+    pub fn set_function_filter(&mut self, function_filter: MoveFunctionFilter) {
+        self.function_filter = function_filter;
+    }
+
+    pub fn set_max_type_arg_combinations(&mut self, max_type_arg_combinations: usize) {
+        self.max_type_arg_combinations = max_type_arg_combinations;
+    }
+
+    pub fn set_value_generator(&mut self, value_generator: Box<dyn MoveValueGenerator>) {
+        self.value_generator = value_generator;
+    }
+
+    /// Functions of the target modules that were not added to the corpus
+    pub fn skipped_functions(&self) -> &[SkippedFunction] {
+        &self.skipped_functions
+    }
+
+    /// Modules that failed to deploy, including the modules depending on them
+    pub fn deploy_failures(&self) -> &[DeployFailure] {
+        &self.deploy_failures
+    }
+
     pub fn setup(&mut self, targets: Vec<String>) {
-        self.basic_setup();
-        self.initialize_glob(targets);
+        self.setup_with_loader(targets, &BuildDirModuleLoader);
     }
 
     pub fn basic_setup(&mut self) {
@@ -103,8 +211,12 @@ where
             .metadata_map_mut()
             .insert(MoveSchedulerMeta::new());
 
-        // setup infant scheduler & corpus
         self.default_state = StagedVMState::new_with_state(MoveVMState::new());
+    }
+
+    /// Add the default state (after module initializers have run) to the
+    /// infant state corpus
+    fn setup_default_state(&mut self) {
         let mut tc = Testcase::new(self.default_state.clone());
         tc.set_exec_time(Duration::from_secs(0));
         let idx = self
@@ -118,30 +230,28 @@ where
             .expect("failed to call infant scheduler on_add");
     }
 
+    pub fn setup_with_loader(&mut self, targets: Vec<String>, loader: &dyn MoveModuleLoader) {
+        self.basic_setup();
+        self.initialize_with_loader(targets, loader);
+    }
+
     pub fn initialize_glob(&mut self, dirs: Vec<String>) {
-        let mut modules = vec![];
-        let mut modules_dependencies = vec![];
-        for directory in dirs {
-            let deps = glob::glob(&format!("{}/*/bytecode_modules/dependencies/*/*.mv", directory)).unwrap();
-
-            for path in deps {
-                let module = std::fs::read(path.unwrap()).unwrap();
-                let module = CompiledModule::deserialize_no_check_bounds(&module).unwrap();
-                modules_dependencies.push(module);
-            }
+        self.initialize_with_loader(dirs, &BuildDirModuleLoader);
+    }
 
-            // find all directory named "bytecode_modules" in the current directory
-            let paths = glob::glob(&format!("{}/*/bytecode_modules/*.mv", directory)).unwrap();
-            for path in paths {
-                // read the file into a vector of bytes
-                let module = std::fs::read(path.unwrap()).unwrap();
-                // deserialize the vector of bytes into a CompiledModule
-                let module = CompiledModule::deserialize_no_check_bounds(&module).unwrap();
-                // add the module to the corpus
-                modules.push(module);
-            }
+    /// Load the modules of all targets with `loader`, then deploy them and
+    /// add their functions to the corpus
+    pub fn initialize_with_loader(&mut self, targets: Vec<String>, loader: &dyn MoveModuleLoader) {
+        let mut loaded = LoadedModules::default();
+        for target in targets {
+            loaded.extend(loader.load(&target));
         }
-        self.add_module(modules, modules_dependencies);
+        info!(
+            "loaded {} modules and {} dependencies",
+            loaded.modules.len(),
+            loaded.dependencies.len()
+        );
+        self.add_module(loaded.modules, loaded.dependencies);
     }
 
     fn extract_constants(&mut self, module: &CompiledModule) {
@@ -182,6 +292,9 @@ where
         });
     }
 
+    /// Deploy the modules after their dependencies. Modules that fail to
+    /// deploy, or whose dependencies do, are skipped and recorded in
+    /// `deploy_failures`, so that the other modules can still be fuzzed.
     fn deployer(
         &mut self,
         to_deploy: Vec<ModuleId>,
@@ -192,19 +305,39 @@ where
             if deployed.contains(&mod_id) {
                 continue;
             }
+            deployed.insert(mod_id.clone());
 
-            let module = module_id_to_module.get(&mod_id).unwrap().clone();
+            let module = match module_id_to_module.get(&mod_id) {
+                Some(module) => module.clone(),
+                None => {
+                    self.record_deploy_failure(mod_id, "module not found".to_string());
+                    continue;
+                }
+            };
 
             // push constants of module to mutator's constant hinting pool
             self.extract_constants(&module);
 
             let deps = module.immediate_dependencies();
-            self.deployer(deps, deployed, module_id_to_module);
-            self.executor.deploy(module, None, AccountAddress::random(), self.state);
-            deployed.insert(mod_id);
+            self.deployer(deps.clone(), deployed, module_id_to_module);
+            if let Some(failed) = deps
+                .iter()
+                .find(|dep| self.deploy_failures.iter().any(|f| f.module == **dep))
+            {
+                self.record_deploy_failure(mod_id, format!("dependency {} failed to deploy", failed));
+                continue;
+            }
+            if let Err(reason) = self.executor.try_deploy(module, self.state) {
+                self.record_deploy_failure(mod_id, reason);
+            }
         }
     }
 
+    fn record_deploy_failure(&mut self, module: ModuleId, reason: String) {
+        warn!("Failed to deploy {}: {}", module, reason);
+        self.deploy_failures.push(DeployFailure { module, reason });
+    }
+
     fn add_module(&mut self, modules: Vec<CompiledModule>, modules_dependencies: Vec<CompiledModule>) {
         macro_rules! wrap_input {
             ($input: expr) => {{
@@ -223,6 +356,10 @@ where
             &module_id_to_module,
         );
 
+        // run module initializers and start fuzzing from the resulting state
+        self.run_initializers(&modules.iter().map(|m| m.self_id()).collect_vec());
+        self.setup_default_state();
+
         let module_id_to_fuzz = modules.iter().map(|m| m.self_id()).collect::<HashSet<_>>();
 
         for (module_id, funcs) in self.executor.functions.clone() {
@@ -231,153 +368,273 @@ where
             }
 
             for (_, func) in funcs {
-                let input = self.build_input(&module_id, func.clone());
-                match input {
-                    Some(input) => {
-                        let idx = self
-                            .state
-                            .add_tx_to_corpus(wrap_input!(input))
-                            .expect("failed to add input to corpus");
-                        self.scheduler
-                            .on_add(self.state, idx)
-                            .expect("failed to call scheduler on_add");
+                if func.name.as_str() == MODULE_INITIALIZER_NAME {
+                    self.skipped_functions.push(SkippedFunction {
+                        module: module_id.clone(),
+                        name: func.name.to_string(),
+                        reason: "module initializer".to_string(),
+                    });
+                    continue;
+                }
+                if !self.function_filter.is_allowed(&module_id, func.name.as_str()) {
+                    self.skipped_functions.push(SkippedFunction {
+                        module: module_id.clone(),
+                        name: func.name.to_string(),
+                        reason: "function filter".to_string(),
+                    });
+                    continue;
+                }
+                let module = module_id_to_module.get(&module_id);
+                let inputs = self
+                    .type_arg_combinations(&module_id, module, &func)
+                    .and_then(|combinations| {
+                        combinations
+                            .into_iter()
+                            .map(|ty_args| self.build_input(&module_id, func.clone(), ty_args))
+                            .collect::<Result<Vec<_>, _>>()
+                    });
+                match inputs {
+                    Ok(inputs) => {
+                        for input in inputs {
+                            let idx = self
+                                .state
+                                .add_tx_to_corpus(wrap_input!(input))
+                                .expect("failed to add input to corpus");
+                            self.scheduler
+                                .on_add(self.state, idx)
+                                .expect("failed to call scheduler on_add");
+                        }
                     }
-                    None => {
-                        // dependent on structs
-                        todo!()
+                    Err(reason) => {
+                        self.skipped_functions.push(SkippedFunction {
+                            module: module_id.clone(),
+                            name: func.name.to_string(),
+                            reason,
+                        });
                     }
                 }
             }
         }
+
+        self.print_skipped_summary();
     }
 
-    // if struct is found, return None because we cannot instantiate a struct
-    #[allow(clippy::boxed_local)]
-    fn gen_default_value(state: &mut MoveFuzzState, ty: Box<Type>) -> MoveInputStatus {
-        match *ty {
-            Type::Bool => MoveInputStatus::Complete(Value::bool(false)),
-            Type::U8 => MoveInputStatus::Complete(Value::u8(0)),
-            Type::U16 => MoveInputStatus::Complete(Value::u16(0)),
-            Type::U32 => MoveInputStatus::Complete(Value::u32(0)),
-            Type::U64 => MoveInputStatus::Complete(Value::u64(0)),
-            Type::U128 => MoveInputStatus::Complete(Value::u128(0)),
-            Type::U256 => MoveInputStatus::Complete(Value::u256(U256::zero())),
-            Type::Address => MoveInputStatus::Complete(Value::address(state.get_rand_address())),
-            Type::Signer => MoveInputStatus::Complete(Value::signer(state.get_rand_address())),
-            Type::Vector(v) => {
-                macro_rules! wrap {
-                    ($v: ident, $default: expr) => {
-                        MoveInputStatus::Complete(Value(ValueImpl::Container(Container::$v(Rc::new(
-                            RefCell::new($default),
-                        )))))
-                    };
-                }
-                match *v.clone() {
-                    Type::Vector(_) => todo!("vector of vector"),
-                    Type::Bool => {
-                        wrap!(VecBool, vec![false])
-                    }
-                    Type::U8 => {
-                        wrap!(VecU8, vec![0])
-                    }
-                    Type::U64 => {
-                        wrap!(VecU64, vec![0])
-                    }
-                    Type::U128 => {
-                        wrap!(VecU128, vec![0])
-                    }
-                    Type::U16 => {
-                        wrap!(VecU16, vec![0])
-                    }
-                    Type::U32 => {
-                        wrap!(VecU32, vec![0])
-                    }
-                    Type::U256 => {
-                        wrap!(VecU256, vec![U256::zero()])
-                    }
-                    Type::Address => {
-                        wrap!(VecAddress, vec![state.get_rand_address()])
-                    }
-                    Type::Signer => {
-                        unreachable!("cannot initialize signer vector")
-                    }
-                    Type::Reference(_) |
-                    Type::MutableReference(_) |
-                    Type::Struct(_) |
-                    Type::StructInstantiation(_, _) => {
-                        let default_inner = Self::gen_default_value(state, v);
-                        if let MoveInputStatus::Complete(Value(inner)) = default_inner {
-                            wrap!(Vec, vec![inner])
-                        } else if let MoveInputStatus::DependentOnStructs(Value(inner), deps) = default_inner {
-                            MoveInputStatus::DependentOnStructs(
-                                Value(ValueImpl::Container(Container::Vec(Rc::new(RefCell::new(vec![inner]))))),
-                                deps,
-                            )
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                    _ => unreachable!(),
-                }
+    fn print_skipped_summary(&self) {
+        if !self.deploy_failures.is_empty() {
+            warn!(
+                "{} module(s) failed to deploy and are not fuzzed:",
+                self.deploy_failures.len()
+            );
+            for failure in &self.deploy_failures {
+                warn!("  {} ({})", failure.module, failure.reason);
             }
-            Type::Struct(_) | Type::StructInstantiation(_, _) => MoveInputStatus::DependentOnStructs(
-                Value(ValueImpl::Container(Container::Struct(Rc::new(RefCell::new(vec![]))))),
-                vec![*ty],
-            ),
-            Type::Reference(ty) | Type::MutableReference(ty) => {
-                let default_inner = Self::gen_default_value(state, ty);
-                if let MoveInputStatus::Complete(Value(inner)) = default_inner {
-                    if let ValueImpl::Container(inner_v) = inner {
-                        MoveInputStatus::Complete(Value(ValueImpl::ContainerRef(ContainerRef::Local(inner_v))))
-                    } else {
-                        MoveInputStatus::Complete(Value(ValueImpl::IndexedRef(IndexedRef {
-                            idx: 0,
-                            container_ref: ContainerRef::Local(Container::Locals(Rc::new(RefCell::new(vec![inner])))),
-                        })))
-                    }
-                } else if let MoveInputStatus::DependentOnStructs(Value(ValueImpl::Container(cont)), deps) =
-                    default_inner
+        }
+        if self.skipped_functions.is_empty() {
+            info!("All functions of the target modules are fuzzed");
+            return;
+        }
+        warn!(
+            "{} function(s) of the target modules are not fuzzed:",
+            self.skipped_functions.len()
+        );
+        for skipped in &self.skipped_functions {
+            warn!("  {}::{} ({})", skipped.module, skipped.name, skipped.reason);
+        }
+    }
+
+    /// Run the `init` function of each module so that objects created by the
+    /// initializers (e.g., shared objects, admin caps) exist before fuzzing
+    fn run_initializers(&mut self, modules: &[ModuleId]) {
+        let type_tag_info = self
+            .state
+            .metadata_map()
+            .get::<TypeTagInfoMeta>()
+            .expect("type tag info not found")
+            .clone();
+
+        let mut vm_state = self.default_state.clone();
+        for module_id in modules {
+            let init = match self.executor.functions.get(module_id).and_then(|funcs| {
+                funcs
+                    .values()
+                    .find(|func| func.name.as_str() == MODULE_INITIALIZER_NAME)
+                    .cloned()
+            }) {
+                Some(init) => init,
+                None => continue,
+            };
+
+            let mut args = vec![];
+            for parameter_type in &init.parameter_types {
+                if let Some(tag) = type_tag_info.get_type_tag(parameter_type) &&
+                    is_tx_context(tag)
                 {
-                    MoveInputStatus::DependentOnStructs(Value(ValueImpl::ContainerRef(ContainerRef::Local(cont))), deps)
+                    args.push(CloneableValue::from(
+                        self.value_generator.gen_tx_context(self.state, parameter_type.clone()),
+                    ));
+                } else if let Type::Struct(_) = parameter_type {
+                    // one-time witness, a struct with a single bool field
+                    args.push(CloneableValue::from(Value(ValueImpl::Container(Container::Struct(
+                        Rc::new(RefCell::new(vec![ValueImpl::Bool(true)])),
+                    )))));
                 } else {
-                    unreachable!()
+                    warn!(
+                        "Cannot run initializer of {}: unsupported param {:?}",
+                        module_id, parameter_type
+                    );
+                    break;
                 }
             }
-            ty => todo!("gen_default_value failed: {:?}", ty),
+            if args.len() != init.parameter_types.len() {
+                continue;
+            }
+
+            let input = MoveFunctionInput {
+                module: module_id.clone(),
+                function: init.name.clone(),
+                function_info: Arc::new(FunctionDefaultable {
+                    function: Some(init.clone()),
+                }),
+                args,
+                ty_args: vec![],
+                caller: self.state.get_rand_caller(),
+                vm_state: vm_state.clone(),
+                vm_state_idx: 0,
+                _deps: HashMap::new(),
+                _resolved: true,
+            };
+            let res = self.executor.execute(&input, self.state);
+            if res.reverted {
+                warn!("Initializer of {} reverted", module_id);
+                continue;
+            }
+
+            let mut new_state = res.new_state;
+            let published = new_state
+                .state
+                .values
+                .iter()
+                .filter(|(ty, vals)| vm_state.state.values.get(*ty).map_or(0, |v| v.len()) < vals.len())
+                .map(|(ty, _)| ty.clone())
+                .collect_vec();
+            info!("Initializer of {} published {} resource(s)", module_id, published.len());
+            new_state.state.published_resources.insert(module_id.clone(), published);
+            vm_state = new_state;
         }
+        self.default_state = vm_state;
+    }
+
+    /// Non-generic structs of the module that appear in the parameters of its
+    /// functions with their abilities, sorted by name
+    fn module_struct_types(&self, module_id: &ModuleId, module: Option<&CompiledModule>) -> Vec<(Type, AbilitySet)> {
+        let type_tag_info = self
+            .state
+            .metadata_map()
+            .get::<TypeTagInfoMeta>()
+            .expect("type tag info not found");
+        type_tag_info
+            .type_to_type_tag
+            .iter()
+            .filter(|(ty, tag)| {
+                matches!(ty, Type::Struct(_)) &&
+                    tag.address == *module_id.address() &&
+                    tag.module.as_ident_str() == module_id.name() &&
+                    !is_tx_context(tag)
+            })
+            .sorted_by_key(|(_, tag)| tag.name.clone())
+            .map(|(ty, tag)| {
+                let abilities = module
+                    .and_then(|module| {
+                        module.struct_defs().iter().find_map(|def| {
+                            let handle = module.struct_handle_at(def.struct_handle);
+                            (module.identifier_at(handle.name) == tag.name.as_ident_str()).then_some(handle.abilities)
+                        })
+                    })
+                    .unwrap_or(AbilitySet::EMPTY);
+                (ty.clone(), abilities)
+            })
+            .collect()
     }
 
-    fn gen_tx_context(&mut self, ty: Type) -> Value {
-        if let Type::MutableReference(ty) = ty {
-            if let Type::Struct(_struct_tag) = *ty {
-                // struct TxContext has drop {
-                //     /// The address of the user that signed the current transaction
-                //     sender: address,
-                //     /// Hash of the current transaction
-                //     tx_hash: vector<u8>,
-                //     /// The current epoch number
-                //     epoch: u64,
-                //     /// Timestamp that the epoch started at
-                //     epoch_timestamp_ms: u64,
-                //     /// Counter recording the number of fresh id's created while executing
-                //     /// this transaction. Always 0 at the start of a transaction
-                //     ids_created: u64
-                // }
-                let inner = Container::Struct(Rc::new(RefCell::new(vec![
-                    ValueImpl::Address(self.state.get_rand_caller()),
-                    ValueImpl::Container(Container::VecU8(Rc::new(RefCell::new(vec![6; 32])))),
-                    ValueImpl::U64(123213),
-                    ValueImpl::U64(2130127412),
-                    ValueImpl::U64(0),
-                ])));
-
-                return Value(ValueImpl::ContainerRef(ContainerRef::Local(inner)));
+    /// Type arguments satisfying the abilities required by `function` to
+    /// instantiate it with, at most `max_type_arg_combinations` of them (all
+    /// of them if 0). Instantiations with `u64` and with each struct of the
+    /// module come first, then the other primitives, and the rest is sampled
+    /// randomly instead of enumerating the cartesian product.
+    fn type_arg_combinations(
+        &mut self,
+        module_id: &ModuleId,
+        module: Option<&CompiledModule>,
+        function: &Function,
+    ) -> Result<Vec<Vec<Type>>, String> {
+        if function.type_parameters.is_empty() {
+            return Ok(vec![vec![]]);
+        }
+        let structs = self.module_struct_types(module_id, module);
+        let candidates = function
+            .type_parameters
+            .iter()
+            .map(|constraints| {
+                // primitives have copy, drop and store
+                let mut tys = if constraints.is_subset(AbilitySet::PRIMITIVES) {
+                    PRIMITIVE_TYPE_ARGS.to_vec()
+                } else {
+                    vec![]
+                };
+                tys.extend(
+                    structs
+                        .iter()
+                        .filter(|(_, abilities)| constraints.is_subset(*abilities))
+                        .map(|(ty, _)| ty.clone()),
+                );
+                tys
+            })
+            .collect_vec();
+        if candidates.iter().any(|tys| tys.is_empty()) {
+            return Err("generic, no type argument satisfies the abilities".to_string());
+        }
+        if self.max_type_arg_combinations == 0 {
+            return Ok(candidates.into_iter().multi_cartesian_product().collect());
+        }
+
+        let total = candidates.iter().fold(1usize, |acc, tys| acc.saturating_mul(tys.len()));
+        let max = self.max_type_arg_combinations.min(total);
+        let mut combinations: Vec<Vec<Type>> = vec![];
+        let preferred = std::iter::once(&PRIMITIVE_TYPE_ARGS[0])
+            .chain(structs.iter().map(|(ty, _)| ty))
+            .chain(PRIMITIVE_TYPE_ARGS[1..].iter());
+        for ty in preferred {
+            if combinations.len() >= max {
+                break;
+            }
+            let combination = candidates
+                .iter()
+                .map(|tys| if tys.contains(ty) { ty.clone() } else { tys[0].clone() })
+                .collect_vec();
+            if !combinations.contains(&combination) {
+                combinations.push(combination);
+            }
+        }
+        let mut attempts = 0;
+        while combinations.len() < max && attempts < max * 10 {
+            attempts += 1;
+            let combination = candidates
+                .iter()
+                .map(|tys| tys[self.state.rand_mut().below(tys.len() as u64) as usize].clone())
+                .collect_vec();
+            if !combinations.contains(&combination) {
+                combinations.push(combination);
             }
         }
-        unreachable!()
+        Ok(combinations)
     }
 
-    fn build_input(&mut self, module_id: &ModuleId, function: Arc<Function>) -> Option<MoveFunctionInput> {
+    fn build_input(
+        &mut self,
+        module_id: &ModuleId,
+        function: Arc<Function>,
+        ty_args: Vec<Type>,
+    ) -> Result<MoveFunctionInput, String> {
         let mut values = vec![];
         let mut resolved = true;
         let mut deps = HashMap::new();
@@ -388,13 +645,21 @@ where
             .expect("type tag info not found")
             .clone();
         for parameter_type in &function.parameter_types {
+            let parameter_type = &if ty_args.is_empty() {
+                parameter_type.clone()
+            } else {
+                parameter_type
+                    .subst(&ty_args)
+                    .map_err(|e| format!("generic, cannot instantiate {:?}: {:?}", parameter_type, e))?
+            };
             let tag = type_tag_info.get_type_tag(parameter_type);
             let default_val = if let Some(tag) = tag &&
                 is_tx_context(tag)
             {
-                MoveInputStatus::Complete(self.gen_tx_context(parameter_type.clone()))
+                MoveInputStatus::Complete(self.value_generator.gen_tx_context(self.state, parameter_type.clone()))
             } else {
-                Self::gen_default_value(self.state, Box::new(parameter_type.clone()))
+                self.value_generator
+                    .gen_value(self.state, Box::new(parameter_type.clone()))?
             };
 
             match default_val {
@@ -418,7 +683,7 @@ where
                 function: Some(function),
             }),
             args: values,
-            ty_args: vec![],
+            ty_args,
             caller: self.state.get_rand_caller(),
             vm_state: StagedVMState::new_uninitialized(),
             vm_state_idx: 0,
@@ -427,6 +692,6 @@ where
         };
 
         // debug!("input: {:?}", input);
-        Some(input)
+        Ok(input)
     }
 }
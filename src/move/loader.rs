@@ -0,0 +1,151 @@
+//! Loaders of the Move modules to fuzz, one per package layout
+
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use move_binary_format::CompiledModule;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// Modules to fuzz and the modules they depend on
+#[derive(Default)]
+pub struct LoadedModules {
+    pub modules: Vec<CompiledModule>,
+    pub dependencies: Vec<CompiledModule>,
+}
+
+impl LoadedModules {
+    pub fn extend(&mut self, other: LoadedModules) {
+        self.modules.extend(other.modules);
+        self.dependencies.extend(other.dependencies);
+    }
+}
+
+/// Loads the modules of a target. The loaded modules (and their dependencies)
+/// are deployed and fuzzed by the corpus initializer.
+pub trait MoveModuleLoader {
+    fn load(&self, target: &str) -> LoadedModules;
+}
+
+fn read_module(path: &std::path::Path) -> Option<CompiledModule> {
+    let bytes = std::fs::read(path).ok()?;
+    CompiledModule::deserialize_no_check_bounds(&bytes)
+        .map_err(|e| warn!("failed to deserialize {}: {:?}", path.display(), e))
+        .ok()
+}
+
+fn read_modules(pattern: &str) -> Vec<CompiledModule> {
+    glob::glob(pattern)
+        .unwrap()
+        .filter_map(|path| read_module(&path.unwrap()))
+        .collect()
+}
+
+/// Loads `.mv` files from the `bytecode_modules` directories of a Move
+/// build, i.e., `<target>/<package>/bytecode_modules/*.mv` with dependencies
+/// in `bytecode_modules/dependencies/<dep>/*.mv`
+#[derive(Default)]
+pub struct BuildDirModuleLoader;
+
+impl MoveModuleLoader for BuildDirModuleLoader {
+    fn load(&self, target: &str) -> LoadedModules {
+        LoadedModules {
+            modules: read_modules(&format!("{}/*/bytecode_modules/*.mv", target)),
+            dependencies: read_modules(&format!("{}/*/bytecode_modules/dependencies/*/*.mv", target)),
+        }
+    }
+}
+
+/// Output of `sui move build --dump-bytecode-as-base64`
+#[derive(Deserialize)]
+struct SuiPackageDump {
+    modules: Vec<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// Loads Sui packages as published, i.e., the JSON files dumped by
+/// `sui move build --dump-bytecode-as-base64` (`<target>/*.json`).
+///
+/// The dump only refers to dependencies by package id, so their modules are
+/// loaded from `.mv` files in `<target>/dependencies/**/` (e.g., the Sui
+/// framework and Move stdlib).
+#[derive(Default)]
+pub struct SuiModuleLoader;
+
+impl SuiModuleLoader {
+    fn load_dump(path: &std::path::Path) -> Vec<CompiledModule> {
+        let Some(dump) = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<SuiPackageDump>(&s).ok())
+        else {
+            warn!("{} is not a Sui package dump", path.display());
+            return vec![];
+        };
+        info!(
+            "loading Sui package {} ({} modules, depends on {})",
+            path.display(),
+            dump.modules.len(),
+            dump.dependencies.join(", ")
+        );
+        dump.modules
+            .iter()
+            .filter_map(|module| {
+                let bytes = STANDARD
+                    .decode(module)
+                    .map_err(|e| warn!("invalid module in {}: {}", path.display(), e))
+                    .ok()?;
+                CompiledModule::deserialize_no_check_bounds(&bytes)
+                    .map_err(|e| warn!("failed to deserialize module in {}: {:?}", path.display(), e))
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+impl MoveModuleLoader for SuiModuleLoader {
+    fn load(&self, target: &str) -> LoadedModules {
+        let modules = glob::glob(&format!("{}/*.json", target))
+            .unwrap()
+            .flat_map(|path| Self::load_dump(&path.unwrap()))
+            .collect();
+        LoadedModules {
+            modules,
+            dependencies: read_modules(&format!("{}/dependencies/**/*.mv", target)),
+        }
+    }
+}
+
+/// Package layout of the Move targets
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MoveModuleFormat {
+    /// `bytecode_modules` directories of a Move build
+    #[default]
+    Build,
+    /// Sui packages dumped with `--dump-bytecode-as-base64`
+    SuiPackage,
+}
+
+impl MoveModuleFormat {
+    pub fn loader(&self) -> Box<dyn MoveModuleLoader> {
+        match self {
+            MoveModuleFormat::Build => Box::new(BuildDirModuleLoader),
+            MoveModuleFormat::SuiPackage => Box::new(SuiModuleLoader),
+        }
+    }
+}
+
+impl FromStr for MoveModuleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "build" => Ok(MoveModuleFormat::Build),
+            "sui_package" => Ok(MoveModuleFormat::SuiPackage),
+            _ => Err(format!(
+                "unknown module format {}, expected one of: build, sui_package",
+                s
+            )),
+        }
+    }
+}
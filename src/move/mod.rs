@@ -1,18 +1,26 @@
 pub mod corpus_initializer;
 pub mod input;
+pub mod loader;
 pub mod minimizer;
 pub mod movevm;
 pub mod mutator;
 pub mod oracles;
 pub mod scheduler;
 pub mod types;
+pub mod value_generator;
 pub mod vm_state;
 
 mod input_printer;
 
 use clap::Parser;
 
-use crate::fuzzers::move_fuzzer::{move_fuzzer, MoveFuzzConfig};
+use crate::{
+    fuzzers::move_fuzzer::{move_fuzzer, MoveFuzzConfig},
+    r#move::{
+        corpus_initializer::{MoveFunctionFilter, DEFAULT_MAX_TYPE_ARG_COMBINATIONS},
+        loader::MoveModuleFormat,
+    },
+};
 
 /// CLI for ItyFuzz for Move smart contracts
 #[derive(Parser, Debug)]
@@ -25,6 +33,26 @@ pub struct MoveArgs {
     /// Seed for the RNG
     #[arg(short, long, default_value = "0")]
     seed: u64,
+
+    /// Only fuzz these functions, separated by comma. Format:
+    /// module::function or function
+    #[arg(long, default_value = "")]
+    fuzz_functions: String,
+
+    /// Never fuzz these functions, same format as --fuzz-functions
+    #[arg(long, default_value = "")]
+    skip_functions: String,
+
+    /// Layout of the target modules. `build`: `bytecode_modules` directories
+    /// of a Move build. `sui_package`: JSON files dumped by `sui move build
+    /// --dump-bytecode-as-base64`
+    #[arg(long, default_value = "build")]
+    module_format: MoveModuleFormat,
+
+    /// Maximum number of type argument instantiations to fuzz per generic
+    /// function, 0 for all of them
+    #[arg(long, default_value_t = DEFAULT_MAX_TYPE_ARG_COMBINATIONS)]
+    max_type_arg_combinations: usize,
 }
 
 pub fn move_main(args: MoveArgs) {
@@ -32,5 +60,11 @@ pub fn move_main(args: MoveArgs) {
         target: args.target,
         work_dir: "./work_dir".to_string(),
         seed: args.seed,
+        function_filter: MoveFunctionFilter {
+            allow: MoveFunctionFilter::parse_patterns(&args.fuzz_functions),
+            deny: MoveFunctionFilter::parse_patterns(&args.skip_functions),
+        },
+        module_format: args.module_format,
+        max_type_arg_combinations: args.max_type_arg_combinations,
     });
 }
@@ -217,6 +217,50 @@ impl<I, S> MoveVM<I, S> {
     }
 }
 
+impl<I, S> MoveVM<I, S>
+where
+    S: HasMetadata,
+{
+    /// Load `module` and register its functions, failing if the module does
+    /// not verify or does not link against the loaded modules
+    pub fn try_deploy(&mut self, module: CompiledModule, state: &mut S) -> Result<AccountAddress, String> {
+        // debug!("deploying module dep: {:?}", module.self_id());
+
+        if !state.metadata_map_mut().contains::<TypeTagInfoMeta>() {
+            state.metadata_map_mut().insert(TypeTagInfoMeta::new());
+        }
+
+        let meta = state.metadata_map_mut().get_mut::<TypeTagInfoMeta>().unwrap();
+
+        let func_off = self.loader.module_cache.read().functions.len();
+        let _module_name = module.name().to_owned();
+        let deployed_module_idx = module.self_id();
+        self.loader
+            .module_cache
+            .write()
+            .insert(
+                &Self::get_natives(),
+                &MoveVMState::default(),
+                deployed_module_idx.clone(),
+                &module,
+            )
+            .map_err(|e| format!("{:?}", e))?;
+        for f in &self.loader.module_cache.read().functions[func_off..] {
+            // debug!("deployed function: {:?}@{}({:?}) returns {:?}", deployed_module_idx,
+            // f.name.as_str(), f.parameter_types, f.return_types());
+            self.functions
+                .entry(deployed_module_idx.clone())
+                .or_default()
+                .insert(f.name.to_owned(), f.clone());
+
+            for ty in &f.parameter_types {
+                meta.register_type_tag(ty.clone(), &self.loader);
+            }
+        }
+        Ok(*deployed_module_idx.address())
+    }
+}
+
 pub struct MoveVMTracer;
 
 impl ItyFuzzTracer for MoveVMTracer {
@@ -451,6 +495,8 @@ where
     I: VMInputT<MoveVMState, ModuleId, AccountAddress, ConciseMoveInput> + MoveFunctionInputT + 'static,
     S: HasMetadata + HasCaller<MoveAddress> + 'static,
 {
+    type Log = ();
+
     fn deploy(
         &mut self,
         module: CompiledModule,
@@ -458,40 +504,7 @@ where
         _deployed_address: AccountAddress,
         state: &mut S,
     ) -> Option<AccountAddress> {
-        // debug!("deploying module dep: {:?}", module.self_id());
-
-        if !state.metadata_map_mut().contains::<TypeTagInfoMeta>() {
-            state.metadata_map_mut().insert(TypeTagInfoMeta::new());
-        }
-
-        let meta = state.metadata_map_mut().get_mut::<TypeTagInfoMeta>().unwrap();
-
-        let func_off = self.loader.module_cache.read().functions.len();
-        let _module_name = module.name().to_owned();
-        let deployed_module_idx = module.self_id();
-        self.loader
-            .module_cache
-            .write()
-            .insert(
-                &Self::get_natives(),
-                &MoveVMState::default(),
-                deployed_module_idx.clone(),
-                &module,
-            )
-            .expect("internal deploy error");
-        for f in &self.loader.module_cache.read().functions[func_off..] {
-            // debug!("deployed function: {:?}@{}({:?}) returns {:?}", deployed_module_idx,
-            // f.name.as_str(), f.parameter_types, f.return_types());
-            self.functions
-                .entry(deployed_module_idx.clone())
-                .or_default()
-                .insert(f.name.to_owned(), f.clone());
-
-            for ty in &f.parameter_types {
-                meta.register_type_tag(ty.clone(), &self.loader);
-            }
-        }
-        Some(*deployed_module_idx.address())
+        self.try_deploy(module, state).ok()
     }
 
     fn fast_static_call(
@@ -571,7 +584,7 @@ where
                 pc: 0,
                 locals,
                 function: initial_function.clone(),
-                ty_args: vec![],
+                ty_args: input.ty_args().clone(),
                 local_tys: vec![],
             }
         };
@@ -782,6 +795,7 @@ where
             output: out,
             reverted,
             additional_info: None,
+            logs: vec![],
         }
     }
 
@@ -922,6 +936,7 @@ mod tests {
                     values: Default::default(),
                     typed_bug: vec![],
                     ref_in_use: vec![],
+                    published_resources: Default::default(),
                 },
                 stage: vec![],
                 initialized: false,
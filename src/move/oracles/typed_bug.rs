@@ -9,8 +9,8 @@ use move_core_types::language_storage::ModuleId;
 use serde_json::json;
 
 use crate::{
-    fuzzer::ORACLE_OUTPUT,
-    oracle::Oracle,
+    fuzzer::push_oracle_output,
+    oracle::{Oracle, OracleDescriptor},
     r#move::{
         input::{ConciseMoveInput, MoveFunctionInput},
         movevm::MoveVM,
@@ -66,9 +66,7 @@ impl
                         "bug_info": format!("{:?} violated", bug_id),
                         "bug_idx": real_bug_idx,
                     });
-                    unsafe {
-                        ORACLE_OUTPUT.push(msg);
-                    }
+                    push_oracle_output("typed_bug", msg);
 
                     real_bug_idx
                 })
@@ -77,4 +75,8 @@ impl
             vec![]
         }
     }
+
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::new("typed_bug")
+    }
 }
@@ -0,0 +1,170 @@
+//! Values of the parameters of the initial inputs.
+//!
+//! The [corpus initializer](super::corpus_initializer::MoveCorpusInitializer)
+//! builds the arguments of each target function with a [`MoveValueGenerator`],
+//! so that domain-specific generators (e.g., biasing addresses toward known
+//! resource owners, or realistic coin amounts) can be plugged in without
+//! changing the initializer.
+
+use std::{cell::RefCell, rc::Rc};
+
+use move_core_types::{account_address::AccountAddress, u256::U256};
+use move_vm_types::{
+    loaded_data::runtime_types::Type,
+    values::{Container, ContainerRef, IndexedRef, Value, ValueImpl},
+};
+
+use crate::{
+    r#move::{corpus_initializer::MoveInputStatus, types::MoveFuzzState},
+    state::HasCaller,
+};
+
+pub trait MoveValueGenerator {
+    /// Value of a parameter of type `ty`. Generators overriding this can fall
+    /// back to [`gen_default_value`] for the types they do not handle.
+    fn gen_value(&self, state: &mut MoveFuzzState, ty: Box<Type>) -> Result<MoveInputStatus, String> {
+        gen_default_value(self, state, ty)
+    }
+
+    /// Address of `address` and `signer` parameters
+    fn gen_address(&self, state: &mut MoveFuzzState) -> AccountAddress {
+        state.get_rand_address()
+    }
+
+    /// `&mut TxContext` parameter
+    fn gen_tx_context(&self, state: &mut MoveFuzzState, ty: Type) -> Value {
+        if let Type::MutableReference(ty) = ty {
+            if let Type::Struct(_struct_tag) = *ty {
+                // struct TxContext has drop {
+                //     /// The address of the user that signed the current transaction
+                //     sender: address,
+                //     /// Hash of the current transaction
+                //     tx_hash: vector<u8>,
+                //     /// The current epoch number
+                //     epoch: u64,
+                //     /// Timestamp that the epoch started at
+                //     epoch_timestamp_ms: u64,
+                //     /// Counter recording the number of fresh id's created while executing
+                //     /// this transaction. Always 0 at the start of a transaction
+                //     ids_created: u64
+                // }
+                let inner = Container::Struct(Rc::new(RefCell::new(vec![
+                    ValueImpl::Address(state.get_rand_caller()),
+                    ValueImpl::Container(Container::VecU8(Rc::new(RefCell::new(vec![6; 32])))),
+                    ValueImpl::U64(123213),
+                    ValueImpl::U64(2130127412),
+                    ValueImpl::U64(0),
+                ])));
+
+                return Value(ValueImpl::ContainerRef(ContainerRef::Local(inner)));
+            }
+        }
+        unreachable!()
+    }
+}
+
+/// The generator used unless another one is set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultMoveValueGenerator;
+
+impl MoveValueGenerator for DefaultMoveValueGenerator {}
+
+/// Values of the default generator: zeros, empty / singleton vectors and
+/// random addresses.
+///
+/// if struct is found, return DependentOnStructs because we cannot instantiate
+/// a struct. if the type is not supported, return the reason as error
+#[allow(clippy::boxed_local)]
+pub fn gen_default_value<G>(generator: &G, state: &mut MoveFuzzState, ty: Box<Type>) -> Result<MoveInputStatus, String>
+where
+    G: MoveValueGenerator + ?Sized,
+{
+    Ok(match *ty {
+        Type::Bool => MoveInputStatus::Complete(Value::bool(false)),
+        Type::U8 => MoveInputStatus::Complete(Value::u8(0)),
+        Type::U16 => MoveInputStatus::Complete(Value::u16(0)),
+        Type::U32 => MoveInputStatus::Complete(Value::u32(0)),
+        Type::U64 => MoveInputStatus::Complete(Value::u64(0)),
+        Type::U128 => MoveInputStatus::Complete(Value::u128(0)),
+        Type::U256 => MoveInputStatus::Complete(Value::u256(U256::zero())),
+        Type::Address => MoveInputStatus::Complete(Value::address(generator.gen_address(state))),
+        Type::Signer => MoveInputStatus::Complete(Value::signer(generator.gen_address(state))),
+        Type::Vector(v) => {
+            macro_rules! wrap {
+                ($v: ident, $default: expr) => {
+                    MoveInputStatus::Complete(Value(ValueImpl::Container(Container::$v(Rc::new(
+                        RefCell::new($default),
+                    )))))
+                };
+            }
+            match *v.clone() {
+                Type::Vector(_) => return Err("nested vector param".to_string()),
+                Type::Bool => {
+                    wrap!(VecBool, vec![false])
+                }
+                Type::U8 => {
+                    wrap!(VecU8, vec![0])
+                }
+                Type::U64 => {
+                    wrap!(VecU64, vec![0])
+                }
+                Type::U128 => {
+                    wrap!(VecU128, vec![0])
+                }
+                Type::U16 => {
+                    wrap!(VecU16, vec![0])
+                }
+                Type::U32 => {
+                    wrap!(VecU32, vec![0])
+                }
+                Type::U256 => {
+                    wrap!(VecU256, vec![U256::zero()])
+                }
+                Type::Address => {
+                    wrap!(VecAddress, vec![generator.gen_address(state)])
+                }
+                Type::Signer => {
+                    return Err("signer vector param".to_string());
+                }
+                Type::Reference(_) | Type::MutableReference(_) | Type::Struct(_) | Type::StructInstantiation(_, _) => {
+                    let default_inner = generator.gen_value(state, v)?;
+                    if let MoveInputStatus::Complete(Value(inner)) = default_inner {
+                        wrap!(Vec, vec![inner])
+                    } else if let MoveInputStatus::DependentOnStructs(Value(inner), deps) = default_inner {
+                        MoveInputStatus::DependentOnStructs(
+                            Value(ValueImpl::Container(Container::Vec(Rc::new(RefCell::new(vec![inner]))))),
+                            deps,
+                        )
+                    } else {
+                        unreachable!()
+                    }
+                }
+                Type::TyParam(_) => return Err("generic".to_string()),
+                ty => return Err(format!("unsupported param {:?}", ty)),
+            }
+        }
+        Type::Struct(_) | Type::StructInstantiation(_, _) => MoveInputStatus::DependentOnStructs(
+            Value(ValueImpl::Container(Container::Struct(Rc::new(RefCell::new(vec![]))))),
+            vec![*ty],
+        ),
+        Type::Reference(ty) | Type::MutableReference(ty) => {
+            let default_inner = generator.gen_value(state, ty)?;
+            if let MoveInputStatus::Complete(Value(inner)) = default_inner {
+                if let ValueImpl::Container(inner_v) = inner {
+                    MoveInputStatus::Complete(Value(ValueImpl::ContainerRef(ContainerRef::Local(inner_v))))
+                } else {
+                    MoveInputStatus::Complete(Value(ValueImpl::IndexedRef(IndexedRef {
+                        idx: 0,
+                        container_ref: ContainerRef::Local(Container::Locals(Rc::new(RefCell::new(vec![inner])))),
+                    })))
+                }
+            } else if let MoveInputStatus::DependentOnStructs(Value(ValueImpl::Container(cont)), deps) = default_inner {
+                MoveInputStatus::DependentOnStructs(Value(ValueImpl::ContainerRef(ContainerRef::Local(cont))), deps)
+            } else {
+                return Err("reference param".to_string());
+            }
+        }
+        Type::TyParam(_) => return Err("generic".to_string()),
+        ty => return Err(format!("unsupported param {:?}", ty)),
+    })
+}
@@ -84,6 +84,9 @@ pub struct MoveVMState {
     pub typed_bug: Vec<String>,
 
     pub ref_in_use: Vec<(Type, GatedValue)>,
+
+    /// Types of the objects published by each module initializer
+    pub published_resources: HashMap<ModuleId, Vec<Type>>,
 }
 
 impl MoveVMStateT for MoveVMState {
@@ -115,6 +118,7 @@ impl MoveVMState {
             values: HashMap::new(),
             typed_bug: vec![],
             ref_in_use: vec![],
+            published_resources: HashMap::new(),
         }
     }
 
@@ -372,6 +376,7 @@ impl Clone for MoveVMState {
             values: self.values.clone(),
             typed_bug: self.typed_bug.clone(),
             ref_in_use: self.ref_in_use.clone(),
+            published_resources: self.published_resources.clone(),
         }
     }
 }
@@ -541,6 +546,8 @@ pub fn value_to_hash(v: &ValueImpl, hasher: &mut DefaultHasher) {
 }
 
 impl VMStateT for MoveVMState {
+    type Log = ();
+
     fn get_hash(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
         self.resources.iter().for_each(|(addr, ty)| {
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 /// Mutation utilities for the EVM
 use libafl::inputs::{HasBytesVec, Input};
@@ -109,6 +109,157 @@ where
     }
 }
 
+/// Selectors of the functions the contracts call on other contracts
+///
+/// This is metadata attached to the global fuzz state, so that calldata passed
+/// to contracts dispatching to arbitrary targets starts with a valid selector
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct SelectorDictionaryMetadata {
+    pub selectors: Vec<[u8; 4]>,
+}
+
+impl SelectorDictionaryMetadata {
+    /// Create a new [`SelectorDictionaryMetadata`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a selector to the dictionary if not known yet
+    pub fn add(&mut self, selector: [u8; 4]) {
+        if !self.selectors.contains(&selector) {
+            self.selectors.push(selector);
+        }
+    }
+}
+
+impl_serdeany!(SelectorDictionaryMetadata);
+
+/// [`SelectorHintedMutator`] is a mutator that sets the head of a variable
+/// size input (e.g., `bytes` forwarded to a call) to a selector called by the
+/// contracts
+#[derive(Default)]
+pub struct SelectorHintedMutator;
+
+impl Named for SelectorHintedMutator {
+    fn name(&self) -> &str {
+        "SelectorHintedMutator"
+    }
+}
+
+impl SelectorHintedMutator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<I, S> Mutator<I, S> for SelectorHintedMutator
+where
+    S: State + HasRand + HasMetadata,
+    I: Input + HasBytesVec,
+{
+    /// Mutate the first 4 bytes of the input to a selector
+    fn mutate(&mut self, state: &mut S, input: &mut I, _stage_idx: i32) -> Result<MutationResult, Error> {
+        let idx = state.rand_mut().next() as usize;
+
+        let selector = match state.metadata_map().get::<SelectorDictionaryMetadata>() {
+            Some(meta) if !meta.selectors.is_empty() => meta.selectors[idx % meta.selectors.len()],
+            _ => return Ok(MutationResult::Skipped),
+        };
+
+        let input_bytes = input.bytes_mut();
+        if input_bytes.len() < 4 {
+            input_bytes.resize(4, 0);
+        }
+        if input_bytes[..4] == selector {
+            return Ok(MutationResult::Skipped);
+        }
+        input_bytes[..4].copy_from_slice(&selector);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+/// Maximum number of operands kept in [`CmpDictionaryMetadata`]
+pub const MAX_CMP_DICTIONARY_SIZE: usize = 4096;
+
+/// Operands of comparisons (EQ, LT, GT, SUB) observed during execution
+///
+/// This is metadata attached to the global fuzz state. The host records the
+/// operands of unsatisfied comparisons so that the mutator can directly use
+/// them to pass magic value checks (similar to AFL's CmpLog).
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct CmpDictionaryMetadata {
+    /// Operands, oldest first
+    pub values: VecDeque<EVMU256>,
+    seen: HashSet<EVMU256>,
+}
+
+impl CmpDictionaryMetadata {
+    /// Create a new [`CmpDictionaryMetadata`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an operand to the dictionary, evicting the oldest one if full.
+    /// Small values are skipped as they are already covered by interesting
+    /// value mutators.
+    pub fn add(&mut self, value: EVMU256) {
+        if value <= EVMU256::from(u8::MAX) || self.seen.contains(&value) {
+            return;
+        }
+        if self.values.len() >= MAX_CMP_DICTIONARY_SIZE &&
+            let Some(evicted) = self.values.pop_front()
+        {
+            self.seen.remove(&evicted);
+        }
+        self.values.push_back(value);
+        self.seen.insert(value);
+    }
+}
+
+impl_serdeany!(CmpDictionaryMetadata);
+
+/// [`CmpHintedMutator`] is a mutator that mutates the input to an operand of a
+/// comparison observed during execution
+#[derive(Default)]
+pub struct CmpHintedMutator;
+
+impl Named for CmpHintedMutator {
+    fn name(&self) -> &str {
+        "CmpHintedMutator"
+    }
+}
+
+impl CmpHintedMutator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<I, S> Mutator<I, S> for CmpHintedMutator
+where
+    S: State + HasRand + HasMetadata,
+    I: Input + HasBytesVec,
+{
+    /// Mutate the input to a compared operand
+    fn mutate(&mut self, state: &mut S, input: &mut I, _stage_idx: i32) -> Result<MutationResult, Error> {
+        let idx = state.rand_mut().next() as usize;
+
+        let value = match state.metadata_map().get::<CmpDictionaryMetadata>() {
+            Some(meta) if !meta.values.is_empty() => meta.values[idx % meta.values.len()],
+            _ => return Ok(MutationResult::Skipped),
+        };
+
+        let input_bytes = input.bytes_mut();
+        let input_len = input_bytes.len();
+        if input_len > 32 {
+            return Ok(MutationResult::Skipped);
+        }
+        let data: [u8; 32] = value.to_be_bytes();
+        input_bytes.copy_from_slice(&data[(32 - input_len)..]);
+        Ok(MutationResult::Mutated)
+    }
+}
+
 /// [`VMStateHintedMutator`] is a mutator that mutates the input to a value in
 /// the VM state
 ///
@@ -166,7 +317,7 @@ where
 
 /// Mutator that mutates the `CONSTANT SIZE` input bytes (e.g., uint256) in
 /// various ways provided by [`libafl::mutators`]. It also uses the
-/// [`ConstantHintedMutator`] and [`VMStateHintedMutator`]
+/// [`ConstantHintedMutator`], [`CmpHintedMutator`] and [`VMStateHintedMutator`]
 pub fn byte_mutator<I, S>(state: &mut S, input: &mut I, vm_slots: Option<HashMap<EVMU256, EVMU256>>) -> MutationResult
 where
     S: State + HasRand + HasMetadata,
@@ -190,6 +341,7 @@ where
         BytesRandSetMutator::new(),
         BytesSwapMutator::new(),
         ConstantHintedMutator::new(),
+        CmpHintedMutator::new(),
     );
 
     if let Some(vm_slots) = vm_slots {
@@ -203,14 +355,14 @@ where
 
 /// Mutator that mutates the `VARIABLE SIZE` input bytes (e.g., string) in
 /// various ways provided by [`libafl::mutators`]. It also uses the
-/// [`ConstantHintedMutator`] and [`VMStateHintedMutator`]
+/// [`SelectorHintedMutator`] and [`VMStateHintedMutator`]
 pub fn byte_mutator_with_expansion<I, S>(
     state: &mut S,
     input: &mut I,
     vm_slots: Option<HashMap<EVMU256, EVMU256>>,
 ) -> MutationResult
 where
-    S: State + HasRand + HasMaxSize,
+    S: State + HasRand + HasMaxSize + HasMetadata,
     I: HasBytesVec + Input,
 {
     let mutations = tuple_list!(
@@ -234,6 +386,7 @@ where
         BytesRandSetMutator::new(),
         BytesCopyMutator::new(),
         BytesSwapMutator::new(),
+        SelectorHintedMutator::new(),
     );
 
     if let Some(vm_slots) = vm_slots {
@@ -1,7 +1,7 @@
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
-    fmt::Debug,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Debug, Display, Formatter},
     marker::PhantomData,
     ops::Deref,
     rc::Rc,
@@ -75,6 +75,14 @@ where
         }
     }
 
+    /// Conduct a batch of static calls on the state before the execution
+    pub(crate) fn call_pre_batch(&mut self, data: &[(Addr, By)]) -> Vec<Out> {
+        self.executor
+            .deref()
+            .borrow_mut()
+            .fast_static_call(data, self.pre_state, self.fuzz_state)
+    }
+
     /// Conduct a batch of static calls on the state after the execution
     pub(crate) fn call_post_batch(&mut self, data: &[(Addr, By)]) -> Vec<Out> {
         self.executor
@@ -83,6 +91,14 @@ where
             .fast_static_call(data, &self.post_state, self.fuzz_state)
     }
 
+    /// Conduct a batch of dynamic calls on the state before the execution
+    pub(crate) fn call_pre_batch_dyn(&mut self, data: &[(Addr, Addr, By)]) -> (Vec<(Out, bool)>, VS) {
+        self.executor
+            .deref()
+            .borrow_mut()
+            .fast_call(data, self.pre_state, self.fuzz_state)
+    }
+
     /// Conduct a batch of dynamic calls on the state after the execution
     pub(crate) fn call_post_batch_dyn(&mut self, data: &[(Addr, Addr, By)]) -> (Vec<(Out, bool)>, VS) {
         self.executor
@@ -127,6 +143,63 @@ where
     /// Oracle function, called everytime after non-reverted execution
     /// Returns Some(bug_idx) if the oracle is violated
     fn oracle(&self, ctx: &mut OracleCtx<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E>, stage: u64) -> Vec<u64>;
+
+    /// Name and configuration of the oracle, recorded with its findings. The
+    /// name is the type name of the oracle unless overridden
+    fn describe(&self) -> OracleDescriptor {
+        OracleDescriptor::of::<Self>()
+    }
+}
+
+/// An oracle and the configuration it checks with (thresholds, monitored
+/// addresses, ...), so that a finding tells which detection criteria
+/// reported it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OracleDescriptor {
+    /// Name the oracle reports its findings with
+    pub name: String,
+    pub config: BTreeMap<String, serde_json::Value>,
+}
+
+impl OracleDescriptor {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            config: BTreeMap::new(),
+        }
+    }
+
+    /// Descriptor named after the type `T`, without its path and generics
+    pub fn of<T: ?Sized>() -> Self {
+        let name = std::any::type_name::<T>();
+        let name = name.split('<').next().unwrap_or(name);
+        Self::new(name.rsplit("::").next().unwrap_or(name))
+    }
+
+    /// Add the configuration entry `key`
+    pub fn with(mut self, key: &str, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).expect("failed to serialize oracle config");
+        self.config.insert(key.to_string(), value);
+        self
+    }
+}
+
+impl Display for OracleDescriptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if self.config.is_empty() {
+            return Ok(());
+        }
+        let config = self
+            .config
+            .iter()
+            .map(|(key, value)| match value.as_str() {
+                Some(s) => format!("{}: {}", key, s),
+                None => format!("{}: {}", key, value),
+            })
+            .collect::<Vec<_>>();
+        write!(f, " ({})", config.join(", "))
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
@@ -147,3 +220,25 @@ impl BugMetadata {
 }
 
 impl_serdeany!(BugMetadata);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oracle_descriptor() {
+        let descriptor = OracleDescriptor::new("erc20")
+            .with("min_profit", "0.01 WETH")
+            .with("tokens", 2);
+        assert_eq!(descriptor.to_string(), "erc20 (min_profit: 0.01 WETH, tokens: 2)");
+        assert_eq!(OracleDescriptor::new("pair").to_string(), "pair");
+
+        let json = serde_json::to_value(&descriptor).unwrap();
+        assert_eq!(json["config"]["tokens"], 2);
+        assert_eq!(serde_json::from_value::<OracleDescriptor>(json).unwrap(), descriptor);
+
+        // the default name of an oracle
+        assert_eq!(OracleDescriptor::of::<BugMetadata>().name, "BugMetadata");
+        assert_eq!(OracleDescriptor::of::<HashMap<u64, u64>>().name, "HashMap");
+    }
+}
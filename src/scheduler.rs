@@ -25,11 +25,9 @@ where
     fn vote(&self, state: &mut S, idx: usize, amount: usize);
 }
 
-/// The maximum number of inputs (or VMState) to keep in the corpus before
-/// pruning
+/// The default maximum number of inputs (or VMState) to keep in the corpus
+/// before pruning
 pub const DROP_THRESHOLD: usize = 500;
-/// The number of inputs (or VMState) to prune each time the corpus is pruned
-pub const PRUNE_AMT: usize = 250;
 /// If inputs (or VMState) has not been visited this many times, it will be
 /// ignored during pruning
 pub const VISIT_IGNORE_THRESHOLD: usize = 2;
@@ -37,6 +35,9 @@ pub const VISIT_IGNORE_THRESHOLD: usize = 2;
 /// A scheduler that drops inputs (or VMState) based on a voting mechanism
 #[derive(Debug, Clone)]
 pub struct SortedDroppingScheduler<S> {
+    /// Entries kept for scheduling, half of them are evicted each time there
+    /// are more
+    max_size: usize,
     phantom: std::marker::PhantomData<S>,
 }
 
@@ -49,7 +50,14 @@ impl<S> Default for SortedDroppingScheduler<S> {
 impl<S> SortedDroppingScheduler<S> {
     /// Create a new SortedDroppingScheduler
     pub fn new() -> Self {
+        Self::with_max_size(DROP_THRESHOLD)
+    }
+
+    /// Create a new SortedDroppingScheduler scheduling at most `max_size`
+    /// entries, besides the ones pinned by findings
+    pub fn with_max_size(max_size: usize) -> Self {
         Self {
+            max_size: max_size.max(2),
             phantom: std::marker::PhantomData,
         }
     }
@@ -123,6 +131,13 @@ impl DependencyTree {
         }
     }
 
+    /// The entry is reported (e.g., by a finding) and can not be evicted.
+    /// Entries that traces of live ones start from are evicted from scheduling
+    /// only, they stay in the corpus until garbage collected.
+    pub fn is_pinned(&self, idx: usize) -> bool {
+        self.nodes.get(&idx).map_or(false, |node| node.never_delete)
+    }
+
     pub fn garbage_collection(&mut self) -> Vec<usize> {
         let mut to_remove = vec![];
         for (idx, node) in self.nodes.iter() {
@@ -157,8 +172,48 @@ pub struct VoteData {
     pub deps: DependencyTree,
     /// To remove, for Move schedulers
     pub to_remove: Vec<usize>,
+    /// Number of times inputs (or VMState) are added or scheduled
+    #[serde(default)]
+    pub ticks: usize,
+    /// Value of `ticks` when each input (or VMState) was last scheduled (or
+    /// added)
+    #[serde(default)]
+    pub last_scheduled: HashMap<usize, usize>,
+    /// Number of inputs (or VMState) evicted so far
+    #[serde(default)]
+    pub evicted: usize,
+    /// Lower bound of the entries kept for scheduling than the scheduler's,
+    /// set under memory pressure
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl VoteData {
+    /// Keep half of the entries at most, evicted when the next one is added.
+    /// Returns the new limit.
+    pub fn halve_limit(&mut self) -> usize {
+        let limit = (self.votes_and_visits.len() / 2).max(2);
+        self.limit = Some(limit);
+        limit
+    }
+
+    /// Drop the limit set by [`VoteData::halve_limit`], once the memory
+    /// pressure is released
+    pub fn restore_limit(&mut self) {
+        self.limit = None;
+    }
 }
 
+/// Votes for the infant state of the current execution requested while it
+/// was executed (e.g., by an oracle finding it worth exploring), given by the
+/// fuzzer once the state is added
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SponsorMetadata {
+    pub votes: usize,
+}
+
+impl_serdeany!(SponsorMetadata);
+
 pub trait HasReportCorpus<S>
 where
     S: HasMetadata,
@@ -186,10 +241,6 @@ where
 
 impl_serdeany!(VoteData);
 
-/// The number of inputs (or VMState) already removed from the corpus
-#[cfg(feature = "full_trace")]
-pub static mut REMOVED_CORPUS: usize = 0;
-
 impl<S> Scheduler for SortedDroppingScheduler<S>
 where
     S: HasCorpus + HasTestcase + HasRand + HasMetadata + HasParent,
@@ -208,6 +259,10 @@ where
                 votes_total: 1,
                 deps: DependencyTree::new(),
                 to_remove: vec![],
+                ticks: 0,
+                last_scheduled: HashMap::new(),
+                evicted: 0,
+                limit: None,
             });
         }
 
@@ -216,6 +271,8 @@ where
             let parent_idx = state.get_parent_idx();
             let data = state.metadata_map_mut().get_mut::<VoteData>().unwrap();
             data.votes_and_visits.insert(idx, (3, 1));
+            data.ticks += 1;
+            data.last_scheduled.insert(idx, data.ticks);
             data.visits_total += 1;
             data.votes_total += 3;
             data.sorted_votes.push(idx);
@@ -228,68 +285,69 @@ where
 
         // this is costly, but we have to do it to keep the corpus not increasing
         // indefinitely
-        let mut to_remove: Vec<usize> = vec![];
-        {
-            let mut corpus_size = state.corpus().count();
-            let _corpus_mut = state.corpus_mut();
-            let data = state.metadata_map().get::<VoteData>().unwrap();
-            #[cfg(feature = "full_trace")]
-            {
-                corpus_size -= unsafe { REMOVED_CORPUS };
-            }
-
-            // If the corpus is too large (> [`DROP_THRESHOLD`]), prune it
-            if corpus_size > DROP_THRESHOLD {
-                // get top 100 entries sorted by votes (descending)
-                let mut sorted: Vec<_> = data.votes_and_visits.iter().collect();
-                sorted.sort_by(|(_idx_1, (votes1, visits1)), (_idx_2, (votes2, visits2))| {
-                    let score_1 = (*votes1 as f64) / (*visits1 as f64);
-                    let score_2 = (*votes2 as f64) / (*visits2 as f64);
-                    score_1.partial_cmp(&score_2).unwrap()
-                });
-
-                for i in sorted.iter().take(PRUNE_AMT) {
-                    // Ignore the artifacts (*i.0 < 3) and the currently executing corpus (*i.0 ==
-                    // idx).
-                    if *i.0 >= 3 && *i.0 != idx {
-                        to_remove.push(*i.0);
-                    }
-                }
-
-                // Remove inputs (or VMState) from metadata and corpus
-                to_remove.iter().for_each(|x| {
-                    let _ = self.on_remove(state, (*x).into(), &None);
-                    #[cfg(feature = "full_trace")]
-                    {
-                        state
-                            .metadata_map_mut()
-                            .get_mut::<VoteData>()
-                            .unwrap()
-                            .deps
-                            .remove_node(*x);
-                        unsafe {
-                            REMOVED_CORPUS += 1;
-                        }
-                    }
-                    #[cfg(not(feature = "full_trace"))]
-                    {
-                        state.corpus_mut().remove((*x).into()).expect("failed to remove");
-                    }
-                });
-                state.metadata_map_mut().get_mut::<VoteData>().unwrap().to_remove = to_remove;
+        let data = state.metadata_map().get::<VoteData>().unwrap();
+        let max_size = data.limit.map_or(self.max_size, |limit| limit.min(self.max_size));
+        // entries removed but still referenced by traces are not counted
+        if data.votes_and_visits.len() > max_size {
+            // the least recently scheduled first, then the lowest votes per visit.
+            // Entries pinned by findings are kept, so that their traces can
+            // still be rebuilt from the corpus (see `TxnTrace::to_string`).
+            let mut candidates = data
+                .votes_and_visits
+                .iter()
+                // ignore the artifacts (< 3) and the currently executing corpus
+                .filter(|(i, _)| **i >= 3 && **i != idx && !data.deps.is_pinned(**i))
+                .map(|(i, (votes, visits))| {
+                    let last_scheduled = data.last_scheduled.get(i).copied().unwrap_or_default();
+                    (*i, last_scheduled, *votes as f64 / *visits as f64)
+                })
+                .collect::<Vec<_>>();
+            candidates.sort_by(|(_, last_1, score_1), (_, last_2, score_2)| {
+                last_1.cmp(last_2).then(score_1.partial_cmp(score_2).unwrap())
+            });
+            let to_remove = candidates
+                .iter()
+                .take(max_size / 2)
+                .map(|(i, _, _)| *i)
+                .collect::<Vec<_>>();
+
+            // Remove inputs (or VMState) from metadata and corpus
+            for x in &to_remove {
+                let _ = self.on_remove(state, (*x).into(), &None);
                 #[cfg(feature = "full_trace")]
                 {
-                    for idx in state
+                    state
                         .metadata_map_mut()
                         .get_mut::<VoteData>()
                         .unwrap()
                         .deps
-                        .garbage_collection()
-                    {
-                        state.corpus_mut().remove(idx.into()).expect("failed to remove");
-                    }
+                        .remove_node(*x);
+                }
+                #[cfg(not(feature = "full_trace"))]
+                {
+                    state.corpus_mut().remove((*x).into()).expect("failed to remove");
+                }
+            }
+            #[cfg(feature = "full_trace")]
+            {
+                for idx in state
+                    .metadata_map_mut()
+                    .get_mut::<VoteData>()
+                    .unwrap()
+                    .deps
+                    .garbage_collection()
+                {
+                    state.corpus_mut().remove(idx.into()).expect("failed to remove");
                 }
             }
+            debug!(
+                "Evicted {} entries, {} are left in the corpus",
+                to_remove.len(),
+                state.corpus().count()
+            );
+            let data = state.metadata_map_mut().get_mut::<VoteData>().unwrap();
+            data.evicted += to_remove.len();
+            data.to_remove = to_remove;
         }
         Ok(())
     }
@@ -342,6 +400,8 @@ where
         // Update metadata
         {
             data.votes_and_visits.get_mut(&idx).unwrap().1 += 1;
+            data.ticks += 1;
+            data.last_scheduled.insert(idx, data.ticks);
             data.visits_total += 1;
         }
 
@@ -366,6 +426,7 @@ where
         data.votes_total -= data.votes_and_visits.get(&idx).unwrap().0;
         data.visits_total -= data.votes_and_visits.get(&idx).unwrap().1;
         data.votes_and_visits.remove(&idx);
+        data.last_scheduled.remove(&idx);
         data.sorted_votes.retain(|x| *x != idx);
         Ok(())
     }
@@ -423,4 +484,43 @@ mod tests {
         assert!(!tree.nodes.contains_key(&2));
         assert!(!tree.nodes.contains_key(&3));
     }
+
+    #[test]
+    fn test_pinned_nodes() {
+        let mut tree = DependencyTree::new();
+        tree.add_node(1, 0);
+        tree.add_node(2, 1);
+        tree.add_node(3, 0);
+        tree.mark_never_delete(2);
+        assert!(tree.is_pinned(1));
+        assert!(tree.is_pinned(2));
+        assert!(!tree.is_pinned(3));
+
+        tree.remove_node(1);
+        tree.remove_node(2);
+        tree.remove_node(3);
+        assert_eq!(tree.garbage_collection(), vec![3]);
+    }
+
+    #[test]
+    fn test_halve_limit() {
+        let mut data = VoteData {
+            votes_and_visits: (0..10).map(|idx| (idx, (3, 1))).collect(),
+            sorted_votes: vec![],
+            visits_total: 1,
+            votes_total: 1,
+            deps: DependencyTree::new(),
+            to_remove: vec![],
+            ticks: 0,
+            last_scheduled: HashMap::new(),
+            evicted: 0,
+            limit: None,
+        };
+        assert_eq!(data.halve_limit(), 5);
+        data.votes_and_visits.clear();
+        assert_eq!(data.halve_limit(), 2);
+        assert_eq!(data.limit, Some(2));
+        data.restore_limit();
+        assert_eq!(data.limit, None);
+    }
 }
@@ -29,6 +29,7 @@ use libafl::{
 use libafl_bolts::{
     bolts_prelude::{NamedSerdeAnyMap, Rand, RomuDuoJrRand, SerdeAnyMap, StdRand},
     current_nanos,
+    impl_serdeany,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::debug;
@@ -47,6 +48,11 @@ use crate::{
 pub const ACCOUNT_AMT: u8 = 2;
 pub const CONTRACT_AMT: u8 = 2;
 
+/// Percentage of callers picked among the privileged callers (e.g., the owners
+/// of the targets) when any is known, the others are picked uniformly from the
+/// whole caller set
+pub const PRIVILEGED_CALLER_PERCENT: u64 = 25;
+
 /// Trait providing state functions needed by ItyFuzz
 pub trait HasItyState<Loc, Addr, VS, CI>
 where
@@ -85,8 +91,18 @@ pub trait HasCaller<Addr> {
     fn get_rand_caller(&mut self) -> Addr;
     /// Does the address exist in the caller set
     fn has_caller(&self, addr: &Addr) -> bool;
+    /// Is the address a caller of the attacker, i.e., a caller not added with
+    /// [`HasCaller::add_privileged_caller`] or
+    /// [`HasCaller::add_bystander_caller`], whose gains are not exploits
+    fn is_attacker(&self, addr: &Addr) -> bool;
     /// Add a caller to the caller set
     fn add_caller(&mut self, caller: &Addr);
+    /// Add a caller picked more often than the others, e.g., the owner of a
+    /// target, so that access-controlled functions are reached
+    fn add_privileged_caller(&mut self, caller: &Addr);
+    /// Add a caller that is not an account of the attacker, e.g., a holder
+    /// acting with its real balances, so that its funds are not profits
+    fn add_bystander_caller(&mut self, caller: &Addr);
     /// Add an address to the address set
     fn add_address(&mut self, caller: &Addr);
 }
@@ -149,6 +165,16 @@ pub trait HasPresets {
     fn get_next_call(&mut self) -> Option<(EVMAddress, BoxedABI)>;
 }
 
+/// Seed the random number generator of the campaign was created with, the one
+/// given or, if none, the time the campaign started. Recorded with the
+/// findings so that the campaign that found them can be run again.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RngSeedMetadata {
+    pub seed: u64,
+}
+
+impl_serdeany!(RngSeedMetadata);
+
 /// The global state of ItyFuzz, containing all the information needed for
 /// fuzzing Implements LibAFL's [`State`] trait and passed to all the fuzzing
 /// components as a reference
@@ -203,6 +229,14 @@ where
 
     /// Caller and address pools, required for implementing [`HasCaller`] trait
     pub callers_pool: Vec<Addr>,
+    /// Callers in the caller pool picked more often, see
+    /// [`HasCaller::add_privileged_caller`]
+    #[serde(default)]
+    pub privileged_callers: Vec<Addr>,
+    /// Callers in the caller pool that are not accounts of the attacker, see
+    /// [`HasCaller::add_bystander_caller`]
+    #[serde(default)]
+    pub bystander_callers: Vec<Addr>,
     pub addresses_pool: Vec<Addr>,
 
     /// Random number generator, required for implementing [`HasRand`] trait
@@ -280,6 +314,8 @@ where
             seed = current_nanos();
         }
         debug!("Seed: {}", seed);
+        let mut metadata = SerdeAnyMap::new();
+        metadata.insert(RngSeedMetadata { seed });
         Self {
             infant_states_state: InfantStateState::new(),
             #[cfg(not(feature = "evaluation"))]
@@ -288,11 +324,13 @@ where
             txn_corpus: OnDiskCorpus::new(Path::new("corpus")).unwrap(),
             solutions: OnDiskCorpus::new(Path::new("solutions")).unwrap(),
             executions: 0,
-            metadata: Default::default(),
+            metadata,
             named_metadata: Default::default(),
             current_input_idx: 0,
             execution_result: ExecutionResult::empty_result(),
             callers_pool: Vec::new(),
+            privileged_callers: Vec::new(),
+            bystander_callers: Vec::new(),
             addresses_pool: Vec::new(),
             rand_generator: RomuDuoJrRand::with_seed(seed),
             max_size: 20,
@@ -342,6 +380,10 @@ where
 
     /// Get a random caller from the caller pool, used for mutating the caller
     fn get_rand_caller(&mut self) -> Addr {
+        if !self.privileged_callers.is_empty() && self.rand_generator.below(100) < PRIVILEGED_CALLER_PERCENT {
+            let idx = self.rand_generator.below(self.privileged_callers.len() as u64);
+            return self.privileged_callers[idx as usize].clone();
+        }
         let idx = self.rand_generator.below(self.callers_pool.len() as u64);
         self.callers_pool[idx as usize].clone()
     }
@@ -351,6 +393,12 @@ where
         self.callers_pool.contains(addr)
     }
 
+    /// Is the address in the caller pool but neither a privileged nor a
+    /// bystander caller
+    fn is_attacker(&self, addr: &Addr) -> bool {
+        self.has_caller(addr) && !self.privileged_callers.contains(addr) && !self.bystander_callers.contains(addr)
+    }
+
     /// Add a caller to the caller pool
     fn add_caller(&mut self, addr: &Addr) {
         if !self.callers_pool.contains(addr) {
@@ -359,6 +407,22 @@ where
         self.add_address(addr);
     }
 
+    /// Add a caller to the caller pool, picked more often than the others
+    fn add_privileged_caller(&mut self, addr: &Addr) {
+        self.add_caller(addr);
+        if !self.privileged_callers.contains(addr) {
+            self.privileged_callers.push(addr.clone());
+        }
+    }
+
+    /// Add a caller to the caller pool, not accounted as the attacker
+    fn add_bystander_caller(&mut self, addr: &Addr) {
+        self.add_caller(addr);
+        if !self.bystander_callers.contains(addr) {
+            self.bystander_callers.push(addr.clone());
+        }
+    }
+
     /// Add an address to the address pool
     fn add_address(&mut self, caller: &Addr) {
         if !self.addresses_pool.contains(caller) {